@@ -0,0 +1,280 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::uniform_in_ball;
+use crate::Sampler;
+use kdtree::distance::squared_euclidean;
+use kdtree::KdTree;
+use num_traits::float::Float;
+use rand::RngCore;
+
+/// Sampler which biases the samples of `base` toward the regions which the trees have
+/// not explored yet.
+///
+/// It keeps a kd-tree of the vertices which the planner reports by
+/// [`Sampler::add_vertex`], draws `num_candidates` samples of `base`, and returns the
+/// one farthest from its nearest vertex. This reinforces the Voronoi bias of RRT, which
+/// is weakened when the vertices are crowded in some regions, e.g. around the start.
+#[derive(Debug)]
+pub struct DensityAdaptiveSampler<S, N>
+where
+    N: Float,
+{
+    base: S,
+    num_candidates: usize,
+    vertices: Option<KdTree<N, (), Vec<N>>>,
+}
+
+impl<S, N> DensityAdaptiveSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float,
+{
+    /// `num_candidates` (>= 1) is the number of the samples of `base` drawn per sample.
+    /// The larger it is, the stronger the bias is.
+    pub fn new(base: S, num_candidates: usize) -> Self {
+        assert!(num_candidates > 0);
+        Self {
+            base,
+            num_candidates,
+            vertices: None,
+        }
+    }
+
+    /// number of the vertices reported by the planner
+    pub fn num_vertices(&self) -> usize {
+        self.vertices.as_ref().map_or(0, |vertices| vertices.size())
+    }
+}
+
+impl<S, N> Sampler<N> for DensityAdaptiveSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let q = self.base.sample(rng);
+        let Some(vertices) = &self.vertices else {
+            return q;
+        };
+        // candidates of a wrong dimension or not finite are returned for the planner to skip
+        let sparseness = |q: &[N]| match vertices.nearest(q, 1, &squared_euclidean) {
+            Ok(nearest) => nearest.first().map(|(d, _)| *d),
+            Err(_) => None,
+        };
+        let Some(mut best_sparseness) = sparseness(&q) else {
+            return q;
+        };
+        let mut best = q;
+        for _ in 1..self.num_candidates {
+            let q = self.base.sample(rng);
+            match sparseness(&q) {
+                Some(d) if d > best_sparseness => {
+                    best = q;
+                    best_sparseness = d;
+                }
+                Some(_) => {}
+                None => return q,
+            }
+        }
+        best
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+        let vertices = self.vertices.get_or_insert_with(|| KdTree::new(q.len()));
+        // vertices of a wrong dimension are ignored
+        let _ = vertices.add(q.to_vec(), ());
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+impl<S, N> Sampler<N> for &mut DensityAdaptiveSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        (**self).add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        (**self).report_stall(num_stalled);
+    }
+}
+
+/// Sampler which narrows the samples around the newest vertex while the trees grow,
+/// and widens them while the planning stalls (simulated-annealing style).
+///
+/// The samples are uniform in the ball around the vertex reported last by
+/// [`Sampler::add_vertex`], whose radius is `min_radius` multiplied by `growth` for each
+/// sample reported by [`Sampler::report_stall`]. Once the radius reaches `max_radius`,
+/// or before any vertex is reported, the samples of `base` are returned.
+#[derive(Debug, Clone)]
+pub struct AnnealingSampler<S, N> {
+    base: S,
+    min_radius: N,
+    max_radius: N,
+    growth: N,
+    radius: N,
+    newest: Option<Vec<N>>,
+}
+
+impl<S, N> AnnealingSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float,
+{
+    /// `growth` must be more than 1.0, and `max_radius` must not be less than `min_radius`.
+    pub fn new(base: S, min_radius: N, max_radius: N, growth: N) -> Self {
+        assert!(min_radius > N::zero());
+        assert!(max_radius >= min_radius);
+        assert!(growth > N::one());
+        Self {
+            base,
+            min_radius,
+            max_radius,
+            growth,
+            radius: min_radius,
+            newest: None,
+        }
+    }
+
+    /// the current radius (temperature) of the samples
+    pub fn radius(&self) -> N {
+        self.radius
+    }
+}
+
+impl<S, N> Sampler<N> for AnnealingSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        match &self.newest {
+            Some(center) if self.radius < self.max_radius => {
+                uniform_in_ball(center, self.radius, rng)
+            }
+            _ => self.base.sample(rng),
+        }
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+        self.newest = Some(q.to_vec());
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+        // growth^num_stalled, saturated at max_radius
+        let exponent = N::from(num_stalled).unwrap();
+        self.radius = (self.min_radius * self.growth.powf(exponent)).min(self.max_radius);
+    }
+}
+
+#[test]
+fn density_adaptive_sampler_works() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    let mut sampler = DensityAdaptiveSampler::new(
+        UniformBoxSampler::new(&[-1.0, -1.0], &[1.0, 1.0]).unwrap(),
+        10,
+    );
+    let mut rng = rand::thread_rng();
+    // the left half is crowded
+    for i in 0..100 {
+        for j in 0..10 {
+            sampler.add_vertex(&[-1.0 + i as f64 * 0.01, -1.0 + j as f64 * 0.2]);
+        }
+    }
+    assert_eq!(sampler.num_vertices(), 1000);
+    let num_right = (0..100)
+        .filter(|_| sampler.sample(&mut rng)[0] > 0.0)
+        .count();
+    assert!(num_right > 90, "{num_right}");
+
+    // the planner reports the vertices of its trees
+    let mut sampler = DensityAdaptiveSampler::new(
+        UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap(),
+        5,
+    );
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path =
+        dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut sampler, 0.2, 1000).unwrap();
+    assert!(sampler.num_vertices() >= path.len() - 2);
+}
+
+#[test]
+fn annealing_sampler_works() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    let base = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let mut sampler = AnnealingSampler::new(base.clone(), 0.1, 1.0, 2.0);
+    let mut rng = rand::thread_rng();
+    sampler.add_vertex(&[1.5, 1.5]);
+    sampler.report_stall(0);
+    for _ in 0..100 {
+        let q = sampler.sample(&mut rng);
+        assert!((q[0] - 1.5).hypot(q[1] - 1.5) <= 0.1 + 1e-9);
+    }
+    sampler.report_stall(2);
+    assert!((sampler.radius() - 0.4).abs() < 1e-9);
+    sampler.report_stall(100);
+    assert_eq!(sampler.radius(), 1.0);
+    // the samples of base
+    assert!((0..100).any(|_| {
+        let q = sampler.sample(&mut rng);
+        (q[0] - 1.5).hypot(q[1] - 1.5) > 1.0
+    }));
+
+    // the planner reports the stalls from 0, counting up while the trees do not grow
+    struct Stalls<'a>(&'a mut Vec<usize>);
+    impl Sampler<f64> for Stalls<'_> {
+        fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<f64> {
+            vec![0.0, 0.0]
+        }
+        fn report_stall(&mut self, num_stalled: usize) {
+            self.0.push(num_stalled);
+        }
+    }
+    let mut stalls = Vec::new();
+    let is_free = |p: &[f64]| p[0].abs() > 1.0;
+    assert!(crate::rrt(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        Stalls(&mut stalls),
+        &crate::RrtOptions {
+            goal_bias: 0.0,
+            ..crate::RrtOptions::new(0.5, 5)
+        },
+    )
+    .is_err());
+    assert_eq!(stalls, vec![0, 1, 2, 3, 4]);
+
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let sampler = AnnealingSampler::new(base, 1.0, 4.0, 2.0);
+    let path = dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, sampler, 0.2, 3000).unwrap();
+    assert!(path.iter().all(|q| is_free(q)));
+}
@@ -0,0 +1,301 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::prm::OpenVertex;
+use crate::{check_start_goal, is_edge_free, is_valid_point, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`aitstar`].
+#[derive(Debug, Clone)]
+pub struct AitStarOptions<N> {
+    /// number of samples added in each batch
+    pub batch_size: usize,
+    /// number of batches. All of them are used to improve the path.
+    pub num_batches: usize,
+    /// samples within this distance are connected by edges
+    pub neighbor_radius: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+    /// max number of random samples drawn to find one in the informed set
+    pub num_max_sample_try: usize,
+}
+
+impl<N> AitStarOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 100 samples in each batch.
+    pub fn new(neighbor_radius: N, num_batches: usize, resolution: N) -> Self {
+        Self {
+            batch_size: 100,
+            num_batches,
+            neighbor_radius,
+            resolution,
+            num_max_sample_try: 100,
+        }
+    }
+}
+
+/// Dijkstra from the goal (index 1) without collision checks, skipping the edges
+/// known to be invalid. Returns the cost-to-go of each sample.
+fn reverse_search<N>(neighbors: &[Vec<(usize, N)>], invalid: &HashSet<(usize, usize)>) -> Vec<N>
+where
+    N: Float,
+{
+    let mut costs = vec![N::infinity(); neighbors.len()];
+    let mut open = BinaryHeap::new();
+    costs[1] = N::zero();
+    open.push(OpenVertex {
+        cost: N::zero(),
+        index: 1,
+    });
+    while let Some(OpenVertex { cost, index }) = open.pop() {
+        if cost > costs[index] {
+            continue;
+        }
+        for (neighbor, dist) in &neighbors[index] {
+            let new_cost = cost + *dist;
+            if new_cost < costs[*neighbor] && !invalid.contains(&edge_key(index, *neighbor)) {
+                costs[*neighbor] = new_cost;
+                open.push(OpenVertex {
+                    cost: new_cost,
+                    index: *neighbor,
+                });
+            }
+        }
+    }
+    costs
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// search the shortest path from start to goal which is free, using random_sample
+/// function (Adaptively Informed Trees, AIT*).
+///
+/// Samples are added in batches, only from the informed set which can improve the
+/// current path. For each batch, a reverse search from the goal without collision checks
+/// gives the heuristic of a forward A* search from the start, which checks the edges
+/// lazily. When an edge is found invalid, the reverse search is updated and the forward
+/// search is restarted with the better heuristic. Checked edges are reused in later
+/// searches and batches.
+pub fn aitstar<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &AitStarOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    aitstar_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`aitstar`] which takes all randomness from `rng`.
+pub fn aitstar_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &AitStarOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.neighbor_radius > N::zero());
+    assert!(options.resolution > N::zero());
+    let dim = start.len();
+    let mut samples = vec![start.to_vec(), goal.to_vec()];
+    // checked edges, as (smaller index, larger index)
+    let mut valid = HashSet::new();
+    let mut invalid = HashSet::new();
+    let mut best_cost = N::infinity();
+    let mut best_path = None;
+    for batch in 0..options.num_batches {
+        let is_informed = |q: &[N]| {
+            squared_euclidean(q, start).sqrt() + squared_euclidean(q, goal).sqrt() < best_cost
+        };
+        // samples which cannot improve the path are pruned, with the checked edges
+        if best_cost.is_finite() {
+            let mut new_indices = vec![None; samples.len()];
+            let mut pruned = Vec::new();
+            for (index, q) in samples.into_iter().enumerate() {
+                if index < 2 || is_informed(&q) {
+                    new_indices[index] = Some(pruned.len());
+                    pruned.push(q);
+                }
+            }
+            samples = pruned;
+            let reindex = |edges: HashSet<(usize, usize)>| {
+                edges
+                    .into_iter()
+                    .filter_map(|(a, b)| Some(edge_key(new_indices[a]?, new_indices[b]?)))
+                    .collect::<HashSet<_>>()
+            };
+            valid = reindex(valid);
+            invalid = reindex(invalid);
+        }
+        let mut num_added = 0;
+        let mut num_tries = 0;
+        let max_tries = options.batch_size * options.num_max_sample_try;
+        while num_added < options.batch_size && num_tries < max_tries {
+            let n = (options.batch_size - num_added).min(max_tries - num_tries);
+            num_tries += n;
+            for q in random_sample.sample_batch(n, rng).rows() {
+                if is_valid_point(q, dim) && is_informed(q) && is_free(q) {
+                    samples.push(q.to_vec());
+                    num_added += 1;
+                }
+            }
+        }
+        let mut kdtree = kdtree::KdTree::new(dim);
+        for (index, q) in samples.iter().enumerate() {
+            kdtree.add(q.clone(), index).unwrap();
+        }
+        let r2 = options.neighbor_radius * options.neighbor_radius;
+        let neighbors = samples
+            .iter()
+            .enumerate()
+            .map(|(index, q)| {
+                kdtree
+                    .within(q, r2, &squared_euclidean)
+                    .unwrap()
+                    .into_iter()
+                    .filter(|(_, i)| **i != index)
+                    .map(|(d, i)| (*i, d.sqrt()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        loop {
+            let heuristic = reverse_search(&neighbors, &invalid);
+            if !heuristic[0].is_finite() || heuristic[0] >= best_cost {
+                break;
+            }
+            // forward A* from the start, checking the edges lazily
+            let mut costs = vec![N::infinity(); samples.len()];
+            let mut parents = vec![None; samples.len()];
+            let mut open = BinaryHeap::new();
+            let mut found_invalid = false;
+            costs[0] = N::zero();
+            open.push(OpenVertex {
+                cost: heuristic[0],
+                index: 0,
+            });
+            while let Some(OpenVertex { cost, index }) = open.pop() {
+                if cost >= best_cost || costs[index] + heuristic[index] < cost {
+                    continue;
+                }
+                if index == 1 {
+                    break;
+                }
+                for (neighbor, dist) in &neighbors[index] {
+                    let new_cost = costs[index] + *dist;
+                    let key = edge_key(index, *neighbor);
+                    if new_cost >= costs[*neighbor]
+                        || new_cost + heuristic[*neighbor] >= best_cost
+                        || invalid.contains(&key)
+                    {
+                        continue;
+                    }
+                    if !valid.contains(&key) {
+                        if is_edge_free(
+                            &samples[index],
+                            &samples[*neighbor],
+                            &mut is_free,
+                            options.resolution,
+                        ) {
+                            valid.insert(key);
+                        } else {
+                            invalid.insert(key);
+                            found_invalid = true;
+                            continue;
+                        }
+                    }
+                    costs[*neighbor] = new_cost;
+                    parents[*neighbor] = Some(index);
+                    open.push(OpenVertex {
+                        cost: new_cost + heuristic[*neighbor],
+                        index: *neighbor,
+                    });
+                }
+            }
+            if costs[1] < best_cost {
+                debug!("batch {batch}: cost = {:?}", costs[1]);
+                best_cost = costs[1];
+                let mut path = vec![samples[1].clone()];
+                let mut cur = 1;
+                while let Some(parent) = parents[cur] {
+                    path.push(samples[parent].clone());
+                    cur = parent;
+                }
+                path.reverse();
+                best_path = Some(path);
+            }
+            if !found_invalid {
+                break;
+            }
+        }
+    }
+    best_path.ok_or_else(|| "failed".to_string())
+}
+
+#[test]
+fn aitstar_works() {
+    use crate::path_length;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = aitstar(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &AitStarOptions::new(0.6, 10, 0.01),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut |q: &[f64]| is_free(q),
+            0.01
+        ));
+    }
+    // the shortest path goes over two corners of the box
+    assert!(path_length(&path) < 2.0 + 2.0 * 1.04f64.sqrt() + 0.3);
+}
@@ -0,0 +1,196 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::linear::squared_distance;
+use crate::{DualRrtConnectOptions, ExtendStatus};
+use kdtree::KdTree;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// tree of fixed-dimension configurations, which are stored without allocations
+#[derive(Debug)]
+struct ArrayTree<N, const D: usize>
+where
+    N: Float,
+{
+    name: &'static str,
+    kdtree: KdTree<N, usize, [N; D]>,
+    vertices: Vec<[N; D]>,
+    parents: Vec<Option<usize>>,
+}
+
+impl<N, const D: usize> ArrayTree<N, D>
+where
+    N: Float + Debug,
+{
+    fn new(name: &'static str, root: [N; D]) -> Self {
+        let mut tree = Self {
+            name,
+            kdtree: KdTree::new(D),
+            vertices: Vec::new(),
+            parents: Vec::new(),
+        };
+        tree.add_vertex(root, None);
+        tree
+    }
+
+    fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    fn add_vertex(&mut self, q: [N; D], parent: Option<usize>) -> usize {
+        let index = self.vertices.len();
+        self.kdtree.add(q, index).unwrap();
+        self.vertices.push(q);
+        self.parents.push(parent);
+        index
+    }
+
+    fn extend<FF>(&mut self, q_target: &[N; D], extend_length: N, is_free: &mut FF) -> ExtendStatus
+    where
+        FF: FnMut(&[N; D]) -> bool,
+    {
+        let nearest_index = *self.kdtree.nearest(q_target, 1, &squared_distance).unwrap()[0].1;
+        let nearest_q = self.vertices[nearest_index];
+        let dist = squared_distance(&nearest_q, q_target).sqrt();
+        let q_new = if dist < extend_length {
+            *q_target
+        } else {
+            let mut q = nearest_q;
+            for (v, target) in q.iter_mut().zip(q_target) {
+                *v = *v + (*target - *v) * extend_length / dist;
+            }
+            q
+        };
+        if !is_free(&q_new) {
+            return ExtendStatus::Trapped;
+        }
+        let new_index = self.add_vertex(q_new, Some(nearest_index));
+        if squared_distance(&q_new, q_target).sqrt() < extend_length {
+            ExtendStatus::Reached(new_index)
+        } else {
+            ExtendStatus::Advanced(new_index)
+        }
+    }
+
+    /// vertices from `index` to the root
+    fn until_root(&self, index: usize) -> Vec<[N; D]> {
+        let mut path = vec![self.vertices[index]];
+        let mut current = index;
+        while let Some(parent) = self.parents[current] {
+            path.push(self.vertices[parent]);
+            current = parent;
+        }
+        path
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with the configurations of the fixed dimension `D` as arrays.
+///
+/// This is [`dual_rrt_connect_with_options`](crate::dual_rrt_connect_with_options)
+/// which doesn't allocate a `Vec` for each sample and vertex, for low-dimensional
+/// problems (e.g. 2 - 7) where the allocations dominate. Samples which are not finite are
+/// skipped. `tree_bias`, `num_nearest_candidates`, `tree_balance`, `sampling_schedule`
+/// and `metric` of the options are not used.
+pub fn dual_rrt_connect_array<FF, FR, N, const D: usize>(
+    start: &[N; D],
+    goal: &[N; D],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<[N; D]>, String>
+where
+    FF: FnMut(&[N; D]) -> bool,
+    FR: FnMut() -> [N; D],
+    N: Float + Debug,
+{
+    crate::check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    let max_connect_extensions = options.max_connect_extensions.unwrap_or(usize::MAX);
+    let mut start_tree = ArrayTree::new("start", *start);
+    let mut goal_tree = ArrayTree::new("goal", *goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample();
+        if !q_rand.iter().all(|v| v.is_finite()) {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        if let ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) =
+            tree_a.extend(&q_rand, options.extend_length, &mut is_free)
+        {
+            let q_new = tree_a.vertices[new_index];
+            for _ in 0..max_connect_extensions {
+                match tree_b.extend(&q_new, options.extend_length, &mut is_free) {
+                    ExtendStatus::Trapped => break,
+                    ExtendStatus::Advanced(_) => {}
+                    ExtendStatus::Reached(reach_index) => {
+                        debug!("connected with {} vertices", tree_a.len() + tree_b.len());
+                        let mut path = tree_a.until_root(new_index);
+                        path.reverse();
+                        path.extend(tree_b.until_root(reach_index));
+                        if tree_b.name == "start" {
+                            path.reverse();
+                        }
+                        return Ok(path);
+                    }
+                }
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn dual_rrt_connect_array_works() {
+    use rand::Rng;
+    let is_free = |p: &[f64; 3]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0);
+    let random_sample = || {
+        let mut rng = rand::thread_rng();
+        [
+            rng.gen_range(-2.0..2.0),
+            rng.gen_range(-2.0..2.0),
+            rng.gen_range(-0.5..0.5),
+        ]
+    };
+    let path = dual_rrt_connect_array(
+        &[-1.2, 0.0, 0.0],
+        &[1.2, 0.0, 0.0],
+        is_free,
+        random_sample,
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], [-1.2, 0.0, 0.0]);
+    assert_eq!(path[path.len() - 1], [1.2, 0.0, 0.0]);
+    assert!(path.iter().all(is_free));
+    for segment in path.windows(2) {
+        assert!(squared_distance(&segment[0], &segment[1]).sqrt() <= 0.2 + 1e-9);
+    }
+    assert!(dual_rrt_connect_array(
+        &[-1.2, 0.0],
+        &[f64::NAN, 0.0],
+        |_: &[f64; 2]| true,
+        || [0.0, 0.0],
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .is_err());
+}
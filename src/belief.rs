@@ -0,0 +1,190 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, ExtendStatus, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`belief_rrt`].
+#[derive(Debug, Clone)]
+pub struct BeliefRrtOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// probability (0.0 - 1.0) to extend toward the goal instead of a random sample
+    pub goal_bias: f64,
+    /// configurations whose collision probability is above this are not free
+    pub max_collision_probability: f64,
+}
+
+impl<N> BeliefRrtOptions<N> {
+    /// Creates options with 0.05 goal bias.
+    pub fn new(extend_length: N, num_max_try: usize, max_collision_probability: f64) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            goal_bias: 0.05,
+            max_collision_probability,
+        }
+    }
+}
+
+/// search the path from start to goal in belief space, where each configuration
+/// has an uncertainty (e.g. covariance of the localization).
+///
+/// `propagate(belief, from, to)` returns the uncertainty after moving from `from` to `to`
+/// with `belief`, and `collision_probability(q, belief)` returns the probability of
+/// collision at `q` with `belief`. A configuration is free if the probability is
+/// `max_collision_probability` or less.
+///
+/// Returns the configurations from start to goal with their uncertainties.
+pub fn belief_rrt<FP, FC, FR, N, U>(
+    start: &[N],
+    start_belief: U,
+    goal: &[N],
+    propagate: FP,
+    collision_probability: FC,
+    random_sample: FR,
+    options: &BeliefRrtOptions<N>,
+) -> Result<Vec<(Vec<N>, U)>, String>
+where
+    FP: FnMut(&U, &[N], &[N]) -> U,
+    FC: FnMut(&[N], &U) -> f64,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+{
+    belief_rrt_with_rng(
+        start,
+        start_belief,
+        goal,
+        propagate,
+        collision_probability,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`belief_rrt`] which takes all randomness from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn belief_rrt_with_rng<FP, FC, FR, N, U, R>(
+    start: &[N],
+    start_belief: U,
+    goal: &[N],
+    mut propagate: FP,
+    mut collision_probability: FC,
+    mut random_sample: FR,
+    options: &BeliefRrtOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<(Vec<N>, U)>, String>
+where
+    FP: FnMut(&U, &[N], &[N]) -> U,
+    FC: FnMut(&[N], &U) -> f64,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    let mut tree = Tree::with_name("start", start.len());
+    tree.add_vertex(start);
+    let mut beliefs = vec![start_belief];
+    for _ in 0..options.num_max_try {
+        let q_target = if rng.gen_bool(options.goal_bias) {
+            goal.to_vec()
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_target, start.len()) {
+            continue;
+        }
+        let nearest_index = tree.get_nearest_index(&q_target);
+        let nearest_q = tree.vertex(nearest_index).to_vec();
+        let mut new_belief = None;
+        let status = tree.extend_from(
+            nearest_index,
+            &q_target,
+            options.extend_length,
+            &mut |q: &[N]| {
+                let belief = propagate(&beliefs[nearest_index], &nearest_q, q);
+                let is_free =
+                    collision_probability(q, &belief) <= options.max_collision_probability;
+                new_belief = Some(belief);
+                is_free
+            },
+        );
+        let new_index = match status {
+            ExtendStatus::Trapped => continue,
+            ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) => index,
+        };
+        beliefs.push(new_belief.unwrap());
+        debug!("belief = {:?}", beliefs[new_index]);
+        let q_new = tree.vertex(new_index);
+        if squared_euclidean(q_new, goal).sqrt() < options.extend_length {
+            let goal_belief = propagate(&beliefs[new_index], q_new, goal);
+            if collision_probability(goal, &goal_belief) <= options.max_collision_probability {
+                let mut path = vec![(goal.to_vec(), goal_belief)];
+                let mut index = Some(new_index);
+                while let Some(i) = index {
+                    path.push((tree.vertex(i).to_vec(), beliefs[i].clone()));
+                    index = tree.parent(i);
+                }
+                path.reverse();
+                return Ok(path);
+            }
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn belief_rrt_works() {
+    use rand::distributions::{Distribution, Uniform};
+    // distance from the box [-1, 1] x [-1, 1]
+    let clearance = |p: &[f64]| {
+        let dx = (p[0].abs() - 1.0).max(0.0);
+        let dy = (p[1].abs() - 1.0).max(0.0);
+        dx.hypot(dy)
+    };
+    let result = belief_rrt(
+        &[-1.5, 0.0],
+        0.01,
+        &[1.5, 0.0],
+        // standard deviation grows with traveled distance
+        |sigma: &f64, from: &[f64], to: &[f64]| {
+            sigma + 0.01 * (from[0] - to[0]).hypot(from[1] - to[1])
+        },
+        |q: &[f64], sigma: &f64| (-clearance(q).powi(2) / (2.0 * sigma * sigma)).exp(),
+        || {
+            let between = Uniform::new(-3.0, 3.0);
+            let mut rng = rand::thread_rng();
+            vec![between.sample(&mut rng), between.sample(&mut rng)]
+        },
+        &BeliefRrtOptions::new(0.2, 10000, 0.01),
+    )
+    .unwrap();
+    assert_eq!(result[0].0, vec![-1.5, 0.0]);
+    assert_eq!(result[result.len() - 1].0, vec![1.5, 0.0]);
+    for (q, sigma) in &result {
+        assert!((-clearance(q).powi(2) / (2.0 * sigma * sigma)).exp() <= 0.01);
+    }
+}
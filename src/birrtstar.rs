@@ -0,0 +1,841 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_edge_free, is_valid_point, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Parameters of [`birrtstar`].
+#[derive(Debug, Clone)]
+pub struct BiRrtStarOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// number of iterations. All of them are used to improve the path.
+    pub num_max_try: usize,
+    /// radius to choose the parent of a new vertex, to rewire, and to connect the trees
+    pub neighbor_radius: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+    /// probability (0.0 - 1.0) to sample around a beacon instead of using
+    /// random_sample, after a path is found (RRT*-Smart). Beacons are the vertices
+    /// of the best path shortcut by straight edges, and samples are taken within
+    /// `neighbor_radius` of them in each axis. 0.0 by default.
+    pub beacon_bias: f64,
+    /// number of ancestors of the neighbors which are also candidates of parents
+    /// for choosing the parent and rewiring (Quick-RRT*). 0 by default.
+    pub ancestor_depth: usize,
+    /// if set, the radius shrinks to `radius_gamma * (log(n) / n) ^ (1 / dim)` as the
+    /// number of vertices `n` of the tree grows, as in the original RRT*, with
+    /// `neighbor_radius` as its max. See [`rrtstar_gamma`]. `None` by default.
+    pub radius_gamma: Option<N>,
+    /// probability (0.0 - 1.0) to extend the tree toward the root of the other tree
+    /// (the goal for the start tree, and the start for the goal tree) instead of
+    /// using random_sample. 0.0 by default.
+    pub goal_bias: f64,
+    /// if `true`, samples are taken uniformly from the prolate hyperspheroid of the
+    /// configurations which can make the path shorter instead of using random_sample,
+    /// after a path is found (Informed RRT*). The samples are not limited to the bounds
+    /// of random_sample, so `is_free` should reject the ones out of the bounds.
+    /// `false` by default.
+    pub informed: bool,
+}
+
+impl<N> BiRrtStarOptions<N>
+where
+    N: Float,
+{
+    /// Creates options whose neighbor radius is twice the extend length.
+    pub fn new(extend_length: N, num_max_try: usize, resolution: N) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            neighbor_radius: extend_length + extend_length,
+            resolution,
+            beacon_bias: 0.0,
+            ancestor_depth: 0,
+            radius_gamma: None,
+            goal_bias: 0.0,
+            informed: false,
+        }
+    }
+}
+
+/// Returns the `radius_gamma` of [`BiRrtStarOptions`] which makes the path converge to
+/// the optimal one, for the space of `dim` dimensions whose free volume is `free_volume`.
+///
+/// This is `2 * (1 + 1 / dim) ^ (1 / dim) * (free_volume / unit ball volume) ^ (1 / dim)`.
+/// The volume of the sampling bounds can be used as a conservative `free_volume`.
+pub fn rrtstar_gamma<N>(dim: usize, free_volume: N) -> N
+where
+    N: Float,
+{
+    assert!(dim > 0);
+    let pi = N::from(std::f64::consts::PI).unwrap();
+    // V(d) = V(d - 2) * 2 * pi / d
+    let mut unit_ball_volume = if dim.is_multiple_of(2) {
+        N::one()
+    } else {
+        N::from(2.0).unwrap()
+    };
+    for d in ((2 + dim % 2)..=dim).step_by(2) {
+        unit_ball_volume = unit_ball_volume * (pi + pi) / N::from(d).unwrap();
+    }
+    let d = N::from(dim).unwrap();
+    let exponent = N::one() / d;
+    N::from(2.0).unwrap()
+        * (N::one() + exponent).powf(exponent)
+        * (free_volume / unit_ball_volume).powf(exponent)
+}
+
+/// Returns a uniform random configuration in the prolate hyperspheroid whose foci are
+/// `start` and `goal`, in which the sum of the distances to them is at most `cost`.
+fn sample_informed<N, R>(start: &[N], goal: &[N], cost: N, rng: &mut R) -> Vec<N>
+where
+    N: Float,
+    R: Rng,
+{
+    let dim = start.len();
+    let two = N::from(2.0).unwrap();
+    let c_min = squared_euclidean(start, goal).sqrt();
+    let radii = (
+        cost / two,
+        (cost * cost - c_min * c_min).max(N::zero()).sqrt() / two,
+    );
+    // uniform in the unit ball: a normal random direction, and a radius of u ^ (1 / dim)
+    let direction = (0..dim)
+        .map(|_| {
+            let u: f64 = 1.0 - rng.gen::<f64>();
+            (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * rng.gen::<f64>()).cos()
+        })
+        .collect::<Vec<_>>();
+    let norm = direction.iter().map(|v| v * v).sum::<f64>().sqrt();
+    let radius = rng.gen::<f64>().powf(1.0 / dim as f64) / norm.max(f64::MIN_POSITIVE);
+    let x = direction
+        .iter()
+        .enumerate()
+        .map(|(i, v)| N::from(v * radius).unwrap() * if i == 0 { radii.0 } else { radii.1 })
+        .collect::<Vec<_>>();
+    // the Householder reflection which maps the first axis to the direction of the foci
+    let v = (0..dim)
+        .map(|i| {
+            let e = if i == 0 { N::one() } else { N::zero() };
+            if c_min > N::zero() {
+                e - (goal[i] - start[i]) / c_min
+            } else {
+                N::zero()
+            }
+        })
+        .collect::<Vec<_>>();
+    let vv = v.iter().fold(N::zero(), |sum, v| sum + *v * *v);
+    let vx = v
+        .iter()
+        .zip(&x)
+        .fold(N::zero(), |sum, (v, x)| sum + *v * *x);
+    (0..dim)
+        .map(|i| {
+            let reflected = if vv > N::epsilon() {
+                x[i] - two * v[i] * vx / vv
+            } else {
+                x[i]
+            };
+            (start[i] + goal[i]) / two + reflected
+        })
+        .collect()
+}
+
+/// Returns the radius to choose the parent and to rewire for the tree of `num_vertices`.
+fn neighbor_radius<N>(options: &BiRrtStarOptions<N>, num_vertices: usize, dim: usize) -> N
+where
+    N: Float,
+{
+    match options.radius_gamma {
+        Some(gamma) if num_vertices > 1 => {
+            let n = N::from(num_vertices).unwrap();
+            (gamma * (n.ln() / n).powf(N::one() / N::from(dim).unwrap()))
+                .min(options.neighbor_radius)
+        }
+        _ => options.neighbor_radius,
+    }
+}
+
+/// Returns at most `depth` ancestors of the vertex at `index`, nearest first.
+fn ancestors<N>(tree: &Tree<N>, index: usize, depth: usize) -> Vec<usize>
+where
+    N: Float + Debug,
+{
+    let mut ancestors = Vec::new();
+    let mut cur = index;
+    while let Some(parent) = tree.parent(cur) {
+        if ancestors.len() == depth {
+            break;
+        }
+        ancestors.push(parent);
+        cur = parent;
+    }
+    ancestors
+}
+
+/// Returns the candidate of the least cost to reach `q` through a free edge,
+/// as `(index, cost)`.
+pub(crate) fn best_parent<FF, N>(
+    tree: &Tree<N>,
+    candidates: &[usize],
+    q: &[N],
+    is_free: &mut FF,
+    resolution: N,
+) -> Option<(usize, N)>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    let mut costs = candidates
+        .iter()
+        .map(|i| {
+            let dist = squared_euclidean(tree.vertex(*i), q).sqrt();
+            (*i, tree.cost_to(*i) + dist)
+        })
+        .collect::<Vec<_>>();
+    costs.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    costs
+        .into_iter()
+        .find(|(i, _)| is_edge_free(tree.vertex(*i), q, is_free, resolution))
+}
+
+/// Adds `q_new` to the tree with the parent of the least cost among `near`
+/// and their `ancestor_depth` ancestors, and rewires `near` through it or its
+/// ancestors. Returns the index of `q_new`, or `None` if no edge to it is free.
+pub(crate) fn add_and_rewire<FF, N>(
+    tree: &mut Tree<N>,
+    q_new: &[N],
+    near: &[usize],
+    is_free: &mut FF,
+    resolution: N,
+    ancestor_depth: usize,
+) -> Option<usize>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    let mut candidates = near.to_vec();
+    for i in near {
+        for ancestor in ancestors(tree, *i, ancestor_depth) {
+            if !candidates.contains(&ancestor) {
+                candidates.push(ancestor);
+            }
+        }
+    }
+    let (parent_index, _) = best_parent(tree, &candidates, q_new, is_free, resolution)?;
+    let new_index = tree.add_vertex(q_new);
+    tree.set_parent(parent_index, new_index);
+    let mut rewire_parents = vec![new_index];
+    rewire_parents.extend(ancestors(tree, new_index, ancestor_depth));
+    for i in near {
+        let candidates = rewire_parents
+            .iter()
+            .copied()
+            .filter(|p| p != i)
+            .collect::<Vec<_>>();
+        let cost = tree.cost_to(*i);
+        let Some((parent, new_cost)) =
+            best_parent(tree, &candidates, tree.vertex(*i), is_free, resolution)
+        else {
+            continue;
+        };
+        // descendants of `i` never have less costs than `i`, so this makes no cycle
+        if new_cost < cost && tree.parent(*i) != Some(parent) {
+            tree.set_parent(parent, *i);
+        }
+    }
+    Some(new_index)
+}
+
+/// Returns (start tree, goal tree).
+fn start_and_goal<'a, N>(a: &'a Tree<N>, b: &'a Tree<N>) -> (&'a Tree<N>, &'a Tree<N>)
+where
+    N: Float + Debug,
+{
+    if a.name == "start" {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Cost of the path joined by the edge between `s` of the start tree and `g` of the goal tree
+fn joined_cost<N>(start_tree: &Tree<N>, goal_tree: &Tree<N>, (s, g): (usize, usize)) -> N
+where
+    N: Float + Debug,
+{
+    start_tree.cost_to(s)
+        + squared_euclidean(start_tree.vertex(s), goal_tree.vertex(g)).sqrt()
+        + goal_tree.cost_to(g)
+}
+
+/// Path joined by the edge between `s` of the start tree and `g` of the goal tree
+fn joined_path<N>(start_tree: &Tree<N>, goal_tree: &Tree<N>, (s, g): (usize, usize)) -> Vec<Vec<N>>
+where
+    N: Float + Debug,
+{
+    let mut path = start_tree.get_until_root(s);
+    path.reverse();
+    path.push(start_tree.vertex(s).to_vec());
+    path.push(goal_tree.vertex(g).to_vec());
+    path.extend(goal_tree.get_until_root(g));
+    path
+}
+
+/// Removes the vertices of the path which can be skipped by free straight edges.
+fn shortcut<FF, N>(path: &[Vec<N>], is_free: &mut FF, resolution: N) -> Vec<Vec<N>>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    let mut result = vec![path[0].clone()];
+    let mut i = 0;
+    while i + 1 < path.len() {
+        let mut j = path.len() - 1;
+        while j > i + 1 && !is_edge_free(&path[i], &path[j], is_free, resolution) {
+            j -= 1;
+        }
+        result.push(path[j].clone());
+        i = j;
+    }
+    result
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with bidirectional RRT* (B-RRT*).
+///
+/// Two trees are grown from start and goal by turns, and rewired like RRT*.
+/// All `num_max_try` iterations are used, and the joined path of the least cost
+/// between the trees is returned.
+pub fn birrtstar<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    birrtstar_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`birrtstar`] which takes all randomness from `rng`.
+pub fn birrtstar_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    birrtstar_anytime_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        |_, _, _| true,
+        rng,
+    )
+}
+
+/// [`birrtstar`] which reports each improved path while planning.
+///
+/// `on_improved(path, cost, iteration)` is called when a joined path of less cost
+/// than before is found, and planning stops if it returns `false`.
+/// The returned path may be better than the last reported one, because rewiring
+/// also improves the paths found before.
+pub fn birrtstar_anytime<FF, FR, FI, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+    on_improved: FI,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    FI: FnMut(&[Vec<N>], N, usize) -> bool,
+    N: Float + Debug,
+{
+    birrtstar_anytime_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        on_improved,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`birrtstar_anytime`] which takes all randomness from `rng`.
+pub fn birrtstar_anytime_with_rng<FF, FR, FI, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+    on_improved: FI,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    FI: FnMut(&[Vec<N>], N, usize) -> bool,
+    N: Float + Debug,
+    R: Rng,
+{
+    let (start_tree, goal_tree, best) = grow_trees(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        on_improved,
+        rng,
+    )?;
+    Ok(joined_path(&start_tree, &goal_tree, best))
+}
+
+/// Result of [`birrtstar_merged`].
+#[derive(Debug)]
+pub struct BiRrtStarSolution<N>
+where
+    N: Float + Debug,
+{
+    /// the joined path of the least cost
+    pub path: Vec<Vec<N>>,
+    /// cost (length) of the path
+    pub cost: N,
+    /// tree rooted at start, merged from both trees through the best connection.
+    /// [`Tree::cost_to`] of the goal is `cost`.
+    pub tree: Tree<N>,
+}
+
+/// [`birrtstar`] which returns the cost of the path and the merged tree too.
+pub fn birrtstar_merged<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+) -> Result<BiRrtStarSolution<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    birrtstar_merged_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`birrtstar_merged`] which takes all randomness from `rng`.
+pub fn birrtstar_merged_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+    rng: &mut R,
+) -> Result<BiRrtStarSolution<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    let (start_tree, goal_tree, best) = grow_trees(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        |_, _, _| true,
+        rng,
+    )?;
+    Ok(BiRrtStarSolution {
+        path: joined_path(&start_tree, &goal_tree, best),
+        cost: joined_cost(&start_tree, &goal_tree, best),
+        tree: merge_trees(&start_tree, &goal_tree, best),
+    })
+}
+
+/// Returns the tree rooted at the root of `start_tree`, which also has the vertices
+/// of `goal_tree` connected by the edge between `s` and `g`.
+fn merge_trees<N>(start_tree: &Tree<N>, goal_tree: &Tree<N>, (s, g): (usize, usize)) -> Tree<N>
+where
+    N: Float + Debug,
+{
+    let mut tree = Tree::with_name("start", start_tree.dim());
+    for i in 0..start_tree.len() {
+        tree.add_vertex(start_tree.vertex(i));
+        if let Some(parent) = start_tree.parent(i) {
+            tree.set_parent(parent, i);
+        }
+    }
+    let offset = start_tree.len();
+    for i in 0..goal_tree.len() {
+        tree.add_vertex(goal_tree.vertex(i));
+        if let Some(parent) = goal_tree.parent(i) {
+            tree.set_parent(parent + offset, i + offset);
+        }
+    }
+    // reverse the edges from g to the goal
+    let mut parent = s;
+    let mut current = Some(g);
+    while let Some(i) = current {
+        current = goal_tree.parent(i);
+        tree.set_parent(parent, i + offset);
+        parent = i + offset;
+    }
+    tree
+}
+
+/// (start tree, goal tree, the best connection)
+type GrownTrees<N> = (Tree<N>, Tree<N>, (usize, usize));
+
+/// Grows the trees, and returns them with the best connection.
+fn grow_trees<FF, FR, FI, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &BiRrtStarOptions<N>,
+    mut on_improved: FI,
+    rng: &mut R,
+) -> Result<GrownTrees<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    FI: FnMut(&[Vec<N>], N, usize) -> bool,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    assert!((0.0..=1.0).contains(&options.beacon_bias));
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    // swapped at the beginning of each iteration, so the start tree grows first
+    let mut tree_a = Tree::with_name("goal", start.len());
+    let mut tree_b = Tree::with_name("start", start.len());
+    tree_a.add_vertex(goal);
+    tree_b.add_vertex(start);
+    // pairs of (index in start tree, index in goal tree) which can be joined
+    let mut connections = Vec::new();
+    let mut best_cost = N::infinity();
+    let mut beacons = Vec::new();
+    'planning: for iteration in 0..options.num_max_try {
+        mem::swap(&mut tree_a, &mut tree_b);
+        let q_rand = if !beacons.is_empty() && rng.gen_bool(options.beacon_bias) {
+            let beacon: &Vec<N> = &beacons[rng.gen_range(0..beacons.len())];
+            beacon
+                .iter()
+                .map(|v| *v + options.neighbor_radius * N::from(rng.gen_range(-1.0..1.0)).unwrap())
+                .collect()
+        } else if rng.gen_bool(options.goal_bias) {
+            tree_b.vertex(0).to_vec()
+        } else if options.informed && best_cost.is_finite() {
+            sample_informed(start, goal, best_cost, rng)
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_rand, start.len()) {
+            continue;
+        }
+        let nearest_index = tree_a.get_nearest_index(&q_rand);
+        let nearest_q = tree_a.vertex(nearest_index);
+        let dist = squared_euclidean(nearest_q, &q_rand).sqrt();
+        let q_new = if dist < options.extend_length {
+            q_rand
+        } else {
+            nearest_q
+                .iter()
+                .zip(&q_rand)
+                .map(|(near, target)| *near + (*target - *near) * options.extend_length / dist)
+                .collect()
+        };
+        if !is_free(&q_new) {
+            continue;
+        }
+        let radius = neighbor_radius(options, tree_a.len(), start.len());
+        let mut near = tree_a
+            .within(&q_new, radius)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if !near.contains(&nearest_index) {
+            near.push(nearest_index);
+        }
+        let Some(new_index) = add_and_rewire(
+            &mut tree_a,
+            &q_new,
+            &near,
+            &mut is_free,
+            options.resolution,
+            options.ancestor_depth,
+        ) else {
+            continue;
+        };
+        for (i, _) in tree_b.within(&q_new, radius) {
+            if !is_edge_free(&q_new, tree_b.vertex(i), &mut is_free, options.resolution) {
+                continue;
+            }
+            let connection = if tree_a.name == "start" {
+                (new_index, i)
+            } else {
+                (i, new_index)
+            };
+            connections.push(connection);
+            let (start_tree, goal_tree) = start_and_goal(&tree_a, &tree_b);
+            let cost = joined_cost(start_tree, goal_tree, connection);
+            if cost < best_cost {
+                best_cost = cost;
+                let path = joined_path(start_tree, goal_tree, connection);
+                if options.beacon_bias > 0.0 {
+                    beacons = shortcut(&path, &mut is_free, options.resolution);
+                    debug!("{} beacons, cost = {cost:?}", beacons.len());
+                }
+                if !on_improved(&path, cost, iteration) {
+                    break 'planning;
+                }
+            }
+        }
+    }
+    let (start_tree, goal_tree) = start_and_goal(&tree_a, &tree_b);
+    // costs may have been improved by rewiring after the connections were found
+    let best = connections
+        .into_iter()
+        .map(|connection| (connection, joined_cost(start_tree, goal_tree, connection)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .ok_or_else(|| "failed".to_string())?
+        .0;
+    debug!(
+        "joined {} start and {} goal vertices",
+        start_tree.len(),
+        goal_tree.len()
+    );
+    if tree_a.name == "start" {
+        Ok((tree_a, tree_b, best))
+    } else {
+        Ok((tree_b, tree_a, best))
+    }
+}
+
+#[test]
+fn birrtstar_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = birrtstar(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &BiRrtStarOptions::new(0.2, 3000, 0.01),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut { is_free },
+            0.01
+        ));
+    }
+    // shortest path goes along the box, whose length is about 4.04
+    let length = path.windows(2).fold(0.0, |sum, segment| {
+        sum + squared_euclidean(&segment[0], &segment[1]).sqrt()
+    });
+    assert!(length < 4.5, "{length}");
+}
+
+#[test]
+fn birrtstar_variants() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let smart = BiRrtStarOptions {
+        beacon_bias: 0.5,
+        ..BiRrtStarOptions::new(0.2, 2000, 0.01)
+    };
+    let quick = BiRrtStarOptions {
+        ancestor_depth: 2,
+        ..BiRrtStarOptions::new(0.2, 2000, 0.01)
+    };
+    // the unit disc has the volume of pi
+    assert!((rrtstar_gamma(2, std::f64::consts::PI) - 2.0 * 1.5f64.sqrt()).abs() < 1e-9);
+    assert!(
+        (rrtstar_gamma(3, 4.0 / 3.0 * std::f64::consts::PI) - 2.0 * 4.0f64.cbrt() / 3.0f64.cbrt())
+            .abs()
+            < 1e-9
+    );
+    let shrinking = BiRrtStarOptions {
+        neighbor_radius: 1.0,
+        radius_gamma: Some(rrtstar_gamma(2, 16.0)),
+        ..BiRrtStarOptions::new(0.2, 2000, 0.01)
+    };
+    let goal_biased = BiRrtStarOptions {
+        goal_bias: 0.1,
+        ..BiRrtStarOptions::new(0.2, 2000, 0.01)
+    };
+    for options in [smart, quick, shrinking, goal_biased] {
+        let path = birrtstar(
+            &[-1.2, 0.0],
+            &[1.2, 0.0],
+            is_free,
+            world.sampler(1),
+            &options,
+        )
+        .unwrap();
+        for segment in path.windows(2) {
+            assert!(is_edge_free(
+                &segment[0],
+                &segment[1],
+                &mut { is_free },
+                0.01
+            ));
+        }
+        let length = path.windows(2).fold(0.0, |sum, segment| {
+            sum + squared_euclidean(&segment[0], &segment[1]).sqrt()
+        });
+        assert!(length < 4.5, "{length}");
+    }
+}
+
+#[test]
+fn informed_sampling() {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let (start, goal) = ([-1.0, 0.5, 0.0], [1.0, -0.5, 1.0]);
+    let cost = 1.5 * squared_euclidean(&start, &goal).sqrt();
+    let samples = (0..2000)
+        .map(|_| sample_informed(&start, &goal, cost, &mut rng))
+        .collect::<Vec<_>>();
+    let focal_sum =
+        |q: &[f64]| squared_euclidean(q, &start).sqrt() + squared_euclidean(q, &goal).sqrt();
+    assert!(samples.iter().all(|q| focal_sum(q) <= cost + 1e-9));
+    // the whole spheroid is covered, also near its surface
+    assert!(samples.iter().any(|q| focal_sum(q) > 0.98 * cost));
+    let center = [0.0, 0.0, 0.5];
+    let mean_offset = (0..3)
+        .map(|i| samples.iter().map(|q| q[i] - center[i]).sum::<f64>() / 2000.0)
+        .collect::<Vec<_>>();
+    assert!(
+        mean_offset.iter().all(|v| v.abs() < 0.05),
+        "{mean_offset:?}"
+    );
+
+    let world = crate::scenario::World::square();
+    let random_sample = world.sampler(1);
+    let mut calls = 0;
+    let mut first_cost = None;
+    let path = birrtstar_anytime(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        || {
+            calls += 1;
+            random_sample()
+        },
+        &BiRrtStarOptions {
+            informed: true,
+            ..BiRrtStarOptions::new(0.2, 2000, 0.01)
+        },
+        |_, cost, _| {
+            first_cost.get_or_insert(cost);
+            true
+        },
+    )
+    .unwrap();
+    // random_sample is not used after the first path
+    assert!(calls < 2000);
+    assert!(crate::path_length(&path) <= first_cost.unwrap() + 1e-9);
+    assert!(crate::path_length(&path) < 4.5);
+}
+
+#[test]
+fn birrtstar_anytime_works() {
+    let world = crate::scenario::World::square();
+    let mut costs = Vec::new();
+    let path = birrtstar_anytime(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &BiRrtStarOptions::new(0.2, 1000, 0.01),
+        |path, cost, _| {
+            assert_eq!(path[0], vec![-1.2, 0.0]);
+            costs.push(cost);
+            // stop at the third improvement
+            costs.len() < 3
+        },
+    )
+    .unwrap();
+    assert_eq!(costs.len(), 3);
+    assert!(costs.windows(2).all(|c| c[1] < c[0]));
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+}
+
+#[test]
+fn birrtstar_merged_works() {
+    use crate::path_length;
+    let world = crate::scenario::World::square();
+    let BiRrtStarSolution { path, cost, tree } = birrtstar_merged(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &BiRrtStarOptions::new(0.2, 1000, 0.01),
+    )
+    .unwrap();
+    assert!((path_length(&path) - cost).abs() < 1e-9);
+    // the goal is reached from start in the merged tree
+    let goal_index = (0..tree.len())
+        .find(|i| tree.vertex(*i) == [1.2, 0.0])
+        .unwrap();
+    assert!((tree.cost_to(goal_index) - cost).abs() < 1e-9);
+    assert!((1..tree.len()).all(|i| tree.parent(i).is_some()));
+}
@@ -0,0 +1,287 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::standard_normal;
+use crate::Sampler;
+use num_traits::float::Float;
+use rand::RngCore;
+
+/// max number of pairs drawn to find one which satisfies the condition of the sampler,
+/// after which the last sample of the base sampler is returned
+const NUM_MAX_TRY: usize = 1000;
+
+/// Returns `q` moved by a random offset of the normal distribution of `std_dev`.
+fn gaussian_neighbor<N: Float>(q: &[N], std_dev: N, rng: &mut dyn RngCore) -> Vec<N> {
+    q.iter()
+        .map(|v| *v + std_dev * N::from(standard_normal(rng)).unwrap())
+        .collect()
+}
+
+/// Sampler which concentrates the samples of `base` near the boundaries of the obstacles
+/// (Gaussian sampling).
+///
+/// A sample of `base` and its neighbor at the normal distance of `std_dev` are drawn,
+/// and the free one is returned only if the other is in collision. Smaller `std_dev`
+/// makes the samples closer to the boundaries.
+#[derive(Debug, Clone)]
+pub struct GaussianSampler<S, FF, N> {
+    base: S,
+    is_free: FF,
+    std_dev: N,
+}
+
+impl<S, FF, N> GaussianSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    /// If no pair is found in 1000 tries, the last sample of `base` is returned as is.
+    pub fn new(base: S, is_free: FF, std_dev: N) -> Self {
+        assert!(std_dev > N::zero());
+        Self {
+            base,
+            is_free,
+            std_dev,
+        }
+    }
+}
+
+impl<S, FF, N> Sampler<N> for GaussianSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let mut q = self.base.sample(rng);
+        for _ in 0..NUM_MAX_TRY {
+            let neighbor = gaussian_neighbor(&q, self.std_dev, rng);
+            match ((self.is_free)(&q), (self.is_free)(&neighbor)) {
+                (true, false) => return q,
+                (false, true) => return neighbor,
+                _ => q = self.base.sample(rng),
+            }
+        }
+        q
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+/// Sampler which concentrates the samples of `base` in narrow passages (bridge test).
+///
+/// A sample of `base` in collision and its neighbor at the normal distance of `std_dev`
+/// are drawn, and their midpoint is returned only if the neighbor is also in collision
+/// and the midpoint is free. `std_dev` should be about the width of the obstacles
+/// around the passages.
+#[derive(Debug, Clone)]
+pub struct BridgeSampler<S, FF, N> {
+    base: S,
+    is_free: FF,
+    std_dev: N,
+}
+
+impl<S, FF, N> BridgeSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    /// If no bridge is found in 1000 tries, the last sample of `base` is returned as is.
+    pub fn new(base: S, is_free: FF, std_dev: N) -> Self {
+        assert!(std_dev > N::zero());
+        Self {
+            base,
+            is_free,
+            std_dev,
+        }
+    }
+}
+
+impl<S, FF, N> Sampler<N> for BridgeSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let two = N::one() + N::one();
+        let mut q = self.base.sample(rng);
+        for _ in 0..NUM_MAX_TRY {
+            if !(self.is_free)(&q) {
+                let neighbor = gaussian_neighbor(&q, self.std_dev, rng);
+                if !(self.is_free)(&neighbor) {
+                    let mid = q
+                        .iter()
+                        .zip(&neighbor)
+                        .map(|(a, b)| (*a + *b) / two)
+                        .collect::<Vec<_>>();
+                    if (self.is_free)(&mid) {
+                        return mid;
+                    }
+                }
+            }
+            q = self.base.sample(rng);
+        }
+        q
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+/// Sampler which returns the points on the boundaries of the obstacles
+/// (obstacle-based sampling).
+///
+/// From a sample of `base` in collision, it walks in a random direction by `step_length`
+/// until it exits the obstacle, and returns the first free point, which is within
+/// `step_length` of the boundary. Free samples of `base` are skipped, so the samples
+/// hug the obstacles, e.g. for contact-rich tasks.
+#[derive(Debug, Clone)]
+pub struct ObstacleBasedSampler<S, FF, N> {
+    base: S,
+    is_free: FF,
+    step_length: N,
+}
+
+impl<S, FF, N> ObstacleBasedSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    /// Each walk takes at most 1000 steps. If no boundary point is found from 1000
+    /// samples, the last sample of `base` is returned as is.
+    pub fn new(base: S, is_free: FF, step_length: N) -> Self {
+        assert!(step_length > N::zero());
+        Self {
+            base,
+            is_free,
+            step_length,
+        }
+    }
+}
+
+impl<S, FF, N> Sampler<N> for ObstacleBasedSampler<S, FF, N>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let mut q = self.base.sample(rng);
+        for _ in 0..NUM_MAX_TRY {
+            if !(self.is_free)(&q) {
+                let direction = gaussian_neighbor(&vec![N::zero(); q.len()], N::one(), rng);
+                let norm = direction
+                    .iter()
+                    .fold(N::zero(), |sum, d| sum + *d * *d)
+                    .sqrt();
+                if norm.is_normal() {
+                    let mut p = q.clone();
+                    for _ in 0..NUM_MAX_TRY {
+                        for (p, d) in p.iter_mut().zip(&direction) {
+                            *p = *p + *d * self.step_length / norm;
+                        }
+                        if (self.is_free)(&p) {
+                            return p;
+                        }
+                    }
+                }
+            }
+            q = self.base.sample(rng);
+        }
+        q
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn gaussian_sampler_works() {
+    use crate::UniformBoxSampler;
+    let is_free = |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0);
+    let mut sampler = GaussianSampler::new(
+        UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap(),
+        is_free,
+        0.05,
+    );
+    let mut rng = rand::thread_rng();
+    let samples = (0..1000)
+        .map(|_| sampler.sample(&mut rng))
+        .collect::<Vec<_>>();
+    assert!(samples.iter().all(|q| is_free(q)));
+    // distance to the box
+    let near = samples
+        .iter()
+        .filter(|q| q[0].abs().max(q[1].abs()) < 1.0 + 0.05 * 4.0)
+        .count();
+    assert!(near > 990, "{near}");
+}
+
+#[test]
+fn bridge_sampler_works() {
+    use crate::UniformBoxSampler;
+    // a thick wall with a narrow gap
+    let is_free = |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() > 0.05);
+    let mut sampler = BridgeSampler::new(
+        UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap(),
+        is_free,
+        0.2,
+    );
+    let mut rng = rand::thread_rng();
+    let in_gap = (0..200)
+        .map(|_| sampler.sample(&mut rng))
+        .filter(|q| q[0].abs() < 1.0 && q[1].abs() <= 0.05)
+        .count();
+    assert!(in_gap > 190, "{in_gap}");
+}
+
+#[test]
+fn obstacle_based_sampler_works() {
+    use crate::UniformBoxSampler;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut sampler = ObstacleBasedSampler::new(
+        UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap(),
+        is_free,
+        0.01,
+    );
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        let q = sampler.sample(&mut rng);
+        assert!(is_free(&q));
+        assert!(q[0].abs().max(q[1].abs()) < 1.0 + 0.01);
+    }
+}
@@ -0,0 +1,220 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, dual_rrt_connect_with_rng_fn, DualRrtConnectOptions};
+use num_traits::float::Float;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use tracing::{debug, warn};
+
+/// Everything needed to re-run [`dual_rrt_connect`](crate::dual_rrt_connect) exactly:
+/// the RNG seed, the problem, the parameters, and the version of this crate.
+///
+/// With the `serde` feature, this can be serialized to keep it with field logs.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlanningBundle<N> {
+    /// seed of the RNG used for planning and sampling
+    pub seed: u64,
+    /// start configuration
+    pub start: Vec<N>,
+    /// goal configuration
+    pub goal: Vec<N>,
+    /// planner parameters
+    pub options: DualRrtConnectOptions<N>,
+    /// version of this crate which created the bundle
+    pub version: String,
+}
+
+impl<N> PlanningBundle<N>
+where
+    N: Float + Debug,
+{
+    /// Creates a bundle with the current version of this crate.
+    pub fn new(seed: u64, start: &[N], goal: &[N], options: DualRrtConnectOptions<N>) -> Self {
+        Self {
+            seed,
+            start: start.to_vec(),
+            goal: goal.to_vec(),
+            options,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Runs the planning of this bundle.
+    ///
+    /// `random_sample` must take all randomness from the given RNG, and `is_free`
+    /// must be deterministic, to get the same result from the same bundle.
+    pub fn run<FF, FR>(&self, is_free: FF, random_sample: FR) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: FnMut(&mut StdRng) -> Vec<N>,
+    {
+        self.run_until(is_free, random_sample, None)
+    }
+
+    /// [`Self::run`] which fails as soon as `cancel` is set.
+    fn run_until<FF, FR>(
+        &self,
+        is_free: FF,
+        random_sample: FR,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: FnMut(&mut StdRng) -> Vec<N>,
+    {
+        if self.version != env!("CARGO_PKG_VERSION") {
+            warn!(
+                "bundle was created by rrt {}, but running on {}",
+                self.version,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        dual_rrt_connect_with_rng_fn(
+            &self.start,
+            &self.goal,
+            is_free,
+            random_sample,
+            &self.options,
+            &mut StdRng::seed_from_u64(self.seed),
+            cancel,
+        )
+    }
+}
+
+/// search the path like [`dual_rrt_connect_with_options`](crate::dual_rrt_connect_with_options)
+/// with a random seed, and return the result with a bundle to reproduce it.
+///
+/// `random_sample` must take all randomness from the given RNG.
+pub fn dual_rrt_connect_reproducible<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> (Result<Vec<Vec<N>>, String>, PlanningBundle<N>)
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: FnMut(&mut StdRng) -> Vec<N>,
+    N: Float + Debug,
+{
+    let bundle = PlanningBundle::new(rand::thread_rng().gen(), start, goal, options.clone());
+    (bundle.run(is_free, random_sample), bundle)
+}
+
+/// search the path like [`dual_rrt_connect_reproducible`] in `num_threads` threads with
+/// different random seeds, and return the first path found with its bundle.
+///
+/// The runtimes of RRT are heavy-tailed, so racing independent runs finds a path
+/// much earlier on average. The other runs are cancelled at their next iteration when
+/// a path is found. `random_sample` must take all randomness from the given RNG to
+/// reproduce the path with the bundle.
+pub fn plan_race<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    num_threads: usize,
+) -> Result<(Vec<Vec<N>>, PlanningBundle<N>), String>
+where
+    FF: Fn(&[N]) -> bool + Sync,
+    FR: Fn(&mut StdRng) -> Vec<N> + Sync,
+    N: Float + Debug + Send + Sync,
+{
+    check_start_goal(start, goal)?;
+    assert!(num_threads > 0);
+    let finished = AtomicBool::new(false);
+    let winner = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| {
+                let bundle =
+                    PlanningBundle::new(rand::thread_rng().gen(), start, goal, options.clone());
+                let result = bundle.run_until(&is_free, &random_sample, Some(&finished));
+                if let Ok(path) = result {
+                    if !finished.swap(true, Ordering::SeqCst) {
+                        debug!("seed {} won", bundle.seed);
+                        *winner.lock().unwrap() = Some((path, bundle));
+                    }
+                }
+            });
+        }
+    });
+    winner
+        .into_inner()
+        .unwrap()
+        .ok_or_else(|| format!("failed in all {num_threads} threads"))
+}
+
+#[test]
+fn bundle_works() {
+    use rand::distributions::{Distribution, Uniform};
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = |rng: &mut StdRng| {
+        let between = Uniform::new(-2.0, 2.0);
+        vec![between.sample(rng), between.sample(rng)]
+    };
+    let options = DualRrtConnectOptions {
+        tree_bias: 0.1,
+        ..DualRrtConnectOptions::new(0.2, 1000)
+    };
+    let (result, bundle) =
+        dual_rrt_connect_reproducible(&[-1.2, 0.0], &[1.2, 0.0], is_free, random_sample, &options);
+    assert_eq!(result, bundle.run(is_free, random_sample));
+}
+
+#[test]
+fn plan_race_works() {
+    use rand::distributions::{Distribution, Uniform};
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = |rng: &mut StdRng| {
+        let between = Uniform::new(-2.0, 2.0);
+        vec![between.sample(rng), between.sample(rng)]
+    };
+    let options = DualRrtConnectOptions::new(0.2, 1000);
+    let (path, bundle) = plan_race(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        random_sample,
+        &options,
+        4,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert_eq!(bundle.run(is_free, random_sample).unwrap(), path);
+    // cancelled runs stop before sampling
+    let mut num_samples = 0;
+    let cancelled = bundle.run_until(
+        is_free,
+        |rng: &mut StdRng| {
+            num_samples += 1;
+            random_sample(rng)
+        },
+        Some(&AtomicBool::new(true)),
+    );
+    assert_eq!(cancelled, Err("cancelled".to_string()));
+    assert_eq!(num_samples, 0);
+}
@@ -0,0 +1,190 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::is_edge_free;
+use crate::sampler::standard_normal;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`cem_refine`].
+#[derive(Debug, Clone)]
+pub struct CemOptions<N> {
+    /// number of candidate paths sampled in each iteration
+    pub num_samples: usize,
+    /// number of the candidates of the least costs which update the distribution
+    pub num_elites: usize,
+    /// number of iterations
+    pub num_iterations: usize,
+    /// initial standard deviation of the noise added to each coordinate of the waypoints
+    pub initial_std: N,
+    /// interval to check the segments of the candidates with `is_free`
+    pub resolution: N,
+}
+
+impl<N> CemOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 50 samples and 10 elites in each iteration.
+    pub fn new(initial_std: N, num_iterations: usize, resolution: N) -> Self {
+        Self {
+            num_samples: 50,
+            num_elites: 10,
+            num_iterations,
+            initial_std,
+            resolution,
+        }
+    }
+}
+
+/// refine the path to reduce `cost`, using the cross-entropy method (CEM).
+///
+/// The waypoints except the first and the last one are perturbed with Gaussian noise,
+/// and the distribution of the noise is refit to the candidates of the least costs
+/// (elites) in each iteration. So any cost of the whole path, e.g. its length with
+/// penalties of small clearance or sharp turns, can be reduced, which
+/// [`smooth_path`](crate::smooth_path) cannot do. Candidates whose waypoints or segments
+/// are not free are rejected. The waypoints are replaced with the best ones found, and
+/// the cost of the path is returned.
+pub fn cem_refine<FC, FF, N>(
+    path: &mut [Vec<N>],
+    cost: FC,
+    is_free: FF,
+    options: &CemOptions<N>,
+) -> N
+where
+    FC: FnMut(&[Vec<N>]) -> N,
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    cem_refine_with_rng(path, cost, is_free, options, &mut rand::thread_rng())
+}
+
+/// [`cem_refine`] which takes all randomness from `rng`.
+pub fn cem_refine_with_rng<FC, FF, N, R>(
+    path: &mut [Vec<N>],
+    mut cost: FC,
+    mut is_free: FF,
+    options: &CemOptions<N>,
+    rng: &mut R,
+) -> N
+where
+    FC: FnMut(&[Vec<N>]) -> N,
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+    R: Rng,
+{
+    assert!(options.num_elites > 0);
+    assert!(options.num_samples >= options.num_elites);
+    assert!(options.initial_std >= N::zero());
+    assert!(options.resolution > N::zero());
+    let mut best_cost = cost(path);
+    if path.len() < 3 {
+        return best_cost;
+    }
+    let dim = path[0].len();
+    let num_waypoints = path.len() - 2;
+    let mut mean = path[1..=num_waypoints].concat();
+    let mut std = vec![options.initial_std; mean.len()];
+    let mut candidate = path.to_vec();
+    for iteration in 0..options.num_iterations {
+        let mut samples = Vec::new();
+        for _ in 0..options.num_samples {
+            let x = mean
+                .iter()
+                .zip(&std)
+                .map(|(m, s)| *m + *s * N::from(standard_normal(rng)).unwrap())
+                .collect::<Vec<_>>();
+            for (q, chunk) in candidate[1..=num_waypoints].iter_mut().zip(x.chunks(dim)) {
+                q.copy_from_slice(chunk);
+            }
+            let is_valid = candidate[1..=num_waypoints].iter().all(|q| is_free(q))
+                && candidate
+                    .windows(2)
+                    .all(|s| is_edge_free(&s[0], &s[1], &mut is_free, options.resolution));
+            if !is_valid {
+                continue;
+            }
+            let c = cost(&candidate);
+            if c.is_finite() {
+                samples.push((c, x));
+            }
+        }
+        if samples.is_empty() {
+            continue;
+        }
+        samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        samples.truncate(options.num_elites);
+        let num = N::from(samples.len()).unwrap();
+        for (k, (m, s)) in mean.iter_mut().zip(std.iter_mut()).enumerate() {
+            *m = samples.iter().fold(N::zero(), |sum, (_, x)| sum + x[k]) / num;
+            *s = (samples
+                .iter()
+                .fold(N::zero(), |sum, (_, x)| sum + (x[k] - *m) * (x[k] - *m))
+                / num)
+                .sqrt();
+        }
+        let (elite_cost, elite) = &samples[0];
+        if *elite_cost < best_cost {
+            debug!("cost = {elite_cost:?} at {iteration}");
+            best_cost = *elite_cost;
+            for (q, chunk) in path[1..=num_waypoints].iter_mut().zip(elite.chunks(dim)) {
+                q.copy_from_slice(chunk);
+            }
+        }
+    }
+    best_cost
+}
+
+#[test]
+fn cem_refine_works() {
+    use crate::path_length;
+    // clearance from the disc of radius 0.5 at the origin
+    let clearance = |p: &[f64]| (p[0] * p[0] + p[1] * p[1]).sqrt() - 0.5;
+    let is_free = |p: &[f64]| clearance(p) > 0.0;
+    // length, with the penalty of the clearance less than 0.5
+    let cost = |path: &[Vec<f64>]| {
+        path_length(path)
+            + path
+                .iter()
+                .map(|q| 10.0 * (0.5 - clearance(q)).max(0.0))
+                .sum::<f64>()
+    };
+    let mut path = vec![
+        vec![-2.0, 0.0],
+        vec![-0.6, 0.4],
+        vec![0.0, 0.6],
+        vec![0.6, 0.4],
+        vec![2.0, 0.0],
+    ];
+    let initial_cost = cost(&path);
+    let refined_cost = cem_refine(&mut path, cost, is_free, &CemOptions::new(0.1, 30, 0.01));
+    assert!(refined_cost < initial_cost - 1.0, "{refined_cost}");
+    assert!((cost(&path) - refined_cost).abs() < 1e-9);
+    assert_eq!(path.len(), 5);
+    assert_eq!(path[0], vec![-2.0, 0.0]);
+    assert_eq!(path[4], vec![2.0, 0.0]);
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut { is_free },
+            0.01
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::birrtstar::add_and_rewire;
+use crate::{check_start_goal, is_edge_free, is_valid_point, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::thread;
+use tracing::debug;
+
+/// Parameters of [`cforest`].
+#[derive(Debug, Clone)]
+pub struct CForestOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// number of iterations of each tree. All of them are used to improve the path.
+    pub num_max_try: usize,
+    /// radius to choose the parent of a new vertex and to rewire
+    pub neighbor_radius: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+    /// number of trees, each of which is grown in its own thread
+    pub num_threads: usize,
+    /// number of iterations between the checks of the solution shared by the other trees
+    pub share_interval: usize,
+}
+
+impl<N> CForestOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 4 threads, the neighbor radius of twice the extend length,
+    /// and the share interval of 100 iterations.
+    pub fn new(extend_length: N, num_max_try: usize, resolution: N) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            neighbor_radius: extend_length + extend_length,
+            resolution,
+            num_threads: 4,
+            share_interval: 100,
+        }
+    }
+}
+
+/// best solution shared between the trees
+#[derive(Debug)]
+struct SharedSolution<N> {
+    cost: N,
+    path: Vec<Vec<N>>,
+    /// incremented at every improvement
+    version: usize,
+}
+
+/// Adds `q` to the tree as RRT*, returning its index.
+fn add_rrtstar_vertex<FF, N>(
+    tree: &mut Tree<N>,
+    q: &[N],
+    is_free: &mut FF,
+    options: &CForestOptions<N>,
+) -> Option<usize>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    let nearest_index = tree.get_nearest_index(q);
+    let mut near = tree
+        .within(q, options.neighbor_radius)
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if !near.contains(&nearest_index) {
+        near.push(nearest_index);
+    }
+    add_and_rewire(tree, q, &near, is_free, options.resolution, 0)
+}
+
+/// Grows one tree of [`cforest`], sharing the best solution through `shared`.
+fn grow_tree<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: &FF,
+    random_sample: &FR,
+    options: &CForestOptions<N>,
+    shared: &Mutex<SharedSolution<N>>,
+) where
+    FF: Fn(&[N]) -> bool,
+    FR: Fn() -> Vec<N>,
+    N: Float + Debug,
+{
+    let mut is_free = |q: &[N]| is_free(q);
+    let heuristic =
+        |q: &[N]| squared_euclidean(q, start).sqrt() + squared_euclidean(q, goal).sqrt();
+    let mut tree = Tree::with_name("start", start.len());
+    tree.add_vertex(start);
+    let mut synced_version = 0;
+    let mut best_cost = N::infinity();
+    for iteration in 0..options.num_max_try {
+        let mut new_indices = Vec::new();
+        if iteration % options.share_interval == 0 {
+            let (version, cost, path) = {
+                let shared = shared.lock().unwrap();
+                (shared.version, shared.cost, shared.path.clone())
+            };
+            if version != synced_version {
+                synced_version = version;
+                best_cost = cost;
+                // prune the vertices which cannot improve the shared path,
+                // and add the vertices of the path
+                let costs = (0..tree.len())
+                    .map(|i| tree.cost_to(i) + squared_euclidean(tree.vertex(i), goal).sqrt())
+                    .collect::<Vec<_>>();
+                tree.prune(|i, _| costs[i] < best_cost);
+                for q in path.iter().skip(1) {
+                    new_indices.extend(add_rrtstar_vertex(&mut tree, q, &mut is_free, options));
+                }
+            }
+        }
+        let q_rand = random_sample();
+        if !is_valid_point(&q_rand, start.len()) || heuristic(&q_rand) >= best_cost {
+            continue;
+        }
+        let nearest_index = tree.get_nearest_index(&q_rand);
+        let nearest_q = tree.vertex(nearest_index);
+        let dist = squared_euclidean(nearest_q, &q_rand).sqrt();
+        let q_new = if dist < options.extend_length {
+            q_rand
+        } else {
+            nearest_q
+                .iter()
+                .zip(&q_rand)
+                .map(|(near, target)| *near + (*target - *near) * options.extend_length / dist)
+                .collect()
+        };
+        if is_free(&q_new) {
+            new_indices.extend(add_rrtstar_vertex(&mut tree, &q_new, &mut is_free, options));
+        }
+        for new_index in new_indices {
+            let q_new = tree.vertex(new_index);
+            let dist_to_goal = squared_euclidean(q_new, goal).sqrt();
+            let cost = tree.cost_to(new_index) + dist_to_goal;
+            if dist_to_goal >= options.extend_length
+                || cost >= best_cost
+                || !is_edge_free(q_new, goal, &mut is_free, options.resolution)
+            {
+                continue;
+            }
+            let mut path = tree.get_until_root(new_index);
+            path.reverse();
+            path.push(q_new.to_vec());
+            if dist_to_goal > N::zero() {
+                path.push(goal.to_vec());
+            }
+            best_cost = cost;
+            let mut shared = shared.lock().unwrap();
+            if cost < shared.cost {
+                debug!("cost = {cost:?} at {iteration}");
+                shared.cost = cost;
+                shared.path = path;
+                shared.version += 1;
+                synced_version = shared.version;
+            }
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with RRT* trees grown in parallel threads (Coupled Forest Of Random Engrafting
+/// Search Trees, CForest).
+///
+/// Each tree shares its improved solutions with the others, which add the vertices of
+/// the shared path to themselves and prune the vertices and samples which cannot
+/// improve it. All `num_max_try` iterations of all trees are used, and the best
+/// path is returned. `random_sample` is shared by the threads, so it is a closure
+/// instead of a [`Sampler`](crate::Sampler).
+pub fn cforest<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &CForestOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: Fn(&[N]) -> bool + Sync,
+    FR: Fn() -> Vec<N> + Sync,
+    N: Float + Debug + Send + Sync,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    assert!(options.num_threads > 0);
+    assert!(options.share_interval > 0);
+    let shared = Mutex::new(SharedSolution {
+        cost: N::infinity(),
+        path: Vec::new(),
+        version: 0,
+    });
+    thread::scope(|scope| {
+        for _ in 0..options.num_threads {
+            scope.spawn(|| grow_tree(start, goal, &is_free, &random_sample, options, &shared));
+        }
+    });
+    let shared = shared.into_inner().unwrap();
+    if shared.path.is_empty() {
+        return Err("failed".to_string());
+    }
+    Ok(shared.path)
+}
+
+#[test]
+fn cforest_works() {
+    use crate::path_length;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = cforest(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &CForestOptions::new(0.2, 1000, 0.01),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut |q: &[f64]| is_free(q),
+            0.01
+        ));
+    }
+    // the shortest path goes over two corners of the box
+    assert!(path_length(&path) < 2.0 + 2.0 * 1.04f64.sqrt() + 0.5);
+}
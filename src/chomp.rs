@@ -0,0 +1,207 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{is_edge_free, path_length};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`chomp_smooth`].
+#[derive(Debug, Clone)]
+pub struct ChompOptions<N> {
+    /// number (>= 2) of the waypoints of the optimized path, including start and goal
+    pub num_waypoints: usize,
+    /// number of the steps of gradient descent
+    pub num_iterations: usize,
+    /// size of the steps of gradient descent. It must be less than
+    /// `0.25 / smoothness_weight` to be stable.
+    pub learning_rate: N,
+    /// weight of the smoothness, the sum of the squared lengths of the segments
+    pub smoothness_weight: N,
+    /// weight of the obstacle cost
+    pub obstacle_weight: N,
+    /// obstacles farther than this distance have no cost
+    pub influence_distance: N,
+    /// interval to check the segments of the optimized path
+    pub resolution: N,
+}
+
+impl<N> ChompOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 500 iterations, the learning rate of 0.1, unit weights,
+    /// and the resolution of 1/10 of `influence_distance`.
+    pub fn new(num_waypoints: usize, influence_distance: N) -> Self {
+        Self {
+            num_waypoints,
+            num_iterations: 500,
+            learning_rate: N::from(0.1).unwrap(),
+            smoothness_weight: N::one(),
+            obstacle_weight: N::one(),
+            influence_distance,
+            resolution: influence_distance / N::from(10.0).unwrap(),
+        }
+    }
+}
+
+/// Returns `num` waypoints at equal intervals along the path.
+fn resample<N>(path: &[Vec<N>], num: usize) -> Vec<Vec<N>>
+where
+    N: Float,
+{
+    let length = path_length(path);
+    let interval = length / N::from(num - 1).unwrap();
+    let mut resampled = vec![path[0].clone()];
+    let mut segment = 0;
+    let mut passed = N::zero();
+    for k in 1..(num - 1) {
+        let target = interval * N::from(k).unwrap();
+        loop {
+            let segment_length = squared_euclidean(&path[segment], &path[segment + 1]).sqrt();
+            if passed + segment_length >= target || segment + 2 == path.len() {
+                let t = if segment_length > N::zero() {
+                    ((target - passed) / segment_length).min(N::one())
+                } else {
+                    N::zero()
+                };
+                resampled.push(
+                    path[segment]
+                        .iter()
+                        .zip(&path[segment + 1])
+                        .map(|(a, b)| *a + (*b - *a) * t)
+                        .collect(),
+                );
+                break;
+            }
+            passed = passed + segment_length;
+            segment += 1;
+        }
+    }
+    resampled.push(path[path.len() - 1].clone());
+    resampled
+}
+
+/// Derivative of the obstacle cost of CHOMP by the signed distance `d`.
+fn obstacle_cost_derivative<N: Float>(d: N, epsilon: N) -> N {
+    if d < N::zero() {
+        -N::one()
+    } else if d < epsilon {
+        (d - epsilon) / epsilon
+    } else {
+        N::zero()
+    }
+}
+
+/// smooth the path by gradient descent of the sum of the smoothness and the obstacle
+/// cost, like CHOMP (Covariant Hamiltonian Optimization for Motion Planning).
+///
+/// The path is resampled to `num_waypoints` at equal intervals, and the waypoints
+/// except start and goal are optimized. `signed_distance` is the distance from the
+/// configuration to the nearest obstacle, negative in collision, and its gradient is
+/// computed by central difference. Unlike [`smooth_path`](crate::smooth_path), this moves
+/// the path away from obstacles within `influence_distance`. Returns an error if the
+/// optimized path is not free.
+pub fn chomp_smooth<FD, N>(
+    path: &[Vec<N>],
+    mut signed_distance: FD,
+    options: &ChompOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FD: FnMut(&[N]) -> N,
+    N: Float + Debug,
+{
+    if path.len() < 2 {
+        return Err("path must have start and goal".to_string());
+    }
+    assert!(options.num_waypoints >= 2);
+    assert!(options.influence_distance > N::zero());
+    assert!(options.resolution > N::zero());
+    let mut waypoints = resample(path, options.num_waypoints);
+    let dim = path[0].len();
+    let two = N::from(2.0).unwrap();
+    let h = options.influence_distance / N::from(100.0).unwrap();
+    let last = waypoints.len() - 1;
+    for _ in 0..options.num_iterations {
+        let mut gradients = vec![vec![N::zero(); dim]; waypoints.len()];
+        for (i, gradient) in gradients.iter_mut().enumerate().take(last).skip(1) {
+            let q = &waypoints[i];
+            let d = signed_distance(q);
+            let derivative = obstacle_cost_derivative(d, options.influence_distance);
+            let mut q_h = q.clone();
+            for k in 0..dim {
+                gradient[k] = options.smoothness_weight
+                    * two
+                    * (two * q[k] - waypoints[i - 1][k] - waypoints[i + 1][k]);
+                if derivative != N::zero() {
+                    q_h[k] = q[k] + h;
+                    let d_plus = signed_distance(&q_h);
+                    q_h[k] = q[k] - h;
+                    let d_minus = signed_distance(&q_h);
+                    q_h[k] = q[k];
+                    gradient[k] = gradient[k]
+                        + options.obstacle_weight * derivative * (d_plus - d_minus) / (h + h);
+                }
+            }
+        }
+        for (q, gradient) in waypoints.iter_mut().zip(&gradients) {
+            for (v, g) in q.iter_mut().zip(gradient) {
+                *v = *v - options.learning_rate * *g;
+            }
+        }
+    }
+    debug!("length = {:?}", path_length(&waypoints));
+    let mut is_free = |q: &[N]| signed_distance(q) > N::zero();
+    if let Some(q) = waypoints.iter().find(|q| !is_free(q)) {
+        return Err(format!("optimized path is in collision at {q:?}"));
+    }
+    if waypoints
+        .windows(2)
+        .any(|s| !is_edge_free(&s[0], &s[1], &mut is_free, options.resolution))
+    {
+        return Err("optimized path is in collision".to_string());
+    }
+    Ok(waypoints)
+}
+
+#[test]
+fn chomp_smooth_works() {
+    // distance from the disc of radius 0.5 at the origin
+    let signed_distance = |p: &[f64]| (p[0] * p[0] + p[1] * p[1]).sqrt() - 0.5;
+    let min_distance = |path: &[Vec<f64>]| {
+        path.iter()
+            .map(|q| signed_distance(q))
+            .fold(f64::INFINITY, f64::min)
+    };
+    // shortcut paths touch obstacles
+    let path = vec![vec![-2.0, 0.0], vec![0.0, 0.55], vec![2.0, 0.0]];
+    let resampled = resample(&path, 20);
+    assert_eq!(resampled.len(), 20);
+    assert_eq!(
+        resample(&[vec![0.0], vec![1.0]], 5),
+        vec![vec![0.0], vec![0.25], vec![0.5], vec![0.75], vec![1.0]]
+    );
+    let smoothed = chomp_smooth(&path, signed_distance, &ChompOptions::new(20, 0.5)).unwrap();
+    assert_eq!(smoothed.len(), 20);
+    assert_eq!(smoothed[0], vec![-2.0, 0.0]);
+    assert_eq!(smoothed[19], vec![2.0, 0.0]);
+    assert!(
+        min_distance(&smoothed) > min_distance(&resampled) + 0.1,
+        "{}",
+        min_distance(&smoothed)
+    );
+}
@@ -0,0 +1,240 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{CompoundSpace, RealVectorSpace, StateSpace};
+use num_traits::float::Float;
+use std::fmt::Debug;
+
+/// Boxed validity checker of one robot, for [`CompositeSpace::is_free`]
+pub type BoxedIsFree<'a, N> = Box<dyn Fn(&[N]) -> bool + 'a>;
+
+/// Boxed sampler of one robot, for [`CompositeSpace::sampler`]
+pub type BoxedSampler<'a, N> = Box<dyn Fn() -> Vec<N> + 'a>;
+
+/// Joint configuration space of several robots, whose configuration is the
+/// concatenation of the configurations of each robot.
+///
+/// This is a [`CompoundSpace`] of a [`RealVectorSpace`] for each robot, so the distance
+/// is the weighted sum of the distances of the robots, and it can be used with
+/// [`dual_rrt_connect_in_space`](crate::dual_rrt_connect_in_space).
+///
+/// ```
+/// let space = rrt::CompositeSpace::new(&[2, 2]);
+/// let is_free = space.is_free(
+///     vec![Box::new(|_: &[f64]| true), Box::new(|_: &[f64]| true)],
+///     // robots must be 0.5 apart from each other
+///     |_, a: &[f64], _, b: &[f64]| (a[0] - b[0]).hypot(a[1] - b[1]) > 0.5,
+/// );
+/// assert!(is_free(&space.concat(&[&[0.0, 0.0], &[1.0, 0.0]])));
+/// assert!(!is_free(&space.concat(&[&[0.0, 0.0], &[0.1, 0.0]])));
+/// ```
+#[derive(Debug)]
+pub struct CompositeSpace<N> {
+    dims: Vec<usize>,
+    space: CompoundSpace<N>,
+}
+
+impl<N> CompositeSpace<N>
+where
+    N: Float + Debug + 'static,
+{
+    /// Creates a space of robots whose configurations have the given dimensions,
+    /// whose distances are weighted equally.
+    pub fn new(dims: &[usize]) -> Self {
+        Self::with_weights(dims, &vec![N::one(); dims.len()]).unwrap()
+    }
+
+    /// Creates a space of robots whose configurations have the given dimensions,
+    /// and whose distances are multiplied by the given weights, e.g. to balance a
+    /// slow robot with a fast one.
+    ///
+    /// Returns an error if the numbers of the dimensions and the weights are different,
+    /// or a weight is not finite and positive.
+    pub fn with_weights(dims: &[usize], weights: &[N]) -> Result<Self, String> {
+        if dims.len() != weights.len() {
+            return Err(format!(
+                "{} robots, but {} weights",
+                dims.len(),
+                weights.len()
+            ));
+        }
+        if let Some(weight) = weights.iter().find(|w| !w.is_finite() || **w <= N::zero()) {
+            return Err(format!("weight must be finite and positive: {weight:?}"));
+        }
+        let space = dims
+            .iter()
+            .zip(weights)
+            .fold(CompoundSpace::new(), |space, (dim, weight)| {
+                space.with_component(RealVectorSpace::new(*dim), *weight)
+            });
+        Ok(Self {
+            dims: dims.to_vec(),
+            space,
+        })
+    }
+
+    /// Returns the number of robots.
+    pub fn num_robots(&self) -> usize {
+        self.dims.len()
+    }
+
+    /// Returns the dimension of the joint configuration.
+    pub fn dim(&self) -> usize {
+        self.dims.iter().sum()
+    }
+
+    /// Splits the joint configuration into the configuration of each robot.
+    pub fn split<'a>(&self, q: &'a [N]) -> Vec<&'a [N]> {
+        split(&self.dims, q)
+    }
+
+    /// Concatenates the configuration of each robot into the joint configuration.
+    pub fn concat(&self, qs: &[&[N]]) -> Vec<N> {
+        assert_eq!(qs.len(), self.num_robots());
+        for (q, dim) in qs.iter().zip(&self.dims) {
+            assert_eq!(q.len(), *dim);
+        }
+        qs.concat()
+    }
+
+    /// Returns a sampler of the joint configuration, which concatenates a sample
+    /// of each robot.
+    pub fn sampler<'a>(&self, samplers: Vec<BoxedSampler<'a, N>>) -> impl Fn() -> Vec<N> + 'a {
+        assert_eq!(samplers.len(), self.num_robots());
+        move || samplers.iter().flat_map(|sample| sample()).collect()
+    }
+
+    /// Returns a validity checker of the joint configuration.
+    ///
+    /// The configuration is free if each robot is free by its own checker and every
+    /// pair of robots `(i, q_i, j, q_j)` (`i < j`) is free by `is_pair_free`.
+    pub fn is_free<'a, FP>(
+        &self,
+        is_free: Vec<BoxedIsFree<'a, N>>,
+        is_pair_free: FP,
+    ) -> impl Fn(&[N]) -> bool + 'a
+    where
+        FP: Fn(usize, &[N], usize, &[N]) -> bool + 'a,
+    {
+        assert_eq!(is_free.len(), self.num_robots());
+        let dims = self.dims.clone();
+        move |q: &[N]| {
+            let qs = split(&dims, q);
+            qs.iter().zip(&is_free).all(|(q, f)| f(q))
+                && (0..qs.len())
+                    .all(|i| ((i + 1)..qs.len()).all(|j| is_pair_free(i, qs[i], j, qs[j])))
+        }
+    }
+}
+
+/// The distance is the weighted sum of the euclidean distances of the robots.
+impl<N> StateSpace<N> for CompositeSpace<N>
+where
+    N: Float,
+{
+    fn dimension(&self) -> usize {
+        self.space.dimension()
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        assert_eq!(a.len(), self.space.dimension());
+        self.space.distance(a, b)
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        self.space.interpolate(from, to, t)
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        self.space.enforce_bounds(q)
+    }
+}
+
+fn split<'a, N>(dims: &[usize], q: &'a [N]) -> Vec<&'a [N]> {
+    assert_eq!(q.len(), dims.iter().sum::<usize>());
+    let mut rest = q;
+    dims.iter()
+        .map(|dim| {
+            let (head, tail) = rest.split_at(*dim);
+            rest = tail;
+            head
+        })
+        .collect()
+}
+
+#[test]
+fn composite_space_works() {
+    let space = CompositeSpace::<f64>::new(&[2, 1]);
+    assert_eq!(space.num_robots(), 2);
+    assert_eq!(space.dim(), 3);
+    assert_eq!(space.dimension(), 3);
+    let q = space.concat(&[&[1.0, 2.0], &[3.0]]);
+    assert_eq!(q, vec![1.0, 2.0, 3.0]);
+    assert_eq!(space.split(&q), vec![&[1.0, 2.0][..], &[3.0][..]]);
+    assert_eq!(space.distance(&[0.0, 0.0, 0.0], &[3.0, 4.0, -1.0]), 6.0);
+    assert_eq!(
+        space.interpolate(&[0.0, 0.0, 0.0], &[3.0, 4.0, -1.0], 0.5),
+        vec![1.5, 2.0, -0.5]
+    );
+
+    let weighted = CompositeSpace::with_weights(&[2, 1], &[0.5, 2.0]).unwrap();
+    assert_eq!(weighted.distance(&[0.0, 0.0, 0.0], &[3.0, 4.0, -1.0]), 4.5);
+    assert!(CompositeSpace::with_weights(&[2, 1], &[1.0]).is_err());
+    assert!(CompositeSpace::with_weights(&[2, 1], &[1.0, 0.0]).is_err());
+    assert!(CompositeSpace::with_weights(&[2, 1], &[1.0, -1.0]).is_err());
+    assert!(CompositeSpace::with_weights(&[2, 1], &[1.0, f64::INFINITY]).is_err());
+    assert!(CompositeSpace::with_weights(&[2, 1], &[f64::NAN, 1.0]).is_err());
+}
+
+#[test]
+fn composite_space_plans_jointly() {
+    use crate::{dual_rrt_connect_in_space, DualRrtConnectOptions};
+    use rand::distributions::{Distribution, Uniform};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    // two robots on a line swap their positions, passing each other in the second axis
+    let space = CompositeSpace::with_weights(&[2, 2], &[1.0, 2.0]).unwrap();
+    let is_free = space.is_free(
+        vec![
+            Box::new(|q: &[f64]| q.iter().all(|v| v.abs() <= 2.0)),
+            Box::new(|q: &[f64]| q.iter().all(|v| v.abs() <= 2.0)),
+        ],
+        |_, a: &[f64], _, b: &[f64]| (a[0] - b[0]).hypot(a[1] - b[1]) > 0.5,
+    );
+    let rng = RefCell::new(rand::rngs::StdRng::seed_from_u64(0));
+    let robot_sampler = || -> BoxedSampler<'_, f64> {
+        Box::new(|| {
+            let between = Uniform::new(-2.0, 2.0);
+            let mut rng = rng.borrow_mut();
+            vec![between.sample(&mut *rng), between.sample(&mut *rng)]
+        })
+    };
+    let sampler = space.sampler(vec![robot_sampler(), robot_sampler()]);
+    let start = space.concat(&[&[-1.0, 0.0], &[1.0, 0.0]]);
+    let goal = space.concat(&[&[1.0, 0.0], &[-1.0, 0.0]]);
+    let path = dual_rrt_connect_in_space(
+        &space,
+        &start,
+        &goal,
+        &is_free,
+        sampler,
+        &DualRrtConnectOptions::new(0.1, 10000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(|q| is_free(q)));
+}
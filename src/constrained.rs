@@ -0,0 +1,200 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{
+    check_start_goal, is_valid_point, options_tree, DualRrtConnectOptions, ExtendStatus, Sampler,
+    Tree,
+};
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Extends the tree from its nearest vertex toward `q_target` by `extend_length`,
+/// projecting the new configuration onto the constraint manifold.
+fn constrained_extend<FF, FP, N>(
+    tree: &mut Tree<N>,
+    q_target: &[N],
+    extend_length: N,
+    is_free: &mut FF,
+    project: &mut FP,
+) -> ExtendStatus
+where
+    FF: FnMut(&[N]) -> bool,
+    FP: FnMut(&mut [N]) -> bool,
+    N: Float + Debug,
+{
+    let nearest_index = tree.get_nearest_index(q_target);
+    let nearest_q = tree.vertex(nearest_index);
+    let dist = tree.distance(nearest_q, q_target);
+    let mut q_new = if dist < extend_length {
+        q_target.to_vec()
+    } else {
+        nearest_q
+            .iter()
+            .zip(q_target)
+            .map(|(near, target)| *near + (*target - *near) * extend_length / dist)
+            .collect::<Vec<_>>()
+    };
+    if !project(&mut q_new) || !is_valid_point(&q_new, tree.dim()) || !is_free(&q_new) {
+        return ExtendStatus::Trapped;
+    }
+    // the projection may move it back, or too far from the nearest vertex
+    let new_dist = tree.distance(&q_new, q_target);
+    if new_dist >= dist
+        || tree.distance(tree.vertex(nearest_index), &q_new) > extend_length + extend_length
+    {
+        return ExtendStatus::Trapped;
+    }
+    let new_index = tree.add_vertex(&q_new);
+    tree.set_parent(nearest_index, new_index);
+    if new_dist < extend_length {
+        ExtendStatus::Reached(new_index)
+    } else {
+        ExtendStatus::Advanced(new_index)
+    }
+}
+
+/// search the path from start to goal on a constraint manifold which is free,
+/// using random_sample function (Constrained Bidirectional RRT, CBiRRT).
+///
+/// `project` moves a configuration onto the constraint manifold in place (e.g. by
+/// iterative inverse kinematics for closed kinematic chains or orientation constraints),
+/// and returns `false` if it fails. Every sample and every new configuration of the
+/// extensions is projected before collision checking, so all vertices of the path
+/// satisfy the constraint. start and goal must be on the manifold.
+/// `tree_bias` and `num_nearest_candidates` of the options are not used.
+pub fn constrained_rrt_connect<FF, FP, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    project: FP,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FP: FnMut(&mut [N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    constrained_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        project,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`constrained_rrt_connect`] which takes all randomness from `rng`.
+pub fn constrained_rrt_connect_with_rng<FF, FP, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut project: FP,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FP: FnMut(&mut [N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    let extend_length = options.extend_length;
+    let mut start_tree = options_tree("start", start.len(), options)?;
+    start_tree.add_vertex(start);
+    let mut goal_tree = options_tree("goal", goal.len(), options)?;
+    goal_tree.add_vertex(goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    for _ in 0..options.num_max_try {
+        let mut q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) || !project(&mut q_rand) {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        if let ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) =
+            constrained_extend(tree_a, &q_rand, extend_length, &mut is_free, &mut project)
+        {
+            let q_new = tree_a.vertex(new_index).to_vec();
+            let reach_index = loop {
+                match constrained_extend(tree_b, &q_new, extend_length, &mut is_free, &mut project)
+                {
+                    ExtendStatus::Trapped => break None,
+                    ExtendStatus::Reached(index) => break Some(index),
+                    ExtendStatus::Advanced(_) => {}
+                }
+            };
+            if let Some(reach_index) = reach_index {
+                debug!("connected with {} vertices", tree_a.len() + tree_b.len());
+                let mut a_all = tree_a.get_until_root(new_index);
+                a_all.reverse();
+                // the vertices which met are within extend_length
+                a_all.push(q_new);
+                a_all.push(tree_b.vertex(reach_index).to_vec());
+                a_all.extend(tree_b.get_until_root(reach_index));
+                if tree_b.name == "start" {
+                    a_all.reverse();
+                }
+                return Ok(a_all);
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn constrained_rrt_connect_works() {
+    let world = crate::scenario::World::square();
+    // on the circle of radius 1.5, avoiding its top
+    let on_circle = |p: &mut [f64]| {
+        let r = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        if r < 1e-9 {
+            return false;
+        }
+        p[0] *= 1.5 / r;
+        p[1] *= 1.5 / r;
+        true
+    };
+    let is_free = |p: &[f64]| !(p[1] > 1.0 && p[0].abs() < 0.5);
+    let path = constrained_rrt_connect(
+        &[-1.5, 0.0],
+        &[1.5, 0.0],
+        is_free,
+        on_circle,
+        world.sampler(1),
+        &DualRrtConnectOptions::new(0.1, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.5, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.5, 0.0]);
+    for q in &path {
+        assert!(((q[0] * q[0] + q[1] * q[1]).sqrt() - 1.5).abs() < 1e-9);
+        assert!(is_free(q));
+    }
+    // the path goes around the bottom
+    assert!(path.iter().any(|q| q[1] < -1.0));
+}
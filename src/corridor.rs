@@ -0,0 +1,205 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::is_edge_free;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Axis-aligned box, which is a free region of a corridor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisAlignedBox<N> {
+    /// lower corner
+    pub lower: Vec<N>,
+    /// upper corner
+    pub upper: Vec<N>,
+}
+
+impl<N> AxisAlignedBox<N>
+where
+    N: Float,
+{
+    /// Returns `true` if `q` is inside the box (including the boundary).
+    pub fn contains(&self, q: &[N]) -> bool {
+        q.iter()
+            .zip(&self.lower)
+            .zip(&self.upper)
+            .all(|((q, l), u)| l <= q && q <= u)
+    }
+}
+
+/// `true` if all points of the lattice with `resolution` spacing in the box are free
+fn is_box_free<FF, N>(lower: &[N], upper: &[N], resolution: N, is_free: &mut FF) -> bool
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    let num_steps = lower
+        .iter()
+        .zip(upper)
+        .map(|(l, u)| ((*u - *l) / resolution).ceil().to_usize().unwrap())
+        .collect::<Vec<_>>();
+    let mut index = vec![0; lower.len()];
+    loop {
+        let q = index
+            .iter()
+            .zip(&num_steps)
+            .zip(lower.iter().zip(upper))
+            .map(|((i, n), (l, u))| {
+                if *n == 0 {
+                    *l
+                } else {
+                    *l + (*u - *l) * N::from(*i).unwrap() / N::from(*n).unwrap()
+                }
+            })
+            .collect::<Vec<_>>();
+        if !is_free(&q) {
+            return false;
+        }
+        // next lattice point
+        let mut dim = 0;
+        loop {
+            if dim == index.len() {
+                return true;
+            }
+            if index[dim] < num_steps[dim] {
+                index[dim] += 1;
+                break;
+            }
+            index[dim] = 0;
+            dim += 1;
+        }
+    }
+}
+
+/// inflate the path into a sequence of free axis-aligned boxes (a corridor),
+/// for trajectory optimizers which need free regions instead of a polyline.
+///
+/// Consecutive boxes overlap, and every point of the path is inside a box.
+/// Boxes grow from the path by `resolution` steps up to `max_inflation` in each
+/// direction, as long as all lattice points of `resolution` spacing in them are free.
+/// Returns an error if the path itself is not free at `resolution`.
+pub fn extract_corridor<FF, N>(
+    path: &[Vec<N>],
+    mut is_free: FF,
+    resolution: N,
+    max_inflation: N,
+) -> Result<Vec<AxisAlignedBox<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    assert!(resolution > N::zero());
+    if path.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    // points on the path at resolution intervals
+    let mut points = vec![path[0].clone()];
+    for segment in path.windows(2) {
+        is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut |q: &[N]| {
+                points.push(q.to_vec());
+                true
+            },
+            resolution,
+        );
+        points.push(segment[1].clone());
+    }
+    let num_inflation_steps = (max_inflation / resolution).floor().to_usize().unwrap();
+    let mut boxes = Vec::new();
+    let mut i = 0;
+    while i < points.len() {
+        let seed = &points[i..(i + 2).min(points.len())];
+        let mut lower = seed[0].clone();
+        let mut upper = seed[0].clone();
+        for q in seed {
+            for (k, v) in q.iter().enumerate() {
+                lower[k] = lower[k].min(*v);
+                upper[k] = upper[k].max(*v);
+            }
+        }
+        if !is_box_free(&lower, &upper, resolution, &mut is_free) {
+            return Err(format!("path is not free around {:?}", points[i]));
+        }
+        let mut growing = vec![[true; 2]; lower.len()];
+        for _ in 0..num_inflation_steps {
+            for (k, growing) in growing.iter_mut().enumerate() {
+                for (side, growing) in growing.iter_mut().enumerate() {
+                    if !*growing {
+                        continue;
+                    }
+                    // check only the newly added slab
+                    let mut slab_lower = lower.clone();
+                    let mut slab_upper = upper.clone();
+                    if side == 0 {
+                        slab_lower[k] = lower[k] - resolution;
+                        slab_upper[k] = lower[k] - resolution;
+                    } else {
+                        slab_lower[k] = upper[k] + resolution;
+                        slab_upper[k] = upper[k] + resolution;
+                    }
+                    if is_box_free(&slab_lower, &slab_upper, resolution, &mut is_free) {
+                        if side == 0 {
+                            lower[k] = slab_lower[k];
+                        } else {
+                            upper[k] = slab_upper[k];
+                        }
+                    } else {
+                        *growing = false;
+                    }
+                }
+            }
+        }
+        let region = AxisAlignedBox { lower, upper };
+        debug!("corridor box {region:?}");
+        // the next box starts from the last point inside this box, to overlap
+        let mut next = i + 1;
+        while next + 1 < points.len() && region.contains(&points[next + 1]) {
+            next += 1;
+        }
+        boxes.push(region);
+        i = if next + 1 >= points.len() {
+            points.len()
+        } else {
+            next
+        };
+    }
+    Ok(boxes)
+}
+
+#[test]
+fn corridor_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = vec![vec![-1.5, 0.0], vec![-1.5, 1.5], vec![1.5, 1.5]];
+    let corridor = extract_corridor(&path, is_free, 0.1, 0.5).unwrap();
+    assert!(corridor.len() >= 2);
+    for q in &path {
+        assert!(corridor.iter().any(|b| b.contains(q)));
+    }
+    for b in corridor.windows(2) {
+        // consecutive boxes overlap
+        assert!((0..2).all(|k| b[0].lower[k] <= b[1].upper[k] && b[1].lower[k] <= b[0].upper[k]));
+    }
+    for b in &corridor {
+        assert!(is_box_free(&b.lower, &b.upper, 0.05, &mut |q: &[f64]| {
+            is_free(q)
+        }));
+    }
+    assert!(extract_corridor(&[vec![-1.5, 0.0], vec![1.5, 0.0]], is_free, 0.1, 0.5).is_err());
+}
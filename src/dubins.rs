@@ -0,0 +1,371 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::hybrid_astar::{drive, normalize_angle};
+use crate::Steering;
+use num_traits::float::Float;
+use num_traits::FloatConst;
+
+/// type of a segment of [`CarPath`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentType {
+    /// arc turning left at the minimum turning radius
+    Left,
+    /// straight line
+    Straight,
+    /// arc turning right at the minimum turning radius
+    Right,
+}
+
+impl SegmentType {
+    fn curvature<N: Float>(self, radius: N) -> N {
+        match self {
+            SegmentType::Left => radius.recip(),
+            SegmentType::Straight => N::zero(),
+            SegmentType::Right => -radius.recip(),
+        }
+    }
+}
+
+/// path of a car-like vehicle from a pose `[x, y, heading]`, made of arcs at the
+/// minimum turning radius and straight lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarPath<N> {
+    start: Vec<N>,
+    radius: N,
+    segments: Vec<(SegmentType, N)>,
+}
+
+impl<N> CarPath<N>
+where
+    N: Float + FloatConst,
+{
+    pub(crate) fn new(start: &[N], radius: N, segments: Vec<(SegmentType, N)>) -> Self {
+        Self {
+            start: start.to_vec(),
+            radius,
+            segments,
+        }
+    }
+
+    /// Returns the pose where the path starts.
+    pub fn start(&self) -> &[N] {
+        &self.start
+    }
+
+    /// Returns the segments and their lengths, which are negative when driving backward.
+    pub fn segments(&self) -> &[(SegmentType, N)] {
+        &self.segments
+    }
+
+    /// Returns the path approximately driven along `states`, e.g. a trajectory of
+    /// [`dual_rrt_connect_steering`](crate::dual_rrt_connect_steering) with
+    /// [`Dubins`] or [`ReedsShepp`](crate::ReedsShepp), to report its segments and gear
+    /// changes.
+    ///
+    /// Each step between the states is classified by the change of the heading and the
+    /// direction of the motion, and the consecutive steps of the same type and direction
+    /// are merged into a segment. The boundaries of the segments are accurate within the
+    /// intervals of the states.
+    pub fn from_states(states: &[Vec<N>], radius: N) -> Self {
+        let mut segments: Vec<(SegmentType, N)> = Vec::new();
+        for step in states.windows(2) {
+            let (a, b) = (&step[0], &step[1]);
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let turn = normalize_angle(b[2] - a[2]);
+            let direction = (a[2].cos() * dx + a[2].sin() * dy).signum();
+            let (segment, length) = if turn.abs() <= N::epsilon().sqrt() {
+                (SegmentType::Straight, dx.hypot(dy))
+            } else if turn * direction > N::zero() {
+                (SegmentType::Left, turn.abs() * radius)
+            } else {
+                (SegmentType::Right, turn.abs() * radius)
+            };
+            if length <= N::zero() {
+                continue;
+            }
+            let length = length * direction;
+            match segments.last_mut() {
+                Some((last, last_length))
+                    if *last == segment && last_length.signum() == direction =>
+                {
+                    *last_length = *last_length + length;
+                }
+                _ => segments.push((segment, length)),
+            }
+        }
+        Self::new(
+            states.first().map(|q| q.as_slice()).unwrap_or(&[]),
+            radius,
+            segments,
+        )
+    }
+
+    /// Returns the number of the changes between driving forward and backward.
+    pub fn num_gear_changes(&self) -> usize {
+        self.segments
+            .windows(2)
+            .filter(|s| s[0].1.signum() != s[1].1.signum())
+            .count()
+    }
+
+    /// Returns the total length driven along the path.
+    pub fn length(&self) -> N {
+        self.segments
+            .iter()
+            .fold(N::zero(), |sum, (_, length)| sum + length.abs())
+    }
+
+    /// Returns the pose after driving `s` (0.0 - [`length`](Self::length)) along the path,
+    /// whose heading is normalized to [-pi, pi).
+    pub fn point_at(&self, s: N) -> Vec<N> {
+        let mut pose = self.start.clone();
+        let mut rest = s.max(N::zero());
+        for (segment, length) in &self.segments {
+            let driven = length.abs().min(rest);
+            pose = drive(
+                &pose,
+                segment.curvature(self.radius),
+                driven * length.signum(),
+            );
+            rest = rest - driven;
+            if rest <= N::zero() {
+                break;
+            }
+        }
+        pose[2] = normalize_angle(pose[2]);
+        pose
+    }
+
+    /// Returns the poses along the path at intervals of at most `resolution`,
+    /// excluding the start and including the end.
+    pub fn states(&self, resolution: N) -> Vec<Vec<N>> {
+        assert!(resolution > N::zero());
+        let length = self.length();
+        let num_steps = (length / resolution).ceil().to_usize().unwrap().max(1);
+        (1..=num_steps)
+            .map(|i| self.point_at(length * N::from(i).unwrap() / N::from(num_steps).unwrap()))
+            .collect()
+    }
+}
+
+/// Returns `angle` normalized to [0, 2 pi).
+pub(crate) fn mod2pi<N: Float + FloatConst>(angle: N) -> N {
+    let two_pi = N::PI() + N::PI();
+    let a = angle % two_pi;
+    if a < N::zero() {
+        a + two_pi
+    } else {
+        a
+    }
+}
+
+/// Dubins curves, the shortest paths of a car which drives only forward with the minimum
+/// turning radius, as the [`Steering`] of car-like robots.
+///
+/// Poses are `[x, y, heading]`. The distance is the length of the shortest path, so
+/// [`dual_rrt_connect_steering`](crate::dual_rrt_connect_steering) returns the
+/// trajectories which the car can drive.
+#[derive(Debug, Clone)]
+pub struct Dubins<N> {
+    /// minimum turning radius of the car
+    pub radius: N,
+    /// max distance between the states returned by [`Steering::steer`]
+    pub resolution: N,
+}
+
+impl<N> Dubins<N>
+where
+    N: Float + FloatConst,
+{
+    /// Creates the curves with the resolution of `radius / 10`.
+    pub fn new(radius: N) -> Self {
+        assert!(radius > N::zero());
+        Self {
+            radius,
+            resolution: radius / N::from(10.0).unwrap(),
+        }
+    }
+
+    /// Returns the shortest path from `from` to `to` of the 6 types (LSL, RSR, LSR, RSL,
+    /// RLR and LRL).
+    pub fn shortest_path(&self, from: &[N], to: &[N]) -> CarPath<N> {
+        use SegmentType::*;
+        let two = N::from(2.0).unwrap();
+        let dx = (to[0] - from[0]) / self.radius;
+        let dy = (to[1] - from[1]) / self.radius;
+        let d = dx.hypot(dy);
+        let theta = if d > N::epsilon() {
+            dy.atan2(dx)
+        } else {
+            N::zero()
+        };
+        let a = mod2pi(from[2] - theta);
+        let b = mod2pi(to[2] - theta);
+        let (sa, ca, sb, cb) = (a.sin(), a.cos(), b.sin(), b.cos());
+        let cab = (a - b).cos();
+        let d2 = d * d;
+        let mut words = Vec::new();
+        // LSL
+        let p2 = two + d2 - two * cab + two * d * (sa - sb);
+        if p2 >= N::zero() {
+            let tmp = (cb - ca).atan2(d + sa - sb);
+            words.push((
+                [Left, Straight, Left],
+                mod2pi(tmp - a),
+                p2.sqrt(),
+                mod2pi(b - tmp),
+            ));
+        }
+        // RSR
+        let p2 = two + d2 - two * cab + two * d * (sb - sa);
+        if p2 >= N::zero() {
+            let tmp = (ca - cb).atan2(d - sa + sb);
+            words.push((
+                [Right, Straight, Right],
+                mod2pi(a - tmp),
+                p2.sqrt(),
+                mod2pi(tmp - b),
+            ));
+        }
+        // LSR
+        let p2 = -two + d2 + two * cab + two * d * (sa + sb);
+        if p2 >= N::zero() {
+            let p = p2.sqrt();
+            let tmp = (-ca - cb).atan2(d + sa + sb) - (-two).atan2(p);
+            words.push(([Left, Straight, Right], mod2pi(tmp - a), p, mod2pi(tmp - b)));
+        }
+        // RSL
+        let p2 = -two + d2 + two * cab - two * d * (sa + sb);
+        if p2 >= N::zero() {
+            let p = p2.sqrt();
+            let tmp = (ca + cb).atan2(d - sa - sb) - two.atan2(p);
+            words.push(([Right, Straight, Left], mod2pi(a - tmp), p, mod2pi(b - tmp)));
+        }
+        let eight = N::from(8.0).unwrap();
+        let six = N::from(6.0).unwrap();
+        // RLR
+        let tmp = (six - d2 + two * cab + two * d * (sa - sb)) / eight;
+        if tmp.abs() <= N::one() {
+            let p = mod2pi(N::PI() + N::PI() - tmp.acos());
+            let t = mod2pi(a - (ca - cb).atan2(d - sa + sb) + p / two);
+            words.push(([Right, Left, Right], t, p, mod2pi(a - b - t + p)));
+        }
+        // LRL
+        let tmp = (six - d2 + two * cab + two * d * (sb - sa)) / eight;
+        if tmp.abs() <= N::one() {
+            let p = mod2pi(N::PI() + N::PI() - tmp.acos());
+            let t = mod2pi(-a - (ca - cb).atan2(d + sa - sb) + p / two);
+            words.push(([Left, Right, Left], t, p, mod2pi(b - a - t + p)));
+        }
+        let (types, t, p, q) = words
+            .into_iter()
+            .min_by(|x, y| {
+                (x.1 + x.2 + x.3)
+                    .partial_cmp(&(y.1 + y.2 + y.3))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            // LSL always exists
+            .unwrap();
+        CarPath::new(
+            from,
+            self.radius,
+            types
+                .into_iter()
+                .zip([t, p, q])
+                .map(|(segment, length)| (segment, length * self.radius))
+                .collect(),
+        )
+    }
+}
+
+impl<N> Steering<N> for Dubins<N>
+where
+    N: Float + FloatConst,
+{
+    fn distance(&self, from: &[N], to: &[N]) -> N {
+        self.shortest_path(from, to).length()
+    }
+
+    fn steer(&self, from: &[N], to: &[N]) -> Option<Vec<Vec<N>>> {
+        let mut states = self.shortest_path(from, to).states(self.resolution);
+        // remove the numerical errors at the end
+        *states.last_mut()? = to.to_vec();
+        Some(states)
+    }
+}
+
+#[test]
+fn dubins_works() {
+    use crate::{dual_rrt_connect_steering, DualRrtConnectOptions, UniformBoxSampler};
+    use rand::Rng;
+    use std::f64::consts::PI;
+    let dubins = Dubins::new(1.0);
+    assert!((dubins.distance(&[0.0, 0.0, 0.0], &[4.0, 0.0, 0.0]) - 4.0).abs() < 1e-9);
+    // a half circle to the left
+    let path = dubins.shortest_path(&[0.0, 0.0, 0.0], &[0.0, 2.0, PI]);
+    assert!((path.length() - PI).abs() < 1e-9);
+    assert_eq!(path.segments()[0].0, SegmentType::Left);
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let mut pose = || {
+            vec![
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-PI..PI),
+            ]
+        };
+        let (from, to) = (pose(), pose());
+        let path = dubins.shortest_path(&from, &to);
+        let end = path.point_at(path.length());
+        assert!(
+            (end[0] - to[0]).hypot(end[1] - to[1]) < 1e-6,
+            "{from:?} {to:?}"
+        );
+        assert!(normalize_angle(end[2] - to[2]).abs() < 1e-6);
+        assert!(path.segments().iter().all(|(_, length)| *length >= 0.0));
+        assert!(path.length() >= (to[0] - from[0]).hypot(to[1] - from[1]) - 1e-9);
+    }
+
+    let is_free =
+        |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0) && p[0].abs() < 4.0 && p[1].abs() < 4.0;
+    let start = [-2.0, 0.0, PI / 2.0];
+    let goal = [2.0, 0.0, -PI / 2.0];
+    let dubins = Dubins::new(0.5);
+    let path = dual_rrt_connect_steering(
+        &start,
+        &goal,
+        is_free,
+        &dubins,
+        UniformBoxSampler::new(&[-4.0, -4.0, -PI], &[4.0, 4.0, PI]).unwrap(),
+        &DualRrtConnectOptions::new(1.0, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        let (a, b) = (&segment[0], &segment[1]);
+        let step = (b[0] - a[0]).hypot(b[1] - a[1]);
+        assert!(step <= 0.05 + 1e-6);
+        // forward within the turning radius, as the arc of the step is at most 0.05
+        let turn = normalize_angle(b[2] - a[2]).abs();
+        assert!(turn <= 0.05 / 0.5 + 1e-6);
+        assert!(step >= 2.0 * 0.5 * (turn / 2.0).sin() - 1e-6);
+        let heading = (b[1] - a[1]).atan2(b[0] - a[0]);
+        assert!(step < 1e-9 || normalize_angle(heading - a[2]).abs() < PI / 2.0);
+    }
+}
@@ -0,0 +1,206 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, ExtendStatus, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Parameters of [`dynamic_domain_rrt_connect`].
+#[derive(Debug, Clone)]
+pub struct DynamicDomainOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// sampling radius given to a vertex at its first failed extension
+    pub initial_radius: N,
+    /// rate (0.0 - 1.0) to shrink the radius after a failure, and to grow it after a success
+    pub alpha: N,
+}
+
+impl<N> DynamicDomainOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with the initial radius of 4 * `extend_length` and 0.1 alpha.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            initial_radius: extend_length * N::from(4.0).unwrap(),
+            alpha: N::from(0.1).unwrap(),
+        }
+    }
+}
+
+/// tree with the sampling radius of each vertex, infinite until an extension fails
+#[derive(Debug)]
+struct DomainTree<N>
+where
+    N: Float + Debug,
+{
+    tree: Tree<N>,
+    radii: Vec<N>,
+}
+
+impl<N> DomainTree<N>
+where
+    N: Float + Debug,
+{
+    fn new(name: &'static str, root: &[N]) -> Self {
+        let mut tree = Tree::with_name(name, root.len());
+        tree.add_vertex(root);
+        Self {
+            tree,
+            radii: vec![N::infinity()],
+        }
+    }
+    fn sync_radii(&mut self) {
+        self.radii.resize(self.tree.len(), N::infinity());
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function
+/// (Dynamic-Domain RRT connect).
+///
+/// Each vertex keeps a sampling radius, which is set when an extension from it fails
+/// and shrunk by `alpha` at every further failure, so samples far from the vertices
+/// near obstacles are rejected. This keeps the trees growing in narrow passages
+/// instead of wasting samples against the walls.
+pub fn dynamic_domain_rrt_connect<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DynamicDomainOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dynamic_domain_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dynamic_domain_rrt_connect`] which takes all randomness from `rng`.
+pub fn dynamic_domain_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DynamicDomainOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    assert!(options.initial_radius > N::zero());
+    assert!(options.alpha > N::zero() && options.alpha < N::one());
+    let mut start_tree = DomainTree::new("start", start);
+    let mut goal_tree = DomainTree::new("goal", goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    let mut num_rejected = 0;
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) {
+            continue;
+        }
+        let nearest_index = tree_a.tree.get_nearest_index(&q_rand);
+        let radius = tree_a.radii[nearest_index];
+        if squared_euclidean(tree_a.tree.vertex(nearest_index), &q_rand).sqrt() > radius {
+            num_rejected += 1;
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        match tree_a
+            .tree
+            .extend_from(nearest_index, &q_rand, options.extend_length, &mut is_free)
+        {
+            ExtendStatus::Trapped => {
+                tree_a.radii[nearest_index] = if radius.is_finite() {
+                    (radius * (N::one() - options.alpha)).max(options.extend_length)
+                } else {
+                    options.initial_radius
+                };
+            }
+            ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) => {
+                if radius.is_finite() {
+                    tree_a.radii[nearest_index] = radius * (N::one() + options.alpha);
+                }
+                tree_a.sync_radii();
+                let q_new = tree_a.tree.vertex(new_index).to_vec();
+                let status = tree_b
+                    .tree
+                    .connect(&q_new, options.extend_length, &mut is_free);
+                tree_b.sync_radii();
+                if let ExtendStatus::Reached(reach_index) = status {
+                    debug!("{num_rejected} samples were rejected by the domains");
+                    let mut a_all = tree_a.tree.get_until_root(new_index);
+                    let mut b_all = tree_b.tree.get_until_root(reach_index);
+                    a_all.reverse();
+                    // the vertices which met are within extend_length
+                    a_all.push(q_new);
+                    a_all.push(tree_b.tree.vertex(reach_index).to_vec());
+                    a_all.append(&mut b_all);
+                    if tree_b.tree.name == "start" {
+                        a_all.reverse();
+                    }
+                    return Ok(a_all);
+                }
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn dynamic_domain_rrt_connect_works() {
+    let world = crate::scenario::World::square();
+    // a wall at x = 0 with a narrow gap around y = 0
+    let is_free = |p: &[f64]| !(p[0].abs() < 0.1 && p[1].abs() > 0.1);
+    let path = dynamic_domain_rrt_connect(
+        &[-1.0, 0.5],
+        &[1.0, -0.5],
+        is_free,
+        world.sampler(1),
+        &DynamicDomainOptions::new(0.05, 20000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.0, 0.5]);
+    assert_eq!(path[path.len() - 1], vec![1.0, -0.5]);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        assert!(squared_euclidean(&segment[0], &segment[1]).sqrt() <= 0.05 + 1e-9);
+    }
+}
@@ -0,0 +1,267 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{is_edge_free, is_valid_point};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`ElasticBand`].
+#[derive(Debug, Clone)]
+pub struct ElasticBandOptions<N> {
+    /// gain of the internal force which pulls each node toward its neighbors
+    pub contraction_gain: N,
+    /// gain of the external force which pushes the nodes away from obstacles
+    pub repulsion_gain: N,
+    /// obstacles farther than this clearance do not push the nodes
+    pub influence_distance: N,
+    /// size of the moves of the nodes in each iteration, relative to the forces
+    pub step_size: N,
+    /// nodes are inserted to keep the gaps between them within this distance
+    pub max_spacing: N,
+    /// number of iterations of each [`ElasticBand::update`]
+    pub num_iterations: usize,
+}
+
+impl<N> ElasticBandOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with unit gains, the step size of 0.2, and 10 iterations of
+    /// each update.
+    pub fn new(max_spacing: N, influence_distance: N) -> Self {
+        Self {
+            contraction_gain: N::one(),
+            repulsion_gain: N::one(),
+            influence_distance,
+            step_size: N::from(0.2).unwrap(),
+            max_spacing,
+            num_iterations: 10,
+        }
+    }
+}
+
+/// Elastic band, which deforms a planned path to avoid obstacles which change
+/// while the robot moves, without replanning.
+///
+/// The nodes of the band are pulled toward their neighbors by the internal
+/// contraction force, which shortens the path, and pushed away from obstacles by the
+/// external repulsion force, computed from `clearance` (distance from the configuration
+/// to the nearest obstacle, zero or negative in collision) with its gradient by central
+/// difference. Nodes are inserted and removed to keep the band dense and short. The
+/// first and the last node are not moved by the forces.
+#[derive(Debug, Clone)]
+pub struct ElasticBand<N> {
+    nodes: Vec<Vec<N>>,
+    options: ElasticBandOptions<N>,
+}
+
+impl<N> ElasticBand<N>
+where
+    N: Float + Debug,
+{
+    /// Creates a band along `path`, which is densified to `max_spacing`.
+    pub fn new(path: &[Vec<N>], options: ElasticBandOptions<N>) -> Result<Self, String> {
+        if path.len() < 2 {
+            return Err("path must have start and goal".to_string());
+        }
+        if let Some(q) = path.iter().find(|q| !is_valid_point(q, path[0].len())) {
+            return Err(format!("invalid point {q:?}"));
+        }
+        assert!(options.max_spacing > N::zero());
+        assert!(options.influence_distance > N::zero());
+        let mut band = Self {
+            nodes: path.to_vec(),
+            options,
+        };
+        band.insert_nodes();
+        Ok(band)
+    }
+
+    /// Returns the current path of the band.
+    pub fn path(&self) -> &[Vec<N>] {
+        &self.nodes
+    }
+
+    /// Moves the first node to `q`, e.g. to the current configuration of the robot,
+    /// and removes the nodes which the robot has passed.
+    pub fn set_start(&mut self, q: &[N]) -> Result<(), String> {
+        if !is_valid_point(q, self.nodes[0].len()) {
+            return Err(format!("invalid start {q:?}"));
+        }
+        // the node nearest to q and the ones before it are passed
+        let nearest = (0..(self.nodes.len() - 1))
+            .min_by(|a, b| {
+                squared_euclidean(&self.nodes[*a], q)
+                    .partial_cmp(&squared_euclidean(&self.nodes[*b], q))
+                    .unwrap()
+            })
+            .unwrap();
+        self.nodes.drain(0..nearest);
+        self.nodes[0] = q.to_vec();
+        self.insert_nodes();
+        Ok(())
+    }
+
+    /// Deforms the band with `num_iterations` steps of the forces from the current
+    /// obstacles.
+    ///
+    /// Returns an error if a node or a gap of the band is in collision, e.g. because an
+    /// obstacle moved onto it, and then the path should be planned again.
+    pub fn update<FC>(&mut self, mut clearance: FC) -> Result<(), String>
+    where
+        FC: FnMut(&[N]) -> N,
+    {
+        let h = self.options.influence_distance / N::from(100.0).unwrap();
+        for _ in 0..self.options.num_iterations {
+            for i in 1..(self.nodes.len() - 1) {
+                let q = &self.nodes[i];
+                let d = clearance(q);
+                if d <= N::zero() {
+                    continue;
+                }
+                let mut force = q
+                    .iter()
+                    .zip(&self.nodes[i - 1])
+                    .zip(&self.nodes[i + 1])
+                    .map(|((v, prev), next)| {
+                        self.options.contraction_gain * (*prev + *next - *v - *v)
+                    })
+                    .collect::<Vec<_>>();
+                if d < self.options.influence_distance {
+                    let mut q_h = q.clone();
+                    for (k, f) in force.iter_mut().enumerate() {
+                        q_h[k] = q[k] + h;
+                        let d_plus = clearance(&q_h);
+                        q_h[k] = q[k] - h;
+                        let d_minus = clearance(&q_h);
+                        q_h[k] = q[k];
+                        *f = *f
+                            + self.options.repulsion_gain
+                                * (self.options.influence_distance - d)
+                                * (d_plus - d_minus)
+                                / (h + h);
+                    }
+                }
+                let q_new = q
+                    .iter()
+                    .zip(&force)
+                    .map(|(v, f)| *v + self.options.step_size * *f)
+                    .collect::<Vec<_>>();
+                // a move into an obstacle is rejected
+                if clearance(&q_new) > N::zero() {
+                    self.nodes[i] = q_new;
+                }
+            }
+            self.remove_nodes(&mut clearance);
+            self.insert_nodes();
+        }
+        if let Some(q) = self.nodes.iter().find(|q| clearance(q) <= N::zero()) {
+            return Err(format!("band is in collision at {q:?}"));
+        }
+        let resolution = self.options.max_spacing / N::from(10.0).unwrap();
+        let mut is_free = |q: &[N]| clearance(q) > N::zero();
+        if self
+            .nodes
+            .windows(2)
+            .any(|s| !is_edge_free(&s[0], &s[1], &mut is_free, resolution))
+        {
+            return Err("band is in collision".to_string());
+        }
+        debug!("{} nodes", self.nodes.len());
+        Ok(())
+    }
+
+    /// Inserts nodes at the middle of the gaps longer than `max_spacing`.
+    fn insert_nodes(&mut self) {
+        let mut i = 0;
+        while i + 1 < self.nodes.len() {
+            if squared_euclidean(&self.nodes[i], &self.nodes[i + 1]).sqrt()
+                > self.options.max_spacing
+            {
+                let middle = self.nodes[i]
+                    .iter()
+                    .zip(&self.nodes[i + 1])
+                    .map(|(a, b)| (*a + *b) / N::from(2.0).unwrap())
+                    .collect();
+                self.nodes.insert(i + 1, middle);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Removes the nodes whose neighbors are within `max_spacing` and connected by
+    /// a free edge.
+    fn remove_nodes<FC>(&mut self, clearance: &mut FC)
+    where
+        FC: FnMut(&[N]) -> N,
+    {
+        let resolution = self.options.max_spacing / N::from(10.0).unwrap();
+        let mut i = 1;
+        while i + 1 < self.nodes.len() {
+            let (prev, next) = (&self.nodes[i - 1], &self.nodes[i + 1]);
+            if squared_euclidean(prev, next).sqrt() < self.options.max_spacing
+                && is_edge_free(
+                    prev,
+                    next,
+                    &mut |q: &[N]| clearance(q) > N::zero(),
+                    resolution,
+                )
+            {
+                self.nodes.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn elastic_band_works() {
+    // disc obstacle of the given center and radius 0.2
+    let clearance_of =
+        |center: [f64; 2]| move |p: &[f64]| (p[0] - center[0]).hypot(p[1] - center[1]) - 0.2;
+    let min_clearance = |band: &ElasticBand<f64>, center| {
+        band.path()
+            .iter()
+            .map(|q| clearance_of(center)(q))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let options = ElasticBandOptions::new(0.2, 0.5);
+    let mut band = ElasticBand::new(&[vec![-2.0, 0.0], vec![2.0, 0.0]], options).unwrap();
+    assert!(band
+        .path()
+        .windows(2)
+        .all(|s| squared_euclidean(&s[0], &s[1]).sqrt() <= 0.2));
+    // the obstacle moves near the band
+    let center = [0.0, -0.3];
+    let before = min_clearance(&band, center);
+    band.update(clearance_of(center)).unwrap();
+    band.update(clearance_of(center)).unwrap();
+    assert!(min_clearance(&band, center) > before + 0.1);
+    assert_eq!(band.path()[0], vec![-2.0, 0.0]);
+    assert_eq!(band.path()[band.path().len() - 1], vec![2.0, 0.0]);
+    // the robot moves
+    band.set_start(&[-1.0, 0.05]).unwrap();
+    assert_eq!(band.path()[0], vec![-1.0, 0.05]);
+    assert!(band.path().iter().all(|q| q[0] >= -1.0));
+    band.update(clearance_of(center)).unwrap();
+    // the obstacle moves onto the band
+    let q = band.path()[band.path().len() / 2].clone();
+    assert!(band.update(clearance_of([q[0], q[1]])).is_err());
+}
@@ -0,0 +1,177 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{dual_rrt_connect_with_rng_fn, DualRrtConnectOptions, Sampler};
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+
+/// Waypoints of previous solutions, which [`errt`] samples to replan quickly.
+///
+/// Keep it across planning calls while the environment changes only slightly.
+#[derive(Debug, Clone)]
+pub struct WaypointCache<N> {
+    waypoints: Vec<Vec<N>>,
+    capacity: usize,
+    /// probability (0.0 - 1.0) to sample a cached waypoint instead of using random_sample
+    pub waypoint_bias: f64,
+}
+
+impl<N> WaypointCache<N>
+where
+    N: Float,
+{
+    /// Creates an empty cache which keeps at most `capacity` waypoints, with 0.5 waypoint bias.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            waypoints: Vec::new(),
+            capacity,
+            waypoint_bias: 0.5,
+        }
+    }
+    /// Returns the number of cached waypoints.
+    pub fn len(&self) -> usize {
+        self.waypoints.len()
+    }
+    /// Returns `true` if no waypoint is cached.
+    pub fn is_empty(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+    /// Returns the cached waypoints.
+    pub fn waypoints(&self) -> &[Vec<N>] {
+        &self.waypoints
+    }
+    /// Removes all cached waypoints.
+    pub fn clear(&mut self) {
+        self.waypoints.clear();
+    }
+    /// Adds the waypoints of the path. If the cache is full, random waypoints are replaced.
+    pub fn add_path(&mut self, path: &[Vec<N>]) {
+        self.add_path_with_rng(path, &mut rand::thread_rng());
+    }
+    /// [`Self::add_path`] which takes all randomness from `rng`.
+    pub fn add_path_with_rng<R: Rng>(&mut self, path: &[Vec<N>], rng: &mut R) {
+        if self.capacity == 0 {
+            return;
+        }
+        for q in path {
+            if self.waypoints.len() < self.capacity {
+                self.waypoints.push(q.clone());
+            } else {
+                let index = rng.gen_range(0..self.capacity);
+                self.waypoints[index] = q.clone();
+            }
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function
+/// and the waypoints of previous solutions (Execution-extended RRT).
+///
+/// Samples are taken from `cache` with its `waypoint_bias`, and the waypoints of
+/// the found path are added to `cache` for the next planning.
+pub fn errt<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    cache: &mut WaypointCache<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    errt_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        cache,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`errt`] which takes all randomness from `rng`.
+pub fn errt_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    cache: &mut WaypointCache<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    assert!((0.0..=1.0).contains(&cache.waypoint_bias));
+    let waypoints = &cache.waypoints;
+    let result = dual_rrt_connect_with_rng_fn(
+        start,
+        goal,
+        is_free,
+        |rng: &mut R| {
+            if !waypoints.is_empty() && rng.gen_bool(cache.waypoint_bias) {
+                waypoints[rng.gen_range(0..waypoints.len())].clone()
+            } else {
+                random_sample.sample(rng)
+            }
+        },
+        options,
+        rng,
+        None,
+    );
+    if let Ok(path) = &result {
+        cache.add_path_with_rng(path, rng);
+    }
+    result
+}
+
+#[test]
+fn errt_works() {
+    let world = crate::scenario::World::square();
+    let random_sample = world.sampler(1);
+    let mut cache = WaypointCache::new(20);
+    let options = DualRrtConnectOptions::new(0.2, 1000);
+    let path = errt(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        &random_sample,
+        &options,
+        &mut cache,
+    )
+    .unwrap();
+    assert_eq!(cache.len(), path.len().min(20));
+    // the robot moved, and the obstacle became larger
+    let path = errt(
+        &[-1.3, 0.2],
+        &[1.2, 0.0],
+        |p: &[f64]| !(p[0].abs() < 1.1 && p[1].abs() < 1.1),
+        &random_sample,
+        &options,
+        &mut cache,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.3, 0.2]);
+    assert!(cache.len() <= 20);
+}
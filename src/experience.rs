@@ -0,0 +1,301 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::standard_normal;
+use crate::{is_valid_point, Sampler};
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+
+/// Waypoints of the paths which were solved before, to learn where the solutions pass.
+///
+/// With the `serde` feature, this can be saved and loaded across runs of repetitive tasks.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExperienceDatabase<N> {
+    dim: usize,
+    waypoints: Vec<Vec<N>>,
+}
+
+impl<N> ExperienceDatabase<N>
+where
+    N: Float + Debug,
+{
+    /// Creates an empty database of configurations of `dim` dimensions.
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            waypoints: Vec::new(),
+        }
+    }
+
+    /// Adds the waypoints of a solved path.
+    ///
+    /// Returns an error if a waypoint is not finite or has a wrong dimension.
+    pub fn add_path(&mut self, path: &[Vec<N>]) -> Result<(), String> {
+        if let Some(q) = path.iter().find(|q| !is_valid_point(q, self.dim)) {
+            return Err(format!("invalid waypoint: {q:?}"));
+        }
+        self.waypoints.extend_from_slice(path);
+        Ok(())
+    }
+
+    /// all waypoints
+    pub fn waypoints(&self) -> &[Vec<N>] {
+        &self.waypoints
+    }
+
+    /// dimension of the configurations
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+}
+
+/// Mixture of Gaussian distributions with diagonal covariances.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GaussianMixture<N> {
+    weights: Vec<N>,
+    means: Vec<Vec<N>>,
+    variances: Vec<Vec<N>>,
+}
+
+impl<N> GaussianMixture<N>
+where
+    N: Float + Debug,
+{
+    /// Fits a mixture of `num_components` to the waypoints of `database` by
+    /// `num_iterations` of the EM algorithm.
+    ///
+    /// The components start at evenly spaced waypoints, so the result is deterministic.
+    /// Returns an error if the database has fewer waypoints than `num_components`.
+    pub fn fit(
+        database: &ExperienceDatabase<N>,
+        num_components: usize,
+        num_iterations: usize,
+    ) -> Result<Self, String> {
+        let points = database.waypoints();
+        assert!(num_components > 0);
+        if points.len() < num_components {
+            return Err(format!(
+                "{} waypoints are too few for {num_components} components",
+                points.len()
+            ));
+        }
+        let n = N::from(points.len()).unwrap();
+        // floor of the variances, so that components on a single point do not collapse
+        let min_variance = N::from(1e-6).unwrap();
+        let overall = (0..database.dim())
+            .map(|axis| {
+                let mean = points.iter().fold(N::zero(), |sum, q| sum + q[axis]) / n;
+                points
+                    .iter()
+                    .fold(N::zero(), |sum, q| sum + (q[axis] - mean).powi(2))
+                    / n
+                    + min_variance
+            })
+            .collect::<Vec<_>>();
+        let mut mixture = Self {
+            weights: vec![N::one() / N::from(num_components).unwrap(); num_components],
+            means: (0..num_components)
+                .map(|j| points[j * points.len() / num_components].clone())
+                .collect(),
+            variances: vec![overall; num_components],
+        };
+        for _ in 0..num_iterations {
+            // E-step: responsibilities of the components for each point
+            let responsibilities = points
+                .iter()
+                .map(|q| {
+                    let log_densities = (0..num_components)
+                        .map(|j| mixture.weights[j].ln() + mixture.log_density(j, q))
+                        .collect::<Vec<_>>();
+                    let max = log_densities
+                        .iter()
+                        .fold(N::neg_infinity(), |max, v| max.max(*v));
+                    let exps = log_densities
+                        .iter()
+                        .map(|v| (*v - max).exp())
+                        .collect::<Vec<_>>();
+                    let sum = exps.iter().fold(N::zero(), |sum, v| sum + *v);
+                    exps.into_iter().map(|v| v / sum).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            // M-step
+            for j in 0..num_components {
+                let total = responsibilities.iter().fold(N::zero(), |sum, r| sum + r[j]);
+                if total <= N::epsilon() {
+                    continue;
+                }
+                mixture.weights[j] = total / n;
+                for axis in 0..database.dim() {
+                    let mean = points
+                        .iter()
+                        .zip(&responsibilities)
+                        .fold(N::zero(), |sum, (q, r)| sum + r[j] * q[axis])
+                        / total;
+                    mixture.means[j][axis] = mean;
+                    mixture.variances[j][axis] = points
+                        .iter()
+                        .zip(&responsibilities)
+                        .fold(N::zero(), |sum, (q, r)| {
+                            sum + r[j] * (q[axis] - mean).powi(2)
+                        })
+                        / total
+                        + min_variance;
+                }
+            }
+        }
+        Ok(mixture)
+    }
+
+    /// Returns the logarithm of the density of component `j` at `q`, without the
+    /// constant term.
+    fn log_density(&self, j: usize, q: &[N]) -> N {
+        let half = N::from(0.5).unwrap();
+        q.iter()
+            .zip(self.means[j].iter().zip(&self.variances[j]))
+            .fold(N::zero(), |sum, (x, (mean, variance))| {
+                sum - half * ((*x - *mean).powi(2) / *variance + variance.ln())
+            })
+    }
+
+    /// weights of the components
+    pub fn weights(&self) -> &[N] {
+        &self.weights
+    }
+
+    /// means of the components
+    pub fn means(&self) -> &[Vec<N>] {
+        &self.means
+    }
+
+    /// Returns a sample of the mixture.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Vec<N> {
+        let mut r = N::from(rng.gen::<f64>()).unwrap();
+        let mut j = self.weights.len() - 1;
+        for (i, weight) in self.weights.iter().enumerate() {
+            if r < *weight {
+                j = i;
+                break;
+            }
+            r = r - *weight;
+        }
+        self.means[j]
+            .iter()
+            .zip(&self.variances[j])
+            .map(|(mean, variance)| {
+                *mean + variance.sqrt() * N::from(standard_normal(rng)).unwrap()
+            })
+            .collect()
+    }
+}
+
+/// Sampler which draws the samples from a [`GaussianMixture`] fitted to the previous
+/// solutions with the probability `fraction` (0.0 - 1.0), and from `base` otherwise.
+///
+/// This accelerates repetitive tasks whose solutions pass similar regions, and `base`
+/// keeps the planner complete when the environment has changed.
+#[derive(Debug, Clone)]
+pub struct ExperienceSampler<S, N> {
+    base: S,
+    mixture: GaussianMixture<N>,
+    fraction: f64,
+}
+
+impl<S, N> ExperienceSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float + Debug,
+{
+    /// Creates a sampler of `mixture`, e.g. fitted by [`GaussianMixture::fit`].
+    pub fn new(base: S, mixture: GaussianMixture<N>, fraction: f64) -> Self {
+        assert!((0.0..=1.0).contains(&fraction));
+        Self {
+            base,
+            mixture,
+            fraction,
+        }
+    }
+}
+
+impl<S, N> Sampler<N> for ExperienceSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        if rng.gen_bool(self.fraction) {
+            self.mixture.sample(rng)
+        } else {
+            self.base.sample(rng)
+        }
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn experience_sampler_works() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    // two clusters of waypoints
+    let mut database = ExperienceDatabase::new(2);
+    for i in 0..10 {
+        let d = i as f64 * 0.01;
+        database
+            .add_path(&[vec![-1.0 + d, 1.0], vec![1.0, -1.0 - d]])
+            .unwrap();
+    }
+    assert_eq!(database.waypoints().len(), 20);
+    assert!(database.add_path(&[vec![0.0]]).is_err());
+    let mixture = GaussianMixture::fit(&database, 2, 20).unwrap();
+    let mut means = mixture.means().to_vec();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert!((means[0][0] + 0.955).abs() < 1e-6 && (means[0][1] - 1.0).abs() < 1e-6);
+    assert!((means[1][0] - 1.0).abs() < 1e-6 && (means[1][1] + 1.045).abs() < 1e-6);
+    assert!(mixture.weights().iter().all(|w| (w - 0.5).abs() < 1e-6));
+    assert!(GaussianMixture::fit(&database, 21, 1).is_err());
+
+    // the way through a narrow gap was found before
+    let is_free = |p: &[f64]| !(p[0].abs() < 0.1 && p[1].abs() > 0.05);
+    let mut database = ExperienceDatabase::new(2);
+    for _ in 0..5 {
+        database
+            .add_path(&[
+                vec![-1.0, 0.0],
+                vec![-0.1, 0.0],
+                vec![0.1, 0.0],
+                vec![1.0, 0.0],
+            ])
+            .unwrap();
+    }
+    let mixture = GaussianMixture::fit(&database, 4, 20).unwrap();
+    let base = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let mut sampler = ExperienceSampler::new(base, mixture, 0.5);
+    let mut rng = rand::thread_rng();
+    let num_near = (0..1000)
+        .filter(|_| sampler.sample(&mut rng)[1].abs() < 0.01)
+        .count();
+    assert!(num_near > 400, "{num_near}");
+    let path = dual_rrt_connect(&[-1.0, 0.0], &[1.0, 0.0], is_free, sampler, 0.05, 1000).unwrap();
+    assert!(path.iter().all(|q| is_free(q)));
+}
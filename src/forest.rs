@@ -0,0 +1,266 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{
+    check_start_goal, is_valid_point, options_tree, DualRrtConnectOptions, ExtendStatus, Sampler,
+    Tree,
+};
+use num_traits::float::Float;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// vertices which met when two trees were connected
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    tree_a: usize,
+    index_a: usize,
+    tree_b: usize,
+    index_b: usize,
+}
+
+/// Returns the root of the merged trees which contain `tree`.
+fn find(merged: &mut [usize], tree: usize) -> usize {
+    let mut root = tree;
+    while merged[root] != root {
+        root = merged[root];
+    }
+    merged[tree] = root;
+    root
+}
+
+/// indices from the root to `index`
+fn branch<N: Float + Debug>(tree: &Tree<N>, index: usize) -> Vec<usize> {
+    let mut indices = vec![index];
+    let mut current = index;
+    while let Some(parent) = tree.parent(current) {
+        indices.push(parent);
+        current = parent;
+    }
+    indices.reverse();
+    indices
+}
+
+/// Returns the vertices from `from` to `to` in the tree, through their common ancestor.
+fn path_in_tree<N: Float + Debug>(tree: &Tree<N>, from: usize, to: usize) -> Vec<Vec<N>> {
+    let from_branch = branch(tree, from);
+    let to_branch = branch(tree, to);
+    let num_common = from_branch
+        .iter()
+        .zip(&to_branch)
+        .take_while(|(a, b)| a == b)
+        .count();
+    from_branch[num_common - 1..]
+        .iter()
+        .rev()
+        .chain(&to_branch[num_common..])
+        .map(|index| tree.vertex(*index).to_vec())
+        .collect()
+}
+
+/// Returns the path from the root of the start tree (0) to the root of the goal tree (1)
+/// through the links.
+fn extract_path<N: Float + Debug>(trees: &[Tree<N>], links: &[Link]) -> Vec<Vec<N>> {
+    // the links form a spanning forest of the trees, so the route is unique
+    let mut previous = vec![None; trees.len()];
+    let mut visited = vec![false; trees.len()];
+    let mut queue = VecDeque::from([0]);
+    visited[0] = true;
+    while let Some(tree) = queue.pop_front() {
+        for link in links {
+            for (from, from_index, to, to_index) in [
+                (link.tree_a, link.index_a, link.tree_b, link.index_b),
+                (link.tree_b, link.index_b, link.tree_a, link.index_a),
+            ] {
+                if from == tree && !visited[to] {
+                    visited[to] = true;
+                    previous[to] = Some((from, from_index, to_index));
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+    // (tree, entry index, exit index) from the goal tree back to the start tree
+    let mut route = Vec::new();
+    let mut tree = 1;
+    let mut exit = 0;
+    while let Some((from, from_index, entry)) = previous[tree] {
+        route.push((tree, entry, exit));
+        tree = from;
+        exit = from_index;
+    }
+    route.push((0, 0, exit));
+    route
+        .into_iter()
+        .rev()
+        .flat_map(|(tree, entry, exit)| path_in_tree(&trees[tree], entry, exit))
+        .collect()
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with additional trees rooted at `seeds` (multi-tree RRT connect).
+///
+/// `seeds` are configurations which are known to be on the way, e.g. in the middle of
+/// narrow corridors. All trees grow by turns, and after each extension the trees which
+/// are not merged yet try to connect to the new vertex. The trees are merged when they
+/// are connected, and the path is extracted across the merged trees once the start and
+/// goal trees are merged. Returns an error if a seed is not finite or not free.
+/// `tree_bias`, `num_nearest_candidates` and `tree_balance` of the options are not used.
+pub fn forest_rrt_connect<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    seeds: &[Vec<N>],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    forest_rrt_connect_with_rng(
+        start,
+        goal,
+        seeds,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`forest_rrt_connect`] which takes all randomness from `rng`.
+pub fn forest_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    seeds: &[Vec<N>],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    let dim = start.len();
+    for seed in seeds {
+        if !is_valid_point(seed, dim) {
+            return Err(format!("seed is not valid: {seed:?}"));
+        }
+        if !is_free(seed) {
+            return Err(format!("seed is not free: {seed:?}"));
+        }
+    }
+    let mut trees = [start, goal]
+        .into_iter()
+        .chain(seeds.iter().map(|seed| seed.as_slice()))
+        .enumerate()
+        .map(|(i, root)| {
+            let name = match i {
+                0 => "start",
+                1 => "goal",
+                _ => "seed",
+            };
+            let mut tree = options_tree(name, dim, options)?;
+            tree.add_vertex(root);
+            Ok(tree)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut merged = (0..trees.len()).collect::<Vec<_>>();
+    let mut links = Vec::new();
+    for iteration in 0..options.num_max_try {
+        let tree_a = iteration % trees.len();
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, dim) {
+            continue;
+        }
+        let new_index = match trees[tree_a].extend(&q_rand, options.extend_length, &mut is_free) {
+            ExtendStatus::Trapped => continue,
+            ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) => index,
+        };
+        let q_new = trees[tree_a].vertex(new_index).to_vec();
+        for tree_b in 0..trees.len() {
+            if find(&mut merged, tree_a) == find(&mut merged, tree_b) {
+                continue;
+            }
+            if let ExtendStatus::Reached(index_b) =
+                trees[tree_b].connect(&q_new, options.extend_length, &mut is_free)
+            {
+                debug!(
+                    "merged {} ({tree_a}) and {} ({tree_b})",
+                    trees[tree_a].name, trees[tree_b].name
+                );
+                let root_b = find(&mut merged, tree_b);
+                merged[root_b] = find(&mut merged, tree_a);
+                links.push(Link {
+                    tree_a,
+                    index_a: new_index,
+                    tree_b,
+                    index_b,
+                });
+            }
+        }
+        if find(&mut merged, 0) == find(&mut merged, 1) {
+            debug!(
+                "connected with {} vertices",
+                trees.iter().map(|tree| tree.len()).sum::<usize>()
+            );
+            return Ok(extract_path(&trees, &links));
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn forest_rrt_connect_works() {
+    use kdtree::distance::squared_euclidean;
+    let world = crate::scenario::World::square();
+    // a wall with a narrow gap at the origin
+    let is_free = |p: &[f64]| !(p[0].abs() < 0.1 && p[1].abs() > 0.05);
+    let random_sample = world.sampler(1);
+    let options = DualRrtConnectOptions::new(0.05, 10000);
+    let path = forest_rrt_connect(
+        &[-1.0, 0.0],
+        &[1.0, 0.0],
+        &[vec![0.0, 0.0], vec![-1.5, 1.5]],
+        is_free,
+        &random_sample,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.0, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.0, 0.0]);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        assert!(squared_euclidean(&segment[0], &segment[1]).sqrt() <= 0.05 + 1e-9);
+    }
+    assert!(forest_rrt_connect(
+        &[-1.0, 0.0],
+        &[1.0, 0.0],
+        &[vec![0.0, 1.0]],
+        is_free,
+        &random_sample,
+        &options,
+    )
+    .is_err());
+}
@@ -0,0 +1,474 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{is_edge_free, is_valid_point};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Grid cells inside the bounds, with `resolution` spacing.
+#[derive(Debug)]
+struct Grid<N> {
+    lower: Vec<N>,
+    resolution: N,
+    num_cells: Vec<usize>,
+}
+
+impl<N> Grid<N>
+where
+    N: Float + Debug,
+{
+    fn new(lower: &[N], upper: &[N], resolution: N) -> Result<Self, String> {
+        if lower.len() != upper.len() {
+            return Err(format!(
+                "lower and upper have different dimensions: {} and {}",
+                lower.len(),
+                upper.len()
+            ));
+        }
+        if resolution <= N::zero() || !resolution.is_finite() {
+            return Err(format!("resolution must be positive: {resolution:?}"));
+        }
+        let mut num_cells = Vec::with_capacity(lower.len());
+        for (l, u) in lower.iter().zip(upper) {
+            if !l.is_finite() || !u.is_finite() || l > u {
+                return Err(format!("invalid bounds: {lower:?} - {upper:?}"));
+            }
+            let n = ((*u - *l) / resolution)
+                .floor()
+                .to_usize()
+                .ok_or_else(|| format!("too many cells with resolution {resolution:?}"))?;
+            num_cells.push(n + 1);
+        }
+        Ok(Grid {
+            lower: lower.to_vec(),
+            resolution,
+            num_cells,
+        })
+    }
+    fn point(&self, cell: &[usize]) -> Vec<N> {
+        cell.iter()
+            .zip(&self.lower)
+            .map(|(i, l)| *l + N::from(*i).unwrap() * self.resolution)
+            .collect()
+    }
+    fn nearest_cell(&self, q: &[N]) -> Vec<usize> {
+        q.iter()
+            .zip(&self.lower)
+            .zip(&self.num_cells)
+            .map(|((q, l), n)| {
+                // clamped into the grid, and NaN is 0
+                let i = ((*q - *l) / self.resolution)
+                    .round()
+                    .max(N::zero())
+                    .min(N::from(n - 1).unwrap());
+                i.to_usize().unwrap_or(0)
+            })
+            .collect()
+    }
+    /// all cells which differ at most one in every axis
+    fn neighbors(&self, cell: &[usize]) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::with_capacity(cell.len())];
+        for (i, n) in cell.iter().zip(&self.num_cells) {
+            let mut next = Vec::with_capacity(neighbors.len() * 3);
+            for candidate in &neighbors {
+                for j in i.saturating_sub(1)..=(*i + 1).min(n - 1) {
+                    let mut c = candidate.clone();
+                    c.push(j);
+                    next.push(c);
+                }
+            }
+            neighbors = next;
+        }
+        neighbors.retain(|c| c != cell);
+        neighbors
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct OpenCell<N> {
+    f: N,
+    cell: Vec<usize>,
+}
+
+impl<N: PartialOrd> Eq for OpenCell<N> {}
+
+impl<N: PartialOrd> PartialOrd for OpenCell<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: PartialOrd> Ord for OpenCell<N> {
+    // reversed to make BinaryHeap a min-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f.partial_cmp(&self.f).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Debug)]
+struct CellState<N> {
+    cost: N,
+    parent: Option<Vec<usize>>,
+    closed: bool,
+}
+
+/// search the path from start to goal on the grid between lower and upper
+/// with resolution spacing, using A*.
+///
+/// Unlike [`dual_rrt_connect`](crate::dual_rrt_connect) this is deterministic,
+/// and it fails only if no path exists on the grid.
+/// The returned path starts with `start`, ends with `goal`, and the other points are grid points.
+/// `start` and `goal` are connected to their nearest grid points by straight segments.
+/// These segments and the moves between the grid points are checked at `resolution / 10`
+/// intervals.
+pub fn grid_astar<FF, N>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    lower: &[N],
+    upper: &[N],
+    resolution: N,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    crate::check_start_goal(start, goal)?;
+    if start.len() != lower.len() {
+        return Err(format!(
+            "start and bounds have different dimensions: {} and {}",
+            start.len(),
+            lower.len()
+        ));
+    }
+    let grid = Grid::new(lower, upper, resolution)?;
+    let start_cell = grid.nearest_cell(start);
+    let goal_cell = grid.nearest_cell(goal);
+    let start_point = grid.point(&start_cell);
+    let goal_point = grid.point(&goal_cell);
+    let step = resolution / N::from(10).unwrap();
+    for (name, q, point) in [("start", start, &start_point), ("goal", goal, &goal_point)] {
+        if !is_free(q) || !is_free(point) || !is_edge_free(q, point, &mut is_free, step) {
+            return Err(format!("{name} cannot be connected to the grid: {q:?}"));
+        }
+    }
+    let heuristic = |q: &[N]| squared_euclidean(q, &goal_point).sqrt();
+
+    let mut nodes = HashMap::new();
+    let mut open = BinaryHeap::new();
+    nodes.insert(
+        start_cell.clone(),
+        CellState {
+            cost: N::zero(),
+            parent: None,
+            closed: false,
+        },
+    );
+    open.push(OpenCell {
+        f: heuristic(&grid.point(&start_cell)),
+        cell: start_cell,
+    });
+    while let Some(OpenCell { cell, .. }) = open.pop() {
+        let state = nodes.get_mut(&cell).unwrap();
+        if state.closed {
+            continue;
+        }
+        state.closed = true;
+        let g = state.cost;
+        if cell == goal_cell {
+            debug!("reached goal cell, expanded {} cells", nodes.len());
+            let mut path = vec![goal.to_vec()];
+            let mut cur = Some(cell);
+            while let Some(c) = cur {
+                path.push(grid.point(&c));
+                cur = nodes[&c].parent.clone();
+            }
+            path.push(start.to_vec());
+            path.reverse();
+            path.dedup();
+            return Ok(path);
+        }
+        let point = grid.point(&cell);
+        for neighbor in grid.neighbors(&cell) {
+            let neighbor_point = grid.point(&neighbor);
+            let new_g = g + squared_euclidean(&point, &neighbor_point).sqrt();
+            if let Some(state) = nodes.get(&neighbor) {
+                if state.closed || state.cost <= new_g {
+                    continue;
+                }
+            } else if !is_free(&neighbor_point) {
+                nodes.insert(
+                    neighbor,
+                    CellState {
+                        cost: N::infinity(),
+                        parent: None,
+                        closed: true,
+                    },
+                );
+                continue;
+            }
+            // diagonal moves may cut through thin obstacles between the grid points
+            if !is_edge_free(&point, &neighbor_point, &mut is_free, step) {
+                continue;
+            }
+            nodes.insert(
+                neighbor.clone(),
+                CellState {
+                    cost: new_g,
+                    parent: Some(cell.clone()),
+                    closed: false,
+                },
+            );
+            open.push(OpenCell {
+                f: new_g + heuristic(&neighbor_point),
+                cell: neighbor,
+            });
+        }
+    }
+    Err("failed".to_string())
+}
+
+/// Cost-to-go of the grid points to a goal, computed by [`grid_cost_to_go`].
+#[derive(Debug)]
+pub struct GridCostToGo<N> {
+    grid: Grid<N>,
+    costs: HashMap<Vec<usize>, N>,
+}
+
+impl<N> GridCostToGo<N>
+where
+    N: Float + Debug,
+{
+    /// Returns the length of the shortest grid path from the nearest grid point of `q`
+    /// to the goal, plus the distance to the grid point, or infinity if it is unreachable
+    /// or `q` is not a finite point of the dimension of the grid.
+    ///
+    /// This is not always a lower bound of the cost, because grid paths are longer than
+    /// straight lines, but it is a good heuristic which knows the obstacles.
+    pub fn cost(&self, q: &[N]) -> N {
+        if !is_valid_point(q, self.grid.lower.len()) {
+            return N::infinity();
+        }
+        let cell = self.grid.nearest_cell(q);
+        match self.costs.get(&cell) {
+            Some(cost) => *cost + squared_euclidean(q, &self.grid.point(&cell)).sqrt(),
+            None => N::infinity(),
+        }
+    }
+}
+
+/// compute the cost-to-go to goal of all free points on the grid between lower and upper
+/// with resolution spacing, using Dijkstra from the goal.
+///
+/// The result can be used as the heuristic of informed planners, for example to bias
+/// samples toward the regions from which the goal is reachable.
+pub fn grid_cost_to_go<FF, N>(
+    goal: &[N],
+    mut is_free: FF,
+    lower: &[N],
+    upper: &[N],
+    resolution: N,
+) -> Result<GridCostToGo<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    if goal.len() != lower.len() {
+        return Err(format!(
+            "goal and bounds have different dimensions: {} and {}",
+            goal.len(),
+            lower.len()
+        ));
+    }
+    let grid = Grid::new(lower, upper, resolution)?;
+    let goal_cell = grid.nearest_cell(goal);
+    let goal_point = grid.point(&goal_cell);
+    let step = resolution / N::from(10).unwrap();
+    if !is_free(goal)
+        || !is_free(&goal_point)
+        || !is_edge_free(goal, &goal_point, &mut is_free, step)
+    {
+        return Err(format!("goal cannot be connected to the grid: {goal:?}"));
+    }
+    let mut nodes = HashMap::new();
+    let mut open = BinaryHeap::new();
+    let goal_cost = squared_euclidean(goal, &goal_point).sqrt();
+    nodes.insert(
+        goal_cell.clone(),
+        CellState {
+            cost: goal_cost,
+            parent: None,
+            closed: false,
+        },
+    );
+    open.push(OpenCell {
+        f: goal_cost,
+        cell: goal_cell,
+    });
+    while let Some(OpenCell { cell, .. }) = open.pop() {
+        let state = nodes.get_mut(&cell).unwrap();
+        if state.closed {
+            continue;
+        }
+        state.closed = true;
+        let g = state.cost;
+        let point = grid.point(&cell);
+        for neighbor in grid.neighbors(&cell) {
+            let neighbor_point = grid.point(&neighbor);
+            let new_g = g + squared_euclidean(&point, &neighbor_point).sqrt();
+            if let Some(state) = nodes.get(&neighbor) {
+                if state.closed || state.cost <= new_g {
+                    continue;
+                }
+            } else if !is_free(&neighbor_point) {
+                nodes.insert(
+                    neighbor,
+                    CellState {
+                        cost: N::infinity(),
+                        parent: None,
+                        closed: true,
+                    },
+                );
+                continue;
+            }
+            if !is_edge_free(&point, &neighbor_point, &mut is_free, step) {
+                continue;
+            }
+            nodes.insert(
+                neighbor.clone(),
+                CellState {
+                    cost: new_g,
+                    parent: None,
+                    closed: false,
+                },
+            );
+            open.push(OpenCell {
+                f: new_g,
+                cell: neighbor,
+            });
+        }
+    }
+    debug!("computed the cost-to-go of {} cells", nodes.len());
+    let costs = nodes
+        .into_iter()
+        .filter(|(_, state)| state.cost.is_finite())
+        .map(|(cell, state)| (cell, state.cost))
+        .collect();
+    Ok(GridCostToGo { grid, costs })
+}
+
+#[test]
+fn grid_astar_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let result = grid_astar(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &[-2.0, -2.0],
+        &[2.0, 2.0],
+        0.1,
+    )
+    .unwrap();
+    assert_eq!(result[0], vec![-1.2, 0.0]);
+    assert_eq!(result[result.len() - 1], vec![1.2, 0.0]);
+    assert!(result.iter().all(|p| is_free(p)));
+    // deterministic
+    let result2 = grid_astar(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &[-2.0, -2.0],
+        &[2.0, 2.0],
+        0.1,
+    )
+    .unwrap();
+    assert_eq!(result, result2);
+    // the nearest grid point of the goal is inside the box
+    assert!(grid_astar(
+        &[-1.2, 0.0],
+        &[1.04, 0.0],
+        |p: &[f64]| !(p[0].abs() < 1.03 && p[1].abs() < 1.03),
+        &[-2.0, -2.0],
+        &[2.0, 2.0],
+        0.1,
+    )
+    .is_err());
+    assert!(grid_astar(&[-1.2, 0.0], &[1.2, 0.0], is_free, &[-2.0], &[2.0], 0.1).is_err());
+}
+
+#[test]
+fn grid_astar_thin_wall() {
+    // a thin diagonal wall between the grid points, open where x > 0.5
+    let is_free = |p: &[f64]| !((p[0] + p[1] - 0.05).abs() < 0.01 && p[0] < 0.5);
+    let path = grid_astar(
+        &[-1.0, -1.0],
+        &[1.0, 1.0],
+        is_free,
+        &[-2.0, -2.0],
+        &[2.0, 2.0],
+        0.1,
+    )
+    .unwrap();
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut { is_free },
+            0.001
+        ));
+    }
+    // through the opening
+    assert!(path.iter().any(|p| p[0] > 0.45 && p[1] < -0.35));
+    // closed everywhere, though all grid points are free
+    let is_free = |p: &[f64]| (p[0] + p[1] - 0.05).abs() >= 0.01;
+    assert!(grid_astar(
+        &[-1.0, -1.0],
+        &[1.0, 1.0],
+        is_free,
+        &[-2.0, -2.0],
+        &[2.0, 2.0],
+        0.1,
+    )
+    .is_err());
+    let cost_to_go =
+        grid_cost_to_go(&[1.0, 1.0], is_free, &[-2.0, -2.0], &[2.0, 2.0], 0.1).unwrap();
+    assert!(cost_to_go.cost(&[-1.0, -1.0]).is_infinite());
+}
+
+#[test]
+fn grid_cost_to_go_works() {
+    // the box and a wall which closes the area of x > 1.5
+    let is_free = |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0) && (p[0] - 1.5).abs() > 0.05;
+    let cost_to_go =
+        grid_cost_to_go(&[1.2, 0.0], is_free, &[-2.0, -2.0], &[2.0, 2.0], 0.1).unwrap();
+    assert!(cost_to_go.cost(&[1.2, 0.0]).abs() < 1e-9);
+    assert!((cost_to_go.cost(&[1.2, 1.0]) - 1.0).abs() < 1e-9);
+    // around the box, and longer than the straight line
+    let cost = cost_to_go.cost(&[-1.2, 0.0]);
+    assert!(cost > 2.0 + 2.0 * 1.04f64.sqrt());
+    assert!(cost < 2.0 + 2.0 * 1.04f64.sqrt() + 0.5);
+    assert!(cost_to_go.cost(&[1.8, 0.0]).is_infinite());
+    assert!(cost_to_go.cost(&[f64::NAN, 0.0]).is_infinite());
+    assert!(cost_to_go.cost(&[1.2]).is_infinite());
+    // out of the bounds, from the nearest grid point
+    assert!((cost_to_go.cost(&[1.2, 1e30]) - 1e30).abs() < 1e20);
+    assert!(grid_cost_to_go(&[0.0, 0.0], is_free, &[-2.0, -2.0], &[2.0, 2.0], 0.1).is_err());
+}
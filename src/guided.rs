@@ -0,0 +1,179 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::uniform_in_ball;
+use crate::{is_valid_point, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+
+/// Sampler which biases the samples of `base` toward a tube around a previous path.
+///
+/// With the probability `bias` (0.0 - 1.0), it returns a point uniformly in the ball of
+/// `radius` around a point chosen uniformly along the path, and a sample of `base`
+/// otherwise. Replanning after small changes of the environment finds a path near the
+/// previous one in a few iterations, and `base` keeps the planner complete when the
+/// previous path is blocked.
+#[derive(Debug, Clone)]
+pub struct PathGuidedSampler<S, N> {
+    base: S,
+    path: Vec<Vec<N>>,
+    /// cumulative lengths of the path at its vertices
+    lengths: Vec<N>,
+    radius: N,
+    bias: f64,
+}
+
+impl<S, N> PathGuidedSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float + Debug,
+{
+    /// Returns an error if `path` is empty, or its vertices are not finite or have
+    /// different dimensions.
+    pub fn new(base: S, path: &[Vec<N>], radius: N, bias: f64) -> Result<Self, String> {
+        assert!(radius >= N::zero());
+        assert!((0.0..=1.0).contains(&bias));
+        if path.is_empty() {
+            return Err("path is empty".to_string());
+        }
+        if let Some(q) = path.iter().find(|q| !is_valid_point(q, path[0].len())) {
+            return Err(format!("invalid vertex of the path: {q:?}"));
+        }
+        let mut lengths = vec![N::zero()];
+        for segment in path.windows(2) {
+            let length = squared_euclidean(&segment[0], &segment[1]).sqrt();
+            lengths.push(lengths[lengths.len() - 1] + length);
+        }
+        Ok(Self {
+            base,
+            path: path.to_vec(),
+            lengths,
+            radius,
+            bias,
+        })
+    }
+
+    /// Returns the point at `length` from the beginning of the path.
+    fn point_at(&self, length: N) -> Vec<N> {
+        // the first vertex whose cumulative length is not less than `length`
+        let i = self.lengths.partition_point(|l| *l < length).max(1);
+        if i >= self.path.len() {
+            return self.path[self.path.len() - 1].clone();
+        }
+        let segment_length = self.lengths[i] - self.lengths[i - 1];
+        if segment_length <= N::zero() {
+            return self.path[i].clone();
+        }
+        let t = (length - self.lengths[i - 1]) / segment_length;
+        self.path[i - 1]
+            .iter()
+            .zip(&self.path[i])
+            .map(|(a, b)| *a + (*b - *a) * t)
+            .collect()
+    }
+}
+
+impl<S, N> Sampler<N> for PathGuidedSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        if !rng.gen_bool(self.bias) {
+            return self.base.sample(rng);
+        }
+        let total_length = self.lengths[self.lengths.len() - 1];
+        let center = self.point_at(total_length * N::from(rng.gen::<f64>()).unwrap());
+        uniform_in_ball(&center, self.radius, rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+impl<S, N> Sampler<N> for &mut PathGuidedSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        (**self).add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        (**self).report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn path_guided_sampler_works() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    let path = vec![
+        vec![-1.2, 0.0],
+        vec![-1.2, 1.2],
+        vec![1.2, 1.2],
+        vec![1.2, 0.0],
+    ];
+    let box_sampler = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let mut sampler = PathGuidedSampler::new(box_sampler.clone(), &path, 0.1, 1.0).unwrap();
+    assert_eq!(sampler.point_at(0.0), vec![-1.2, 0.0]);
+    assert_eq!(sampler.point_at(1.2), vec![-1.2, 1.2]);
+    let middle = sampler.point_at(2.4);
+    assert!(middle[0].abs() < 1e-9 && middle[1] == 1.2);
+    assert_eq!(sampler.point_at(4.8), vec![1.2, 0.0]);
+    let mut rng = rand::thread_rng();
+    // distance to the path
+    let distance = |q: &[f64]| {
+        path.windows(2)
+            .map(|segment| {
+                let (a, b) = (&segment[0], &segment[1]);
+                let ab = [b[0] - a[0], b[1] - a[1]];
+                let t = (((q[0] - a[0]) * ab[0] + (q[1] - a[1]) * ab[1])
+                    / (ab[0] * ab[0] + ab[1] * ab[1]))
+                    .clamp(0.0, 1.0);
+                (q[0] - a[0] - ab[0] * t).hypot(q[1] - a[1] - ab[1] * t)
+            })
+            .fold(f64::INFINITY, f64::min)
+    };
+    for _ in 0..1000 {
+        let q = sampler.sample(&mut rng);
+        assert!(distance(&q) <= 0.1 + 1e-9, "{q:?}");
+    }
+    assert!(PathGuidedSampler::new(box_sampler.clone(), &[], 0.1, 0.5).is_err());
+    assert!(
+        PathGuidedSampler::new(box_sampler.clone(), &[vec![0.0], vec![0.0, 1.0]], 0.1, 0.5)
+            .is_err()
+    );
+
+    // replanning around the previous path
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut sampler = PathGuidedSampler::new(box_sampler, &path, 0.1, 0.9).unwrap();
+    let new_path =
+        dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut sampler, 0.2, 1000).unwrap();
+    assert_eq!(new_path[new_path.len() - 1], vec![1.2, 0.0]);
+}
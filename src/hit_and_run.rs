@@ -0,0 +1,152 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::{check_bounds, standard_normal};
+use crate::{is_valid_point, Sampler};
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+
+/// max number of points tried on a chord, shrinking it toward the current point
+const NUM_MAX_SHRINK: usize = 100;
+
+/// Sampler of the approximately uniform distribution in the set where `is_free` returns
+/// `true`, by the hit-and-run random walk.
+///
+/// Each sample moves the current point along a random direction to a point drawn
+/// uniformly on the chord through the box between `lower` and `upper`. A point which is
+/// not free shrinks the chord toward the current point, so the walk stays in non-convex
+/// sets. This finds samples in thin free spaces, e.g. around a manifold of constraints,
+/// where uniform samples in the box almost never hit. Consecutive samples are
+/// correlated, and the distribution approaches the uniform one over many samples.
+#[derive(Debug, Clone)]
+pub struct HitAndRunSampler<FF, N> {
+    is_free: FF,
+    current: Vec<N>,
+    lower: Vec<N>,
+    upper: Vec<N>,
+}
+
+impl<FF, N> HitAndRunSampler<FF, N>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    /// Returns an error if the bounds are invalid as
+    /// [`UniformBoxSampler::new`](crate::UniformBoxSampler::new), or `start` is not a
+    /// free point in them.
+    pub fn new(mut is_free: FF, start: &[N], lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        let in_bounds = start
+            .iter()
+            .zip(lower.iter().zip(upper))
+            .all(|(v, (l, u))| l <= v && v <= u);
+        if !is_valid_point(start, lower.len()) || !in_bounds || !is_free(start) {
+            return Err(format!(
+                "start is not a free point in the bounds: {start:?}"
+            ));
+        }
+        Ok(Self {
+            is_free,
+            current: start.to_vec(),
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+        })
+    }
+
+    /// the last sample
+    pub fn current(&self) -> &[N] {
+        &self.current
+    }
+}
+
+impl<FF, N> Sampler<N> for HitAndRunSampler<FF, N>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let direction = self
+            .current
+            .iter()
+            .map(|_| N::from(standard_normal(rng)).unwrap())
+            .collect::<Vec<_>>();
+        // the chord is current + t * direction in the box
+        let (mut t_min, mut t_max) = (N::neg_infinity(), N::infinity());
+        for ((x, d), (l, u)) in self
+            .current
+            .iter()
+            .zip(&direction)
+            .zip(self.lower.iter().zip(&self.upper))
+        {
+            if *d != N::zero() {
+                let (a, b) = ((*l - *x) / *d, (*u - *x) / *d);
+                t_min = t_min.max(a.min(b));
+                t_max = t_max.min(a.max(b));
+            }
+        }
+        if !t_min.is_finite() || !t_max.is_finite() {
+            return self.current.clone();
+        }
+        for _ in 0..NUM_MAX_SHRINK {
+            let t = t_min + (t_max - t_min) * N::from(rng.gen::<f64>()).unwrap();
+            let q = self
+                .current
+                .iter()
+                .zip(&direction)
+                .map(|(x, d)| *x + *d * t)
+                .collect::<Vec<_>>();
+            if (self.is_free)(&q) {
+                self.current = q;
+                break;
+            }
+            if t < N::zero() {
+                t_min = t;
+            } else {
+                t_max = t;
+            }
+        }
+        self.current.clone()
+    }
+}
+
+#[test]
+fn hit_and_run_sampler_works() {
+    use crate::dual_rrt_connect;
+    // a thin ring, which uniform samples in the box hit with the probability of 1%
+    let is_free = |p: &[f64]| (1.0..=1.02).contains(&p[0].hypot(p[1]));
+    let mut sampler =
+        HitAndRunSampler::new(is_free, &[1.01, 0.0], &[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let mut rng = rand::thread_rng();
+    let samples = (0..5000)
+        .map(|_| sampler.sample(&mut rng))
+        .collect::<Vec<_>>();
+    assert!(samples.iter().all(|q| is_free(q)));
+    assert_eq!(sampler.current(), samples[samples.len() - 1].as_slice());
+    // all around the ring
+    for quadrant in [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)] {
+        assert!(samples
+            .iter()
+            .any(|q| q[0] * quadrant.0 > 0.5 && q[1] * quadrant.1 > 0.5));
+    }
+    assert!(HitAndRunSampler::new(is_free, &[0.0, 0.0], &[-2.0, -2.0], &[2.0, 2.0]).is_err());
+    assert!(HitAndRunSampler::new(is_free, &[3.0, 0.0], &[-2.0, -2.0], &[2.0, 2.0]).is_err());
+
+    // the samples are free, so the planner does not waste the iterations
+    let path =
+        dual_rrt_connect(&[1.01, 0.0], &[-1.01, 0.0], is_free, sampler, 0.05, 10000).unwrap();
+    assert!(path.iter().all(|q| is_free(q)));
+}
@@ -0,0 +1,265 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::prm::OpenVertex;
+use num_traits::float::Float;
+use num_traits::FloatConst;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`hybrid_astar`].
+#[derive(Debug, Clone)]
+pub struct HybridAStarOptions<N> {
+    /// length of the arc of each motion primitive
+    pub step_length: N,
+    /// minimum turning radius of the vehicle
+    pub min_turning_radius: N,
+    /// number (>= 2) of curvatures from `-1 / min_turning_radius` to `1 / min_turning_radius`
+    /// which are used as the steering set
+    pub num_steering: usize,
+    /// size of the cells of the position
+    pub cell_size: N,
+    /// number of the discretized headings
+    pub num_headings: usize,
+    /// interval to check the poses along the primitives with `is_free`
+    pub resolution: N,
+    /// the goal is reached within this distance of the position
+    pub goal_distance_tolerance: N,
+    /// the goal is reached within this difference of the heading
+    pub goal_heading_tolerance: N,
+    /// factor (>= 1.0) of the cost to drive backward, or `None` to drive only forward
+    pub reverse_penalty: Option<N>,
+    /// max number of the expanded nodes
+    pub num_max_expansion: usize,
+}
+
+impl<N> HybridAStarOptions<N>
+where
+    N: Float + FloatConst,
+{
+    /// Creates options with 5 curvatures, 72 headings, the cell size of `step_length / 2`,
+    /// the resolution of `step_length / 10`, the goal tolerances of `step_length` and
+    /// 5 degrees, forward driving only and 100000 max expansions.
+    pub fn new(step_length: N, min_turning_radius: N) -> Self {
+        Self {
+            step_length,
+            min_turning_radius,
+            num_steering: 5,
+            cell_size: step_length / N::from(2.0).unwrap(),
+            num_headings: 72,
+            resolution: step_length / N::from(10.0).unwrap(),
+            goal_distance_tolerance: step_length,
+            goal_heading_tolerance: N::PI() / N::from(36.0).unwrap(),
+            reverse_penalty: None,
+            num_max_expansion: 100000,
+        }
+    }
+}
+
+/// Returns `angle` normalized to [-pi, pi).
+pub(crate) fn normalize_angle<N: Float + FloatConst>(angle: N) -> N {
+    let two_pi = N::PI() + N::PI();
+    let a = (angle + N::PI()) % two_pi;
+    if a < N::zero() {
+        a + two_pi - N::PI()
+    } else {
+        a - N::PI()
+    }
+}
+
+/// Returns the pose after driving `length` (negative for backward) along the arc of `curvature`.
+pub(crate) fn drive<N: Float>(pose: &[N], curvature: N, length: N) -> Vec<N> {
+    let (x, y, theta) = (pose[0], pose[1], pose[2]);
+    if curvature.abs() < N::epsilon() {
+        vec![x + length * theta.cos(), y + length * theta.sin(), theta]
+    } else {
+        let new_theta = theta + curvature * length;
+        vec![
+            x + (new_theta.sin() - theta.sin()) / curvature,
+            y + (theta.cos() - new_theta.cos()) / curvature,
+            new_theta,
+        ]
+    }
+}
+
+#[derive(Debug)]
+struct HybridNode<N> {
+    pose: Vec<N>,
+    cost: N,
+    parent: Option<usize>,
+}
+
+/// search the path of a car-like vehicle from start to goal which is free,
+/// using Hybrid A*.
+///
+/// Poses are `[x, y, heading]`. The search expands motion primitives, which are arcs of
+/// `step_length` with the curvatures of the steering set, and keeps the continuous pose
+/// of the best node in each cell of the discretized position and heading. All poses
+/// along the primitives are checked with `is_free` at `resolution` intervals, so the
+/// path is drivable with the minimum turning radius. The returned path is the poses
+/// at the ends of the primitives, from `start` to the first pose within the goal
+/// tolerances (not exactly `goal`).
+pub fn hybrid_astar<FF, N>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    options: &HybridAStarOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + FloatConst + Debug,
+{
+    crate::check_start_goal(start, goal)?;
+    if start.len() != 3 {
+        return Err(format!("pose must be [x, y, heading]: {start:?}"));
+    }
+    assert!(options.step_length > N::zero());
+    assert!(options.min_turning_radius > N::zero());
+    assert!(options.num_steering >= 2);
+    assert!(options.cell_size > N::zero());
+    assert!(options.num_headings > 0);
+    assert!(options.resolution > N::zero());
+    if !is_free(start) {
+        return Err(format!("start is not free: {start:?}"));
+    }
+    let max_curvature = N::one() / options.min_turning_radius;
+    let curvatures = (0..options.num_steering)
+        .map(|i| {
+            max_curvature
+                * (N::from(2 * i).unwrap() / N::from(options.num_steering - 1).unwrap() - N::one())
+        })
+        .collect::<Vec<_>>();
+    let mut directions = vec![(N::one(), N::one())];
+    if let Some(penalty) = options.reverse_penalty {
+        assert!(penalty >= N::one());
+        directions.push((-N::one(), penalty));
+    }
+    let num_substeps = (options.step_length / options.resolution)
+        .ceil()
+        .to_usize()
+        .unwrap()
+        .max(1);
+    let heading_size = (N::PI() + N::PI()) / N::from(options.num_headings).unwrap();
+    let key = |pose: &[N]| {
+        (
+            (pose[0] / options.cell_size).floor().to_i64().unwrap(),
+            (pose[1] / options.cell_size).floor().to_i64().unwrap(),
+            ((normalize_angle(pose[2]) + N::PI()) / heading_size)
+                .floor()
+                .to_usize()
+                .unwrap()
+                % options.num_headings,
+        )
+    };
+    let heuristic = |pose: &[N]| (pose[0] - goal[0]).hypot(pose[1] - goal[1]);
+    let is_goal = |pose: &[N]| {
+        heuristic(pose) <= options.goal_distance_tolerance
+            && normalize_angle(pose[2] - goal[2]).abs() <= options.goal_heading_tolerance
+    };
+
+    let mut nodes = vec![HybridNode {
+        pose: start.to_vec(),
+        cost: N::zero(),
+        parent: None,
+    }];
+    // the best node of each cell, and whether it is expanded
+    let mut cells = HashMap::new();
+    cells.insert(key(start), (0, false));
+    let mut open = BinaryHeap::new();
+    open.push(OpenVertex {
+        cost: heuristic(start),
+        index: 0,
+    });
+    let mut num_expansion = 0;
+    while let Some(OpenVertex { index, .. }) = open.pop() {
+        let cell = key(&nodes[index].pose);
+        match cells.get_mut(&cell) {
+            Some((best, expanded)) if *best == index && !*expanded => *expanded = true,
+            _ => continue,
+        }
+        if is_goal(&nodes[index].pose) {
+            debug!("reached the goal, expanded {num_expansion} nodes");
+            let mut path = Vec::new();
+            let mut cur = Some(index);
+            while let Some(i) = cur {
+                path.push(nodes[i].pose.clone());
+                cur = nodes[i].parent;
+            }
+            path.reverse();
+            return Ok(path);
+        }
+        num_expansion += 1;
+        if num_expansion > options.num_max_expansion {
+            break;
+        }
+        for (direction, penalty) in &directions {
+            for curvature in &curvatures {
+                let pose = &nodes[index].pose;
+                let is_drivable = (1..=num_substeps).all(|step| {
+                    let length = *direction * options.step_length * N::from(step).unwrap()
+                        / N::from(num_substeps).unwrap();
+                    is_free(&drive(pose, *curvature, length))
+                });
+                if !is_drivable {
+                    continue;
+                }
+                let mut new_pose = drive(pose, *curvature, *direction * options.step_length);
+                new_pose[2] = normalize_angle(new_pose[2]);
+                let cost = nodes[index].cost + options.step_length * *penalty;
+                let new_cell = key(&new_pose);
+                if let Some((best, expanded)) = cells.get(&new_cell) {
+                    if *expanded || nodes[*best].cost <= cost {
+                        continue;
+                    }
+                }
+                let new_index = nodes.len();
+                open.push(OpenVertex {
+                    cost: cost + heuristic(&new_pose),
+                    index: new_index,
+                });
+                nodes.push(HybridNode {
+                    pose: new_pose,
+                    cost,
+                    parent: Some(index),
+                });
+                cells.insert(new_cell, (new_index, false));
+            }
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn hybrid_astar_works() {
+    use std::f64::consts::PI;
+    let is_free =
+        |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0) && p[0].abs() < 3.0 && p[1].abs() < 3.0;
+    let options = HybridAStarOptions::new(0.2, 0.5);
+    let goal = [1.5, 0.0, -PI / 2.0];
+    let path = hybrid_astar(&[-1.5, 0.0, PI / 2.0], &goal, is_free, &options).unwrap();
+    assert_eq!(path[0], vec![-1.5, 0.0, PI / 2.0]);
+    let last = &path[path.len() - 1];
+    assert!((last[0] - goal[0]).hypot(last[1] - goal[1]) <= 0.2);
+    assert!(normalize_angle(last[2] - goal[2]).abs() <= PI / 36.0);
+    // every primitive is an arc within the minimum turning radius
+    for segment in path.windows(2) {
+        assert!(is_free(&segment[1]));
+        let turn = normalize_angle(segment[1][2] - segment[0][2]).abs();
+        assert!(turn <= 0.2 / 0.5 + 1e-9);
+    }
+    assert!((normalize_angle(3.0 * PI) + PI).abs() < 1e-9);
+}
@@ -0,0 +1,173 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::prm::OpenVertex;
+use crate::{check_start_goal, is_edge_free};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::collections::BinaryHeap;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Returns the shortest path combined from the waypoints of `paths`, which all
+/// have the same start and goal (path hybridization).
+///
+/// The waypoints of all paths are the vertices of a graph, whose edges are the segments
+/// of the paths and the straight edges between the other waypoints which are free,
+/// checked at `resolution` intervals. The shortest path in the graph is never longer
+/// than any of `paths`, and is often shorter than all of them, e.g. for the solutions
+/// of [`plan_race`](crate::plan_race) or of several planning runs.
+/// The segments of `paths` are not checked.
+pub fn hybridize_paths<FF, N>(
+    paths: &[Vec<Vec<N>>],
+    mut is_free: FF,
+    resolution: N,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    assert!(resolution > N::zero());
+    let Some(first) = paths.first().filter(|path| !path.is_empty()) else {
+        return Err("no path is given".to_string());
+    };
+    let (start, goal) = (&first[0], &first[first.len() - 1]);
+    check_start_goal(start, goal)?;
+    for path in paths {
+        if path.first() != Some(start) || path.last() != Some(goal) {
+            return Err(format!("paths must be from {start:?} to {goal:?}"));
+        }
+        if let Some(q) = path.iter().find(|q| q.len() != start.len()) {
+            return Err(format!(
+                "waypoint {q:?} does not have {} dimensions",
+                start.len()
+            ));
+        }
+    }
+    // waypoints shared by the paths are the same vertex
+    let mut vertices: Vec<&Vec<N>> = Vec::new();
+    let mut path_edges = Vec::new();
+    for path in paths {
+        let mut previous = None;
+        for q in path {
+            let index = match vertices.iter().position(|v| *v == q) {
+                Some(index) => index,
+                None => {
+                    vertices.push(q);
+                    vertices.len() - 1
+                }
+            };
+            if let Some(previous) = previous.filter(|p| *p != index) {
+                path_edges.push((previous, index));
+            }
+            previous = Some(index);
+        }
+    }
+    let goal_index = vertices.iter().position(|v| *v == goal).unwrap();
+    let mut neighbors = vec![Vec::new(); vertices.len()];
+    for i in 0..vertices.len() {
+        for j in (i + 1)..vertices.len() {
+            if path_edges.contains(&(i, j))
+                || path_edges.contains(&(j, i))
+                || is_edge_free(vertices[i], vertices[j], &mut is_free, resolution)
+            {
+                let dist = squared_euclidean(vertices[i], vertices[j]).sqrt();
+                neighbors[i].push((j, dist));
+                neighbors[j].push((i, dist));
+            }
+        }
+    }
+    let mut costs = vec![N::infinity(); vertices.len()];
+    let mut parents = vec![None; vertices.len()];
+    let mut open = BinaryHeap::new();
+    costs[0] = N::zero();
+    open.push(OpenVertex {
+        cost: N::zero(),
+        index: 0,
+    });
+    while let Some(OpenVertex { cost, index }) = open.pop() {
+        if index == goal_index {
+            break;
+        }
+        if cost > costs[index] {
+            continue;
+        }
+        for (neighbor, dist) in &neighbors[index] {
+            let new_cost = cost + *dist;
+            if new_cost < costs[*neighbor] {
+                costs[*neighbor] = new_cost;
+                parents[*neighbor] = Some(index);
+                open.push(OpenVertex {
+                    cost: new_cost,
+                    index: *neighbor,
+                });
+            }
+        }
+    }
+    debug!(
+        "{} waypoints, length = {:?}",
+        vertices.len(),
+        costs[goal_index]
+    );
+    let mut path = vec![goal.clone()];
+    let mut current = goal_index;
+    while let Some(parent) = parents[current] {
+        path.push(vertices[parent].clone());
+        current = parent;
+    }
+    path.reverse();
+    Ok(path)
+}
+
+#[test]
+fn hybridize_paths_works() {
+    use crate::path_length;
+    // two boxes on the straight line from start to goal
+    let is_free = |p: &[f64]| !((p[0].abs() - 1.5).abs() < 0.5 && p[1].abs() < 1.0);
+    let start = vec![-3.0, 0.0];
+    let goal = vec![3.0, 0.0];
+    // each path is good only in one half
+    let over = vec![
+        start.clone(),
+        vec![-1.5, 1.2],
+        vec![0.0, 0.0],
+        vec![1.5, 5.0],
+        goal.clone(),
+    ];
+    let under = vec![
+        start.clone(),
+        vec![-1.5, -5.0],
+        vec![0.0, 0.0],
+        vec![1.5, -1.2],
+        goal.clone(),
+    ];
+    let path = hybridize_paths(&[over.clone(), under.clone()], is_free, 0.01).unwrap();
+    assert_eq!(
+        path,
+        vec![
+            start.clone(),
+            vec![-1.5, 1.2],
+            vec![0.0, 0.0],
+            vec![1.5, -1.2],
+            goal.clone()
+        ]
+    );
+    assert!(path_length(&path) < path_length(&over).min(path_length(&under)));
+    // a single path is shortcut by the free straight edges
+    let path = hybridize_paths(std::slice::from_ref(&over), is_free, 0.01).unwrap();
+    assert_eq!(path, vec![start.clone(), vec![1.5, 5.0], goal.clone()]);
+    assert!(hybridize_paths(&[over, vec![start, vec![0.0, 3.0]]], is_free, 0.01).is_err());
+}
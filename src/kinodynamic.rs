@@ -0,0 +1,730 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Trajectory of a kinodynamic planner, which can be executed by applying
+/// the controls in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory<N, U> {
+    /// states from start to the end
+    pub states: Vec<Vec<N>>,
+    /// controls with their durations, applied between consecutive states
+    pub controls: Vec<(U, N)>,
+}
+
+impl<N, U> Trajectory<N, U>
+where
+    N: Float,
+{
+    /// Returns the sum of the durations.
+    pub fn duration(&self) -> N {
+        self.controls
+            .iter()
+            .fold(N::zero(), |sum, (_, duration)| sum + *duration)
+    }
+}
+
+/// Parameters of [`kinodynamic_rrt`].
+#[derive(Debug, Clone)]
+pub struct KinodynamicRrtOptions<N> {
+    /// max repeat num
+    pub num_max_try: usize,
+    /// max duration of a control, which is sampled from `0 - max_duration`
+    pub max_duration: N,
+    /// number of controls sampled in each extension. The one which reaches
+    /// the nearest state to the target is used.
+    pub num_control_samples: usize,
+    /// states within this distance of the goal reach the goal
+    pub goal_radius: N,
+    /// probability (0.0 - 1.0) to extend toward the goal instead of a random sample
+    pub goal_bias: f64,
+}
+
+impl<N> KinodynamicRrtOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 10 control samples and 0.05 goal bias.
+    pub fn new(num_max_try: usize, max_duration: N, goal_radius: N) -> Self {
+        Self {
+            num_max_try,
+            max_duration,
+            num_control_samples: 10,
+            goal_radius,
+            goal_bias: 0.05,
+        }
+    }
+}
+
+/// Parameters of [`sst`].
+#[derive(Debug, Clone)]
+pub struct SstOptions<N> {
+    /// number of iterations. All of them are used to improve the trajectory.
+    pub num_max_try: usize,
+    /// max duration of a control, which is sampled from `0 - max_duration`
+    pub max_duration: N,
+    /// radius to select the node of the least cost to extend near a sample
+    pub selection_radius: N,
+    /// radius of witnesses. Only the node of the least cost is kept active
+    /// within the radius of each witness.
+    pub witness_radius: N,
+    /// states within this distance of the goal reach the goal
+    pub goal_radius: N,
+}
+
+/// Parameters of [`kpiece`].
+#[derive(Debug, Clone)]
+pub struct KpieceOptions<N> {
+    /// max repeat num
+    pub num_max_try: usize,
+    /// max duration of a control, which is sampled from `0 - max_duration`
+    pub max_duration: N,
+    /// size of the grid cells in the projected space
+    pub cell_size: N,
+    /// states within this distance of the goal reach the goal
+    pub goal_radius: N,
+    /// factor (>= 1.0) to prefer exterior cells, which have free neighbor cells
+    pub exterior_weight: f64,
+}
+
+impl<N> KpieceOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 2.0 exterior weight.
+    pub fn new(num_max_try: usize, max_duration: N, cell_size: N, goal_radius: N) -> Self {
+        Self {
+            num_max_try,
+            max_duration,
+            cell_size,
+            goal_radius,
+            exterior_weight: 2.0,
+        }
+    }
+}
+
+/// cell of the projected grid of [`kpiece`]
+#[derive(Debug)]
+struct Cell {
+    coordinates: Vec<i64>,
+    vertices: Vec<usize>,
+    num_selections: usize,
+    num_neighbors: usize,
+}
+
+#[derive(Debug)]
+struct SstNode<N, U> {
+    state: Vec<N>,
+    parent: Option<usize>,
+    control: Option<(U, N)>,
+    cost: N,
+    active: bool,
+    num_children: usize,
+}
+
+/// Returns the trajectory from the root to `index`.
+fn trajectory_to<N, U>(nodes: &[SstNode<N, U>], index: usize) -> Trajectory<N, U>
+where
+    U: Clone,
+    N: Clone,
+{
+    let mut states = Vec::new();
+    let mut controls = Vec::new();
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        states.push(nodes[i].state.clone());
+        if let Some(control) = &nodes[i].control {
+            controls.push(control.clone());
+        }
+        cur = nodes[i].parent;
+    }
+    states.reverse();
+    controls.reverse();
+    Trajectory { states, controls }
+}
+
+/// Returns the trajectory from the root of `tree` to `index`, where `controls[i]`
+/// is the control from the parent of the vertex `i`.
+fn tree_trajectory_to<N, U>(
+    tree: &Tree<N>,
+    controls: &[Option<(U, N)>],
+    index: usize,
+) -> Trajectory<N, U>
+where
+    N: Float + Debug,
+    U: Clone,
+{
+    let mut states = Vec::new();
+    let mut trajectory_controls = Vec::new();
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        states.push(tree.vertex(i).to_vec());
+        if let Some(control) = &controls[i] {
+            trajectory_controls.push(control.clone());
+        }
+        cur = tree.parent(i);
+    }
+    states.reverse();
+    trajectory_controls.reverse();
+    Trajectory {
+        states,
+        controls: trajectory_controls,
+    }
+}
+
+/// search the trajectory from start to goal which is free, with RRT extended by
+/// sampled controls instead of straight lines.
+///
+/// `propagate(state, control, duration)` returns the state after applying `control`
+/// for `duration` from `state`, and `sample_control` returns a random control.
+/// In each extension, `num_control_samples` controls are propagated from the nearest
+/// vertex, and the free state nearest to the target is added with its control.
+/// `is_free` is checked only at the propagated states, so `max_duration` should be
+/// short enough.
+pub fn kinodynamic_rrt<FP, FC, FF, FR, N, U>(
+    start: &[N],
+    goal: &[N],
+    propagate: FP,
+    sample_control: FC,
+    is_free: FF,
+    random_sample: FR,
+    options: &KinodynamicRrtOptions<N>,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+{
+    kinodynamic_rrt_with_rng(
+        start,
+        goal,
+        propagate,
+        sample_control,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`kinodynamic_rrt`] which takes all randomness from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn kinodynamic_rrt_with_rng<FP, FC, FF, FR, N, U, R>(
+    start: &[N],
+    goal: &[N],
+    mut propagate: FP,
+    mut sample_control: FC,
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &KinodynamicRrtOptions<N>,
+    rng: &mut R,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.max_duration > N::zero());
+    assert!(options.num_control_samples > 0);
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    let dim = start.len();
+    let mut tree = Tree::with_name("start", dim);
+    tree.add_vertex(start);
+    // control from the parent of each vertex
+    let mut controls: Vec<Option<(U, N)>> = vec![None];
+    for _ in 0..options.num_max_try {
+        let q_target = if rng.gen_bool(options.goal_bias) {
+            goal.to_vec()
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_target, dim) {
+            continue;
+        }
+        let nearest_index = tree.get_nearest_index(&q_target);
+        let mut best: Option<(Vec<N>, U, N, N)> = None;
+        for _ in 0..options.num_control_samples {
+            let control = sample_control();
+            let duration = options.max_duration * N::from(rng.gen::<f64>()).unwrap();
+            let q = propagate(tree.vertex(nearest_index), &control, duration);
+            if !is_valid_point(&q, dim) || !is_free(&q) {
+                continue;
+            }
+            let dist = squared_euclidean(&q, &q_target);
+            if best.as_ref().is_none_or(|(_, _, _, d)| dist < *d) {
+                best = Some((q, control, duration, dist));
+            }
+        }
+        let Some((q_new, control, duration, _)) = best else {
+            continue;
+        };
+        let new_index = tree.add_vertex(&q_new);
+        tree.set_parent(nearest_index, new_index);
+        controls.push(Some((control, duration)));
+        if squared_euclidean(&q_new, goal).sqrt() < options.goal_radius {
+            debug!("reached the goal with {} vertices", tree.len());
+            return Ok(tree_trajectory_to(&tree, &controls, new_index));
+        }
+    }
+    Err("failed".to_string())
+}
+
+/// search the trajectory from start to goal which is free, with Stable Sparse RRT (SST).
+///
+/// `propagate(state, control, duration)` returns the state after applying `control`
+/// for `duration` from `state`, and `sample_control` returns a random control.
+/// The cost is the duration of the trajectory. `is_free` is checked only at the
+/// propagated states, so `max_duration` should be short enough.
+///
+/// Dominated nodes, which have larger costs than another node near the same
+/// witness, are pruned to keep the tree sparse.
+pub fn sst<FP, FC, FF, FR, N, U>(
+    start: &[N],
+    goal: &[N],
+    propagate: FP,
+    sample_control: FC,
+    is_free: FF,
+    random_sample: FR,
+    options: &SstOptions<N>,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+{
+    sst_with_rng(
+        start,
+        goal,
+        propagate,
+        sample_control,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`sst`] which takes all randomness from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn sst_with_rng<FP, FC, FF, FR, N, U, R>(
+    start: &[N],
+    goal: &[N],
+    mut propagate: FP,
+    mut sample_control: FC,
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &SstOptions<N>,
+    rng: &mut R,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.max_duration > N::zero());
+    let dim = start.len();
+    let mut nodes = vec![SstNode {
+        state: start.to_vec(),
+        parent: None,
+        control: None,
+        cost: N::zero(),
+        active: true,
+        num_children: 0,
+    }];
+    // contains inactive nodes until rebuilt
+    let mut active_kdtree = kdtree::KdTree::new(dim);
+    active_kdtree.add(start.to_vec(), 0).unwrap();
+    let mut num_inactive_in_kdtree = 0;
+    let mut witnesses = kdtree::KdTree::new(dim);
+    // representative node of each witness
+    let mut representatives = vec![0];
+    witnesses.add(start.to_vec(), 0).unwrap();
+    let mut best: Option<Trajectory<N, U>> = None;
+    let mut best_cost = N::infinity();
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, dim) {
+            continue;
+        }
+        // the active node of the least cost near the sample, or the nearest one
+        let r2 = options.selection_radius * options.selection_radius;
+        let selected = active_kdtree
+            .within(&q_rand, r2, &squared_euclidean)
+            .unwrap()
+            .into_iter()
+            .map(|(_, i)| *i)
+            .filter(|i| nodes[*i].active)
+            .min_by(|a, b| nodes[*a].cost.partial_cmp(&nodes[*b].cost).unwrap())
+            .or_else(|| {
+                active_kdtree
+                    .iter_nearest(&q_rand, &squared_euclidean)
+                    .unwrap()
+                    .map(|(_, i)| *i)
+                    .find(|i| nodes[*i].active)
+            });
+        let Some(selected) = selected else {
+            continue;
+        };
+        let control = sample_control();
+        let duration = options.max_duration * N::from(rng.gen::<f64>()).unwrap();
+        let q_new = propagate(&nodes[selected].state, &control, duration);
+        if !is_valid_point(&q_new, dim) || !is_free(&q_new) {
+            continue;
+        }
+        let cost = nodes[selected].cost + duration;
+        // the witness of the new node
+        let (witness_dist, witness) = {
+            let nearest = witnesses.nearest(&q_new, 1, &squared_euclidean).unwrap();
+            (nearest[0].0.sqrt(), *nearest[0].1)
+        };
+        let witness = if witness_dist > options.witness_radius {
+            witnesses.add(q_new.clone(), representatives.len()).unwrap();
+            representatives.push(usize::MAX);
+            representatives.len() - 1
+        } else {
+            witness
+        };
+        let old = representatives[witness];
+        if old != usize::MAX && nodes[old].active && nodes[old].cost <= cost {
+            continue;
+        }
+        let new_index = nodes.len();
+        nodes.push(SstNode {
+            state: q_new.clone(),
+            parent: Some(selected),
+            control: Some((control, duration)),
+            cost,
+            active: true,
+            num_children: 0,
+        });
+        nodes[selected].num_children += 1;
+        active_kdtree.add(q_new.clone(), new_index).unwrap();
+        representatives[witness] = new_index;
+        // prune the dominated node, and its ancestors which became inactive leaves
+        if old != usize::MAX && nodes[old].active {
+            nodes[old].active = false;
+            num_inactive_in_kdtree += 1;
+            let mut cur = old;
+            while nodes[cur].num_children == 0 && !nodes[cur].active {
+                let Some(parent) = nodes[cur].parent.take() else {
+                    break;
+                };
+                nodes[parent].num_children -= 1;
+                cur = parent;
+            }
+        }
+        if num_inactive_in_kdtree > nodes.len() / 2 {
+            active_kdtree = kdtree::KdTree::new(dim);
+            for (i, node) in nodes.iter().enumerate() {
+                if node.active {
+                    active_kdtree.add(node.state.clone(), i).unwrap();
+                }
+            }
+            num_inactive_in_kdtree = 0;
+        }
+        if squared_euclidean(&q_new, goal).sqrt() < options.goal_radius && cost < best_cost {
+            debug!("reached the goal, cost = {cost:?}");
+            best_cost = cost;
+            best = Some(trajectory_to(&nodes, new_index));
+        }
+    }
+    best.ok_or_else(|| "failed".to_string())
+}
+
+/// search the trajectory from start to goal which is free, with a KPIECE-like planner
+/// (Kinodynamic motion Planning by Interior-Exterior Cell Exploration).
+///
+/// `project` maps a state to a low dimensional space, which is divided into cells of
+/// `cell_size`. In each iteration, a cell is selected preferring the ones which are
+/// rarely selected, have few vertices, and are exterior (some of their neighbors are
+/// not reached yet), and a random control is propagated from a random vertex of it.
+/// No state is sampled, so this explores well for underactuated systems.
+/// `is_free` is checked only at the propagated states, so `max_duration` should be
+/// short enough.
+pub fn kpiece<FP, FC, FF, FJ, N, U>(
+    start: &[N],
+    goal: &[N],
+    propagate: FP,
+    sample_control: FC,
+    is_free: FF,
+    project: FJ,
+    options: &KpieceOptions<N>,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FJ: FnMut(&[N]) -> Vec<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+{
+    kpiece_with_rng(
+        start,
+        goal,
+        propagate,
+        sample_control,
+        is_free,
+        project,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`kpiece`] which takes all randomness from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn kpiece_with_rng<FP, FC, FF, FJ, N, U, R>(
+    start: &[N],
+    goal: &[N],
+    mut propagate: FP,
+    mut sample_control: FC,
+    mut is_free: FF,
+    mut project: FJ,
+    options: &KpieceOptions<N>,
+    rng: &mut R,
+) -> Result<Trajectory<N, U>, String>
+where
+    FP: FnMut(&[N], &U, N) -> Vec<N>,
+    FC: FnMut() -> U,
+    FF: FnMut(&[N]) -> bool,
+    FJ: FnMut(&[N]) -> Vec<N>,
+    N: Float + Debug,
+    U: Clone + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.max_duration > N::zero());
+    assert!(options.cell_size > N::zero());
+    assert!(options.exterior_weight >= 1.0);
+    let dim = start.len();
+    let mut tree = Tree::with_name("start", dim);
+    tree.add_vertex(start);
+    // control from the parent of each vertex
+    let mut controls: Vec<Option<(U, N)>> = vec![None];
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut cell_indices: HashMap<Vec<i64>, usize> = HashMap::new();
+    let mut add_to_cell = |cells: &mut Vec<Cell>, projected: Vec<N>, vertex: usize| {
+        let coordinates = projected
+            .iter()
+            .map(|v| {
+                (*v / options.cell_size)
+                    .floor()
+                    .to_i64()
+                    .unwrap_or(i64::MAX)
+            })
+            .collect::<Vec<_>>();
+        if let Some(&index) = cell_indices.get(&coordinates) {
+            cells[index].vertices.push(vertex);
+            return;
+        }
+        let mut num_neighbors = 0;
+        for k in 0..coordinates.len() {
+            for delta in [-1, 1] {
+                let mut neighbor = coordinates.clone();
+                neighbor[k] = neighbor[k].saturating_add(delta);
+                if let Some(&index) = cell_indices.get(&neighbor) {
+                    cells[index].num_neighbors += 1;
+                    num_neighbors += 1;
+                }
+            }
+        }
+        cell_indices.insert(coordinates.clone(), cells.len());
+        cells.push(Cell {
+            coordinates,
+            vertices: vec![vertex],
+            num_selections: 0,
+            num_neighbors,
+        });
+    };
+    add_to_cell(&mut cells, project(start), 0);
+    for _ in 0..options.num_max_try {
+        let importances = cells
+            .iter()
+            .map(|cell| {
+                let weight = if cell.num_neighbors < 2 * cell.coordinates.len() {
+                    options.exterior_weight
+                } else {
+                    1.0
+                };
+                weight / ((1 + cell.num_selections) * cell.vertices.len()) as f64
+            })
+            .collect::<Vec<_>>();
+        let mut threshold = rng.gen::<f64>() * importances.iter().sum::<f64>();
+        let cell_index = importances
+            .iter()
+            .position(|importance| {
+                threshold -= importance;
+                threshold <= 0.0
+            })
+            .unwrap_or(cells.len() - 1);
+        let cell = &mut cells[cell_index];
+        cell.num_selections += 1;
+        let selected = cell.vertices[rng.gen_range(0..cell.vertices.len())];
+        let control = sample_control();
+        let duration = options.max_duration * N::from(rng.gen::<f64>()).unwrap();
+        let q_new = propagate(tree.vertex(selected), &control, duration);
+        if !is_valid_point(&q_new, dim) || !is_free(&q_new) {
+            continue;
+        }
+        let projected = project(&q_new);
+        if !projected.iter().all(|v| v.is_finite()) {
+            continue;
+        }
+        let new_index = tree.add_vertex(&q_new);
+        tree.set_parent(selected, new_index);
+        controls.push(Some((control, duration)));
+        add_to_cell(&mut cells, projected, new_index);
+        if squared_euclidean(&q_new, goal).sqrt() < options.goal_radius {
+            debug!(
+                "reached the goal with {} vertices in {} cells",
+                tree.len(),
+                cells.len()
+            );
+            return Ok(tree_trajectory_to(&tree, &controls, new_index));
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn sst_works() {
+    use rand::distributions::{Distribution, Uniform};
+    // 1D double integrator, whose state is (position, velocity)
+    let propagate =
+        |q: &[f64], a: &f64, t: f64| vec![q[0] + q[1] * t + 0.5 * a * t * t, q[1] + a * t];
+    let options = SstOptions {
+        num_max_try: 5000,
+        max_duration: 0.5,
+        selection_radius: 0.2,
+        witness_radius: 0.05,
+        goal_radius: 0.1,
+    };
+    let trajectory = sst(
+        &[0.0, 0.0],
+        &[1.0, 0.0],
+        propagate,
+        || Uniform::new(-1.0, 1.0).sample(&mut rand::thread_rng()),
+        |q: &[f64]| q[1].abs() < 1.0 && (-0.5..1.5).contains(&q[0]),
+        || {
+            let mut rng = rand::thread_rng();
+            vec![
+                Uniform::new(-0.5, 1.5).sample(&mut rng),
+                Uniform::new(-1.0, 1.0).sample(&mut rng),
+            ]
+        },
+        &options,
+    )
+    .unwrap();
+    assert_eq!(trajectory.states.len(), trajectory.controls.len() + 1);
+    // the controls reproduce the states
+    let mut q = trajectory.states[0].clone();
+    for ((a, t), expected) in trajectory.controls.iter().zip(&trajectory.states[1..]) {
+        q = propagate(&q, a, *t);
+        assert_eq!(&q, expected);
+    }
+    assert!(squared_euclidean(&q, &[1.0, 0.0]).sqrt() < 0.1);
+}
+
+#[test]
+fn kinodynamic_rrt_works() {
+    use rand::distributions::{Distribution, Uniform};
+    // unicycle, whose state is (x, y, theta) and control is (velocity, angular velocity)
+    let propagate = |q: &[f64], u: &(f64, f64), t: f64| {
+        let theta = q[2] + u.1 * t;
+        vec![
+            q[0] + u.0 * t * theta.cos(),
+            q[1] + u.0 * t * theta.sin(),
+            theta,
+        ]
+    };
+    let options = KinodynamicRrtOptions::new(30000, 0.3, 0.3);
+    let trajectory = kinodynamic_rrt(
+        &[-1.5, 0.0, 0.0],
+        &[1.5, 0.0, 0.0],
+        propagate,
+        || {
+            let mut rng = rand::thread_rng();
+            (
+                Uniform::new(0.0, 1.0).sample(&mut rng),
+                Uniform::new(-2.0, 2.0).sample(&mut rng),
+            )
+        },
+        |q: &[f64]| !(q[0].abs() < 0.5 && q[1].abs() < 0.5),
+        || {
+            let mut rng = rand::thread_rng();
+            vec![
+                Uniform::new(-2.0, 2.0).sample(&mut rng),
+                Uniform::new(-2.0, 2.0).sample(&mut rng),
+                Uniform::new(-3.2, 3.2).sample(&mut rng),
+            ]
+        },
+        &options,
+    )
+    .unwrap();
+    let mut q = trajectory.states[0].clone();
+    for (u, t) in &trajectory.controls {
+        q = propagate(&q, u, *t);
+    }
+    assert_eq!(&q, trajectory.states.last().unwrap());
+    assert!(squared_euclidean(&q, &[1.5, 0.0, 0.0]).sqrt() < 0.3);
+}
+
+#[test]
+fn kpiece_works() {
+    use rand::distributions::{Distribution, Uniform};
+    // 1D double integrator, whose state is (position, velocity)
+    let propagate =
+        |q: &[f64], a: &f64, t: f64| vec![q[0] + q[1] * t + 0.5 * a * t * t, q[1] + a * t];
+    let trajectory = kpiece(
+        &[0.0, 0.0],
+        &[1.0, 0.0],
+        propagate,
+        || Uniform::new(-1.0, 1.0).sample(&mut rand::thread_rng()),
+        |q: &[f64]| q[1].abs() < 1.0 && (-0.5..1.5).contains(&q[0]),
+        // only the position is projected
+        |q: &[f64]| vec![q[0]],
+        &KpieceOptions::new(20000, 0.5, 0.05, 0.1),
+    )
+    .unwrap();
+    let mut q = trajectory.states[0].clone();
+    for ((a, t), expected) in trajectory.controls.iter().zip(&trajectory.states[1..]) {
+        q = propagate(&q, a, *t);
+        assert_eq!(&q, expected);
+    }
+    assert!(squared_euclidean(&q, &[1.0, 0.0]).sqrt() < 0.1);
+}
@@ -0,0 +1,266 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_edge_free, is_valid_point, ExtendStatus, Sampler, Tree};
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Parameters of [`lazy_rrt_connect`].
+#[derive(Debug, Clone)]
+pub struct LazyRrtConnectOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// interval to check the edges of a candidate path
+    pub resolution: N,
+}
+
+impl<N> LazyRrtConnectOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with the resolution of 1/10 of `extend_length`.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            resolution: extend_length / N::from(10.0).unwrap(),
+        }
+    }
+}
+
+/// tree which remembers the vertices whose edges to the parents are already checked
+#[derive(Debug)]
+struct LazyTree<N>
+where
+    N: Float + Debug,
+{
+    tree: Tree<N>,
+    checked: Vec<bool>,
+}
+
+impl<N> LazyTree<N>
+where
+    N: Float + Debug,
+{
+    fn new(name: &'static str, root: &[N]) -> Self {
+        let mut tree = Tree::with_name(name, root.len());
+        tree.add_vertex(root);
+        Self {
+            tree,
+            checked: vec![true],
+        }
+    }
+
+    /// indices from the root to `index`
+    fn branch(&self, index: usize) -> Vec<usize> {
+        let mut indices = vec![index];
+        let mut current = index;
+        while let Some(parent) = self.tree.parent(current) {
+            indices.push(parent);
+            current = parent;
+        }
+        indices.reverse();
+        indices
+    }
+
+    /// Checks the vertices and edges from the root to `index`, and detaches the first
+    /// invalid vertex or the vertex after the first invalid edge, with its descendants.
+    fn validate<FF>(&mut self, index: usize, is_free: &mut FF, resolution: N) -> bool
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        self.checked.resize(self.tree.len(), false);
+        for pair in self.branch(index).windows(2) {
+            let (parent, child) = (pair[0], pair[1]);
+            if self.checked[child] {
+                continue;
+            }
+            let mut invalid = vec![false; self.tree.len()];
+            let mut cut = vec![false; self.tree.len()];
+            if !is_free(self.tree.vertex(child)) {
+                invalid[child] = true;
+            } else if !is_edge_free(
+                self.tree.vertex(parent),
+                self.tree.vertex(child),
+                is_free,
+                resolution,
+            ) {
+                cut[child] = true;
+            } else {
+                self.checked[child] = true;
+                continue;
+            }
+            self.tree.detach(&invalid, &cut);
+            return false;
+        }
+        true
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// checking collisions lazily (Lazy RRT connect).
+///
+/// The trees grow without any collision checks until they are connected. Then only the
+/// vertices and edges on the candidate path are checked at `resolution` intervals, and the
+/// invalid parts are detached from the trees before growing them again. This is much
+/// faster than [`dual_rrt_connect`](crate::dual_rrt_connect) if `is_free` is expensive.
+pub fn lazy_rrt_connect<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &LazyRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    lazy_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`lazy_rrt_connect`] which takes all randomness from `rng`.
+pub fn lazy_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &LazyRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    assert!(options.resolution > N::zero());
+    if !is_free(start) {
+        return Err(format!("start is not free: {start:?}"));
+    }
+    if !is_free(goal) {
+        return Err(format!("goal is not free: {goal:?}"));
+    }
+    let mut start_tree = LazyTree::new("start", start);
+    let mut goal_tree = LazyTree::new("goal", goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    let mut not_checked = |_: &[N]| true;
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) {
+            continue;
+        }
+        let new_index = match tree_a
+            .tree
+            .extend(&q_rand, options.extend_length, &mut not_checked)
+        {
+            ExtendStatus::Trapped => unreachable!(),
+            ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) => index,
+        };
+        let q_new = tree_a.tree.vertex(new_index).to_vec();
+        let ExtendStatus::Reached(reach_index) =
+            tree_b
+                .tree
+                .connect(&q_new, options.extend_length, &mut not_checked)
+        else {
+            unreachable!()
+        };
+        if !tree_a.validate(new_index, &mut is_free, options.resolution)
+            || !tree_b.validate(reach_index, &mut is_free, options.resolution)
+        {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        // the vertices which met are within extend_length
+        let q_reach = tree_b.tree.vertex(reach_index);
+        if !is_edge_free(&q_new, q_reach, &mut is_free, options.resolution) {
+            let mut cut = vec![false; tree_b.tree.len()];
+            cut[reach_index] = true;
+            tree_b.tree.detach(&vec![false; cut.len()], &cut);
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        debug!(
+            "connected with {} vertices",
+            tree_a.tree.len() + tree_b.tree.len()
+        );
+        let mut path = tree_a
+            .branch(new_index)
+            .into_iter()
+            .map(|index| tree_a.tree.vertex(index).to_vec())
+            .collect::<Vec<_>>();
+        path.extend(
+            tree_b
+                .branch(reach_index)
+                .into_iter()
+                .rev()
+                .map(|index| tree_b.tree.vertex(index).to_vec()),
+        );
+        if tree_b.tree.name == "start" {
+            path.reverse();
+        }
+        return Ok(path);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn lazy_rrt_connect_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = lazy_rrt_connect(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &LazyRrtConnectOptions::new(0.2, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut |q: &[f64]| is_free(q),
+            0.02
+        ));
+    }
+    assert!(lazy_rrt_connect(
+        &[0.0, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        || vec![0.0, 0.0],
+        &LazyRrtConnectOptions::new(0.2, 1000),
+    )
+    .is_err());
+}
@@ -17,14 +17,151 @@
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 
+use crate::prm::OpenVertex;
+use crate::sampler::StallTracker;
 use kdtree::distance::squared_euclidean;
 use num_traits::float::Float;
 use num_traits::identities::Zero;
 use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::fmt::Debug;
 use std::mem;
+use std::sync::atomic::{self, AtomicBool};
 use tracing::debug;
 
+mod adaptive;
+mod aitstar;
+mod array;
+mod belief;
+mod birrtstar;
+mod boundary;
+mod bundle;
+mod cem;
+mod cforest;
+mod chomp;
+mod composite;
+mod constrained;
+mod corridor;
+mod dubins;
+mod dynamic_domain;
+mod elastic_band;
+mod errt;
+mod experience;
+mod forest;
+mod grid;
+mod guided;
+mod hit_and_run;
+mod hybrid_astar;
+mod hybridize;
+mod kinodynamic;
+mod lazy;
+mod linear;
+mod lqr;
+mod margin;
+mod metric;
+mod planner;
+mod potential_field;
+mod prm;
+mod quasi_random;
+mod record;
+mod reeds_shepp;
+mod region;
+mod retraction;
+mod rrg;
+mod rrtx;
+mod sampler;
+pub mod scenario;
+mod single;
+mod space;
+mod spars;
+mod state;
+mod steer;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod trrt;
+mod valid;
+mod visibility;
+mod workspace;
+mod wrap;
+pub use adaptive::{AnnealingSampler, DensityAdaptiveSampler};
+pub use aitstar::{aitstar, aitstar_with_rng, AitStarOptions};
+pub use array::dual_rrt_connect_array;
+pub use belief::{belief_rrt, belief_rrt_with_rng, BeliefRrtOptions};
+pub use birrtstar::{
+    birrtstar, birrtstar_anytime, birrtstar_anytime_with_rng, birrtstar_merged,
+    birrtstar_merged_with_rng, birrtstar_with_rng, rrtstar_gamma, BiRrtStarOptions,
+    BiRrtStarSolution,
+};
+pub use boundary::{BridgeSampler, GaussianSampler, ObstacleBasedSampler};
+pub use bundle::{dual_rrt_connect_reproducible, plan_race, PlanningBundle};
+pub use cem::{cem_refine, cem_refine_with_rng, CemOptions};
+pub use cforest::{cforest, CForestOptions};
+pub use chomp::{chomp_smooth, ChompOptions};
+pub use composite::{BoxedIsFree, BoxedSampler, CompositeSpace};
+pub use constrained::{constrained_rrt_connect, constrained_rrt_connect_with_rng};
+pub use corridor::{extract_corridor, AxisAlignedBox};
+pub use dubins::{CarPath, Dubins, SegmentType};
+pub use dynamic_domain::{
+    dynamic_domain_rrt_connect, dynamic_domain_rrt_connect_with_rng, DynamicDomainOptions,
+};
+pub use elastic_band::{ElasticBand, ElasticBandOptions};
+pub use errt::{errt, errt_with_rng, WaypointCache};
+pub use experience::{ExperienceDatabase, ExperienceSampler, GaussianMixture};
+pub use forest::{forest_rrt_connect, forest_rrt_connect_with_rng};
+pub use grid::{grid_astar, grid_cost_to_go, GridCostToGo};
+pub use guided::PathGuidedSampler;
+pub use hit_and_run::HitAndRunSampler;
+pub use hybrid_astar::{hybrid_astar, HybridAStarOptions};
+pub use hybridize::hybridize_paths;
+pub use kinodynamic::{
+    kinodynamic_rrt, kinodynamic_rrt_with_rng, kpiece, kpiece_with_rng, sst, sst_with_rng,
+    KinodynamicRrtOptions, KpieceOptions, SstOptions, Trajectory,
+};
+pub use lazy::{lazy_rrt_connect, lazy_rrt_connect_with_rng, LazyRrtConnectOptions};
+pub use linear::{
+    dual_rrt_connect_in_space, dual_rrt_connect_in_space_with_rng, linear_rrt_connect,
+    linear_rrt_connect_with_rng,
+};
+pub use lqr::{lqr_rrtstar, lqr_rrtstar_with_rng, LqrModel, LqrRrtStarOptions};
+pub use margin::{with_clearance_margin, with_margin};
+pub use metric::{CustomMetric, Metric};
+pub use planner::{
+    dual_rrt_connect_anytime, dual_rrt_connect_anytime_with_rng, dual_rrt_connect_goal_region,
+    dual_rrt_connect_goal_region_with_rng, dual_rrt_connect_multi_goal,
+    dual_rrt_connect_multi_goal_with_rng, dual_rrt_connect_with_restarts,
+    dual_rrt_connect_with_restarts_with_rng, plan, plan_through, plan_through_with_rng,
+    AnytimeOptions, DualRrtConnectPlanner, PlanStats, RestartOptions,
+};
+pub use potential_field::{potential_field, potential_field_step, PotentialFieldOptions};
+pub use prm::{ConnectionRule, PrmOptions, Roadmap};
+pub use quasi_random::{HaltonSampler, LatticeSampler, SobolSampler};
+pub use record::{RecordingSampler, ReplaySampler, SampleRecord};
+pub use reeds_shepp::ReedsShepp;
+pub use region::{Region, RegionSampler};
+pub use retraction::{retraction_rrt_connect, retraction_rrt_connect_with_rng, RetractionOptions};
+pub use rrg::{rrg, rrg_with_rng, RrgOptions};
+pub use rrtx::{RrtX, RrtXOptions};
+pub use sampler::{
+    from_rng_fn, with_bias, with_goal_bias, AxisDistribution, RngFn, SampleBatch, Sampler,
+    UniformBoxSampler,
+};
+pub use single::{rrt, rrt_with_rng, RrtOptions};
+pub use space::{CompoundSpace, DiscreteSpace, RealVectorSpace, Se2Space, So3Space, StateSpace};
+pub use spars::SparsOptions;
+pub use state::{dual_rrt_connect_state, dual_rrt_connect_state_with_rng, State};
+pub use steer::{
+    dual_rrt_connect_steer, dual_rrt_connect_steer_with_rng, dual_rrt_connect_steering,
+    dual_rrt_connect_steering_with_rng, Steering,
+};
+pub use trrt::{trrt, trrt_with_rng, TrrtOptions};
+pub use valid::{SamplingStatistics, ValidStateSampler};
+pub use visibility::visibility_graph;
+pub use workspace::WorkspaceBiasedSampler;
+pub use wrap::{unwrap_angles, wrapped_rrt_connect, wrapped_rrt_connect_with_rng, WrappedSpace};
+
 #[derive(Debug)]
 enum ExtendStatus {
     Reached(usize),
@@ -32,11 +169,22 @@ enum ExtendStatus {
     Trapped,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeState {
+    /// connected to a root, and used for queries and extension
+    Attached,
+    /// valid, but disconnected from roots by invalid ancestors
+    Detached,
+    /// in the invalidated region
+    Invalid,
+}
+
 /// Node that contains user data
 #[derive(Debug, Clone)]
 struct Node<T> {
     parent_index: Option<usize>,
     data: T,
+    state: NodeState,
 }
 
 impl<T> Node<T> {
@@ -44,66 +192,388 @@ impl<T> Node<T> {
         Node {
             parent_index: None,
             data,
+            state: NodeState::Attached,
         }
     }
 }
 
 /// RRT
+///
+/// The distance and the interpolation of the extension are those of the [`StateSpace`]
+/// `S`, which is a euclidean [`RealVectorSpace`] by default. The nearest vertices are
+/// searched by a kd-tree if the space has a [`Metric`] which it can prune by, and
+/// linearly otherwise.
 #[derive(Debug)]
-struct Tree<N>
+pub struct Tree<N, S = RealVectorSpace<N>>
 where
     N: Float + Zero + Debug,
 {
     kdtree: kdtree::KdTree<N, usize, Vec<N>>,
     vertices: Vec<Node<Vec<N>>>,
     name: &'static str,
+    dim: usize,
+    space: S,
 }
 
 impl<N> Tree<N>
 where
     N: Float + Zero + Debug,
 {
-    fn new(name: &'static str, dim: usize) -> Self {
+    /// Creates an empty tree for `dim` dimensional configurations.
+    pub fn new(dim: usize) -> Self {
+        Self::with_name("tree", dim)
+    }
+    /// Creates an empty tree with the name which tells the planners whether
+    /// it is grown from the start or the goal.
+    pub(crate) fn with_name(name: &'static str, dim: usize) -> Self {
+        Tree::named_in_space(name, RealVectorSpace::new(dim))
+    }
+    /// Uses `metric` for the nearest vertices and the extension.
+    ///
+    /// Returns an error if `metric` is not for the dimension of the tree.
+    pub fn with_metric(mut self, metric: Metric<N>) -> Result<Self, String> {
+        self.space = RealVectorSpace::new(self.dim).with_metric(metric)?;
+        Ok(self)
+    }
+    /// Returns the path from the root to the vertex at `index` found by an any-angle
+    /// search (Theta*) over the tree, instead of following the parents.
+    ///
+    /// The edges of the tree are searched in both directions, and a vertex is connected
+    /// to the parent of the expanded vertex directly if the straight edge between them is
+    /// free when checked at `resolution` intervals. This is near the shortest path within
+    /// the tree, and it is never longer than the path to the root.
+    pub fn any_angle_path<FF>(&self, index: usize, mut is_free: FF, resolution: N) -> Vec<Vec<N>>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        assert!(resolution > N::zero());
+        let mut root = index;
+        while let Some(parent) = self.vertices[root].parent_index {
+            root = parent;
+        }
+        let mut neighbors = vec![Vec::new(); self.vertices.len()];
+        for (i, node) in self.vertices.iter().enumerate() {
+            if let Some(parent) = node.parent_index {
+                neighbors[i].push(parent);
+                neighbors[parent].push(i);
+            }
+        }
+        let target = &self.vertices[index].data;
+        let heuristic = |i: usize| self.distance(&self.vertices[i].data, target);
+        let distance =
+            |a: usize, b: usize| self.distance(&self.vertices[a].data, &self.vertices[b].data);
+        let mut costs = vec![N::infinity(); self.vertices.len()];
+        let mut parents = vec![root; self.vertices.len()];
+        let mut closed = vec![false; self.vertices.len()];
+        let mut open = BinaryHeap::new();
+        costs[root] = N::zero();
+        open.push(OpenVertex {
+            cost: heuristic(root),
+            index: root,
+        });
+        while let Some(OpenVertex { index: current, .. }) = open.pop() {
+            if closed[current] {
+                continue;
+            }
+            closed[current] = true;
+            if current == index {
+                break;
+            }
+            let parent = parents[current];
+            for &neighbor in &neighbors[current] {
+                if closed[neighbor] {
+                    continue;
+                }
+                let (new_parent, cost) = if parent != current
+                    && is_edge_free(
+                        &self.vertices[parent].data,
+                        &self.vertices[neighbor].data,
+                        &mut is_free,
+                        resolution,
+                    ) {
+                    (parent, costs[parent] + distance(parent, neighbor))
+                } else {
+                    (current, costs[current] + distance(current, neighbor))
+                };
+                if cost < costs[neighbor] {
+                    costs[neighbor] = cost;
+                    parents[neighbor] = new_parent;
+                    open.push(OpenVertex {
+                        cost: cost + heuristic(neighbor),
+                        index: neighbor,
+                    });
+                }
+            }
+        }
+        let mut path = vec![target.clone()];
+        let mut cur = index;
+        while cur != root {
+            cur = parents[cur];
+            path.push(self.vertices[cur].data.clone());
+        }
+        path.reverse();
+        path
+    }
+    /// Tries to reconnect each detached subtree to its nearest attached vertex
+    /// within `max_distance`, if the straight edge between them is free when
+    /// checked at `resolution` intervals. Returns the number of vertices attached again.
+    pub fn reconnect_detached<FF>(
+        &mut self,
+        mut is_free: FF,
+        max_distance: N,
+        resolution: N,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        assert!(resolution > N::zero());
+        let mut num_attached = 0;
+        for index in 0..self.vertices.len() {
+            let node = &self.vertices[index];
+            if node.state != NodeState::Detached || node.parent_index.is_some() {
+                continue;
+            }
+            let q = node.data.clone();
+            let Some((nearest_index, dist)) = self.nearest_k(&q, 1).into_iter().next() else {
+                continue;
+            };
+            if dist > max_distance {
+                continue;
+            }
+            if !is_edge_free(
+                &self.vertices[nearest_index].data,
+                &q,
+                &mut is_free,
+                resolution,
+            ) {
+                continue;
+            }
+            self.set_parent(nearest_index, index);
+            // attach the whole subtree
+            self.vertices[index].state = NodeState::Attached;
+            self.kdtree.add(q, index).unwrap();
+            num_attached += 1;
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for i in 0..self.vertices.len() {
+                    if self.vertices[i].state != NodeState::Detached {
+                        continue;
+                    }
+                    if let Some(parent_index) = self.vertices[i].parent_index {
+                        if self.vertices[parent_index].state == NodeState::Attached {
+                            self.vertices[i].state = NodeState::Attached;
+                            self.kdtree.add(self.vertices[i].data.clone(), i).unwrap();
+                            num_attached += 1;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        num_attached
+    }
+}
+
+impl<N, S> Tree<N, S>
+where
+    N: Float + Zero + Debug,
+    S: StateSpace<N>,
+{
+    /// Creates an empty tree for the configurations of `space`.
+    pub fn in_space(space: S) -> Self {
+        Self::named_in_space("tree", space)
+    }
+    pub(crate) fn named_in_space(name: &'static str, space: S) -> Self {
+        let dim = space.dimension();
         Tree {
             kdtree: kdtree::KdTree::new(dim),
             vertices: Vec::new(),
             name,
+            dim,
+            space,
         }
     }
-    fn add_vertex(&mut self, q: &[N]) -> usize {
+    /// Returns the distance between `a` and `b` used by the tree.
+    pub fn distance(&self, a: &[N], b: &[N]) -> N {
+        self.space.distance(a, b)
+    }
+    /// Returns the metric of the space if the kd-tree can prune by it.
+    fn kd_metric(&self) -> Option<&Metric<N>> {
+        self.space
+            .metric()
+            .filter(|metric| metric.is_kd_compatible())
+    }
+    /// Adds `q` as a new vertex and returns its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is not finite or its dimension is not that of the tree.
+    /// The planners check their samples before adding them.
+    pub fn add_vertex(&mut self, q: &[N]) -> usize {
+        assert!(
+            is_valid_point(q, self.dim),
+            "invalid vertex for {} dimensions: {q:?}",
+            self.dim
+        );
         let index = self.vertices.len();
         self.kdtree.add(q.to_vec(), index).unwrap();
         self.vertices.push(Node::new(q.to_vec()));
         index
     }
-    fn add_edge(&mut self, q1_index: usize, q2_index: usize) {
+    /// Makes the vertex at `q1_index` the parent of the vertex at `q2_index`.
+    ///
+    /// Returns `Err` if an index is out of range or the edge makes a cycle.
+    pub fn add_edge(&mut self, q1_index: usize, q2_index: usize) -> Result<(), String> {
+        if q1_index >= self.len() || q2_index >= self.len() {
+            return Err(format!(
+                "index out of range: {q1_index} or {q2_index} for {} vertices",
+                self.len()
+            ));
+        }
+        let mut current = Some(q1_index);
+        while let Some(index) = current {
+            if index == q2_index {
+                return Err(format!("{q2_index} is an ancestor of {q1_index}"));
+            }
+            current = self.vertices[index].parent_index;
+        }
+        self.set_parent(q1_index, q2_index);
+        Ok(())
+    }
+    /// Same as [`add_edge`](Self::add_edge) without checks, for planners which
+    /// only add edges to new vertices.
+    pub(crate) fn set_parent(&mut self, q1_index: usize, q2_index: usize) {
         self.vertices[q2_index].parent_index = Some(q1_index);
     }
+    /// Returns the number of vertices.
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+    /// Returns the dimension of the configurations.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+    /// Returns `true` if the tree has no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+    /// Returns the configuration of the vertex at `index`.
+    pub fn vertex(&self, index: usize) -> &[N] {
+        &self.vertices[index].data
+    }
+    /// Returns the parent index of the vertex at `index`, or `None` for a root.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        self.vertices[index].parent_index
+    }
+    /// Returns at most `k` vertices nearest to `q` as `(index, distance)`, nearest first.
+    ///
+    /// Returns an empty vec if `q` is not finite or its dimension is wrong.
+    pub fn nearest_k(&self, q: &[N], k: usize) -> Vec<(usize, N)> {
+        if !is_valid_point(q, self.dim) {
+            return Vec::new();
+        }
+        let Some(metric) = self.kd_metric() else {
+            let mut nearest = self.linear_within(q, N::infinity());
+            nearest.truncate(k);
+            return nearest;
+        };
+        self.kdtree
+            .nearest(q, k, &|a: &[N], b: &[N]| metric.kd_distance(a, b))
+            .unwrap()
+            .into_iter()
+            .map(|(dist, index)| (*index, metric.kd_to_distance(dist)))
+            .collect()
+    }
+    /// Returns all vertices within distance `r` of `q` as `(index, distance)`, nearest first.
+    ///
+    /// Returns an empty vec if `q` is not finite or its dimension is wrong.
+    pub fn within(&self, q: &[N], r: N) -> Vec<(usize, N)> {
+        if !is_valid_point(q, self.dim) {
+            return Vec::new();
+        }
+        let Some(metric) = self.kd_metric() else {
+            return self.linear_within(q, r);
+        };
+        self.kdtree
+            .within(q, metric.distance_to_kd(r), &|a: &[N], b: &[N]| {
+                metric.kd_distance(a, b)
+            })
+            .unwrap()
+            .into_iter()
+            .map(|(dist, index)| (*index, metric.kd_to_distance(dist)))
+            .collect()
+    }
+    /// `within` by a linear search over the attached vertices (the ones in the kd-tree),
+    /// for the metrics which the kd-tree can't prune by
+    fn linear_within(&self, q: &[N], r: N) -> Vec<(usize, N)> {
+        let mut vertices = self
+            .vertices
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.state == NodeState::Attached)
+            .map(|(index, node)| (index, self.distance(&node.data, q)))
+            .filter(|(_, dist)| *dist <= r)
+            .collect::<Vec<_>>();
+        vertices.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        vertices
+    }
     fn get_nearest_index(&self, q: &[N]) -> usize {
-        *self.kdtree.nearest(q, 1, &squared_euclidean).unwrap()[0].1
+        let Some(metric) = self.kd_metric() else {
+            return self.linear_within(q, N::infinity())[0].0;
+        };
+        *self
+            .kdtree
+            .nearest(q, 1, &|a: &[N], b: &[N]| metric.kd_distance(a, b))
+            .unwrap()[0]
+            .1
+    }
+    /// Returns the length of the path from the root to the vertex at `index`.
+    pub fn cost_to(&self, index: usize) -> N {
+        let mut cost = N::zero();
+        let mut cur_index = index;
+        while let Some(parent_index) = self.vertices[cur_index].parent_index {
+            cost = cost
+                + self.distance(
+                    &self.vertices[cur_index].data,
+                    &self.vertices[parent_index].data,
+                );
+            cur_index = parent_index;
+        }
+        cost
     }
     fn extend<FF>(&mut self, q_target: &[N], extend_length: N, is_free: &mut FF) -> ExtendStatus
     where
         FF: FnMut(&[N]) -> bool,
     {
-        assert!(extend_length > N::zero());
         let nearest_index = self.get_nearest_index(q_target);
+        self.extend_from(nearest_index, q_target, extend_length, is_free)
+    }
+    fn extend_from<FF>(
+        &mut self,
+        nearest_index: usize,
+        q_target: &[N],
+        extend_length: N,
+        is_free: &mut FF,
+    ) -> ExtendStatus
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        assert!(extend_length > N::zero());
         let nearest_q = &self.vertices[nearest_index].data;
-        let diff_dist = squared_euclidean(q_target, nearest_q).sqrt();
-        let q_new = if diff_dist < extend_length {
+        let diff_dist = self.distance(q_target, nearest_q);
+        let mut q_new = if diff_dist < extend_length {
             q_target.to_vec()
         } else {
-            nearest_q
-                .iter()
-                .zip(q_target)
-                .map(|(near, target)| *near + (*target - *near) * extend_length / diff_dist)
-                .collect::<Vec<_>>()
+            self.space
+                .interpolate(nearest_q, q_target, extend_length / diff_dist)
         };
+        self.space.enforce_bounds(&mut q_new);
         debug!("q_new={q_new:?}");
         if is_free(&q_new) {
             let new_index = self.add_vertex(&q_new);
-            self.add_edge(nearest_index, new_index);
-            if squared_euclidean(&q_new, q_target).sqrt() < extend_length {
+            self.set_parent(nearest_index, new_index);
+            if self.distance(&q_new, q_target) < extend_length {
                 return ExtendStatus::Reached(new_index);
             }
             debug!("target = {q_target:?}");
@@ -116,13 +586,158 @@ where
     where
         FF: FnMut(&[N]) -> bool,
     {
-        loop {
+        self.connect_within(q_target, extend_length, None, is_free)
+    }
+    /// connect, giving up with the last `Advanced` after `max_extensions` extensions
+    fn connect_within<FF>(
+        &mut self,
+        q_target: &[N],
+        extend_length: N,
+        max_extensions: Option<usize>,
+        is_free: &mut FF,
+    ) -> ExtendStatus
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let max_extensions = max_extensions.unwrap_or(usize::MAX);
+        assert!(max_extensions > 0);
+        let mut status = ExtendStatus::Trapped;
+        for _ in 0..max_extensions {
             debug!("connecting...{q_target:?}");
-            match self.extend(q_target, extend_length, is_free) {
-                ExtendStatus::Trapped => return ExtendStatus::Trapped,
-                ExtendStatus::Reached(index) => return ExtendStatus::Reached(index),
-                ExtendStatus::Advanced(_) => {}
-            };
+            status = self.extend(q_target, extend_length, is_free);
+            if !matches!(status, ExtendStatus::Advanced(_)) {
+                break;
+            }
+        }
+        status
+    }
+    /// Returns `true` if the vertex at `index` is connected to a root.
+    ///
+    /// Only attached vertices are returned by queries and used for extending.
+    pub fn is_attached(&self, index: usize) -> bool {
+        self.vertices[index].state == NodeState::Attached
+    }
+    /// Detaches the vertices in the region where `is_invalid` returns `true`
+    /// (e.g. around changed obstacles), and all of their descendants.
+    ///
+    /// Detached vertices keep their indices, but are not attached until
+    /// [`reconnect_detached`](Self::reconnect_detached) reconnects them.
+    /// Roots are never detached. Returns the number of vertices detached.
+    pub fn detach_invalid<FI>(&mut self, mut is_invalid: FI) -> usize
+    where
+        FI: FnMut(&[N]) -> bool,
+    {
+        let invalid = self
+            .vertices
+            .iter()
+            .map(|node| is_invalid(&node.data))
+            .collect::<Vec<_>>();
+        self.detach(&invalid, &vec![false; invalid.len()])
+    }
+    /// Makes the attached non-root vertices with `invalid[i]` invalid, cuts the
+    /// edges to the parents of those with `cut[i]`, and detaches all of their
+    /// descendants. Returns the number of vertices detached.
+    fn detach(&mut self, invalid: &[bool], cut: &[bool]) -> usize {
+        let mut num_detached = 0;
+        for index in 0..self.vertices.len() {
+            let node = &mut self.vertices[index];
+            if node.state != NodeState::Attached || node.parent_index.is_none() {
+                continue;
+            }
+            if invalid[index] {
+                node.state = NodeState::Invalid;
+            } else if cut[index] {
+                node.state = NodeState::Detached;
+            } else {
+                continue;
+            }
+            node.parent_index = None;
+            num_detached += 1;
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.vertices.len() {
+                let Some(parent_index) = self.vertices[index].parent_index else {
+                    continue;
+                };
+                let parent_state = self.vertices[parent_index].state;
+                if parent_state == NodeState::Invalid {
+                    self.vertices[index].parent_index = None;
+                }
+                if parent_state != NodeState::Attached
+                    && self.vertices[index].state == NodeState::Attached
+                {
+                    self.vertices[index].state = NodeState::Detached;
+                    num_detached += 1;
+                    changed = true;
+                }
+            }
+        }
+        self.rebuild_kdtree();
+        num_detached
+    }
+    /// Removes the vertices for which `keep(index, q)` returns `false`
+    /// (e.g. in collision after a map update, or over a cost bound), compacts the
+    /// indices and rebuilds the kd-tree.
+    ///
+    /// Roots are never removed. Vertices whose parents are removed are detached,
+    /// as by [`detach_invalid`](Self::detach_invalid). Returns the new index of
+    /// each old index, or `None` for removed vertices.
+    pub fn prune<FK>(&mut self, mut keep: FK) -> Vec<Option<usize>>
+    where
+        FK: FnMut(usize, &[N]) -> bool,
+    {
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut num_kept = 0;
+        for (index, node) in self.vertices.iter().enumerate() {
+            let is_root = node.state == NodeState::Attached && node.parent_index.is_none();
+            if is_root || keep(index, &node.data) {
+                remap.push(Some(num_kept));
+                num_kept += 1;
+            } else {
+                remap.push(None);
+            }
+        }
+        let vertices = std::mem::take(&mut self.vertices);
+        self.vertices = vertices
+            .into_iter()
+            .zip(&remap)
+            .filter(|(_, new_index)| new_index.is_some())
+            .map(|(mut node, _)| {
+                if let Some(parent_index) = node.parent_index {
+                    node.parent_index = remap[parent_index];
+                    if node.parent_index.is_none() && node.state == NodeState::Attached {
+                        node.state = NodeState::Detached;
+                    }
+                }
+                node
+            })
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.vertices.len() {
+                let Some(parent_index) = self.vertices[index].parent_index else {
+                    continue;
+                };
+                if self.vertices[parent_index].state != NodeState::Attached
+                    && self.vertices[index].state == NodeState::Attached
+                {
+                    self.vertices[index].state = NodeState::Detached;
+                    changed = true;
+                }
+            }
+        }
+        self.rebuild_kdtree();
+        remap
+    }
+    fn rebuild_kdtree(&mut self) {
+        self.kdtree = kdtree::KdTree::new(self.dim);
+        for (index, node) in self.vertices.iter().enumerate() {
+            if node.state == NodeState::Attached {
+                self.kdtree.add(node.data.clone(), index).unwrap();
+            }
         }
     }
     fn get_until_root(&self, index: usize) -> Vec<Vec<N>> {
@@ -136,40 +751,446 @@ where
     }
 }
 
+/// `true` if the points between `a` and `b` at `resolution` intervals are free.
+/// `a` and `b` themselves are not checked.
+fn is_edge_free<FF, N>(a: &[N], b: &[N], is_free: &mut FF, resolution: N) -> bool
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    let num_steps = (squared_euclidean(a, b).sqrt() / resolution)
+        .ceil()
+        .to_usize()
+        .unwrap();
+    (1..num_steps).all(|step| {
+        let t = N::from(step).unwrap() / N::from(num_steps).unwrap();
+        let q = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| *a + (*b - *a) * t)
+            .collect::<Vec<_>>();
+        is_free(&q)
+    })
+}
+
+/// `true` if `q` has `dim` elements and all of them are finite
+fn is_valid_point<N: Float>(q: &[N], dim: usize) -> bool {
+    q.len() == dim && q.iter().all(|v| v.is_finite())
+}
+
+fn check_start_goal<N: Float + Debug>(start: &[N], goal: &[N]) -> Result<(), String> {
+    if start.len() != goal.len() {
+        return Err(format!(
+            "start and goal have different dimensions: {} and {}",
+            start.len(),
+            goal.len()
+        ));
+    }
+    if !is_valid_point(start, start.len()) {
+        return Err(format!("start is not finite: {start:?}"));
+    }
+    if !is_valid_point(goal, goal.len()) {
+        return Err(format!("goal is not finite: {goal:?}"));
+    }
+    Ok(())
+}
+
 /// search the path from start to goal which is free, using random_sample function
 pub fn dual_rrt_connect<FF, FR, N>(
     start: &[N],
     goal: &[N],
-    mut is_free: FF,
+    is_free: FF,
     random_sample: FR,
     extend_length: N,
     num_max_try: usize,
 ) -> Result<Vec<Vec<N>>, String>
 where
     FF: FnMut(&[N]) -> bool,
-    FR: Fn() -> Vec<N>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_with_options(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        &DualRrtConnectOptions::new(extend_length, num_max_try),
+    )
+}
+
+/// Policy to choose which of the two trees is extended toward the sample
+/// in each iteration of [`dual_rrt_connect_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TreeBalance {
+    /// the trees are extended by turns
+    #[default]
+    Alternate,
+    /// the tree with fewer vertices is extended
+    Smaller,
+    /// each tree is extended with the probability of the share of the other tree
+    /// in the vertices, so the smaller tree is extended more often
+    Weighted,
+}
+
+/// Ratios of the kinds of the samples which the trees of [`dual_rrt_connect_with_options`]
+/// are extended toward.
+///
+/// Uniform samples waste the iterations once the trees are large, so the schedule mixes
+/// them with the root of the other tree (the goal for the start tree, and vice versa)
+/// and points near the frontier of the other tree, which are its newest vertices.
+/// The kinds are chosen randomly in proportion to their weights.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingSchedule<N> {
+    /// weight of the samples of random_sample
+    pub uniform: f64,
+    /// weight of the root of the other tree
+    pub goal: f64,
+    /// weight of the points near the frontier of the other tree
+    pub frontier: f64,
+    /// number of the newest vertices of the other tree which are the frontier
+    pub frontier_size: usize,
+    /// standard deviation of the distance of the points from the frontier vertices
+    pub frontier_std_dev: N,
+}
+
+impl<N> SamplingSchedule<N>
+where
+    N: Float + Debug,
+{
+    /// Creates a schedule of 0.8 uniform, 0.1 goal and 0.1 frontier samples, where the
+    /// frontier is the newest 10 vertices and the standard deviation is `extend_length`.
+    pub fn new(extend_length: N) -> Self {
+        Self {
+            uniform: 0.8,
+            goal: 0.1,
+            frontier: 0.1,
+            frontier_size: 10,
+            frontier_std_dev: extend_length,
+        }
+    }
+
+    /// Returns a sample of the goal or frontier kind for the tree extended toward
+    /// `other`, or `None` to use random_sample.
+    fn sample<S, R>(&self, other: &Tree<N, S>, rng: &mut R) -> Option<Vec<N>>
+    where
+        S: StateSpace<N>,
+        R: Rng,
+    {
+        let r = rng.gen::<f64>() * (self.uniform + self.goal + self.frontier);
+        if r < self.uniform {
+            None
+        } else if r < self.uniform + self.goal {
+            Some(other.vertex(0).to_vec())
+        } else {
+            let len = other.len();
+            let index = rng.gen_range(len.saturating_sub(self.frontier_size.max(1))..len);
+            // detached vertices are not the frontier any more
+            if !other.is_attached(index) {
+                return None;
+            }
+            Some(
+                other
+                    .vertex(index)
+                    .iter()
+                    .map(|v| {
+                        *v + self.frontier_std_dev * N::from(sampler::standard_normal(rng)).unwrap()
+                    })
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Parameters of [`dual_rrt_connect_with_options`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DualRrtConnectOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// probability (0.0 - 1.0) to extend toward a random node of the other tree
+    /// instead of a random sample. 0.0 by default.
+    pub tree_bias: f64,
+    /// number of nearest nodes to a sample which are candidates to extend from.
+    /// If more than 1, the candidate minimizing
+    /// `cost-to-come + heuristic_weight * distance to the root of the other tree`
+    /// is extended, instead of the nearest one. 1 by default.
+    pub num_nearest_candidates: usize,
+    /// weight of the heuristic, used if `num_nearest_candidates` is more than 1.
+    /// 1.0 by default.
+    pub heuristic_weight: N,
+    /// policy to choose the tree to extend. [`TreeBalance::Alternate`] by default,
+    /// and the others help when one tree is trapped, e.g. in a narrow space around
+    /// start or goal.
+    pub tree_balance: TreeBalance,
+    /// max number of extensions in one connect of the other tree toward the new vertex,
+    /// which bounds the collision checks in each iteration. `None` (unlimited) by default.
+    pub max_connect_extensions: Option<usize>,
+    /// schedule to mix goal and frontier samples with random_sample, used when the
+    /// sample is not chosen by `tree_bias`. `None` (only random_sample) by default.
+    pub sampling_schedule: Option<SamplingSchedule<N>>,
+    /// distance for the nearest vertices and the extension, e.g. with per-dimension
+    /// weights. [`Metric::Euclidean`] by default.
+    pub metric: Metric<N>,
+}
+
+impl<N> DualRrtConnectOptions<N>
+where
+    N: Float,
+{
+    /// Creates options equivalent to the arguments of [`dual_rrt_connect`].
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            tree_bias: 0.0,
+            num_nearest_candidates: 1,
+            heuristic_weight: N::one(),
+            tree_balance: TreeBalance::Alternate,
+            max_connect_extensions: None,
+            sampling_schedule: None,
+            metric: Metric::Euclidean,
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function
+/// and the given options
+///
+/// Returns an error if start or goal is not finite or their dimensions differ.
+/// Samples which are not finite or have a wrong dimension are skipped.
+pub fn dual_rrt_connect_with_options<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_with_options`] which takes all randomness from `rng`.
+///
+/// The planning is reproducible with a seeded rng, e.g. `StdRng::seed_from_u64`,
+/// if `random_sample` also uses the rng given to [`Sampler::sample`].
+pub fn dual_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    let (mut tree_a, mut tree_b) = start_goal_trees(start, goal, options)?;
+    let random_sample = RefCell::new(StallTracker::new(random_sample));
+    connect_trees(
+        &mut tree_a,
+        &mut tree_b,
+        &mut is_free,
+        &mut |rng: &mut R| random_sample.borrow_mut().sample(rng),
+        &mut |q: &[N]| random_sample.borrow_mut().add_vertex(q),
+        options,
+        rng,
+        None,
+    )
+}
+
+/// dual_rrt_connect which takes all randomness from rng, with a sampler closure of the rng,
+/// and fails as soon as `cancel` is set
+fn dual_rrt_connect_with_rng_fn<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+    cancel: Option<&AtomicBool>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: FnMut(&mut R) -> Vec<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    let (mut tree_a, mut tree_b) = start_goal_trees(start, goal, options)?;
+    connect_trees(
+        &mut tree_a,
+        &mut tree_b,
+        &mut is_free,
+        &mut random_sample,
+        &mut |_: &[N]| {},
+        options,
+        rng,
+        cancel,
+    )
+}
+
+/// Returns the trees named "start" and "goal" rooted at them, with the metric of the
+/// options.
+pub(crate) fn start_goal_trees<N>(
+    start: &[N],
+    goal: &[N],
+    options: &DualRrtConnectOptions<N>,
+) -> Result<(Tree<N>, Tree<N>), String>
+where
     N: Float + Debug,
 {
-    assert_eq!(start.len(), goal.len());
-    let mut tree_a = Tree::new("start", start.len());
-    let mut tree_b = Tree::new("goal", start.len());
+    check_start_goal(start, goal)?;
+    let mut tree_a = options_tree("start", start.len(), options)?;
+    let mut tree_b = options_tree("goal", start.len(), options)?;
     tree_a.add_vertex(start);
     tree_b.add_vertex(goal);
-    for _ in 0..num_max_try {
+    Ok((tree_a, tree_b))
+}
+
+/// Returns an empty tree named `name` with the metric of the options.
+pub(crate) fn options_tree<N>(
+    name: &'static str,
+    dim: usize,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Tree<N>, String>
+where
+    N: Float + Debug,
+{
+    Tree::with_name(name, dim).with_metric(options.metric.clone())
+}
+
+/// grow the trees named "start" and "goal" until they are connected,
+/// and return the path from the root of "start" to the root of "goal".
+/// `add_vertex` is called with each new vertex, and planning fails as soon as
+/// `cancel` is set.
+#[allow(clippy::too_many_arguments)]
+fn connect_trees<'a, FF, FR, FV, N, S, R>(
+    mut tree_a: &'a mut Tree<N, S>,
+    mut tree_b: &'a mut Tree<N, S>,
+    is_free: &mut FF,
+    random_sample: &mut FR,
+    add_vertex: &mut FV,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+    cancel: Option<&AtomicBool>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: FnMut(&mut R) -> Vec<N>,
+    FV: FnMut(&[N]),
+    N: Float + Debug,
+    S: StateSpace<N>,
+    R: Rng,
+{
+    assert!((0.0..=1.0).contains(&options.tree_bias));
+    if let Some(schedule) = &options.sampling_schedule {
+        let weights = [schedule.uniform, schedule.goal, schedule.frontier];
+        assert!(weights.iter().all(|w| *w >= 0.0) && weights.iter().sum::<f64>() > 0.0);
+        assert!(schedule.frontier_std_dev >= N::zero());
+    }
+    let dim = tree_a.dim;
+    let extend_length = options.extend_length;
+    let mut num_invalid_samples = 0;
+    for _ in 0..options.num_max_try {
+        if cancel.is_some_and(|cancel| cancel.load(atomic::Ordering::Relaxed)) {
+            return Err("cancelled".to_string());
+        }
+        let (len_a, len_b) = (tree_a.vertices.len(), tree_b.vertices.len());
+        let swap = match options.tree_balance {
+            TreeBalance::Alternate => false,
+            TreeBalance::Smaller => len_a > len_b,
+            TreeBalance::Weighted => rng.gen_bool(len_a as f64 / (len_a + len_b) as f64),
+        };
+        if swap {
+            mem::swap(&mut tree_a, &mut tree_b);
+        }
         debug!("tree_a = {:?}", tree_a.vertices.len());
         debug!("tree_b = {:?}", tree_b.vertices.len());
-        let q_rand = random_sample();
-        let extend_status = tree_a.extend(&q_rand, extend_length, &mut is_free);
+        // vertices detached from tree_b are not sampled, trying a few times
+        let tree_sample = if rng.gen_bool(options.tree_bias) {
+            (0..10)
+                .map(|_| rng.gen_range(0..tree_b.vertices.len()))
+                .find(|index| tree_b.is_attached(*index))
+                .map(|index| tree_b.vertices[index].data.clone())
+        } else {
+            None
+        };
+        let tree_sample = tree_sample.or_else(|| {
+            options
+                .sampling_schedule
+                .as_ref()
+                .and_then(|schedule| schedule.sample(tree_b, rng))
+        });
+        let mut q_rand = match tree_sample {
+            Some(q) => q,
+            None => random_sample(rng),
+        };
+        if !is_valid_point(&q_rand, dim) {
+            debug!("skip invalid sample {q_rand:?}");
+            num_invalid_samples += 1;
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        tree_a.space.enforce_bounds(&mut q_rand);
+        let extend_status = if options.num_nearest_candidates > 1 {
+            let goal = tree_b.vertex(0);
+            let (index, _) = tree_a
+                .nearest_k(&q_rand, options.num_nearest_candidates)
+                .into_iter()
+                .map(|(index, _)| {
+                    let heuristic = tree_a.distance(tree_a.vertex(index), goal);
+                    (
+                        index,
+                        tree_a.cost_to(index) + options.heuristic_weight * heuristic,
+                    )
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .unwrap();
+            tree_a.extend_from(index, &q_rand, extend_length, is_free)
+        } else {
+            tree_a.extend(&q_rand, extend_length, is_free)
+        };
         match extend_status {
             ExtendStatus::Trapped => {}
             ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) => {
                 let q_new = &tree_a.vertices[new_index].data;
-                if let ExtendStatus::Reached(reach_index) =
-                    tree_b.connect(q_new, extend_length, &mut is_free)
-                {
+                add_vertex(q_new);
+                let num_vertices_b = tree_b.vertices.len();
+                let connect_status = tree_b.connect_within(
+                    q_new,
+                    extend_length,
+                    options.max_connect_extensions,
+                    is_free,
+                );
+                for vertex in &tree_b.vertices[num_vertices_b..] {
+                    add_vertex(&vertex.data);
+                }
+                if let ExtendStatus::Reached(reach_index) = connect_status {
                     let mut a_all = tree_a.get_until_root(new_index);
                     let mut b_all = tree_b.get_until_root(reach_index);
                     a_all.reverse();
+                    // the vertices which met are within extend_length, or the same
+                    a_all.push(tree_a.vertex(new_index).to_vec());
+                    if tree_b.vertex(reach_index) != tree_a.vertex(new_index) {
+                        a_all.push(tree_b.vertex(reach_index).to_vec());
+                    }
                     a_all.append(&mut b_all);
                     if tree_b.name == "start" {
                         a_all.reverse();
@@ -180,28 +1201,76 @@ where
         }
         mem::swap(&mut tree_a, &mut tree_b);
     }
+    if num_invalid_samples > 0 {
+        return Err(format!(
+            "failed ({num_invalid_samples} invalid samples were skipped)"
+        ));
+    }
     Err("failed".to_string())
 }
 
+/// Returns the euclidean length of the path, which is the cost of the solutions of the
+/// planners with the default [`Metric::Euclidean`].
+///
+/// For a path extracted from a [`Tree`] of another metric, use [`path_length_with`] to
+/// get [`Tree::cost_to`] of its end.
+pub fn path_length<N>(path: &[Vec<N>]) -> N
+where
+    N: Float,
+{
+    path.windows(2).fold(N::zero(), |sum, segment| {
+        sum + squared_euclidean(&segment[0], &segment[1]).sqrt()
+    })
+}
+
+/// Returns the length of the path in `metric`.
+///
+/// For a path extracted from a [`Tree`] with `metric`, this is the same as
+/// [`Tree::cost_to`] of its end.
+pub fn path_length_with<N>(metric: &Metric<N>, path: &[Vec<N>]) -> N
+where
+    N: Float + Debug,
+{
+    path.windows(2).fold(N::zero(), |sum, segment| {
+        sum + metric.distance(&segment[0], &segment[1])
+    })
+}
+
 /// select random two points, and try to connect.
-pub fn smooth_path<FF, N>(
+pub fn smooth_path<FF, N>(path: &mut Vec<Vec<N>>, is_free: FF, extend_length: N, num_max_try: usize)
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    smooth_path_with_rng(
+        path,
+        is_free,
+        extend_length,
+        num_max_try,
+        &mut rand::thread_rng(),
+    );
+}
+
+/// [`smooth_path`] which takes all randomness from `rng`.
+pub fn smooth_path_with_rng<FF, N, R>(
     path: &mut Vec<Vec<N>>,
     mut is_free: FF,
     extend_length: N,
     num_max_try: usize,
+    rng: &mut R,
 ) where
     FF: FnMut(&[N]) -> bool,
     N: Float + Debug,
+    R: Rng,
 {
     if path.len() < 3 {
         return;
     }
-    let mut rng = rand::thread_rng();
     for _ in 0..num_max_try {
         let range1 = Uniform::new(0, path.len() - 2);
-        let ind1 = range1.sample(&mut rng);
+        let ind1 = range1.sample(rng);
         let range2 = Uniform::new(ind1 + 2, path.len());
-        let ind2 = range2.sample(&mut rng);
+        let ind2 = range2.sample(rng);
         let mut base_point = path[ind1].clone();
         let point2 = path[ind2].clone();
         let mut is_searching = true;
@@ -238,28 +1307,512 @@ pub fn smooth_path<FF, N>(
 
 #[test]
 fn it_works() {
-    use rand::distributions::{Distribution, Uniform};
+    let world = crate::scenario::World::square();
     let mut result = dual_rrt_connect(
         &[-1.2, 0.0],
         &[1.2, 0.0],
-        |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0),
-        || {
-            let between = Uniform::new(-2.0, 2.0);
-            let mut rng = rand::thread_rng();
-            vec![between.sample(&mut rng), between.sample(&mut rng)]
-        },
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
         0.2,
         1000,
     )
     .unwrap();
     println!("{result:?}");
     assert!(result.len() >= 4);
-    smooth_path(
-        &mut result,
-        |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0),
-        0.2,
-        100,
-    );
+    smooth_path(&mut result, |p: &[f64]| world.is_free(p), 0.2, 100);
     println!("{result:?}");
     assert!(result.len() >= 3);
 }
+
+#[test]
+fn tree_bias() {
+    let world = crate::scenario::World::square();
+    let options = DualRrtConnectOptions {
+        tree_bias: 0.3,
+        ..DualRrtConnectOptions::new(0.2, 1000)
+    };
+    let result = dual_rrt_connect_with_options(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &options,
+    )
+    .unwrap();
+    assert!(result.len() >= 4);
+}
+
+#[test]
+fn tree_balance() {
+    let world = crate::scenario::World::square();
+    // start is in a box which opens only at a narrow slit
+    let is_free = |p: &[f64]| {
+        let in_wall = (p[0].abs() - 0.5).abs() < 0.05 && p[1].abs() < 0.55
+            || (p[1].abs() - 0.5).abs() < 0.05 && p[0].abs() < 0.55;
+        !in_wall || (p[0] > 0.0 && p[1].abs() < 0.05)
+    };
+    for tree_balance in [
+        TreeBalance::Alternate,
+        TreeBalance::Smaller,
+        TreeBalance::Weighted,
+    ] {
+        let options = DualRrtConnectOptions {
+            tree_balance,
+            ..DualRrtConnectOptions::new(0.05, 30000)
+        };
+        let result = dual_rrt_connect_with_options(
+            &[0.0, 0.0],
+            &[-1.5, 0.0],
+            is_free,
+            world.sampler(1),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result[0], vec![0.0, 0.0]);
+        assert_eq!(result[result.len() - 1], vec![-1.5, 0.0]);
+        assert!(result.iter().all(|q| is_free(q)));
+    }
+}
+
+#[test]
+fn max_connect_extensions() {
+    use std::cell::Cell;
+    let world = crate::scenario::World::square();
+    let num_checks = Cell::new(0);
+    let is_free = |p: &[f64]| {
+        num_checks.set(num_checks.get() + 1);
+        !(p[0].abs() < 1.0 && p[1].abs() < 1.0)
+    };
+    let random_sample = world.sampler(1);
+    let options = DualRrtConnectOptions {
+        max_connect_extensions: Some(3),
+        ..DualRrtConnectOptions::new(0.2, 200)
+    };
+    // an extension and at most 3 extensions of connect in each iteration
+    let _ =
+        dual_rrt_connect_with_options(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options);
+    assert!(num_checks.get() <= 200 * 4);
+    let options = DualRrtConnectOptions {
+        num_max_try: 5000,
+        ..options
+    };
+    let result =
+        dual_rrt_connect_with_options(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options)
+            .unwrap();
+    assert_eq!(result[0], vec![-1.2, 0.0]);
+    assert_eq!(result[result.len() - 1], vec![1.2, 0.0]);
+}
+
+#[test]
+fn invalid_inputs() {
+    let is_free = |_: &[f64]| true;
+    let random_sample = || vec![f64::NAN, 0.0];
+    assert!(dual_rrt_connect(
+        &[0.0, 0.0],
+        &[f64::NAN, 0.0],
+        is_free,
+        random_sample,
+        0.1,
+        10
+    )
+    .is_err());
+    assert!(dual_rrt_connect(&[0.0, 0.0], &[1.0], is_free, random_sample, 0.1, 10).is_err());
+    let err =
+        dual_rrt_connect(&[0.0, 0.0], &[1.0, 0.0], is_free, random_sample, 0.1, 10).unwrap_err();
+    assert!(err.contains("10 invalid samples"));
+}
+
+#[test]
+fn detach_and_reconnect() {
+    // 0 - 1 - 2 - 3 on a line
+    let mut tree = Tree::new(1);
+    for i in 0..4 {
+        tree.add_vertex(&[i as f64]);
+        if i > 0 {
+            tree.add_edge(i - 1, i).unwrap();
+        }
+    }
+    assert_eq!(tree.detach_invalid(|q| (q[0] - 1.0).abs() < 0.5), 3);
+    assert!(tree.is_attached(0));
+    assert!(!tree.is_attached(1) && !tree.is_attached(2) && !tree.is_attached(3));
+    assert_eq!(tree.nearest_k(&[3.0], 1)[0].0, 0);
+    // vertex 1 is invalid, so 2 (with 3) is reconnected to 0
+    assert_eq!(tree.reconnect_detached(|_| true, 2.5, 0.1), 2);
+    assert_eq!(tree.parent(2), Some(0));
+    assert_eq!(tree.parent(3), Some(2));
+    assert!(!tree.is_attached(1));
+}
+
+#[test]
+fn detach_and_reconnect_with_linear_metric() {
+    // the linear search of the custom metric must skip detached vertices too
+    let mut tree = Tree::new(1)
+        .with_metric(Metric::custom(|a: &[f64], b: &[f64]| (a[0] - b[0]).abs()))
+        .unwrap();
+    for i in 0..3 {
+        tree.add_vertex(&[i as f64]);
+        if i > 0 {
+            tree.add_edge(i - 1, i).unwrap();
+        }
+    }
+    assert_eq!(tree.detach_invalid(|q| (q[0] - 1.0).abs() < 0.5), 2);
+    assert_eq!(tree.nearest_k(&[2.0], 3), vec![(0, 2.0)]);
+    assert!(tree.within(&[2.0], 0.5).is_empty());
+    assert_eq!(tree.reconnect_detached(|_| true, 10.0, 0.1), 1);
+    assert_eq!(tree.parent(2), Some(0));
+    assert_eq!(tree.cost_to(2), 2.0);
+    assert_eq!(tree.get_until_root(2), vec![vec![0.0]]);
+}
+
+#[test]
+fn heuristic_extension() {
+    let world = crate::scenario::World::square();
+    let options = DualRrtConnectOptions {
+        num_nearest_candidates: 5,
+        ..DualRrtConnectOptions::new(0.2, 1000)
+    };
+    let result = dual_rrt_connect_with_options(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &options,
+    )
+    .unwrap();
+    assert!(result.len() >= 4);
+}
+
+#[test]
+fn tree_query() {
+    let mut tree = Tree::new(2);
+    for i in 0..10 {
+        tree.add_vertex(&[i as f64, 0.0]);
+    }
+    let nearest = tree.nearest_k(&[3.1, 0.0], 3);
+    assert_eq!(
+        nearest.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+        vec![3, 4, 2]
+    );
+    assert!((nearest[0].1 - 0.1).abs() < 1e-9);
+    let within = tree.within(&[3.1, 0.0], 1.5);
+    assert_eq!(
+        within.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+        vec![3, 4, 2]
+    );
+    assert!(tree.within(&[3.5, 10.0], 1.0).is_empty());
+}
+
+#[test]
+fn tree_in_space() {
+    // an angle wrapping at +-pi
+    let mut tree = Tree::in_space(WrappedSpace::new(&[true]));
+    tree.add_vertex(&[3.0]);
+    tree.add_vertex(&[0.0]);
+    tree.set_parent(0, 1);
+    let (nearest, dist) = tree.nearest_k(&[-3.0], 1)[0];
+    assert_eq!(nearest, 0);
+    assert!((dist - (2.0 * std::f64::consts::PI - 6.0)).abs() < 1e-9);
+    // extends across +-pi, and the new vertex is normalized
+    let ExtendStatus::Advanced(index) = tree.extend(&[-2.5], 0.2, &mut |_: &[f64]| true) else {
+        panic!("not advanced");
+    };
+    assert_eq!(tree.parent(index), Some(0));
+    assert!((tree.vertex(index)[0] - (3.2 - 2.0 * std::f64::consts::PI)).abs() < 1e-9);
+
+    // new vertices are clamped to the bounds
+    let space = RealVectorSpace::with_bounds(&[-1.0, -1.0], &[1.0, 1.0]).unwrap();
+    let mut tree = Tree::in_space(space);
+    tree.add_vertex(&[0.9, 0.0]);
+    let ExtendStatus::Advanced(index) = tree.extend(&[3.0, 0.5], 0.5, &mut |_: &[f64]| true) else {
+        panic!("not advanced");
+    };
+    assert_eq!(tree.vertex(index)[0], 1.0);
+}
+
+#[test]
+fn any_angle_path() {
+    // 0 - 1 - 2 - 3 - 4 around a box, whose straight path is 0 - 2 - 4
+    let mut tree = Tree::new(2);
+    for (i, q) in [[0.0, 0.0], [1.0, 1.0], [2.0, 1.2], [3.0, 1.0], [4.0, 0.0]]
+        .iter()
+        .enumerate()
+    {
+        tree.add_vertex(q);
+        if i > 0 {
+            tree.add_edge(i - 1, i).unwrap();
+        }
+    }
+    let is_free = |p: &[f64]| !((p[0] - 2.0).abs() < 0.5 && p[1] < 0.8);
+    assert_eq!(
+        tree.any_angle_path(4, is_free, 0.01),
+        vec![vec![0.0, 0.0], vec![2.0, 1.2], vec![4.0, 0.0]]
+    );
+    assert_eq!(
+        tree.any_angle_path(1, is_free, 0.01),
+        vec![vec![0.0, 0.0], vec![1.0, 1.0]]
+    );
+}
+
+#[test]
+fn prune() {
+    // 0 - 1 - 2 - 3 on a line, and 0 - 4
+    let mut tree = Tree::new(1);
+    for i in 0..4 {
+        tree.add_vertex(&[i as f64]);
+        if i > 0 {
+            tree.add_edge(i - 1, i).unwrap();
+        }
+    }
+    tree.add_vertex(&[-1.0]);
+    tree.add_edge(0, 4).unwrap();
+    assert!(tree.add_edge(3, 1).is_err());
+    let path = (0..4).map(|i| vec![i as f64]).collect::<Vec<_>>();
+    assert_eq!(tree.cost_to(3), path_length(&path));
+    let remap = tree.prune(|i, _| i != 0 && i != 1);
+    assert_eq!(remap, vec![Some(0), None, Some(1), Some(2), Some(3)]);
+    assert_eq!(tree.len(), 4);
+    assert_eq!(tree.vertex(1), &[2.0]);
+    assert_eq!(tree.parent(1), None);
+    assert_eq!(tree.parent(2), Some(1));
+    assert_eq!(tree.parent(3), Some(0));
+    assert!(tree.is_attached(0) && tree.is_attached(3));
+    assert!(!tree.is_attached(1) && !tree.is_attached(2));
+    assert_eq!(tree.nearest_k(&[3.0], 1)[0].0, 0);
+}
+
+#[test]
+fn with_rng_is_reproducible() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let plan = |seed: u64| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sampler = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+        let mut path = dual_rrt_connect_with_rng(
+            &[-1.2, 0.0],
+            &[1.2, 0.0],
+            is_free,
+            sampler.clone(),
+            &DualRrtConnectOptions::new(0.2, 1000),
+            &mut rng,
+        )
+        .unwrap();
+        smooth_path_with_rng(&mut path, is_free, 0.2, 100, &mut rng);
+        let single = rrt_with_rng(
+            &[-1.2, 0.0],
+            &[1.2, 0.0],
+            is_free,
+            sampler,
+            &RrtOptions::new(0.2, 10000),
+            &mut rng,
+        )
+        .unwrap();
+        (path, single)
+    };
+    assert_eq!(plan(0), plan(0));
+    assert_eq!(plan(1), plan(1));
+}
+
+#[test]
+fn planners_with_rng_are_reproducible() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let clearance = |p: &[f64]| (p[0].abs() - 1.0).max(p[1].abs() - 1.0);
+    let (start, goal) = ([-1.2, 0.0], [1.2, 0.0]);
+    let plan = |seed: u64| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sampler = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+        let options = DualRrtConnectOptions::new(0.2, 1000);
+        let mut paths = vec![
+            birrtstar_with_rng(
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &BiRrtStarOptions::new(0.2, 300, 0.01),
+                &mut rng,
+            ),
+            aitstar_with_rng(
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &AitStarOptions::new(0.6, 3, 0.01),
+                &mut rng,
+            ),
+            lazy_rrt_connect_with_rng(
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &LazyRrtConnectOptions::new(0.2, 1000),
+                &mut rng,
+            ),
+            retraction_rrt_connect_with_rng(
+                &start,
+                &goal,
+                clearance,
+                sampler.clone(),
+                &RetractionOptions::new(0.2, 1000),
+                &mut rng,
+            ),
+            linear_rrt_connect_with_rng(
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &options,
+                &mut rng,
+            ),
+            dual_rrt_connect_in_space_with_rng(
+                &RealVectorSpace::new(2),
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &options,
+                &mut rng,
+            ),
+            errt_with_rng(
+                &start,
+                &goal,
+                is_free,
+                sampler.clone(),
+                &options,
+                &mut WaypointCache::new(10),
+                &mut rng,
+            ),
+            DualRrtConnectPlanner::new(&start, &goal, options.clone())
+                .and_then(|mut planner| planner.plan_with_rng(is_free, sampler.clone(), &mut rng)),
+            plan_through_with_rng(
+                &[start.to_vec(), vec![0.0, 1.5], goal.to_vec()],
+                is_free,
+                sampler.clone(),
+                &options,
+                100,
+                &mut rng,
+            ),
+            RrtX::new(&goal, RrtXOptions::new(0.2, 1000, 0.01)).and_then(|mut rrtx| {
+                rrtx.plan_with_rng(&start, is_free, sampler.clone(), &mut rng)
+            }),
+        ];
+        let mut roadmap = Roadmap::new(2, PrmOptions::new(0.5, 0.01));
+        roadmap.grow_with_rng(is_free, sampler, 300, &mut rng);
+        paths.push(roadmap.query(&start, &goal, is_free));
+        paths
+            .into_iter()
+            .map(|path| path.unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(plan(0), plan(0));
+    assert_eq!(plan(1), plan(1));
+}
+
+#[test]
+fn sampling_schedule() {
+    use rand::distributions::{Distribution, Uniform};
+    // a large space where the trees rarely meet by uniform samples
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = || {
+        let between = Uniform::new(-50.0, 50.0);
+        let mut rng = rand::thread_rng();
+        vec![between.sample(&mut rng), between.sample(&mut rng)]
+    };
+    let options = DualRrtConnectOptions {
+        sampling_schedule: Some(SamplingSchedule::new(0.1)),
+        ..DualRrtConnectOptions::new(0.1, 3000)
+    };
+    let path =
+        dual_rrt_connect_with_options(&[-1.2, 0.0], &[1.2, 0.0], is_free, random_sample, &options)
+            .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path.iter().all(|q| is_free(q)));
+
+    let mut tree = Tree::new(1);
+    for i in 0..20 {
+        tree.add_vertex(&[i as f64]);
+    }
+    let schedule = SamplingSchedule {
+        uniform: 0.0,
+        goal: 0.0,
+        frontier: 1.0,
+        frontier_size: 5,
+        frontier_std_dev: 0.0,
+    };
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        assert!(schedule.sample(&tree, &mut rng).unwrap()[0] >= 15.0);
+    }
+    let schedule = SamplingSchedule {
+        goal: 1.0,
+        frontier: 0.0,
+        ..schedule
+    };
+    assert_eq!(schedule.sample(&tree, &mut rng), Some(vec![0.0]));
+}
+
+#[test]
+fn distance_weights() {
+    let world = crate::scenario::World::square();
+    let mut tree = Tree::new(2)
+        .with_metric(Metric::WeightedEuclidean(vec![1.0, 100.0]))
+        .unwrap();
+    tree.add_vertex(&[0.0, 1.0]);
+    tree.add_vertex(&[5.0, 0.0]);
+    // 10 away from the first and 5 away from the second
+    assert_eq!(tree.nearest_k(&[0.0, 0.0], 1), vec![(1, 5.0)]);
+    assert_eq!(tree.within(&[0.0, 0.0], 6.0), vec![(1, 5.0)]);
+    assert!(Tree::<f64>::new(2)
+        .with_metric(Metric::WeightedEuclidean(vec![1.0]))
+        .is_err());
+    assert!(Tree::<f64>::new(2)
+        .with_metric(Metric::WeightedEuclidean(vec![1.0, 0.0]))
+        .is_err());
+    // the cost in the tree is the length in its metric
+    tree.add_edge(0, 1).unwrap();
+    let path = tree.get_until_root(1);
+    let path = [vec![tree.vertex(1).to_vec()], path].concat();
+    let metric = Metric::WeightedEuclidean(vec![1.0, 100.0]);
+    assert!((tree.cost_to(1) - path_length_with(&metric, &path)).abs() < 1e-9);
+    assert!((tree.cost_to(1) - path_length(&path)).abs() > 1.0);
+
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    let weights = [1.0, 4.0];
+    let weighted =
+        |a: &[f64], b: &[f64]| ((a[0] - b[0]).powi(2) + weights[1] * (a[1] - b[1]).powi(2)).sqrt();
+    let options = DualRrtConnectOptions {
+        metric: Metric::WeightedEuclidean(weights.to_vec()),
+        ..DualRrtConnectOptions::new(0.2, 10000)
+    };
+    for path in [
+        dual_rrt_connect_with_options(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options)
+            .unwrap(),
+        linear_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options).unwrap(),
+    ] {
+        assert_eq!(path[0], vec![-1.2, 0.0]);
+        assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+        assert!(path.iter().all(|q| is_free(q)));
+        for segment in path.windows(2) {
+            assert!(weighted(&segment[0], &segment[1]) <= 0.2 + 1e-9);
+        }
+    }
+    let options = RrtOptions {
+        metric: Metric::WeightedEuclidean(weights.to_vec()),
+        ..RrtOptions::new(0.2, 10000)
+    };
+    let path = rrt(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options).unwrap();
+    for segment in path.windows(2) {
+        assert!(weighted(&segment[0], &segment[1]) <= 0.2 + 1e-9);
+    }
+    let options = RrtOptions {
+        metric: Metric::WeightedEuclidean(vec![1.0]),
+        ..RrtOptions::new(0.2, 10000)
+    };
+    assert!(rrt(&[-1.2, 0.0], &[1.2, 0.0], is_free, &random_sample, &options).is_err());
+}
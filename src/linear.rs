@@ -0,0 +1,367 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::StallTracker;
+use crate::{
+    check_start_goal, connect_trees, is_valid_point, DualRrtConnectOptions, ExtendStatus,
+    RealVectorSpace, Sampler, StateSpace, Tree,
+};
+use num_traits::float::Float;
+use rand::Rng;
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Returns the squared distance of `a` and `b`, summing in 4 lanes so that
+/// the compiler can vectorize it.
+pub(crate) fn squared_distance<N: Float>(a: &[N], b: &[N]) -> N {
+    let mut lanes = [N::zero(); 4];
+    let mut a_chunks = a.chunks_exact(4);
+    let mut b_chunks = b.chunks_exact(4);
+    for (a, b) in (&mut a_chunks).zip(&mut b_chunks) {
+        for i in 0..4 {
+            let d = a[i] - b[i];
+            lanes[i] = lanes[i] + d * d;
+        }
+    }
+    let rest = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .fold(N::zero(), |sum, (a, b)| sum + (*a - *b) * (*a - *b));
+    (lanes[0] + lanes[1]) + (lanes[2] + lanes[3]) + rest
+}
+
+/// tree whose vertices are stored in one contiguous buffer, searched by linear scan
+#[derive(Debug)]
+struct FlatTree<N> {
+    name: &'static str,
+    dim: usize,
+    data: Vec<N>,
+    parents: Vec<Option<usize>>,
+}
+
+impl<N> FlatTree<N>
+where
+    N: Float + Debug,
+{
+    fn new(name: &'static str, root: &[N]) -> Self {
+        Self {
+            name,
+            dim: root.len(),
+            data: root.to_vec(),
+            parents: vec![None],
+        }
+    }
+    fn len(&self) -> usize {
+        self.parents.len()
+    }
+    fn vertex(&self, index: usize) -> &[N] {
+        &self.data[index * self.dim..(index + 1) * self.dim]
+    }
+    fn nearest<S: StateSpace<N>>(&self, space: &S, q: &[N]) -> usize {
+        let mut best = (0, N::infinity());
+        for (index, v) in self.data.chunks_exact(self.dim).enumerate() {
+            let d = space.distance(v, q);
+            if d < best.1 {
+                best = (index, d);
+            }
+        }
+        best.0
+    }
+    fn extend<S, FF>(
+        &mut self,
+        space: &S,
+        q_target: &[N],
+        extend_length: N,
+        is_free: &mut FF,
+    ) -> ExtendStatus
+    where
+        S: StateSpace<N>,
+        FF: FnMut(&[N]) -> bool,
+    {
+        let nearest_index = self.nearest(space, q_target);
+        let nearest_q = self.vertex(nearest_index);
+        let dist = space.distance(nearest_q, q_target);
+        let q_new = if dist < extend_length {
+            q_target.to_vec()
+        } else {
+            space.interpolate(nearest_q, q_target, extend_length / dist)
+        };
+        if !is_free(&q_new) {
+            return ExtendStatus::Trapped;
+        }
+        let new_index = self.len();
+        self.data.extend_from_slice(&q_new);
+        self.parents.push(Some(nearest_index));
+        if space.distance(&q_new, q_target) < extend_length {
+            ExtendStatus::Reached(new_index)
+        } else {
+            ExtendStatus::Advanced(new_index)
+        }
+    }
+    /// vertices from `index` to the root
+    fn until_root(&self, index: usize) -> Vec<Vec<N>> {
+        let mut path = vec![self.vertex(index).to_vec()];
+        let mut current = index;
+        while let Some(parent) = self.parents[current] {
+            path.push(self.vertex(parent).to_vec());
+            current = parent;
+        }
+        path
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// finding the nearest vertices by linear scan instead of kd-trees.
+///
+/// Kd-trees degrade to a slow linear search in high-dimensional spaces (e.g. more
+/// than 20 joints of a humanoid), so this is faster than
+/// [`dual_rrt_connect`](crate::dual_rrt_connect) there. The vertices are stored
+/// contiguously and the distances are computed in a vectorizable loop.
+/// Use [`retraction_rrt_connect`](crate::retraction_rrt_connect) if many samples are
+/// in collision. `tree_bias`, `num_nearest_candidates`, `tree_balance` and
+/// `sampling_schedule` of the options are not used.
+pub fn linear_rrt_connect<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    linear_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`linear_rrt_connect`] which takes all randomness from `rng`.
+pub fn linear_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    let space = RealVectorSpace::new(start.len()).with_metric(options.metric.clone())?;
+    flat_rrt_connect(&space, start, goal, is_free, random_sample, options, rng)
+}
+
+/// `linear_rrt_connect` with the distance and the interpolation of `space`
+fn flat_rrt_connect<S, FF, FR, N, R>(
+    space: &S,
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    S: StateSpace<N>,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    assert!(options.extend_length > N::zero());
+    let max_connect_extensions = options.max_connect_extensions.unwrap_or(usize::MAX);
+    let mut start_tree = FlatTree::new("start", start);
+    let mut goal_tree = FlatTree::new("goal", goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    for _ in 0..options.num_max_try {
+        let mut q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        space.enforce_bounds(&mut q_rand);
+        if let ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) =
+            tree_a.extend(space, &q_rand, options.extend_length, &mut is_free)
+        {
+            let q_new = tree_a.vertex(new_index).to_vec();
+            random_sample.add_vertex(&q_new);
+            for _ in 0..max_connect_extensions {
+                match tree_b.extend(space, &q_new, options.extend_length, &mut is_free) {
+                    ExtendStatus::Trapped => break,
+                    ExtendStatus::Advanced(index) => {
+                        random_sample.add_vertex(tree_b.vertex(index));
+                    }
+                    ExtendStatus::Reached(reach_index) => {
+                        debug!("connected with {} vertices", tree_a.len() + tree_b.len());
+                        let mut path = tree_a.until_root(new_index);
+                        path.reverse();
+                        // the vertices which met are within extend_length
+                        path.extend(tree_b.until_root(reach_index));
+                        if tree_b.name == "start" {
+                            path.reverse();
+                        }
+                        return Ok(path);
+                    }
+                }
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with the distance and the interpolation of `space`.
+///
+/// This is [`dual_rrt_connect_with_options`](crate::dual_rrt_connect_with_options)
+/// generalized to any [`StateSpace`], e.g. with angles or poses, whose trees are
+/// [`Tree::in_space`]. The samples and the new vertices are moved into the bounds of the
+/// space, and the consecutive vertices of the path are within `extend_length` in the
+/// space. All options but `metric`, which is replaced by the distance of the space, are
+/// used. Returns an error if the dimension of start or goal is not that of the space.
+pub fn dual_rrt_connect_in_space<S, FF, FR, N>(
+    space: &S,
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    S: StateSpace<N>,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_in_space_with_rng(
+        space,
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_in_space`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_in_space_with_rng<S, FF, FR, N, R>(
+    space: &S,
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    S: StateSpace<N>,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    if start.len() != space.dimension() {
+        return Err(format!(
+            "dimension of start and goal must be {}: {start:?}",
+            space.dimension()
+        ));
+    }
+    let mut start_tree = Tree::named_in_space("start", space);
+    start_tree.add_vertex(start);
+    let mut goal_tree = Tree::named_in_space("goal", space);
+    goal_tree.add_vertex(goal);
+    let random_sample = RefCell::new(StallTracker::new(random_sample));
+    connect_trees(
+        &mut start_tree,
+        &mut goal_tree,
+        &mut is_free,
+        &mut |rng: &mut R| random_sample.borrow_mut().sample(rng),
+        &mut |q: &[N]| random_sample.borrow_mut().add_vertex(q),
+        options,
+        rng,
+        None,
+    )
+}
+
+#[test]
+fn linear_rrt_connect_works() {
+    use kdtree::distance::squared_euclidean;
+    use rand::distributions::{Distribution, Uniform};
+    const DIM: usize = 30;
+    let between = Uniform::new(-1.5, 1.5);
+    let random_sample = || {
+        let mut rng = rand::thread_rng();
+        (0..DIM)
+            .map(|_| between.sample(&mut rng))
+            .collect::<Vec<f64>>()
+    };
+    // the linear scan finds the same nearest vertex as kd-trees
+    let space = RealVectorSpace::new(DIM);
+    let mut tree = FlatTree::new("start", &random_sample());
+    let mut kdtree = kdtree::KdTree::new(DIM);
+    kdtree.add(tree.vertex(0).to_vec(), 0).unwrap();
+    for _ in 0..100 {
+        let q: Vec<f64> = random_sample();
+        if let ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) =
+            tree.extend(&space, &q, 0.5, &mut |_: &[f64]| true)
+        {
+            kdtree.add(tree.vertex(index).to_vec(), index).unwrap();
+        }
+    }
+    for _ in 0..10 {
+        let q: Vec<f64> = random_sample();
+        let (_, expected) = kdtree.nearest(&q, 1, &squared_euclidean).unwrap()[0];
+        assert_eq!(tree.nearest(&space, &q), *expected);
+        assert!(
+            (squared_distance(&q, tree.vertex(0)) - squared_euclidean(&q, tree.vertex(0))).abs()
+                < 1e-9
+        );
+    }
+
+    let is_free = |p: &[f64]| !(p[0].abs() < 0.2 && p[1].abs() < 0.5);
+    let mut start = vec![0.0; DIM];
+    start[0] = -1.0;
+    let mut goal = vec![0.0; DIM];
+    goal[0] = 1.0;
+    let path = linear_rrt_connect(
+        &start,
+        &goal,
+        is_free,
+        random_sample,
+        &DualRrtConnectOptions::new(0.5, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(|q| is_free(q)));
+}
@@ -0,0 +1,658 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, Sampler, Trajectory};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+type Matrix<N> = Vec<Vec<N>>;
+
+fn zeros<N: Float>(rows: usize, cols: usize) -> Matrix<N> {
+    vec![vec![N::zero(); cols]; rows]
+}
+
+fn identity<N: Float>(n: usize) -> Matrix<N> {
+    let mut m = zeros(n, n);
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = N::one();
+    }
+    m
+}
+
+fn mul<N: Float>(a: &[Vec<N>], b: &[Vec<N>]) -> Matrix<N> {
+    let mut m = zeros(a.len(), b[0].len());
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, x) in row.iter_mut().enumerate() {
+            *x = (0..b.len()).fold(N::zero(), |sum, k| sum + a[i][k] * b[k][j]);
+        }
+    }
+    m
+}
+
+fn mul_vec<N: Float>(a: &[Vec<N>], v: &[N]) -> Vec<N> {
+    a.iter()
+        .map(|row| {
+            row.iter()
+                .zip(v)
+                .fold(N::zero(), |sum, (x, y)| sum + *x * *y)
+        })
+        .collect()
+}
+
+fn transpose<N: Float>(a: &[Vec<N>]) -> Matrix<N> {
+    (0..a[0].len())
+        .map(|j| a.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+fn add<N: Float>(a: &[Vec<N>], b: &[Vec<N>], scale: N) -> Matrix<N> {
+    a.iter()
+        .zip(b)
+        .map(|(ra, rb)| ra.iter().zip(rb).map(|(x, y)| *x + *y * scale).collect())
+        .collect()
+}
+
+/// Gauss-Jordan elimination with partial pivoting.
+pub(crate) fn inverse<N: Float>(a: &[Vec<N>]) -> Option<Matrix<N>> {
+    let n = a.len();
+    let mut m = a.to_vec();
+    let mut inv = identity(n);
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|i, j| m[*i][col].abs().partial_cmp(&m[*j][col].abs()).unwrap())?;
+        if m[pivot][col].abs() <= N::epsilon() {
+            return None;
+        }
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+        let p = m[col][col];
+        for j in 0..n {
+            m[col][j] = m[col][j] / p;
+            inv[col][j] = inv[col][j] / p;
+        }
+        for i in 0..n {
+            if i != col {
+                let f = m[i][col];
+                for j in 0..n {
+                    m[i][j] = m[i][j] - f * m[col][j];
+                    inv[i][j] = inv[i][j] - f * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
+
+/// quadratic form `v^T m v`
+pub(crate) fn quadratic<N: Float>(m: &[Vec<N>], v: &[N]) -> N {
+    v.iter()
+        .zip(mul_vec(m, v))
+        .fold(N::zero(), |sum, (x, y)| sum + *x * y)
+}
+
+/// Linear system `dx/dt = A x + B u` with the quadratic cost `x^T Q x + u^T R u`,
+/// and its infinite horizon LQR policy for [`lqr_rrtstar`].
+///
+/// Matrices are row-major `Vec<Vec<N>>` like the points of this crate, so that
+/// no linear algebra crate is required. The system is discretized with `dt`
+/// by the Euler method.
+#[derive(Debug, Clone)]
+pub struct LqrModel<N> {
+    a: Matrix<N>,
+    b: Matrix<N>,
+    q: Matrix<N>,
+    r: Matrix<N>,
+    dt: N,
+    gain: Matrix<N>,
+    /// maps a target to the control which cancels the drift `A x` there as far as possible
+    feedforward: Matrix<N>,
+    cost_to_go: Matrix<N>,
+}
+
+impl<N> LqrModel<N>
+where
+    N: Float + Debug,
+{
+    /// Creates the model, solving the discrete Riccati equation by iteration.
+    ///
+    /// `a` is n x n, `b` is n x m with full column rank, `q` is n x n and `r` is m x m.
+    /// Returns `Err` if the sizes do not match or the iteration does not converge.
+    pub fn new(
+        a: Vec<Vec<N>>,
+        b: Vec<Vec<N>>,
+        q: Vec<Vec<N>>,
+        r: Vec<Vec<N>>,
+        dt: N,
+    ) -> Result<Self, String> {
+        let n = a.len();
+        let m = b.first().map_or(0, Vec::len);
+        if n == 0 || m == 0 {
+            return Err("empty matrix".to_string());
+        }
+        let is_size = |x: &[Vec<N>], rows: usize, cols: usize| {
+            x.len() == rows && x.iter().all(|row| row.len() == cols)
+        };
+        if !is_size(&a, n, n) || !is_size(&b, n, m) || !is_size(&q, n, n) || !is_size(&r, m, m) {
+            return Err(format!("invalid matrix sizes, n = {n}, m = {m}"));
+        }
+        if dt <= N::zero() || !dt.is_finite() {
+            return Err(format!("dt must be positive: {dt:?}"));
+        }
+        let b_t = transpose(&b);
+        let feedforward = mul(
+            &inverse(&mul(&b_t, &b)).ok_or_else(|| "B^T B is singular".to_string())?,
+            &mul(&b_t, &a),
+        );
+        let ad = add(&identity(n), &a, dt);
+        let bd = add(&zeros(n, m), &b, dt);
+        let qd = add(&zeros(n, n), &q, dt);
+        let rd = add(&zeros(m, m), &r, dt);
+        let ad_t = transpose(&ad);
+        let bd_t = transpose(&bd);
+        let mut s = qd.clone();
+        let tolerance = N::from(1e-9).unwrap();
+        for _ in 0..100_000 {
+            let s_bd = mul(&s, &bd);
+            let inv = inverse(&add(&rd, &mul(&bd_t, &s_bd), N::one()))
+                .ok_or_else(|| "R + B^T S B is singular".to_string())?;
+            let gain = mul(&inv, &mul(&bd_t, &mul(&s, &ad)));
+            let next = add(
+                &add(&qd, &mul(&ad_t, &mul(&s, &ad)), N::one()),
+                &mul(&mul(&ad_t, &s_bd), &gain),
+                -N::one(),
+            );
+            let change = next
+                .iter()
+                .zip(&s)
+                .flat_map(|(rn, rs)| rn.iter().zip(rs).map(|(x, y)| (*x - *y).abs()))
+                .fold(N::zero(), N::max);
+            s = next;
+            if !change.is_finite() {
+                break;
+            }
+            if change < tolerance {
+                return Ok(Self {
+                    a,
+                    b,
+                    q,
+                    r,
+                    dt,
+                    gain,
+                    feedforward,
+                    cost_to_go: s,
+                });
+            }
+        }
+        Err("Riccati iteration did not converge".to_string())
+    }
+
+    /// Returns the time step.
+    pub fn dt(&self) -> N {
+        self.dt
+    }
+
+    /// Returns the LQR cost-to-go from `from` to `to`, used as the distance.
+    pub fn distance(&self, from: &[N], to: &[N]) -> N {
+        let diff = from
+            .iter()
+            .zip(to)
+            .map(|(x, y)| *x - *y)
+            .collect::<Vec<_>>();
+        quadratic(&self.cost_to_go, &diff)
+    }
+
+    /// Returns the state after applying the control `u` for `dt` from `x`.
+    pub fn propagate(&self, x: &[N], u: &[N]) -> Vec<N> {
+        let dx = mul_vec(&self.a, x)
+            .into_iter()
+            .zip(mul_vec(&self.b, u))
+            .map(|(ax, bu)| ax + bu);
+        x.iter().zip(dx).map(|(x, dx)| *x + dx * self.dt).collect()
+    }
+
+    /// Returns the cost of the states and controls after `from`, relative to the last state.
+    fn segment_cost(&self, from: &[N], states: &[Vec<N>], controls: &[Vec<N>]) -> N {
+        let Some(end) = states.last() else {
+            return N::zero();
+        };
+        std::iter::once(from)
+            .chain(states.iter().map(Vec::as_slice))
+            .zip(controls)
+            .fold(N::zero(), |sum, (x, u)| {
+                let error = x.iter().zip(end).map(|(x, e)| *x - *e).collect::<Vec<_>>();
+                sum + (quadratic(&self.q, &error) + quadratic(&self.r, u)) * self.dt
+            })
+    }
+
+    /// Steers from `from` toward `to` with the LQR policy around `to` for at most
+    /// `max_steps`. The drift `A to` is canceled by the feedforward control as far as
+    /// `B` allows, so `to` is passed through even if it is not an equilibrium.
+    ///
+    /// Returns the states after `from`, the controls, the cost and whether `to`
+    /// was reached within `tolerance`. Stops before a state which is not free.
+    fn steer<FF>(
+        &self,
+        from: &[N],
+        to: &[N],
+        max_steps: usize,
+        tolerance: N,
+        is_free: &mut FF,
+    ) -> Segment<N>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let feedforward = mul_vec(&self.feedforward, to);
+        let mut states = Vec::new();
+        let mut controls = Vec::new();
+        let mut reached = false;
+        let mut x = from.to_vec();
+        for _ in 0..max_steps {
+            let error = x.iter().zip(to).map(|(x, t)| *x - *t).collect::<Vec<_>>();
+            let u = mul_vec(&self.gain, &error)
+                .into_iter()
+                .zip(&feedforward)
+                .map(|(u, f)| -u - *f)
+                .collect::<Vec<_>>();
+            let next = self.propagate(&x, &u);
+            if !is_valid_point(&next, x.len()) || !is_free(&next) {
+                break;
+            }
+            states.push(next.clone());
+            controls.push(u);
+            x = next;
+            if squared_euclidean(&x, to).sqrt() < tolerance {
+                reached = true;
+                break;
+            }
+        }
+        Segment {
+            cost: self.segment_cost(from, &states, &controls),
+            states,
+            controls,
+            reached,
+        }
+    }
+
+    /// Applies the controls again from `from`, or returns `None` if a state is not free.
+    fn replay<FF>(&self, from: &[N], controls: Vec<Vec<N>>, is_free: &mut FF) -> Option<Segment<N>>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let mut states = Vec::with_capacity(controls.len());
+        let mut x = from.to_vec();
+        for u in &controls {
+            x = self.propagate(&x, u);
+            if !is_valid_point(&x, from.len()) || !is_free(&x) {
+                return None;
+            }
+            states.push(x.clone());
+        }
+        Some(Segment {
+            cost: self.segment_cost(from, &states, &controls),
+            states,
+            controls,
+            reached: true,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Segment<N> {
+    states: Vec<Vec<N>>,
+    controls: Vec<Vec<N>>,
+    cost: N,
+    reached: bool,
+}
+
+#[derive(Debug)]
+struct LqrNode<N> {
+    /// the last state of `segment`, or the start
+    state: Vec<N>,
+    parent: Option<usize>,
+    /// segment from the parent
+    segment: Segment<N>,
+}
+
+fn cost_to<N: Float>(nodes: &[LqrNode<N>], index: usize) -> N {
+    let mut cost = N::zero();
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        cost = cost + nodes[i].segment.cost;
+        cur = nodes[i].parent;
+    }
+    cost
+}
+
+/// Connects `index` to `new_parent` with `segment`, and applies the controls of
+/// the descendants again from their moved parents.
+///
+/// Returns `false` and changes nothing if a replayed state is not free, or a
+/// descendant moves by `tolerance` or more.
+fn rewire<FF, N>(
+    nodes: &mut [LqrNode<N>],
+    model: &LqrModel<N>,
+    index: usize,
+    new_parent: usize,
+    segment: Segment<N>,
+    tolerance: N,
+    is_free: &mut FF,
+) -> bool
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    let mut children = vec![Vec::new(); nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(parent) = node.parent {
+            children[parent].push(i);
+        }
+    }
+    // (node, its new parent, its new segment) in the order from index to the leaves
+    let mut updates = vec![(index, new_parent, segment)];
+    let mut k = 0;
+    while k < updates.len() {
+        let (i, _, segment) = &updates[k];
+        let i = *i;
+        let state = segment.states.last().unwrap().clone();
+        for &child in &children[i] {
+            let Some(replayed) =
+                model.replay(&state, nodes[child].segment.controls.clone(), is_free)
+            else {
+                return false;
+            };
+            if squared_euclidean(replayed.states.last().unwrap(), &nodes[child].state).sqrt()
+                >= tolerance
+            {
+                return false;
+            }
+            updates.push((child, i, replayed));
+        }
+        k += 1;
+    }
+    for (i, parent, segment) in updates {
+        nodes[i].state = segment.states.last().unwrap().clone();
+        nodes[i].parent = Some(parent);
+        nodes[i].segment = segment;
+    }
+    true
+}
+
+/// Parameters of [`lqr_rrtstar`].
+#[derive(Debug, Clone)]
+pub struct LqrRrtStarOptions<N> {
+    /// max repeat num. All of them are used to improve the trajectory.
+    pub num_max_try: usize,
+    /// max number of `dt` steps of an extension
+    pub max_steps: usize,
+    /// radius in the LQR cost-to-go to choose the parent of a new vertex and to rewire
+    pub neighbor_radius: N,
+    /// states within this distance of the target are reached
+    pub tolerance: N,
+    /// probability (0.0 - 1.0) to extend toward the goal instead of a random sample
+    pub goal_bias: f64,
+}
+
+/// search the trajectory from start to goal which is free, with LQR-RRT*.
+///
+/// Extensions follow the LQR policy of `model`, and the nearest and near vertices
+/// are chosen by its cost-to-go. The controls of the returned trajectory are applied
+/// for `model.dt()` each, and reproduce its states exactly with
+/// [`LqrModel::propagate`]. The last state is within `tolerance` of the goal.
+pub fn lqr_rrtstar<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    model: &LqrModel<N>,
+    is_free: FF,
+    random_sample: FR,
+    options: &LqrRrtStarOptions<N>,
+) -> Result<Trajectory<N, Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    lqr_rrtstar_with_rng(
+        start,
+        goal,
+        model,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`lqr_rrtstar`] which takes all randomness from `rng`.
+pub fn lqr_rrtstar_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    model: &LqrModel<N>,
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &LqrRrtStarOptions<N>,
+    rng: &mut R,
+) -> Result<Trajectory<N, Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    if start.len() != model.a.len() {
+        return Err(format!(
+            "dimension of the model is {}, but start is {start:?}",
+            model.a.len()
+        ));
+    }
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    let dim = start.len();
+    let mut nodes = vec![LqrNode {
+        state: start.to_vec(),
+        parent: None,
+        segment: Segment {
+            states: Vec::new(),
+            controls: Vec::new(),
+            cost: N::zero(),
+            reached: true,
+        },
+    }];
+    // vertices which reached the goal
+    let mut goal_parents = Vec::new();
+    for _ in 0..options.num_max_try {
+        let q_target = if rng.gen_bool(options.goal_bias) {
+            goal.to_vec()
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_target, dim) {
+            continue;
+        }
+        let nearest_index = (0..nodes.len())
+            .min_by(|a, b| {
+                let da = model.distance(&nodes[*a].state, &q_target);
+                let db = model.distance(&nodes[*b].state, &q_target);
+                da.partial_cmp(&db).unwrap()
+            })
+            .unwrap();
+        let segment = model.steer(
+            &nodes[nearest_index].state,
+            &q_target,
+            options.max_steps,
+            options.tolerance,
+            &mut is_free,
+        );
+        let Some(q_new) = segment.states.last().cloned() else {
+            continue;
+        };
+        // choose the parent which reaches q_new with the least cost
+        let near = (0..nodes.len())
+            .filter(|i| {
+                *i != nearest_index
+                    && model.distance(&nodes[*i].state, &q_new) < options.neighbor_radius
+            })
+            .collect::<Vec<_>>();
+        let mut parent = nearest_index;
+        let mut parent_cost = cost_to(&nodes, nearest_index) + segment.cost;
+        let mut parent_segment = segment;
+        for &i in &near {
+            let candidate = model.steer(
+                &nodes[i].state,
+                &q_new,
+                options.max_steps,
+                options.tolerance,
+                &mut is_free,
+            );
+            let cost = cost_to(&nodes, i) + candidate.cost;
+            if candidate.reached && cost < parent_cost {
+                parent = i;
+                parent_cost = cost;
+                parent_segment = candidate;
+            }
+        }
+        let new_index = nodes.len();
+        nodes.push(LqrNode {
+            state: parent_segment.states.last().unwrap().clone(),
+            parent: Some(parent),
+            segment: parent_segment,
+        });
+        // rewire the near vertices through the new vertex
+        for &i in &near {
+            if i == parent {
+                continue;
+            }
+            let candidate = model.steer(
+                &nodes[new_index].state,
+                &nodes[i].state,
+                options.max_steps,
+                options.tolerance,
+                &mut is_free,
+            );
+            if candidate.reached
+                && parent_cost + candidate.cost < cost_to(&nodes, i)
+                && rewire(
+                    &mut nodes,
+                    model,
+                    i,
+                    new_index,
+                    candidate,
+                    options.tolerance,
+                    &mut is_free,
+                )
+            {
+                debug!("rewired {i}");
+            }
+        }
+        let to_goal = model.steer(
+            &nodes[new_index].state,
+            goal,
+            options.max_steps,
+            options.tolerance,
+            &mut is_free,
+        );
+        if to_goal.reached {
+            debug!("reached the goal with {} vertices", nodes.len());
+            goal_parents.push(new_index);
+        }
+    }
+    // rewiring may have moved the vertices, so connect them to the goal again
+    let (index, to_goal) = goal_parents
+        .into_iter()
+        .filter_map(|i| {
+            let to_goal = model.steer(
+                &nodes[i].state,
+                goal,
+                options.max_steps,
+                options.tolerance,
+                &mut is_free,
+            );
+            to_goal
+                .reached
+                .then(|| (cost_to(&nodes, i) + to_goal.cost, i, to_goal))
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+        .map(|(_, i, to_goal)| (i, to_goal))
+        .ok_or_else(|| "failed".to_string())?;
+    let mut segments = vec![to_goal];
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        segments.push(nodes[i].segment.clone());
+        cur = nodes[i].parent;
+    }
+    segments.reverse();
+    let mut trajectory = Trajectory {
+        states: vec![start.to_vec()],
+        controls: Vec::new(),
+    };
+    for segment in segments {
+        trajectory.states.extend(segment.states);
+        trajectory
+            .controls
+            .extend(segment.controls.into_iter().map(|u| (u, model.dt)));
+    }
+    Ok(trajectory)
+}
+
+#[test]
+fn lqr_rrtstar_works() {
+    use rand::distributions::{Distribution, Uniform};
+    // 1D double integrator, whose state is (position, velocity)
+    let model = LqrModel::new(
+        vec![vec![0.0, 1.0], vec![0.0, 0.0]],
+        vec![vec![0.0], vec![1.0]],
+        vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+        vec![vec![1.0]],
+        0.05,
+    )
+    .unwrap();
+    let options = LqrRrtStarOptions {
+        num_max_try: 300,
+        max_steps: 60,
+        neighbor_radius: 0.5,
+        tolerance: 0.05,
+        goal_bias: 0.2,
+    };
+    // the velocity is limited
+    let is_free = |q: &[f64]| q[1].abs() < 0.8;
+    let trajectory = lqr_rrtstar(
+        &[0.0, 0.0],
+        &[1.0, 0.0],
+        &model,
+        is_free,
+        || {
+            let mut rng = rand::thread_rng();
+            vec![
+                Uniform::new(-0.5, 1.5).sample(&mut rng),
+                Uniform::new(-0.8, 0.8).sample(&mut rng),
+            ]
+        },
+        &options,
+    )
+    .unwrap();
+    assert_eq!(trajectory.states.len(), trajectory.controls.len() + 1);
+    assert!(trajectory.states.iter().all(|q| is_free(q)));
+    let end = trajectory.states.last().unwrap();
+    assert!(squared_euclidean(end, &[1.0, 0.0]).sqrt() < 0.05);
+    // the controls reproduce the states
+    let mut q = trajectory.states[0].clone();
+    for ((u, _), expected) in trajectory.controls.iter().zip(&trajectory.states[1..]) {
+        q = model.propagate(&q, u);
+        assert_eq!(&q, expected);
+    }
+}
@@ -0,0 +1,120 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use num_traits::float::Float;
+use std::cell::OnceCell;
+
+/// Offsets on the sphere of `margin` radius which are probed by [`with_margin`]
+fn probe_offsets<N>(dim: usize, margin: N) -> Vec<Vec<N>>
+where
+    N: Float,
+{
+    let mut offsets = Vec::new();
+    for k in 0..dim {
+        for sign in [N::one(), -N::one()] {
+            let mut offset = vec![N::zero(); dim];
+            offset[k] = sign * margin;
+            offsets.push(offset);
+        }
+    }
+    if dim <= 3 {
+        let diagonal = margin / N::from(dim).unwrap().sqrt();
+        for signs in 0..(1 << dim) {
+            offsets.push(
+                (0..dim)
+                    .map(|k| {
+                        if signs & (1 << k) == 0 {
+                            diagonal
+                        } else {
+                            -diagonal
+                        }
+                    })
+                    .collect(),
+            );
+        }
+    }
+    offsets
+}
+
+/// Returns a validity checker which is free only if `is_free` is free at `q` and
+/// on the sphere of `margin` radius around `q`.
+///
+/// The sphere is probed at the 2 * dim points on the axes and, up to 3 dimensions,
+/// at the corners of the inscribed cube, so obstacles thinner than the gaps between
+/// the probes can be missed. Use [`with_clearance_margin`] if the distance to
+/// obstacles is available.
+pub fn with_margin<'a, FF, N>(is_free: FF, margin: N) -> impl Fn(&[N]) -> bool + 'a
+where
+    FF: Fn(&[N]) -> bool + 'a,
+    N: Float + 'a,
+{
+    assert!(margin >= N::zero());
+    // computed once for the dimension of the first call
+    let cache = OnceCell::new();
+    move |q: &[N]| {
+        if !is_free(q) {
+            return false;
+        }
+        let cached = cache.get_or_init(|| probe_offsets(q.len(), margin));
+        let recomputed;
+        let offsets = if cached.first().is_none_or(|offset| offset.len() == q.len()) {
+            cached
+        } else {
+            recomputed = probe_offsets(q.len(), margin);
+            &recomputed
+        };
+        offsets.iter().all(|offset| {
+            let probe = q
+                .iter()
+                .zip(offset)
+                .map(|(q, d)| *q + *d)
+                .collect::<Vec<_>>();
+            is_free(&probe)
+        })
+    }
+}
+
+/// Returns a validity checker which is free if the distance to obstacles returned by
+/// `clearance` is larger than `margin`.
+pub fn with_clearance_margin<'a, FC, N>(clearance: FC, margin: N) -> impl Fn(&[N]) -> bool + 'a
+where
+    FC: Fn(&[N]) -> N + 'a,
+    N: Float + 'a,
+{
+    assert!(margin >= N::zero());
+    move |q: &[N]| clearance(q) > margin
+}
+
+#[test]
+fn margin_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let inflated = with_margin(is_free, 0.2);
+    assert!(inflated(&[1.3, 0.0]));
+    assert!(!inflated(&[1.1, 0.0]));
+    assert!(!inflated(&[1.1, 1.1]));
+    assert!(inflated(&[1.2, 1.2]));
+
+    let clearance = |p: &[f64]| {
+        let dx = (p[0].abs() - 1.0).max(0.0);
+        let dy = (p[1].abs() - 1.0).max(0.0);
+        dx.hypot(dy)
+    };
+    let inflated = with_clearance_margin(clearance, 0.2);
+    assert!(inflated(&[1.3, 0.0]));
+    assert!(!inflated(&[1.1, 0.0]));
+    assert!(!inflated(&[1.1, 1.1]));
+}
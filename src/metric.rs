@@ -0,0 +1,275 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::is_valid_point;
+use crate::linear::squared_distance;
+use crate::lqr::{inverse, quadratic};
+use num_traits::float::Float;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+/// Distance between configurations, which [`Tree`](crate::Tree) uses for the nearest
+/// vertices and the extension.
+///
+/// This is the distance of a [`RealVectorSpace`](crate::RealVectorSpace), the default
+/// [`StateSpace`](crate::StateSpace) of the trees, for the planners which take it by
+/// their options. The trees extend by `extend_length` in the metric along straight
+/// lines, which is exact for the norms (all but [`Metric::Custom`]).
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Metric<N> {
+    /// euclidean distance
+    #[default]
+    Euclidean,
+    /// `sqrt(sum(weights[i] * (a[i] - b[i])^2))` of the weights, e.g. to compare radians
+    /// with meters
+    WeightedEuclidean(Vec<N>),
+    /// sum of the absolute differences (Manhattan distance)
+    L1,
+    /// max of the absolute differences (Chebyshev distance)
+    LInfinity,
+    /// `sqrt(d^T C^-1 d)` of the difference `d`, where `C^-1` is the inverse of the
+    /// covariance, created by [`Metric::mahalanobis`]
+    Mahalanobis(Vec<Vec<N>>),
+    /// user function, created by [`Metric::custom`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(CustomMetric<N>),
+}
+
+type DistanceFn<N> = dyn Fn(&[N], &[N]) -> N + Send + Sync;
+
+/// distance function of [`Metric::Custom`]
+#[derive(Clone)]
+pub struct CustomMetric<N>(Arc<DistanceFn<N>>);
+
+impl<N> Debug for CustomMetric<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomMetric")
+    }
+}
+
+impl<N> PartialEq for CustomMetric<N> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<N> Metric<N>
+where
+    N: Float + Debug,
+{
+    /// Creates the Mahalanobis distance of the symmetric positive definite `covariance`,
+    /// which makes the directions of large variance short.
+    ///
+    /// Returns an error if `covariance` is not square, symmetric or positive definite.
+    pub fn mahalanobis(covariance: &[Vec<N>]) -> Result<Self, String> {
+        let n = covariance.len();
+        if n == 0 || covariance.iter().any(|row| !is_valid_point(row, n)) {
+            return Err(format!("covariance must be square: {covariance:?}"));
+        }
+        let tolerance = N::epsilon().sqrt();
+        for i in 0..n {
+            for j in 0..i {
+                if (covariance[i][j] - covariance[j][i]).abs() > tolerance {
+                    return Err(format!("covariance must be symmetric: {covariance:?}"));
+                }
+            }
+        }
+        // Cholesky decomposition exists only for positive definite matrices
+        let mut l = vec![vec![N::zero(); n]; n];
+        for i in 0..n {
+            for j in 0..=i {
+                let sum = (0..j).fold(covariance[i][j], |sum, k| sum - l[i][k] * l[j][k]);
+                if i == j {
+                    if sum <= N::zero() {
+                        return Err(format!(
+                            "covariance must be positive definite: {covariance:?}"
+                        ));
+                    }
+                    l[i][j] = sum.sqrt();
+                } else {
+                    l[i][j] = sum / l[j][j];
+                }
+            }
+        }
+        let inverse_covariance = inverse(covariance)
+            .ok_or_else(|| format!("covariance must be invertible: {covariance:?}"))?;
+        Ok(Metric::Mahalanobis(inverse_covariance))
+    }
+
+    /// Creates the metric of `distance`, which should be non-negative and symmetric.
+    ///
+    /// The nearest vertices are searched linearly, because the kd-trees can't prune by
+    /// an arbitrary function.
+    pub fn custom<F>(distance: F) -> Self
+    where
+        F: Fn(&[N], &[N]) -> N + Send + Sync + 'static,
+    {
+        Metric::Custom(CustomMetric(Arc::new(distance)))
+    }
+
+    /// Returns the distance between `a` and `b`.
+    pub fn distance(&self, a: &[N], b: &[N]) -> N {
+        match self {
+            Metric::Euclidean | Metric::WeightedEuclidean(_) => self.kd_distance(a, b).sqrt(),
+            Metric::L1 => a
+                .iter()
+                .zip(b)
+                .fold(N::zero(), |sum, (a, b)| sum + (*a - *b).abs()),
+            Metric::LInfinity => a
+                .iter()
+                .zip(b)
+                .fold(N::zero(), |max, (a, b)| max.max((*a - *b).abs())),
+            Metric::Mahalanobis(inverse_covariance) => {
+                let d = a.iter().zip(b).map(|(a, b)| *a - *b).collect::<Vec<_>>();
+                quadratic(inverse_covariance, &d).max(N::zero()).sqrt()
+            }
+            Metric::Custom(CustomMetric(distance)) => distance(a, b),
+        }
+    }
+
+    /// Returns an error if the metric is not for `dim` dimensional configurations.
+    pub(crate) fn check(&self, dim: usize) -> Result<(), String> {
+        match self {
+            Metric::WeightedEuclidean(weights)
+                if !is_valid_point(weights, dim) || weights.iter().any(|w| *w <= N::zero()) =>
+            {
+                Err(format!(
+                    "weights must be {dim} positive values: {weights:?}"
+                ))
+            }
+            Metric::Mahalanobis(inverse_covariance) if inverse_covariance.len() != dim => Err(
+                format!("covariance must be {dim}x{dim}: {inverse_covariance:?}"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// `true` if the kd-trees can search by [`kd_distance`](Self::kd_distance), which is
+    /// the case if the distance to the nearest point of a box is the distance to the
+    /// point clamped into the box.
+    pub(crate) fn is_kd_compatible(&self) -> bool {
+        !matches!(self, Metric::Mahalanobis(_) | Metric::Custom(_))
+    }
+
+    /// Returns the distance for the kd-trees, which is squared for the euclidean ones to
+    /// skip the square roots.
+    pub(crate) fn kd_distance(&self, a: &[N], b: &[N]) -> N {
+        match self {
+            Metric::Euclidean => squared_distance(a, b),
+            Metric::WeightedEuclidean(weights) => a
+                .iter()
+                .zip(b)
+                .zip(weights)
+                .fold(N::zero(), |sum, ((a, b), w)| {
+                    sum + *w * (*a - *b) * (*a - *b)
+                }),
+            _ => self.distance(a, b),
+        }
+    }
+
+    /// Converts `distance` to the value of [`kd_distance`](Self::kd_distance).
+    pub(crate) fn distance_to_kd(&self, distance: N) -> N {
+        match self {
+            Metric::Euclidean | Metric::WeightedEuclidean(_) => distance * distance,
+            _ => distance,
+        }
+    }
+
+    /// Converts the value of [`kd_distance`](Self::kd_distance) to the distance.
+    pub(crate) fn kd_to_distance(&self, kd_distance: N) -> N {
+        match self {
+            Metric::Euclidean | Metric::WeightedEuclidean(_) => kd_distance.sqrt(),
+            _ => kd_distance,
+        }
+    }
+}
+
+#[test]
+fn metric_works() {
+    use crate::{dual_rrt_connect_with_options, DualRrtConnectOptions, Tree};
+    use rand::Rng;
+    let world = crate::scenario::World::square();
+    let (a, b) = ([0.0, 0.0], [3.0, -4.0]);
+    assert_eq!(Metric::Euclidean.distance(&a, &b), 5.0);
+    assert_eq!(Metric::L1.distance(&a, &b), 7.0);
+    assert_eq!(Metric::LInfinity.distance(&a, &b), 4.0);
+    let mahalanobis = Metric::mahalanobis(&[vec![4.0, 0.0], vec![0.0, 1.0]]).unwrap();
+    assert!((mahalanobis.distance(&a, &[2.0, 0.0]) - 1.0).abs() < 1e-9);
+    assert!(Metric::mahalanobis(&[vec![1.0, 2.0], vec![2.0, 1.0]]).is_err());
+    assert!(Metric::mahalanobis(&[vec![1.0, 0.5], vec![0.0, 1.0]]).is_err());
+    assert!(Metric::mahalanobis(&[vec![1.0, 0.0]]).is_err());
+    let custom = Metric::custom(|a: &[f64], b: &[f64]| (a[0] - b[0]).abs());
+    assert_eq!(custom.distance(&a, &b), 3.0);
+    assert_eq!(custom, custom.clone());
+    assert_ne!(custom, Metric::custom(|_: &[f64], _: &[f64]| 0.0));
+
+    // kd-trees and linear search find the nearest vertices by the metric
+    let mut rng = rand::thread_rng();
+    let correlated = Metric::mahalanobis(&[vec![1.0, 0.9], vec![0.9, 1.0]]).unwrap();
+    for metric in [Metric::L1, Metric::LInfinity, correlated.clone(), custom] {
+        let mut tree = Tree::new(2).with_metric(metric.clone()).unwrap();
+        let points = (0..100)
+            .map(|_| vec![rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)])
+            .collect::<Vec<_>>();
+        for p in &points {
+            tree.add_vertex(p);
+        }
+        for _ in 0..10 {
+            let q = [rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)];
+            let min = points
+                .iter()
+                .map(|p| metric.distance(p, &q))
+                .fold(f64::INFINITY, f64::min);
+            let nearest = tree.nearest_k(&q, 3);
+            assert!((nearest[0].1 - min).abs() < 1e-9, "{metric:?}");
+            assert!(nearest.windows(2).all(|n| n[0].1 <= n[1].1));
+            assert_eq!(tree.within(&q, min + 1e-9)[0], nearest[0]);
+        }
+    }
+
+    // the steps are extend_length long in the metric
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    for metric in [Metric::LInfinity, correlated] {
+        let options = DualRrtConnectOptions {
+            metric: metric.clone(),
+            ..DualRrtConnectOptions::new(0.2, 10000)
+        };
+        let path = dual_rrt_connect_with_options(
+            &[-1.2, 0.0],
+            &[1.2, 0.0],
+            is_free,
+            &random_sample,
+            &options,
+        )
+        .unwrap();
+        assert!(path.iter().all(|q| is_free(q)));
+        // except where the trees met, whose vertices are not in the path
+        let lengths = path
+            .windows(2)
+            .map(|segment| metric.distance(&segment[0], &segment[1]))
+            .collect::<Vec<_>>();
+        assert!(
+            lengths
+                .iter()
+                .filter(|length| **length > 0.2 + 1e-9)
+                .count()
+                <= 1
+        );
+        assert!(lengths.iter().all(|length| *length <= 0.6 + 1e-9));
+    }
+}
@@ -0,0 +1,908 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::StallTracker;
+use crate::{
+    check_start_goal, connect_trees, dual_rrt_connect_with_rng, from_rng_fn, is_valid_point,
+    options_tree, path_length, smooth_path, smooth_path_with_rng, start_goal_trees,
+    DualRrtConnectOptions, Sampler, Tree, UniformBoxSampler,
+};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// [`dual_rrt_connect`](crate::dual_rrt_connect) which keeps its trees between queries.
+///
+/// The trees are reused by the next [`plan`](Self::plan), so they are only valid
+/// while the environment does not change.
+#[derive(Debug)]
+pub struct DualRrtConnectPlanner<N>
+where
+    N: Float + Debug,
+{
+    start_tree: Tree<N>,
+    goal_tree: Tree<N>,
+    options: DualRrtConnectOptions<N>,
+}
+
+impl<N> DualRrtConnectPlanner<N>
+where
+    N: Float + Debug,
+{
+    /// Creates a planner with trees which only have start and goal.
+    pub fn new(start: &[N], goal: &[N], options: DualRrtConnectOptions<N>) -> Result<Self, String> {
+        let (start_tree, goal_tree) = start_goal_trees(start, goal, &options)?;
+        Ok(Self {
+            start_tree,
+            goal_tree,
+            options,
+        })
+    }
+
+    /// search the path from start to goal which is free, using random_sample function.
+    ///
+    /// The trees keep growing from where the previous call stopped.
+    pub fn plan<FF, FR>(&mut self, is_free: FF, random_sample: FR) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+    {
+        self.plan_with_rng(is_free, random_sample, &mut rand::thread_rng())
+    }
+
+    /// [`Self::plan`] which takes all randomness from `rng`.
+    pub fn plan_with_rng<FF, FR, R>(
+        &mut self,
+        mut is_free: FF,
+        random_sample: FR,
+        rng: &mut R,
+    ) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+        R: Rng,
+    {
+        let random_sample = RefCell::new(StallTracker::new(random_sample));
+        connect_trees(
+            &mut self.start_tree,
+            &mut self.goal_tree,
+            &mut is_free,
+            &mut |rng: &mut R| random_sample.borrow_mut().sample(rng),
+            &mut |q: &[N]| random_sample.borrow_mut().add_vertex(q),
+            &self.options,
+            rng,
+            None,
+        )
+    }
+
+    /// Moves the goal. Only the goal tree is grown again, and the start tree is reused.
+    pub fn update_goal(&mut self, goal: &[N]) -> Result<(), String> {
+        check_start_goal(self.start_tree.vertex(0), goal)?;
+        self.goal_tree = options_tree("goal", goal.len(), &self.options)?;
+        self.goal_tree.add_vertex(goal);
+        Ok(())
+    }
+
+    /// Detaches the vertices in the region where `is_invalid` returns `true`
+    /// from both trees. See [`Tree::detach_invalid`].
+    pub fn detach_invalid<FI>(&mut self, mut is_invalid: FI) -> usize
+    where
+        FI: FnMut(&[N]) -> bool,
+    {
+        self.start_tree.detach_invalid(&mut is_invalid)
+            + self.goal_tree.detach_invalid(&mut is_invalid)
+    }
+
+    /// Tries to reconnect detached vertices of both trees.
+    /// See [`Tree::reconnect_detached`].
+    pub fn reconnect_detached<FF>(
+        &mut self,
+        mut is_free: FF,
+        max_distance: N,
+        resolution: N,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        self.start_tree
+            .reconnect_detached(&mut is_free, max_distance, resolution)
+            + self
+                .goal_tree
+                .reconnect_detached(&mut is_free, max_distance, resolution)
+    }
+
+    /// Returns the tree grown from the start.
+    pub fn start_tree(&self) -> &Tree<N> {
+        &self.start_tree
+    }
+
+    /// Returns the tree grown from the goal.
+    pub fn goal_tree(&self) -> &Tree<N> {
+        &self.goal_tree
+    }
+}
+
+/// search the path which visits all waypoints in order, using random_sample function.
+///
+/// Each leg is planned by dual RRT connect and smoothed with `num_smooth_try` by
+/// [`smooth_path`], keeping the waypoints. The tree grown from the goal of a leg is
+/// reused as the start tree of the next leg.
+pub fn plan_through<FF, FR, N>(
+    waypoints: &[Vec<N>],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    num_smooth_try: usize,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    plan_through_with_rng(
+        waypoints,
+        is_free,
+        random_sample,
+        options,
+        num_smooth_try,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`plan_through`] which takes all randomness from `rng`.
+pub fn plan_through_with_rng<FF, FR, N, R>(
+    waypoints: &[Vec<N>],
+    mut is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    num_smooth_try: usize,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    if waypoints.len() < 2 {
+        return Err("at least two waypoints are needed".to_string());
+    }
+    for leg in waypoints.windows(2) {
+        check_start_goal(&leg[0], &leg[1])?;
+    }
+    let random_sample = RefCell::new(StallTracker::new(random_sample));
+    let mut start_tree = options_tree("start", waypoints[0].len(), options)?;
+    start_tree.add_vertex(&waypoints[0]);
+    let mut path = vec![waypoints[0].clone()];
+    for (i, leg) in waypoints.windows(2).enumerate() {
+        let mut goal_tree = options_tree("goal", leg[1].len(), options)?;
+        goal_tree.add_vertex(&leg[1]);
+        let mut leg_path = connect_trees(
+            &mut start_tree,
+            &mut goal_tree,
+            &mut is_free,
+            &mut |rng: &mut R| random_sample.borrow_mut().sample(rng),
+            &mut |q: &[N]| random_sample.borrow_mut().add_vertex(q),
+            options,
+            rng,
+            None,
+        )
+        .map_err(|e| format!("leg {i}: {e}"))?;
+        smooth_path_with_rng(
+            &mut leg_path,
+            &mut is_free,
+            options.extend_length,
+            num_smooth_try,
+            rng,
+        );
+        path.extend(leg_path.into_iter().skip(1));
+        start_tree = goal_tree;
+        start_tree.name = "start";
+    }
+    Ok(path)
+}
+
+/// Parameters of [`dual_rrt_connect_anytime`].
+#[derive(Debug, Clone)]
+pub struct AnytimeOptions<N> {
+    /// parameters of the underlying dual RRT connect
+    pub rrt: DualRrtConnectOptions<N>,
+    /// the path is improved until this time is used up
+    pub time_budget: Duration,
+    /// number of tries of [`smooth_path`] after each improvement
+    pub num_smooth_try: usize,
+}
+
+impl<N> AnytimeOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 100 tries of smoothing.
+    pub fn new(extend_length: N, num_max_try: usize, time_budget: Duration) -> Self {
+        Self {
+            rrt: DualRrtConnectOptions::new(extend_length, num_max_try),
+            time_budget,
+            num_smooth_try: 100,
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// and keeps improving it until the time budget is used up.
+///
+/// After the first path is found by dual RRT connect, its random sub-paths are replaced
+/// by the paths planned again between their ends, with samples only from the region
+/// which can shorten them, and the path is smoothed by [`smooth_path`] each time.
+/// `on_improved(path, length)` is called with the first path and each shorter one,
+/// and planning stops if it returns `false`.
+/// This is a cheap alternative to RRT* when the optimal path is not needed.
+pub fn dual_rrt_connect_anytime<FF, FR, FI, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &AnytimeOptions<N>,
+    on_improved: FI,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    FI: FnMut(&[Vec<N>], N) -> bool,
+    N: Float + Debug,
+{
+    dual_rrt_connect_anytime_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        on_improved,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_anytime`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_anytime_with_rng<FF, FR, FI, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &AnytimeOptions<N>,
+    mut on_improved: FI,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    FI: FnMut(&[Vec<N>], N) -> bool,
+    N: Float + Debug,
+    R: Rng,
+{
+    let begin = Instant::now();
+    let (num_smooth_try, time_budget) = (options.num_smooth_try, options.time_budget);
+    let options = &options.rrt;
+    let mut path = dual_rrt_connect_with_rng(
+        start,
+        goal,
+        &mut is_free,
+        from_rng_fn(|rng: &mut dyn RngCore| random_sample.sample(rng)),
+        options,
+        rng,
+    )?;
+    smooth_path_with_rng(
+        &mut path,
+        &mut is_free,
+        options.extend_length,
+        num_smooth_try,
+        rng,
+    );
+    let mut best_length = path_length(&path);
+    if !on_improved(&path, best_length) {
+        return Ok(path);
+    }
+    // a path of two vertices is straight, and cannot be shortened
+    while path.len() > 2 && begin.elapsed() < time_budget {
+        let i = rng.gen_range(0..path.len() - 2);
+        let j = rng.gen_range(i + 2..path.len());
+        let (a, b) = (path[i].clone(), path[j].clone());
+        let old_length = path_length(&path[i..=j]);
+        // samples which cannot shorten the sub-path are rejected up to 100 times
+        let informed_sample = from_rng_fn(|rng: &mut dyn RngCore| {
+            let mut q = random_sample.sample(rng);
+            for _ in 0..100 {
+                if !is_valid_point(&q, a.len())
+                    || squared_euclidean(&q, &a).sqrt() + squared_euclidean(&q, &b).sqrt()
+                        < old_length
+                {
+                    break;
+                }
+                q = random_sample.sample(rng);
+            }
+            q
+        });
+        let Ok(mut sub_path) =
+            dual_rrt_connect_with_rng(&a, &b, &mut is_free, informed_sample, options, rng)
+        else {
+            continue;
+        };
+        smooth_path_with_rng(
+            &mut sub_path,
+            &mut is_free,
+            options.extend_length,
+            num_smooth_try,
+            rng,
+        );
+        if path_length(&sub_path) >= old_length {
+            continue;
+        }
+        path.splice(i..=j, sub_path);
+        smooth_path_with_rng(
+            &mut path,
+            &mut is_free,
+            options.extend_length,
+            num_smooth_try,
+            rng,
+        );
+        let length = path_length(&path);
+        if length < best_length {
+            debug!("length = {length:?} in {:?}", begin.elapsed());
+            best_length = length;
+            if !on_improved(&path, length) {
+                break;
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Parameters of [`dual_rrt_connect_with_restarts`].
+#[derive(Debug, Clone)]
+pub struct RestartOptions<N> {
+    /// parameters of the first attempt
+    pub rrt: DualRrtConnectOptions<N>,
+    /// max number of attempts after the first one fails
+    pub num_restarts: usize,
+    /// `extend_length` is multiplied by this at each restart
+    pub extend_length_factor: N,
+    /// `num_max_try` is multiplied by this at each restart
+    pub num_max_try_factor: usize,
+}
+
+impl<N> RestartOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 3 restarts, each of which halves the extend length and
+    /// doubles the max number of tries.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            rrt: DualRrtConnectOptions::new(extend_length, num_max_try),
+            num_restarts: 3,
+            extend_length_factor: N::from(0.5).unwrap(),
+            num_max_try_factor: 2,
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// and restarts with adjusted parameters if it fails.
+///
+/// Each restart plans from scratch with `extend_length` and `num_max_try` multiplied
+/// by the factors of the options, which helps in narrow passages. Returns the path and
+/// the parameters of the attempt which found it.
+pub fn dual_rrt_connect_with_restarts<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &RestartOptions<N>,
+) -> Result<(Vec<Vec<N>>, DualRrtConnectOptions<N>), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_with_restarts_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_with_restarts`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_with_restarts_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &RestartOptions<N>,
+    rng: &mut R,
+) -> Result<(Vec<Vec<N>>, DualRrtConnectOptions<N>), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    assert!(options.extend_length_factor > N::zero());
+    let mut rrt = options.rrt.clone();
+    let mut errors = Vec::new();
+    for attempt in 0..=options.num_restarts {
+        match dual_rrt_connect_with_rng(
+            start,
+            goal,
+            &mut is_free,
+            from_rng_fn(|rng: &mut dyn RngCore| random_sample.sample(rng)),
+            &rrt,
+            rng,
+        ) {
+            Ok(path) => {
+                debug!("found at attempt {attempt} with {rrt:?}");
+                return Ok((path, rrt));
+            }
+            Err(e) => errors.push(e),
+        }
+        rrt.extend_length = rrt.extend_length * options.extend_length_factor;
+        rrt.num_max_try = rrt.num_max_try.saturating_mul(options.num_max_try_factor);
+    }
+    Err(format!(
+        "failed in {} attempts: {}",
+        errors.len(),
+        errors.join(", ")
+    ))
+}
+
+/// search the path from start to the nearest reachable one of `goals` which is free,
+/// using random_sample function.
+///
+/// The goal tree of dual RRT connect is grown from all goals as its roots, so this is
+/// as fast as planning to a single goal. Returns the path and the index of the goal
+/// which it reaches.
+pub fn dual_rrt_connect_multi_goal<FF, FR, N>(
+    start: &[N],
+    goals: &[Vec<N>],
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<(Vec<Vec<N>>, usize), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_multi_goal_with_rng(
+        start,
+        goals,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_multi_goal`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_multi_goal_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goals: &[Vec<N>],
+    mut is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<(Vec<Vec<N>>, usize), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    if goals.is_empty() {
+        return Err("no goal is given".to_string());
+    }
+    for goal in goals {
+        check_start_goal(start, goal)?;
+    }
+    let mut start_tree = options_tree("start", start.len(), options)?;
+    start_tree.add_vertex(start);
+    let mut goal_tree = options_tree("goal", start.len(), options)?;
+    for goal in goals {
+        goal_tree.add_vertex(goal);
+    }
+    let random_sample = RefCell::new(StallTracker::new(random_sample));
+    let path = connect_trees(
+        &mut start_tree,
+        &mut goal_tree,
+        &mut is_free,
+        &mut |rng: &mut R| random_sample.borrow_mut().sample(rng),
+        &mut |q: &[N]| random_sample.borrow_mut().add_vertex(q),
+        options,
+        rng,
+        None,
+    )?;
+    let reached = path.last().unwrap();
+    let goal_index = goals.iter().position(|goal| goal == reached).unwrap();
+    debug!("reached goal {goal_index}");
+    Ok((path, goal_index))
+}
+
+/// search the path from start to the goal region which is free, using random_sample function.
+///
+/// The goal region is given by `is_goal`, and `sample_goal` returns a random
+/// configuration in it (e.g. by inverse kinematics of a random end effector pose on a shelf).
+/// `num_goal_samples` goals which are free are sampled, and the goal tree is grown from all
+/// of them as in [`dual_rrt_connect_multi_goal`]. Goal samples which are not valid,
+/// not free or not in the region are skipped.
+pub fn dual_rrt_connect_goal_region<FG, FS, FF, FR, N>(
+    start: &[N],
+    is_goal: FG,
+    sample_goal: FS,
+    is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    num_goal_samples: usize,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FG: FnMut(&[N]) -> bool,
+    FS: FnMut() -> Vec<N>,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_goal_region_with_rng(
+        start,
+        is_goal,
+        sample_goal,
+        is_free,
+        random_sample,
+        options,
+        num_goal_samples,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_goal_region`] which takes all randomness from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn dual_rrt_connect_goal_region_with_rng<FG, FS, FF, FR, N, R>(
+    start: &[N],
+    mut is_goal: FG,
+    mut sample_goal: FS,
+    mut is_free: FF,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    num_goal_samples: usize,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FG: FnMut(&[N]) -> bool,
+    FS: FnMut() -> Vec<N>,
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    if !is_valid_point(start, start.len()) {
+        return Err(format!("start is not finite: {start:?}"));
+    }
+    if is_goal(start) {
+        return Ok(vec![start.to_vec()]);
+    }
+    let mut goals = Vec::new();
+    for _ in 0..num_goal_samples * 10 {
+        if goals.len() == num_goal_samples {
+            break;
+        }
+        let goal = sample_goal();
+        if is_valid_point(&goal, start.len()) && is_goal(&goal) && is_free(&goal) {
+            goals.push(goal);
+        }
+    }
+    debug!("{} goals are sampled", goals.len());
+    if goals.is_empty() {
+        return Err("no free goal is sampled".to_string());
+    }
+    dual_rrt_connect_multi_goal_with_rng(start, &goals, is_free, random_sample, options, rng)
+        .map(|(path, _)| path)
+}
+
+/// Statistics of [`plan`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStats<N> {
+    /// length of the returned path
+    pub path_length: N,
+    /// number of vertices of both trees
+    pub num_vertices: usize,
+    /// time spent for planning and smoothing
+    pub elapsed: Duration,
+}
+
+/// search the free path from start to goal within `lower` and `upper` bounds,
+/// with reasonable defaults for the other parameters.
+///
+/// Samples are taken uniformly from the bounds, and the extend length is 1/50 of
+/// the diagonal of the bounds. The trees keep growing until a path is found or
+/// `time_budget` runs out, and the path is smoothed.
+///
+/// ```
+/// let (path, stats) = rrt::plan(
+///     &[-2.0, -2.0],
+///     &[2.0, 2.0],
+///     |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0),
+///     &[-1.2, 0.0],
+///     &[1.2, 0.0],
+///     std::time::Duration::from_secs(1),
+/// )
+/// .unwrap();
+/// assert_eq!(path[0], vec![-1.2, 0.0]);
+/// assert!(stats.path_length > 2.4);
+/// ```
+pub fn plan<FF, N>(
+    lower: &[N],
+    upper: &[N],
+    mut is_free: FF,
+    start: &[N],
+    goal: &[N],
+    time_budget: Duration,
+) -> Result<(Vec<Vec<N>>, PlanStats<N>), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug + rand::distributions::uniform::SampleUniform,
+{
+    let begin = Instant::now();
+    if lower.len() != start.len() || upper.len() != start.len() {
+        return Err(format!(
+            "dimension of bounds ({}, {}) is not {}",
+            lower.len(),
+            upper.len(),
+            start.len()
+        ));
+    }
+    let mut sampler = UniformBoxSampler::new(lower, upper)?;
+    let extend_length = squared_euclidean(lower, upper).sqrt() / N::from(50).unwrap();
+    // grow the trees in small steps to check the time budget
+    let mut planner =
+        DualRrtConnectPlanner::new(start, goal, DualRrtConnectOptions::new(extend_length, 100))?;
+    let mut path = loop {
+        match planner.plan(&mut is_free, &mut sampler) {
+            Ok(path) => break path,
+            Err(e) => {
+                if begin.elapsed() > time_budget {
+                    return Err(format!("{e} within {time_budget:?}"));
+                }
+            }
+        }
+    };
+    debug!("planned in {:?}", begin.elapsed());
+    smooth_path(&mut path, &mut is_free, extend_length, 100);
+    let stats = PlanStats {
+        path_length: path_length(&path),
+        num_vertices: planner.start_tree().len() + planner.goal_tree().len(),
+        elapsed: begin.elapsed(),
+    };
+    Ok((path, stats))
+}
+
+#[test]
+fn update_goal() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    let mut planner = DualRrtConnectPlanner::new(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        DualRrtConnectOptions::new(0.2, 1000),
+    )
+    .unwrap();
+    let path = planner.plan(is_free, &random_sample).unwrap();
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    let num_start_vertices = planner.start_tree().len();
+    planner.update_goal(&[0.0, 1.2]).unwrap();
+    assert_eq!(planner.goal_tree().len(), 1);
+    let path = planner.plan(is_free, &random_sample).unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![0.0, 1.2]);
+    assert!(planner.start_tree().len() >= num_start_vertices);
+}
+
+#[test]
+fn planners_use_metric() {
+    use crate::Metric;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    let weighted =
+        |a: &[f64], b: &[f64]| ((a[0] - b[0]).powi(2) + 4.0 * (a[1] - b[1]).powi(2)).sqrt();
+    let options = DualRrtConnectOptions {
+        metric: Metric::WeightedEuclidean(vec![1.0, 4.0]),
+        ..DualRrtConnectOptions::new(0.2, 10000)
+    };
+    let check_edges = |tree: &Tree<f64>| {
+        for index in 1..tree.len() {
+            let parent = tree.parent(index).unwrap();
+            assert!(weighted(tree.vertex(index), tree.vertex(parent)) <= 0.2 + 1e-9);
+        }
+    };
+    let mut planner =
+        DualRrtConnectPlanner::new(&[-1.2, 0.0], &[1.2, 0.0], options.clone()).unwrap();
+    planner.plan(is_free, &random_sample).unwrap();
+    planner.update_goal(&[0.0, 1.2]).unwrap();
+    planner.plan(is_free, &random_sample).unwrap();
+    check_edges(planner.start_tree());
+    check_edges(planner.goal_tree());
+
+    // a metric for another dimension is an error instead of being ignored
+    let wrong = DualRrtConnectOptions {
+        metric: Metric::WeightedEuclidean(vec![1.0]),
+        ..options
+    };
+    assert!(DualRrtConnectPlanner::new(&[-1.2, 0.0], &[1.2, 0.0], wrong.clone()).is_err());
+    assert!(plan_through(
+        &[vec![-1.2, 0.0], vec![1.2, 0.0]],
+        is_free,
+        &random_sample,
+        &wrong,
+        0
+    )
+    .is_err());
+    assert!(dual_rrt_connect_multi_goal(
+        &[-1.2, 0.0],
+        &[vec![1.2, 0.0]],
+        is_free,
+        &random_sample,
+        &wrong
+    )
+    .is_err());
+}
+
+#[test]
+fn plan_through_works() {
+    let world = crate::scenario::World::square();
+    let waypoints = vec![
+        vec![-1.2, 0.0],
+        vec![0.0, 1.2],
+        vec![1.2, 0.0],
+        vec![0.0, -1.2],
+    ];
+    let path = plan_through(
+        &waypoints,
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &DualRrtConnectOptions::new(0.2, 1000),
+        100,
+    )
+    .unwrap();
+    let mut visited = path.iter().filter(|q| waypoints.contains(q));
+    for waypoint in &waypoints {
+        assert_eq!(visited.next(), Some(waypoint));
+    }
+    assert!(plan_through(
+        &[vec![f64::NAN, 0.0], vec![1.2, 0.0]],
+        |_: &[f64]| true,
+        || vec![0.0, 0.0],
+        &DualRrtConnectOptions::new(0.2, 1000),
+        100,
+    )
+    .is_err());
+}
+
+#[test]
+fn dual_rrt_connect_multi_goal_works() {
+    let world = crate::scenario::World::square();
+    // the first goal is in the obstacle
+    let goals = vec![vec![0.0, 0.0], vec![1.2, 0.0], vec![0.0, 1.2]];
+    let (path, goal_index) = dual_rrt_connect_multi_goal(
+        &[-1.2, 0.0],
+        &goals,
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &DualRrtConnectOptions::new(0.2, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_ne!(goal_index, 0);
+    assert_eq!(path[path.len() - 1], goals[goal_index]);
+    assert!(dual_rrt_connect_multi_goal(
+        &[-1.2, 0.0],
+        &[],
+        |_: &[f64]| true,
+        || vec![0.0, 0.0],
+        &DualRrtConnectOptions::new(0.2, 1000),
+    )
+    .is_err());
+}
+
+#[test]
+fn dual_rrt_connect_goal_region_works() {
+    let world = crate::scenario::World::square();
+    let goal_sample = world.sampler(2);
+    // the goal region is the segment x = 1.5, -2.0 <= y <= 2.0
+    let is_goal = |p: &[f64]| p[0] == 1.5 && p[1].abs() <= 2.0;
+    let path = dual_rrt_connect_goal_region(
+        &[-1.2, 0.0],
+        is_goal,
+        || vec![1.5, goal_sample()[1]],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &DualRrtConnectOptions::new(0.2, 1000),
+        5,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert!(is_goal(&path[path.len() - 1]));
+}
+
+#[test]
+fn dual_rrt_connect_anytime_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut lengths = Vec::new();
+    let path = dual_rrt_connect_anytime(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &AnytimeOptions::new(0.2, 1000, Duration::from_millis(200)),
+        |path, length| {
+            assert_eq!(path[0], vec![-1.2, 0.0]);
+            lengths.push(length);
+            true
+        },
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path.iter().all(|q| is_free(q)));
+    assert!(!lengths.is_empty());
+    assert!(lengths.windows(2).all(|l| l[1] < l[0]));
+    assert_eq!(*lengths.last().unwrap(), path_length(&path));
+}
+
+#[test]
+fn dual_rrt_connect_with_restarts_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    // a single try cannot go around the box
+    let mut options = RestartOptions::new(0.2, 1);
+    options.num_restarts = 5;
+    options.num_max_try_factor = 10;
+    let (path, succeeded) = dual_rrt_connect_with_restarts(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &random_sample,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(succeeded.num_max_try > 1);
+    assert!(succeeded.extend_length < 0.2);
+    options.num_restarts = 0;
+    assert!(dual_rrt_connect_with_restarts(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &random_sample,
+        &options
+    )
+    .is_err());
+}
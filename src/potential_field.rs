@@ -0,0 +1,175 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_edge_free};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`potential_field`] and [`potential_field_step`].
+#[derive(Debug, Clone)]
+pub struct PotentialFieldOptions<N> {
+    /// length of one step
+    pub step_length: N,
+    /// gain of the force toward the goal
+    pub attractive_gain: N,
+    /// gain of the force away from obstacles
+    pub repulsive_gain: N,
+    /// obstacles farther than this clearance do not push the robot
+    pub influence_distance: N,
+    /// max repeat num
+    pub num_max_try: usize,
+}
+
+impl<N> PotentialFieldOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with unit gains.
+    pub fn new(step_length: N, influence_distance: N, num_max_try: usize) -> Self {
+        Self {
+            step_length,
+            attractive_gain: N::one(),
+            repulsive_gain: N::one(),
+            influence_distance,
+            num_max_try,
+        }
+    }
+}
+
+fn force<FC, N>(
+    q: &[N],
+    goal: &[N],
+    clearance: &mut FC,
+    options: &PotentialFieldOptions<N>,
+) -> Vec<N>
+where
+    FC: FnMut(&[N]) -> N,
+    N: Float + Debug,
+{
+    let mut force = q
+        .iter()
+        .zip(goal)
+        .map(|(q, g)| (*g - *q) * options.attractive_gain)
+        .collect::<Vec<_>>();
+    let d = clearance(q);
+    // the repulsive force is infinite at d = 0, and callers reject q in collision
+    if d > N::zero() && d < options.influence_distance {
+        // gradient of the clearance by central difference
+        let h = options.step_length / N::from(10.0).unwrap();
+        let mut q_h = q.to_vec();
+        let magnitude = options.repulsive_gain
+            * (N::one() / d - N::one() / options.influence_distance)
+            / (d * d);
+        for (i, f) in force.iter_mut().enumerate() {
+            q_h[i] = q[i] + h;
+            let d_plus = clearance(&q_h);
+            q_h[i] = q[i] - h;
+            let d_minus = clearance(&q_h);
+            q_h[i] = q[i];
+            *f = *f + magnitude * (d_plus - d_minus) / (h + h);
+        }
+    }
+    force
+}
+
+/// move one step from q along the artificial potential field.
+///
+/// The field attracts toward goal and repels from obstacles, using `clearance`
+/// (distance from the configuration to the nearest obstacle, zero or negative in collision).
+/// Returns `Err` if q is in collision or the forces cancel out (local minimum).
+pub fn potential_field_step<FC, N>(
+    q: &[N],
+    goal: &[N],
+    mut clearance: FC,
+    options: &PotentialFieldOptions<N>,
+) -> Result<Vec<N>, String>
+where
+    FC: FnMut(&[N]) -> N,
+    N: Float + Debug,
+{
+    check_start_goal(q, goal)?;
+    let d = clearance(q);
+    if d <= N::zero() || d.is_nan() {
+        return Err(format!("in collision: {q:?}"));
+    }
+    let f = force(q, goal, &mut clearance, options);
+    let norm = f.iter().fold(N::zero(), |sum, f| sum + *f * *f).sqrt();
+    if !norm.is_finite() {
+        return Err(format!("invalid force at {q:?}"));
+    }
+    if norm <= N::epsilon() {
+        return Err("stuck in local minimum".to_string());
+    }
+    let dist_to_goal = squared_euclidean(q, goal).sqrt();
+    let step = options.step_length.min(dist_to_goal);
+    Ok(q.iter()
+        .zip(&f)
+        .map(|(q, f)| *q + *f * step / norm)
+        .collect())
+}
+
+/// search the path from start to goal by following the artificial potential field.
+///
+/// This is a local planner: it is fast and reactive, but can get stuck in local minima.
+/// Returns an error if it collides, gets stuck, or does not reach goal in `num_max_try` steps.
+pub fn potential_field<FC, N>(
+    start: &[N],
+    goal: &[N],
+    mut clearance: FC,
+    options: &PotentialFieldOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FC: FnMut(&[N]) -> N,
+    N: Float + Debug,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.step_length > N::zero());
+    let mut path = vec![start.to_vec()];
+    for _ in 0..options.num_max_try {
+        let q = &path[path.len() - 1];
+        if squared_euclidean(q, goal).sqrt() < options.step_length {
+            let resolution = options.step_length / N::from(10.0).unwrap();
+            if clearance(goal) <= N::zero()
+                || !is_edge_free(q, goal, &mut |p: &[N]| clearance(p) > N::zero(), resolution)
+            {
+                return Err("collision".to_string());
+            }
+            path.push(goal.to_vec());
+            return Ok(path);
+        }
+        let q_new = potential_field_step(q, goal, &mut clearance, options)?;
+        debug!("q_new={q_new:?}");
+        if clearance(&q_new) <= N::zero() {
+            return Err("collision".to_string());
+        }
+        path.push(q_new);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn potential_field_works() {
+    let clearance = |p: &[f64]| (p[0] * p[0] + (p[1] + 0.2) * (p[1] + 0.2)).sqrt() - 0.5;
+    let options = PotentialFieldOptions::new(0.05, 0.5, 1000);
+    let result = potential_field(&[-2.0, 0.0], &[2.0, 0.0], clearance, &options).unwrap();
+    assert_eq!(result[result.len() - 1], vec![2.0, 0.0]);
+    assert!(result.iter().all(|p| clearance(p) > 0.0));
+    // the goal is in the obstacle
+    assert!(potential_field(&[-2.0, 0.0], &[0.0, -0.2], clearance, &options).is_err());
+    assert!(potential_field_step(&[0.0, -0.2], &[2.0, 0.0], clearance, &options).is_err());
+}
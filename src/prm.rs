@@ -0,0 +1,646 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_edge_free, is_valid_point, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Rule to choose the vertices which a new vertex is connected to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionRule<N> {
+    /// at most `num_neighbors` nearest vertices (PRM)
+    Fixed,
+    /// `k_factor * log(n)` nearest vertices, where `n` is the number of vertices
+    /// (k-nearest PRM*). The solution converges to optimal if `k_factor` is
+    /// larger than `e * (1 + 1 / dim)`.
+    KNearestStar {
+        /// factor of the number of neighbors
+        k_factor: f64,
+    },
+    /// vertices within `gamma * (log(n) / n) ^ (1 / dim)`, where `n` is the number
+    /// of vertices (r-disc PRM*). The solution converges to optimal if `gamma` is
+    /// larger than `2 * (1 + 1 / dim) ^ (1 / dim) * (free volume / unit ball volume) ^ (1 / dim)`.
+    RadiusStar {
+        /// factor of the connection radius
+        gamma: N,
+    },
+}
+
+/// Parameters of [`Roadmap`].
+#[derive(Debug, Clone)]
+pub struct PrmOptions<N> {
+    /// rule to choose the vertices to connect
+    pub connection_rule: ConnectionRule<N>,
+    /// max number of nearest vertices which a new vertex is connected to,
+    /// used by [`ConnectionRule::Fixed`]
+    pub num_neighbors: usize,
+    /// max length of edges
+    pub max_distance: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+    /// if `true`, edges are not checked when added, but only when they are on
+    /// the shortest path of a query (Lazy PRM). `false` by default.
+    pub lazy: bool,
+}
+
+impl<N> PrmOptions<N>
+where
+    N: Float,
+{
+    /// Creates options which connect a new vertex to at most 10 neighbors.
+    pub fn new(max_distance: N, resolution: N) -> Self {
+        Self {
+            connection_rule: ConnectionRule::Fixed,
+            num_neighbors: 10,
+            max_distance,
+            resolution,
+            lazy: false,
+        }
+    }
+
+    /// Creates options of PRM*, which uses [`ConnectionRule::KNearestStar`]
+    /// with `k_factor` `2 * e` and has no limit of edge length.
+    pub fn prmstar(resolution: N) -> Self {
+        Self {
+            connection_rule: ConnectionRule::KNearestStar {
+                k_factor: 2.0 * std::f64::consts::E,
+            },
+            ..Self::new(N::infinity(), resolution)
+        }
+    }
+}
+
+/// vertex in the open list of Dijkstra and A*, popped in the order of `cost`
+#[derive(Debug, PartialEq)]
+pub(crate) struct OpenVertex<N> {
+    pub(crate) cost: N,
+    pub(crate) index: usize,
+}
+
+impl<N: PartialOrd> Eq for OpenVertex<N> {}
+
+impl<N: PartialOrd> PartialOrd for OpenVertex<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: PartialOrd> Ord for OpenVertex<N> {
+    // reversed to make BinaryHeap a min-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Probabilistic roadmap (PRM), which is built once and answers many
+/// start/goal queries while the environment does not change.
+///
+/// ```
+/// use rand::distributions::{Distribution, Uniform};
+/// let is_free = |p: &[f64]| !(p[0].abs() < 1.0 && p[1].abs() < 1.0);
+/// let mut roadmap = rrt::Roadmap::new(2, rrt::PrmOptions::new(0.5, 0.05));
+/// roadmap.grow(
+///     is_free,
+///     || {
+///         let between = Uniform::new(-2.0, 2.0);
+///         let mut rng = rand::thread_rng();
+///         vec![between.sample(&mut rng), between.sample(&mut rng)]
+///     },
+///     500,
+/// );
+/// let path = roadmap.query(&[-1.2, 0.0], &[1.2, 0.0], is_free).unwrap();
+/// assert_eq!(path[0], vec![-1.2, 0.0]);
+/// ```
+#[derive(Debug)]
+pub struct Roadmap<N>
+where
+    N: Float + Debug,
+{
+    kdtree: kdtree::KdTree<N, usize, Vec<N>>,
+    vertices: Vec<Vec<N>>,
+    edges: Vec<Vec<(usize, N)>>,
+    /// edges checked by lazy queries, as (smaller index, larger index)
+    checked_edges: HashSet<(usize, usize)>,
+    pub(crate) dim: usize,
+    pub(crate) options: PrmOptions<N>,
+}
+
+impl<N> Roadmap<N>
+where
+    N: Float + Debug,
+{
+    /// Creates an empty roadmap for `dim` dimensional configurations.
+    pub fn new(dim: usize, options: PrmOptions<N>) -> Self {
+        assert!(options.resolution > N::zero());
+        Self {
+            kdtree: kdtree::KdTree::new(dim),
+            vertices: Vec::new(),
+            edges: Vec::new(),
+            checked_edges: HashSet::new(),
+            dim,
+            options,
+        }
+    }
+    /// Returns the number of vertices.
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+    /// Returns `true` if the roadmap has no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+    /// Returns the configuration of the vertex at `index`.
+    pub fn vertex(&self, index: usize) -> &[N] {
+        &self.vertices[index]
+    }
+    /// Returns the vertices connected to the vertex at `index` as `(index, distance)`.
+    ///
+    /// With [`PrmOptions::lazy`], some of the edges may not be checked yet.
+    pub fn neighbors(&self, index: usize) -> &[(usize, N)] {
+        &self.edges[index]
+    }
+    /// Adds free samples out of `num_samples` samples to the roadmap, and
+    /// returns the number of vertices added.
+    pub fn grow<FF, FR>(&mut self, is_free: FF, random_sample: FR, num_samples: usize) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+    {
+        self.grow_with_rng(is_free, random_sample, num_samples, &mut rand::thread_rng())
+    }
+    /// [`Self::grow`] which takes all randomness from `rng`.
+    pub fn grow_with_rng<FF, FR, R>(
+        &mut self,
+        mut is_free: FF,
+        mut random_sample: FR,
+        num_samples: usize,
+        rng: &mut R,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+        R: Rng,
+    {
+        let mut num_added = 0;
+        for q in random_sample.sample_batch(num_samples, rng).rows() {
+            if !is_valid_point(q, self.dim) {
+                debug!("skipped invalid sample {q:?}");
+                continue;
+            }
+            if is_free(q) {
+                self.add_vertex(q, &mut is_free);
+                num_added += 1;
+            }
+        }
+        num_added
+    }
+    /// Adds `q` and the free edges to its neighbors, and returns its index.
+    fn add_vertex<FF>(&mut self, q: &[N], is_free: &mut FF) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let index = self.add_unindexed_vertex(q, is_free);
+        self.kdtree.add(q.to_vec(), index).unwrap();
+        index
+    }
+    /// Adds `q` and the free edges to its neighbors without adding it to the kd-tree,
+    /// so later vertices are not connected to it.
+    fn add_unindexed_vertex<FF>(&mut self, q: &[N], is_free: &mut FF) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let neighbors = if self.kdtree.size() == 0 {
+            Vec::new()
+        } else {
+            // number of vertices including q
+            let n = (self.kdtree.size() + 1) as f64;
+            let neighbors = match self.options.connection_rule {
+                ConnectionRule::Fixed => {
+                    self.kdtree
+                        .nearest(q, self.options.num_neighbors, &squared_euclidean)
+                }
+                ConnectionRule::KNearestStar { k_factor } => {
+                    self.kdtree
+                        .nearest(q, (k_factor * n.ln()).ceil() as usize, &squared_euclidean)
+                }
+                ConnectionRule::RadiusStar { gamma } => {
+                    let r = gamma * N::from((n.ln() / n).powf(1.0 / self.dim as f64)).unwrap();
+                    self.kdtree.within(q, r * r, &squared_euclidean)
+                }
+            };
+            neighbors
+                .unwrap()
+                .into_iter()
+                .map(|(dist, index)| (*index, dist.sqrt()))
+                .collect()
+        };
+        let index = self.vertices.len();
+        self.vertices.push(q.to_vec());
+        self.edges.push(Vec::new());
+        for (neighbor, dist) in neighbors {
+            if dist > self.options.max_distance {
+                break;
+            }
+            self.add_edge_if_free(neighbor, index, dist, is_free);
+        }
+        index
+    }
+    fn add_edge_if_free<FF>(&mut self, a: usize, b: usize, dist: N, is_free: &mut FF)
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        if self.options.lazy
+            || is_edge_free(
+                &self.vertices[a],
+                &self.vertices[b],
+                is_free,
+                self.options.resolution,
+            )
+        {
+            self.edges[a].push((b, dist));
+            self.edges[b].push((a, dist));
+        }
+    }
+    /// Adds `q` without edges, and returns its index.
+    pub(crate) fn add_isolated_vertex(&mut self, q: &[N]) -> usize {
+        let index = self.vertices.len();
+        self.vertices.push(q.to_vec());
+        self.edges.push(Vec::new());
+        self.kdtree.add(q.to_vec(), index).unwrap();
+        index
+    }
+    /// Adds the edge between `a` and `b` without checking it.
+    pub(crate) fn add_edge(&mut self, a: usize, b: usize) {
+        let dist = squared_euclidean(&self.vertices[a], &self.vertices[b]).sqrt();
+        self.edges[a].push((b, dist));
+        self.edges[b].push((a, dist));
+    }
+    /// Returns the nearest vertex to `q` as `(index, distance)`. The roadmap must not be empty.
+    pub(crate) fn nearest(&self, q: &[N]) -> (usize, N) {
+        let nearest = self.kdtree.nearest(q, 1, &squared_euclidean).unwrap();
+        (*nearest[0].1, nearest[0].0.sqrt())
+    }
+    /// Returns the vertices within `radius` of `q` as `(index, distance)`, nearest first.
+    pub(crate) fn within(&self, q: &[N], radius: N) -> Vec<(usize, N)> {
+        if self.kdtree.size() == 0 {
+            return Vec::new();
+        }
+        self.kdtree
+            .within(q, radius * radius, &squared_euclidean)
+            .unwrap()
+            .into_iter()
+            .map(|(dist, index)| (*index, dist.sqrt()))
+            .collect()
+    }
+    /// Returns the index of the vertex at `q` if it exists.
+    fn find_vertex(&self, q: &[N]) -> Option<usize> {
+        let nearest = self.kdtree.nearest(q, 1, &squared_euclidean).ok()?;
+        let (dist, index) = nearest.first()?;
+        (*dist == N::zero()).then_some(**index)
+    }
+    /// Removes the vertices from `len`, which were added for a query, and their edges.
+    fn truncate(&mut self, len: usize) {
+        if self.vertices.len() == len {
+            return;
+        }
+        for index in len..self.vertices.len() {
+            for (neighbor, _) in std::mem::take(&mut self.edges[index]) {
+                if neighbor < len {
+                    self.edges[neighbor].retain(|(i, _)| *i < len);
+                }
+            }
+        }
+        self.vertices.truncate(len);
+        self.edges.truncate(len);
+        self.checked_edges.retain(|(_, b)| *b < len);
+    }
+    /// search the shortest path from start to goal on the roadmap.
+    ///
+    /// Start and goal are connected to the roadmap only during the query, unless
+    /// they are already vertices, so the roadmap does not grow with queries.
+    pub fn query<FF>(
+        &mut self,
+        start: &[N],
+        goal: &[N],
+        mut is_free: FF,
+    ) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let (len, start_index, goal_index) = self.add_query_vertices(start, goal, &mut is_free)?;
+        let result = loop {
+            let Some(path) = self.shortest_path(start_index, goal_index) else {
+                break Err("start and goal are not connected on the roadmap".to_string());
+            };
+            if !self.options.lazy || self.check_lazy_edges(&path, &mut is_free) {
+                break Ok(path.into_iter().map(|i| self.vertices[i].clone()).collect());
+            }
+        };
+        self.truncate(len);
+        result
+    }
+    /// search at most `k` shortest paths without loops from start to goal on the roadmap,
+    /// shortest first (Yen's algorithm).
+    ///
+    /// The paths after the first one are the alternatives, e.g. in other homotopy
+    /// classes if the roadmap has cycles around obstacles. Start and goal are connected
+    /// as in [`query`](Self::query). The roadmap must not be lazy.
+    pub fn k_shortest_paths<FF>(
+        &mut self,
+        start: &[N],
+        goal: &[N],
+        k: usize,
+        mut is_free: FF,
+    ) -> Result<Vec<Vec<Vec<N>>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        if self.options.lazy {
+            return Err("k shortest paths are not supported by lazy roadmaps".to_string());
+        }
+        let (len, start_index, goal_index) = self.add_query_vertices(start, goal, &mut is_free)?;
+        let paths = self
+            .yen(start_index, goal_index, k)
+            .into_iter()
+            .map(|path| path.into_iter().map(|i| self.vertices[i].clone()).collect())
+            .collect::<Vec<_>>();
+        self.truncate(len);
+        if paths.is_empty() {
+            return Err("start and goal are not connected on the roadmap".to_string());
+        }
+        Ok(paths)
+    }
+    /// Checks start and goal of a query, and adds them to the roadmap unless they are
+    /// already vertices. Returns (the number of vertices before the query, start index,
+    /// goal index).
+    fn add_query_vertices<FF>(
+        &mut self,
+        start: &[N],
+        goal: &[N],
+        is_free: &mut FF,
+    ) -> Result<(usize, usize, usize), String>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        check_start_goal(start, goal)?;
+        if start.len() != self.dim {
+            return Err(format!(
+                "dimension of start and goal is {}, but the roadmap is {}",
+                start.len(),
+                self.dim
+            ));
+        }
+        if !is_free(start) {
+            return Err(format!("start is not free: {start:?}"));
+        }
+        if !is_free(goal) {
+            return Err(format!("goal is not free: {goal:?}"));
+        }
+        let len = self.vertices.len();
+        let start_index = match self.find_vertex(start) {
+            Some(index) => index,
+            None => self.add_unindexed_vertex(start, is_free),
+        };
+        let goal_index = match self.find_vertex(goal) {
+            Some(index) => index,
+            None => self.add_unindexed_vertex(goal, is_free),
+        };
+        if start_index >= len && goal_index >= len {
+            let dist = squared_euclidean(start, goal).sqrt();
+            if dist <= self.options.max_distance {
+                self.add_edge_if_free(start_index, goal_index, dist, is_free);
+            }
+        }
+        Ok((len, start_index, goal_index))
+    }
+    /// Yen's k shortest loopless paths from `from` to `to`, as the indices of the paths.
+    fn yen(&self, from: usize, to: usize, k: usize) -> Vec<Vec<usize>> {
+        let no_edges = HashSet::new();
+        let no_vertices = vec![false; self.vertices.len()];
+        let Some(shortest) = self.shortest_path_avoiding(from, to, &no_edges, &no_vertices) else {
+            return Vec::new();
+        };
+        let cost = |path: &[usize]| {
+            path.windows(2).fold(N::zero(), |sum, edge| {
+                sum + squared_euclidean(&self.vertices[edge[0]], &self.vertices[edge[1]]).sqrt()
+            })
+        };
+        let mut paths = vec![shortest];
+        let mut candidates: Vec<(N, Vec<usize>)> = Vec::new();
+        while paths.len() < k {
+            let previous = &paths[paths.len() - 1];
+            for i in 0..(previous.len() - 1) {
+                let root = &previous[..=i];
+                let mut removed_edges = HashSet::new();
+                for path in &paths {
+                    if path.len() > i + 1 && path[..=i] == *root {
+                        removed_edges.insert((path[i].min(path[i + 1]), path[i].max(path[i + 1])));
+                    }
+                }
+                let mut removed_vertices = vec![false; self.vertices.len()];
+                for v in &root[..i] {
+                    removed_vertices[*v] = true;
+                }
+                let Some(spur) =
+                    self.shortest_path_avoiding(previous[i], to, &removed_edges, &removed_vertices)
+                else {
+                    continue;
+                };
+                let mut candidate = root[..i].to_vec();
+                candidate.extend(spur);
+                if !paths.contains(&candidate) && !candidates.iter().any(|(_, c)| *c == candidate) {
+                    candidates.push((cost(&candidate), candidate));
+                }
+            }
+            let Some(best) = (0..candidates.len())
+                .min_by(|a, b| candidates[*a].0.partial_cmp(&candidates[*b].0).unwrap())
+            else {
+                break;
+            };
+            paths.push(candidates.swap_remove(best).1);
+        }
+        paths
+    }
+    /// Checks the unchecked edges on the path, and removes them from the roadmap
+    /// if not free. Returns `true` if all edges are free.
+    fn check_lazy_edges<FF>(&mut self, path: &[usize], is_free: &mut FF) -> bool
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        for edge in path.windows(2) {
+            let key = (edge[0].min(edge[1]), edge[0].max(edge[1]));
+            if self.checked_edges.contains(&key) {
+                continue;
+            }
+            if !is_edge_free(
+                &self.vertices[edge[0]],
+                &self.vertices[edge[1]],
+                is_free,
+                self.options.resolution,
+            ) {
+                debug!("removed edge {key:?}");
+                self.edges[edge[0]].retain(|(i, _)| *i != edge[1]);
+                self.edges[edge[1]].retain(|(i, _)| *i != edge[0]);
+                return false;
+            }
+            self.checked_edges.insert(key);
+        }
+        true
+    }
+    /// Dijkstra from `from` to `to`, returning the indices of the path.
+    pub(crate) fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let no_vertices = vec![false; self.vertices.len()];
+        self.shortest_path_avoiding(from, to, &HashSet::new(), &no_vertices)
+    }
+    /// Dijkstra from `from` to `to` without `removed_edges`, given as
+    /// (smaller index, larger index), and the vertices marked in `removed_vertices`.
+    fn shortest_path_avoiding(
+        &self,
+        from: usize,
+        to: usize,
+        removed_edges: &HashSet<(usize, usize)>,
+        removed_vertices: &[bool],
+    ) -> Option<Vec<usize>> {
+        let mut costs = vec![N::infinity(); self.vertices.len()];
+        let mut parents = vec![None; self.vertices.len()];
+        let mut open = BinaryHeap::new();
+        costs[from] = N::zero();
+        open.push(OpenVertex {
+            cost: N::zero(),
+            index: from,
+        });
+        while let Some(OpenVertex { cost, index }) = open.pop() {
+            if cost > costs[index] {
+                continue;
+            }
+            if index == to {
+                let mut path = vec![to];
+                let mut cur = to;
+                while let Some(parent) = parents[cur] {
+                    path.push(parent);
+                    cur = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (neighbor, dist) in &self.edges[index] {
+                let new_cost = cost + *dist;
+                if new_cost < costs[*neighbor]
+                    && !removed_vertices[*neighbor]
+                    && !removed_edges.contains(&(index.min(*neighbor), index.max(*neighbor)))
+                {
+                    costs[*neighbor] = new_cost;
+                    parents[*neighbor] = Some(index);
+                    open.push(OpenVertex {
+                        cost: new_cost,
+                        index: *neighbor,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn prm_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut roadmap = Roadmap::new(2, PrmOptions::new(0.5, 0.05));
+    let num_added = roadmap.grow(is_free, world.sampler(1), 1000);
+    assert_eq!(roadmap.len(), num_added);
+    for (start, goal) in [([-1.2, 0.0], [1.2, 0.0]), ([0.0, 1.5], [0.0, -1.5])] {
+        let path = roadmap.query(&start, &goal, is_free).unwrap();
+        assert_eq!(path[0], start.to_vec());
+        assert_eq!(path[path.len() - 1], goal.to_vec());
+        for segment in path.windows(2) {
+            assert!(squared_euclidean(&segment[0], &segment[1]).sqrt() <= 0.5);
+            assert!(is_edge_free(
+                &segment[0],
+                &segment[1],
+                &mut { is_free },
+                0.05
+            ));
+        }
+    }
+    assert!(roadmap.query(&[0.0, 0.0], &[1.2, 0.0], is_free).is_err());
+    // queries do not change the roadmap
+    assert_eq!(roadmap.len(), num_added);
+}
+
+#[test]
+fn prmstar_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    // shortest path goes along the box, whose length is about 4.04
+    for connection_rule in [
+        PrmOptions::prmstar(0.05).connection_rule,
+        ConnectionRule::RadiusStar { gamma: 6.0 },
+    ] {
+        let mut roadmap = Roadmap::new(
+            2,
+            PrmOptions {
+                connection_rule,
+                ..PrmOptions::prmstar(0.05)
+            },
+        );
+        roadmap.grow(is_free, &random_sample, 1000);
+        let path = roadmap.query(&[-1.2, 0.0], &[1.2, 0.0], is_free).unwrap();
+        let length = path.windows(2).fold(0.0, |sum, segment| {
+            sum + squared_euclidean(&segment[0], &segment[1]).sqrt()
+        });
+        assert!(length < 4.5, "{length}");
+    }
+}
+
+#[test]
+fn lazy_prm_works() {
+    use std::cell::Cell;
+    let world = crate::scenario::World::square();
+    let num_checks = Cell::new(0);
+    let is_free = |p: &[f64]| {
+        num_checks.set(num_checks.get() + 1);
+        !(p[0].abs() < 1.0 && p[1].abs() < 1.0)
+    };
+    let random_sample = world.sampler(1);
+    let options = PrmOptions {
+        lazy: true,
+        ..PrmOptions::new(0.5, 0.05)
+    };
+    let mut roadmap = Roadmap::new(2, options);
+    let num_added = roadmap.grow(is_free, &random_sample, 1000);
+    // only vertices are checked
+    assert_eq!(num_checks.get(), 1000);
+    let path = roadmap.query(&[-1.2, 0.0], &[1.2, 0.0], is_free).unwrap();
+    for segment in path.windows(2) {
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut { is_free },
+            0.05
+        ));
+    }
+    assert_eq!(roadmap.len(), num_added);
+}
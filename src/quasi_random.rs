@@ -0,0 +1,416 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::check_bounds;
+use crate::Sampler;
+use num_traits::float::Float;
+use rand::RngCore;
+use std::fmt::Debug;
+
+/// Returns `x` in [0, 1) scaled into the box between `lower` and `upper`.
+fn scale<N: Float>(x: f64, lower: N, upper: N) -> N {
+    lower + (upper - lower) * N::from(x).unwrap()
+}
+
+/// the first `n` prime numbers
+fn primes(n: usize) -> Vec<u64> {
+    let mut primes = Vec::with_capacity(n);
+    let mut candidate = 2;
+    while primes.len() < n {
+        if primes.iter().all(|p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+/// Returns the digits of `index` in `base` mirrored at the radix point.
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let mut inverse = 0.0;
+    let mut scale = 1.0 / base as f64;
+    while index > 0 {
+        inverse += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    inverse
+}
+
+/// Sampler of the Halton sequence in the box between `lower` and `upper`.
+///
+/// The Halton sequence is a deterministic low-discrepancy sequence, which covers
+/// the space more evenly than uniform random samples, and makes the planning
+/// reproducible. Axis `i` uses the radical inverse in the `i`-th prime base, so
+/// the coverage degrades in high dimensions. The rng of the planner is not used.
+///
+/// Consecutive samples are correlated, e.g. they alternate between the halves of the
+/// first axis, so planners which use them by turns for two trees, like
+/// [`dual_rrt_connect`](crate::dual_rrt_connect), should randomize the order of the
+/// trees with [`TreeBalance::Weighted`](crate::TreeBalance::Weighted).
+#[derive(Debug, Clone)]
+pub struct HaltonSampler<N> {
+    lower: Vec<N>,
+    upper: Vec<N>,
+    bases: Vec<u64>,
+    index: u64,
+}
+
+impl<N> HaltonSampler<N>
+where
+    N: Float + Debug,
+{
+    /// Returns an error if the bounds are invalid as [`UniformBoxSampler::new`](crate::UniformBoxSampler::new).
+    pub fn new(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        Ok(Self {
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+            bases: primes(lower.len()),
+            // 0 is skipped because it is the lower corner in all axes
+            index: 1,
+        })
+    }
+}
+
+impl<N> Sampler<N> for HaltonSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<N> {
+        let index = self.index;
+        self.index += 1;
+        self.bases
+            .iter()
+            .zip(self.lower.iter().zip(&self.upper))
+            .map(|(base, (l, u))| scale(radical_inverse(index, *base), *l, *u))
+            .collect()
+    }
+}
+
+impl<N> Sampler<N> for &mut HaltonSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+}
+
+/// degree, coefficients and initial direction numbers of the primitive polynomials
+/// for the axes after the first one (Joe and Kuo)
+const SOBOL_POLYNOMIALS: [(usize, u32, &[u32]); 15] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+];
+
+const SOBOL_BITS: usize = 32;
+
+/// Returns the direction numbers of axis `axis`, scaled to 32 bits.
+fn sobol_directions(axis: usize) -> [u32; SOBOL_BITS] {
+    let mut v = [0; SOBOL_BITS];
+    if axis == 0 {
+        for (i, v) in v.iter_mut().enumerate() {
+            *v = 1 << (SOBOL_BITS - 1 - i);
+        }
+        return v;
+    }
+    let (s, a, m) = SOBOL_POLYNOMIALS[axis - 1];
+    for i in 0..SOBOL_BITS {
+        v[i] = if i < s {
+            m[i] << (SOBOL_BITS - 1 - i)
+        } else {
+            let mut value = v[i - s] ^ (v[i - s] >> s);
+            for k in 1..s {
+                if (a >> (s - 1 - k)) & 1 == 1 {
+                    value ^= v[i - k];
+                }
+            }
+            value
+        };
+    }
+    v
+}
+
+/// Sampler of the Sobol sequence in the box between `lower` and `upper`.
+///
+/// The Sobol sequence is a deterministic low-discrepancy sequence like
+/// [`HaltonSampler`], which keeps its uniformity better in higher dimensions.
+/// Up to 16 dimensions are supported. The rng of the planner is not used, and
+/// consecutive samples are correlated as those of [`HaltonSampler`].
+#[derive(Debug, Clone)]
+pub struct SobolSampler<N> {
+    lower: Vec<N>,
+    upper: Vec<N>,
+    directions: Vec<[u32; SOBOL_BITS]>,
+    /// the last point of the sequence, as 32 bits fractions
+    point: Vec<u32>,
+    index: u64,
+}
+
+impl<N> SobolSampler<N>
+where
+    N: Float + Debug,
+{
+    /// Returns an error if the bounds are invalid as [`UniformBoxSampler::new`](crate::UniformBoxSampler::new),
+    /// or the dimension is more than 16.
+    pub fn new(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        if lower.len() > SOBOL_POLYNOMIALS.len() + 1 {
+            return Err(format!(
+                "Sobol sequence supports up to {} dimensions: {}",
+                SOBOL_POLYNOMIALS.len() + 1,
+                lower.len()
+            ));
+        }
+        Ok(Self {
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+            directions: (0..lower.len()).map(sobol_directions).collect(),
+            point: vec![0; lower.len()],
+            index: 0,
+        })
+    }
+}
+
+impl<N> Sampler<N> for SobolSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<N> {
+        // Gray code order: flip the direction of the lowest zero bit of the index.
+        // The first point of the sequence, which is the lower corner, is skipped.
+        let bit = (!self.index).trailing_zeros() as usize % SOBOL_BITS;
+        self.index += 1;
+        for (x, v) in self.point.iter_mut().zip(&self.directions) {
+            *x ^= v[bit];
+        }
+        self.point
+            .iter()
+            .zip(self.lower.iter().zip(&self.upper))
+            .map(|(x, (l, u))| scale(*x as f64 / (1u64 << SOBOL_BITS) as f64, *l, *u))
+            .collect()
+    }
+}
+
+impl<N> Sampler<N> for &mut SobolSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+}
+
+/// Sampler of an incremental lattice in the box between `lower` and `upper`.
+///
+/// Level `k` of the lattice is the centers of the `2^k` cells per axis, and the levels
+/// are returned in order, so the dispersion halves with each level and the planners
+/// using it are resolution complete. The points in each level are spread by dealing
+/// the bits of their indices to the axes, from the highest bits of the cells. No rng is used at all, and the number of the levels is
+/// limited to `63 / dimension`, after which the lattice starts over.
+#[derive(Debug, Clone)]
+pub struct LatticeSampler<N> {
+    lower: Vec<N>,
+    upper: Vec<N>,
+    level: u32,
+    index: u64,
+}
+
+impl<N> LatticeSampler<N>
+where
+    N: Float + Debug,
+{
+    /// Returns an error if the bounds are invalid as [`UniformBoxSampler::new`](crate::UniformBoxSampler::new).
+    pub fn new(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        Ok(Self {
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+            level: 0,
+            index: 0,
+        })
+    }
+}
+
+impl<N> Sampler<N> for LatticeSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<N> {
+        let dim = self.lower.len() as u32;
+        let num_bits = self.level * dim;
+        let num_cells = (1u64 << self.level) as f64;
+        let q = self
+            .lower
+            .iter()
+            .zip(&self.upper)
+            .enumerate()
+            .map(|(axis, (l, u))| {
+                // the lowest bits of the index are the highest bits of the cells
+                let cell = (0..self.level).fold(0, |cell, bit| {
+                    (cell << 1) | ((self.index >> (bit * dim + axis as u32)) & 1)
+                });
+                scale((cell as f64 + 0.5) / num_cells, *l, *u)
+            })
+            .collect();
+        self.index += 1;
+        if self.index >> num_bits != 0 {
+            self.index = 0;
+            self.level += 1;
+            if (self.level + 1) * dim > 63 {
+                self.level = 0;
+            }
+        }
+        q
+    }
+}
+
+impl<N> Sampler<N> for &mut LatticeSampler<N>
+where
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+}
+
+#[test]
+fn quasi_random_samplers_work() {
+    use crate::{
+        dual_rrt_connect_with_options, rrt, DualRrtConnectOptions, RrtOptions, TreeBalance,
+    };
+    let mut rng = rand::thread_rng();
+    let mut halton = HaltonSampler::new(&[0.0, 0.0], &[1.0, 1.0]).unwrap();
+    assert_eq!(halton.sample(&mut rng), vec![0.5, 1.0 / 3.0]);
+    assert_eq!(halton.sample(&mut rng), vec![0.25, 2.0 / 3.0]);
+    let mut sobol = SobolSampler::new(&[0.0; 16], &[1.0; 16]).unwrap();
+    assert_eq!(sobol.sample(&mut rng), vec![0.5; 16]);
+    // each axis of the first 2^k points has one point in each interval of 1/2^k
+    let mut sobol = SobolSampler::new(&[0.0; 16], &[1.0; 16]).unwrap();
+    let points = (0..63)
+        .map(|_| sobol.sample(&mut rng))
+        .collect::<Vec<Vec<f64>>>();
+    for axis in 0..16 {
+        let mut cells = points
+            .iter()
+            .map(|p| (p[axis] * 64.0) as usize)
+            .chain([0])
+            .collect::<Vec<_>>();
+        cells.sort_unstable();
+        assert_eq!(cells, (0..64).collect::<Vec<_>>(), "axis {axis}");
+    }
+    // deterministic
+    let mut sobol2 = SobolSampler::new(&[0.0; 16], &[1.0; 16]).unwrap();
+    assert_eq!(sobol2.sample(&mut rng), points[0]);
+    assert!(SobolSampler::new(&[0.0; 17], &[1.0; 17]).is_err());
+    assert!(HaltonSampler::new(&[1.0], &[0.0]).is_err());
+
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut halton = HaltonSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let mut sobol = SobolSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let options = RrtOptions::new(0.2, 5000);
+    for path in [
+        rrt(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut halton, &options),
+        rrt(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut sobol, &options),
+    ] {
+        let path = path.unwrap();
+        assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    }
+    let mut halton = HaltonSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let options = DualRrtConnectOptions {
+        tree_balance: TreeBalance::Weighted,
+        ..DualRrtConnectOptions::new(0.2, 1000)
+    };
+    assert!(dual_rrt_connect_with_options(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &mut halton,
+        &options
+    )
+    .is_ok());
+}
+
+#[test]
+fn lattice_sampler_works() {
+    use crate::{rrt, RrtOptions};
+    let mut rng = rand::thread_rng();
+    let mut lattice = LatticeSampler::new(&[0.0, 0.0], &[1.0, 1.0]).unwrap();
+    assert_eq!(lattice.sample(&mut rng), vec![0.5, 0.5]);
+    let mut level1 = (0..4)
+        .map(|_| lattice.sample(&mut rng))
+        .collect::<Vec<Vec<f64>>>();
+    level1.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(
+        level1,
+        vec![
+            vec![0.25, 0.25],
+            vec![0.25, 0.75],
+            vec![0.75, 0.25],
+            vec![0.75, 0.75]
+        ]
+    );
+    // the first points of level 2 are spread, not in a row
+    let level2 = (0..16)
+        .map(|_| lattice.sample(&mut rng))
+        .collect::<Vec<Vec<f64>>>();
+    assert!(level2[..4].iter().any(|q| q[0] < 0.5) && level2[..4].iter().any(|q| q[0] > 0.5));
+    assert!(level2[..4].iter().any(|q| q[1] < 0.5) && level2[..4].iter().any(|q| q[1] > 0.5));
+    // every point is within the half diagonal of the cells of level 2
+    for i in 0..=20 {
+        for j in 0..=20 {
+            let p = [i as f64 / 20.0, j as f64 / 20.0];
+            let nearest = level2
+                .iter()
+                .map(|q| (q[0] - p[0]).hypot(q[1] - p[1]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(nearest <= 2.0f64.sqrt() / 8.0 + 1e-9);
+        }
+    }
+    // deterministic, and the levels are bounded in high dimensions
+    let mut a = LatticeSampler::new(&[0.0; 40], &[1.0; 40]).unwrap();
+    let mut b = a.clone();
+    assert_eq!(a.sample(&mut rng), b.sample(&mut rng));
+    assert_eq!(a.sample(&mut rng), vec![0.5; 40]);
+
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut lattice = LatticeSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let path = rrt(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        &mut lattice,
+        &RrtOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+}
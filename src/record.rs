@@ -0,0 +1,242 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::Sampler;
+use rand::RngCore;
+use tracing::warn;
+
+/// A sample drawn during planning, and the vertices which the planner added after it.
+///
+/// No vertices means that the extension toward the sample was trapped.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleRecord<N> {
+    /// the sample returned to the planner
+    pub sample: Vec<N>,
+    /// vertices reported by [`Sampler::add_vertex`] until the next sample
+    pub vertices: Vec<Vec<N>>,
+}
+
+/// Sampler which records the samples of `base` and the outcomes of the extensions,
+/// to replay them by [`ReplaySampler`].
+///
+/// With the `serde` feature, the records can be saved to reproduce a rare failure
+/// of the planner later.
+#[derive(Debug, Clone)]
+pub struct RecordingSampler<S, N> {
+    base: S,
+    records: Vec<SampleRecord<N>>,
+}
+
+impl<S, N> RecordingSampler<S, N>
+where
+    S: Sampler<N>,
+{
+    /// Creates a sampler with no records.
+    pub fn new(base: S) -> Self {
+        Self {
+            base,
+            records: Vec::new(),
+        }
+    }
+
+    /// records so far
+    pub fn records(&self) -> &[SampleRecord<N>] {
+        &self.records
+    }
+
+    /// Returns the records, consuming the sampler.
+    pub fn into_records(self) -> Vec<SampleRecord<N>> {
+        self.records
+    }
+}
+
+impl<S, N> Sampler<N> for RecordingSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Clone,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let sample = self.base.sample(rng);
+        self.records.push(SampleRecord {
+            sample: sample.clone(),
+            vertices: Vec::new(),
+        });
+        sample
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+        // vertices before the first sample, if any, are not recorded
+        if let Some(record) = self.records.last_mut() {
+            record.vertices.push(q.to_vec());
+        }
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+impl<S, N> Sampler<N> for &mut RecordingSampler<S, N>
+where
+    S: Sampler<N>,
+    N: Clone,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        (**self).add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        (**self).report_stall(num_stalled);
+    }
+}
+
+/// Sampler which returns the samples recorded by [`RecordingSampler`] in order.
+///
+/// The planning is reproduced if the planner and `is_free` take no other randomness,
+/// e.g. [`dual_rrt_connect`](crate::dual_rrt_connect) with the default options, or
+/// the `*_with_rng` planners with the same seed. The outcomes of the extensions are
+/// compared with the records, and [`diverged_at`](Self::diverged_at) reports the first
+/// sample whose outcome differs. Empty samples, which the planners skip, are returned
+/// after the records run out.
+#[derive(Debug, Clone)]
+pub struct ReplaySampler<N> {
+    records: Vec<SampleRecord<N>>,
+    /// number of the returned samples
+    index: usize,
+    /// number of the reported vertices since the last sample
+    num_vertices: usize,
+    diverged_at: Option<usize>,
+}
+
+impl<N> ReplaySampler<N>
+where
+    N: PartialEq,
+{
+    /// Creates a sampler which replays `records` from the beginning.
+    pub fn new(records: Vec<SampleRecord<N>>) -> Self {
+        Self {
+            records,
+            index: 0,
+            num_vertices: 0,
+            diverged_at: None,
+        }
+    }
+
+    /// Returns the index of the first sample whose outcome differs from the records,
+    /// or `None` if the replay matches so far.
+    pub fn diverged_at(&self) -> Option<usize> {
+        self.diverged_at
+    }
+
+    fn check_outcome(&mut self, matches: bool) {
+        if !matches && self.diverged_at.is_none() && self.index > 0 {
+            warn!(
+                "replay diverged from the records at sample {}",
+                self.index - 1
+            );
+            self.diverged_at = Some(self.index - 1);
+        }
+    }
+}
+
+impl<N> Sampler<N> for ReplaySampler<N>
+where
+    N: Clone + PartialEq,
+{
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<N> {
+        // fewer vertices than recorded after the previous sample
+        let expected = self
+            .index
+            .checked_sub(1)
+            .and_then(|i| self.records.get(i))
+            .map_or(0, |record| record.vertices.len());
+        self.check_outcome(self.num_vertices == expected);
+        self.num_vertices = 0;
+        let sample = self
+            .records
+            .get(self.index)
+            .map_or_else(Vec::new, |record| record.sample.clone());
+        self.index += 1;
+        sample
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        let matches = self
+            .index
+            .checked_sub(1)
+            .and_then(|i| self.records.get(i))
+            .and_then(|record| record.vertices.get(self.num_vertices))
+            .is_some_and(|v| v.as_slice() == q);
+        self.num_vertices += 1;
+        self.check_outcome(matches);
+    }
+}
+
+impl<N> Sampler<N> for &mut ReplaySampler<N>
+where
+    N: Clone + PartialEq,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        (**self).add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        (**self).report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut recorder =
+        RecordingSampler::new(UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap());
+    let path =
+        dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut recorder, 0.2, 1000).unwrap();
+    let records = recorder.into_records();
+    assert!(!records.is_empty());
+    assert!(records.iter().any(|record| !record.vertices.is_empty()));
+
+    let mut replay = ReplaySampler::new(records.clone());
+    let replayed =
+        dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut replay, 0.2, 1000).unwrap();
+    assert_eq!(replayed, path);
+    assert_eq!(replay.diverged_at(), None);
+
+    // a different environment changes the outcomes
+    let mut replay = ReplaySampler::new(records);
+    let is_free_larger = |p: &[f64]| !(p[0].abs() < 1.1 && p[1].abs() < 1.1);
+    let _ = dual_rrt_connect(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free_larger,
+        &mut replay,
+        0.2,
+        1000,
+    );
+    assert!(replay.diverged_at().is_some());
+}
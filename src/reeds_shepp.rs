@@ -0,0 +1,472 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::hybrid_astar::normalize_angle;
+use crate::{CarPath, SegmentType, Steering};
+use num_traits::float::Float;
+use num_traits::FloatConst;
+
+/// segments of a path whose lengths are in the units of the turning radius
+type Word<N> = Vec<(SegmentType, N)>;
+
+fn polar<N: Float>(x: N, y: N) -> (N, N) {
+    (x.hypot(y), y.atan2(x))
+}
+
+fn word<N: Float>(segments: &[SegmentType], lengths: &[N]) -> Option<Word<N>> {
+    Some(
+        segments
+            .iter()
+            .copied()
+            .zip(lengths.iter().copied())
+            .collect(),
+    )
+}
+
+// The formulas of the families below are from Reeds and Shepp, "Optimal paths for a car
+// that goes both forwards and backwards", for the goal at (x, y, phi) from the origin
+// with the turning radius of 1. Negative lengths are backward.
+
+/// C S C with the same turns
+fn lsl<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let (u, t) = polar(x - phi.sin(), y - N::one() + phi.cos());
+    if t < N::zero() || t > N::PI() {
+        return None;
+    }
+    let v = normalize_angle(phi - t);
+    if v < N::zero() {
+        return None;
+    }
+    word(&[Left, Straight, Left], &[t, u, v])
+}
+
+/// C S C with the opposite turns
+fn lsr<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, t1) = polar(x + phi.sin(), y - N::one() - phi.cos());
+    let u1 = u1 * u1;
+    if u1 < two * two {
+        return None;
+    }
+    let u = (u1 - two * two).sqrt();
+    let t = normalize_angle(t1 + two.atan2(u));
+    let v = normalize_angle(t - phi);
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(&[Left, Straight, Right], &[t, u, v])
+}
+
+/// position of the center of the last arc turning left
+fn left_center<N: Float>(x: N, y: N, phi: N) -> (N, N) {
+    polar(x - phi.sin(), y - N::one() + phi.cos())
+}
+
+/// position of the center of the last arc turning right
+fn right_center<N: Float>(x: N, y: N, phi: N) -> (N, N) {
+    polar(x + phi.sin(), y - N::one() - phi.cos())
+}
+
+/// C | C | C
+fn lrl_cusps<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let four = N::from(4.0).unwrap();
+    let (u1, theta) = left_center(x, y, phi);
+    if u1 > four {
+        return None;
+    }
+    let a = (u1 / four).acos();
+    let t = normalize_angle(a + theta + N::FRAC_PI_2());
+    let u = normalize_angle(N::PI() - (a + a));
+    let v = normalize_angle(phi - t - u);
+    word(&[Left, Right, Left], &[t, -u, v])
+}
+
+/// C | C C
+fn lrl_cusp_first<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let four = N::from(4.0).unwrap();
+    let (u1, theta) = left_center(x, y, phi);
+    if u1 > four {
+        return None;
+    }
+    let a = (u1 / four).acos();
+    let t = normalize_angle(a + theta + N::FRAC_PI_2());
+    let u = normalize_angle(N::PI() - (a + a));
+    let v = normalize_angle(-phi + t + u);
+    word(&[Left, Right, Left], &[t, -u, -v])
+}
+
+/// C C | C
+fn lrl_cusp_last<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let four = N::from(4.0).unwrap();
+    let (u1, theta) = left_center(x, y, phi);
+    if u1 > four || u1 <= N::zero() {
+        return None;
+    }
+    let u = (N::one() - u1 * u1 / N::from(8.0).unwrap()).acos();
+    let a = (N::from(2.0).unwrap() * u.sin() / u1).min(N::one()).asin();
+    let t = normalize_angle(-a + theta + N::FRAC_PI_2());
+    let v = normalize_angle(t - u - phi);
+    word(&[Left, Right, Left], &[t, u, -v])
+}
+
+/// C C | C C
+fn lrlr_cusp_middle<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, theta) = right_center(x, y, phi);
+    if u1 > two {
+        return None;
+    }
+    let a = ((u1 + two) / N::from(4.0).unwrap()).acos();
+    let t = normalize_angle(theta + a + N::FRAC_PI_2());
+    let u = normalize_angle(a);
+    let v = normalize_angle(phi - t + u + u);
+    if t < N::zero() || u < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(&[Left, Right, Left, Right], &[t, u, -u, -v])
+}
+
+/// C | C C | C
+fn lrlr_cusps<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let (u1, theta) = right_center(x, y, phi);
+    let u2 = (N::from(20.0).unwrap() - u1 * u1) / N::from(16.0).unwrap();
+    if u2 < N::zero() || u2 > N::one() || u1 <= N::zero() {
+        return None;
+    }
+    let u = u2.acos();
+    let a = (N::from(2.0).unwrap() * u.sin() / u1).min(N::one()).asin();
+    let t = normalize_angle(theta + a + N::FRAC_PI_2());
+    let v = normalize_angle(t - phi);
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(&[Left, Right, Left, Right], &[t, -u, -u, v])
+}
+
+/// C | C[pi/2] S C
+fn lrsl<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, theta) = left_center(x, y, phi);
+    if u1 < two {
+        return None;
+    }
+    let w = (u1 * u1 - two * two).sqrt();
+    let u = w - two;
+    let t = normalize_angle(theta + two.atan2(w) + N::FRAC_PI_2());
+    let v = normalize_angle(t - phi + N::FRAC_PI_2());
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(
+        &[Left, Right, Straight, Left],
+        &[t, -N::FRAC_PI_2(), -u, -v],
+    )
+}
+
+/// C | C[pi/2] S C with the opposite last turn
+fn lrsr<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, theta) = right_center(x, y, phi);
+    if u1 < two {
+        return None;
+    }
+    let t = normalize_angle(theta + N::FRAC_PI_2());
+    let u = u1 - two;
+    let v = normalize_angle(phi - t - N::FRAC_PI_2());
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(
+        &[Left, Right, Straight, Right],
+        &[t, -N::FRAC_PI_2(), -u, -v],
+    )
+}
+
+/// C S C[pi/2] | C
+fn lsrl<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, theta) = left_center(x, y, phi);
+    if u1 < two {
+        return None;
+    }
+    let w = (u1 * u1 - two * two).sqrt();
+    let u = w - two;
+    let t = normalize_angle(theta - w.atan2(two) + N::FRAC_PI_2());
+    let v = normalize_angle(t - phi - N::FRAC_PI_2());
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(&[Left, Straight, Right, Left], &[t, u, N::FRAC_PI_2(), -v])
+}
+
+/// C S C[pi/2] | C with the opposite last turn
+fn lslr<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let (u1, theta) = right_center(x, y, phi);
+    if u1 < two {
+        return None;
+    }
+    let t = normalize_angle(theta);
+    let u = u1 - two;
+    let v = normalize_angle(phi - t - N::FRAC_PI_2());
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(&[Left, Straight, Left, Right], &[t, u, N::FRAC_PI_2(), -v])
+}
+
+/// C | C[pi/2] S C[pi/2] | C
+fn lrslr<N: Float + FloatConst>(x: N, y: N, phi: N) -> Option<Word<N>> {
+    use SegmentType::*;
+    let two = N::from(2.0).unwrap();
+    let four = two + two;
+    let (u1, theta) = right_center(x, y, phi);
+    if u1 < four {
+        return None;
+    }
+    let w = (u1 * u1 - four).sqrt();
+    let u = w - four;
+    let t = normalize_angle(theta + two.atan2(w) + N::FRAC_PI_2());
+    let v = normalize_angle(t - phi);
+    if t < N::zero() || v < N::zero() {
+        return None;
+    }
+    word(
+        &[Left, Right, Straight, Left, Right],
+        &[t, -N::FRAC_PI_2(), -u, -N::FRAC_PI_2(), v],
+    )
+}
+
+/// Reeds-Shepp curves, the shortest paths of a car which drives both forward and
+/// backward with the minimum turning radius, as the [`Steering`] of parking maneuvers.
+///
+/// Poses are `[x, y, heading]`. The distance is the length of the shortest path, which
+/// is symmetric. [`CarPath::from_states`] reports the segments and the gear changes of
+/// the returned trajectories.
+#[derive(Debug, Clone)]
+pub struct ReedsShepp<N> {
+    /// minimum turning radius of the car
+    pub radius: N,
+    /// max distance between the states returned by [`Steering::steer`]
+    pub resolution: N,
+}
+
+impl<N> ReedsShepp<N>
+where
+    N: Float + FloatConst,
+{
+    /// Creates the curves with the resolution of `radius / 10`.
+    pub fn new(radius: N) -> Self {
+        assert!(radius > N::zero());
+        Self {
+            radius,
+            resolution: radius / N::from(10.0).unwrap(),
+        }
+    }
+
+    /// Returns the shortest path from `from` to `to` of the families CSC, CCC, CCCC,
+    /// CCSC, CSCC and CCSCC, with their reflections and backward versions.
+    ///
+    /// Returns `None` only if no path reaches `to` because of numerical errors.
+    pub fn shortest_path(&self, from: &[N], to: &[N]) -> Option<CarPath<N>> {
+        type Family<N> = fn(N, N, N) -> Option<Word<N>>;
+        let families: [Family<N>; 12] = [
+            lsl,
+            lsr,
+            lrl_cusps,
+            lrl_cusp_first,
+            lrl_cusp_last,
+            lrlr_cusp_middle,
+            lrlr_cusps,
+            lrsl,
+            lrsr,
+            lsrl,
+            lslr,
+            lrslr,
+        ];
+        // the goal in the frame of the start
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let (s, c) = from[2].sin_cos();
+        let x = (c * dx + s * dy) / self.radius;
+        let y = (-s * dx + c * dy) / self.radius;
+        let phi = to[2] - from[2];
+        let tolerance = N::from(1e-6).unwrap() * self.radius.max(N::one());
+        let mut best: Option<CarPath<N>> = None;
+        for family in families {
+            for (flip, reflect) in [(false, false), (true, false), (false, true), (true, true)] {
+                let (x, y, phi) = match (flip, reflect) {
+                    (false, false) => (x, y, phi),
+                    (true, false) => (-x, y, -phi),
+                    (false, true) => (x, -y, -phi),
+                    (true, true) => (-x, -y, phi),
+                };
+                let Some(word) = family(x, y, phi) else {
+                    continue;
+                };
+                let segments = word
+                    .into_iter()
+                    .map(|(mut segment, length)| {
+                        if reflect {
+                            segment = match segment {
+                                SegmentType::Left => SegmentType::Right,
+                                SegmentType::Straight => SegmentType::Straight,
+                                SegmentType::Right => SegmentType::Left,
+                            };
+                        }
+                        let length = if flip { -length } else { length };
+                        (segment, length * self.radius)
+                    })
+                    .filter(|(_, length)| *length != N::zero())
+                    .collect();
+                let path = CarPath::new(from, self.radius, segments);
+                if best
+                    .as_ref()
+                    .is_some_and(|best| best.length() <= path.length())
+                {
+                    continue;
+                }
+                // only the paths which reach the goal, against the numerical errors
+                let end = path.point_at(path.length());
+                if (end[0] - to[0]).hypot(end[1] - to[1]) < tolerance
+                    && normalize_angle(end[2] - to[2]).abs() < N::from(1e-6).unwrap()
+                {
+                    best = Some(path);
+                }
+            }
+        }
+        best
+    }
+}
+
+impl<N> Steering<N> for ReedsShepp<N>
+where
+    N: Float + FloatConst,
+{
+    fn distance(&self, from: &[N], to: &[N]) -> N {
+        self.shortest_path(from, to)
+            .map_or(N::infinity(), |path| path.length())
+    }
+
+    fn steer(&self, from: &[N], to: &[N]) -> Option<Vec<Vec<N>>> {
+        let mut states = self.shortest_path(from, to)?.states(self.resolution);
+        // remove the numerical errors at the end
+        *states.last_mut()? = to.to_vec();
+        Some(states)
+    }
+}
+
+#[test]
+fn reeds_shepp_works() {
+    use crate::{dual_rrt_connect_steering, DualRrtConnectOptions, Dubins, UniformBoxSampler};
+    use rand::Rng;
+    use std::f64::consts::PI;
+    let reeds_shepp = ReedsShepp::new(1.0);
+    let dubins = Dubins::new(1.0);
+    // straight backward
+    let path = reeds_shepp
+        .shortest_path(&[0.0, 0.0, 0.0], &[-2.0, 0.0, 0.0])
+        .unwrap();
+    assert!((path.length() - 2.0).abs() < 1e-9);
+    assert_eq!(path.segments(), &[(SegmentType::Straight, -2.0)]);
+    // sideways needs a cusp, which is also found from the trajectory
+    let path = reeds_shepp
+        .shortest_path(&[0.0, 0.0, 0.0], &[0.0, 0.5, 0.0])
+        .unwrap();
+    assert!(path.num_gear_changes() >= 1);
+    let states = std::iter::once(vec![0.0, 0.0, 0.0])
+        .chain(
+            reeds_shepp
+                .steer(&[0.0, 0.0, 0.0], &[0.0, 0.5, 0.0])
+                .unwrap(),
+        )
+        .collect::<Vec<_>>();
+    let from_states = CarPath::from_states(&states, 1.0);
+    assert_eq!(from_states.num_gear_changes(), path.num_gear_changes());
+    assert!((from_states.length() - path.length()).abs() < 0.05);
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let mut pose = || {
+            vec![
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-PI..PI),
+            ]
+        };
+        let (from, to) = (pose(), pose());
+        let path = reeds_shepp.shortest_path(&from, &to).unwrap();
+        let back = reeds_shepp.shortest_path(&to, &from).unwrap();
+        assert!((path.length() - back.length()).abs() < 1e-6);
+        assert!(path.length() <= dubins.distance(&from, &to) + 1e-9);
+        assert!(path.length() >= (to[0] - from[0]).hypot(to[1] - from[1]) - 1e-9);
+    }
+
+    // park into a dead end, which needs driving backward
+    let is_free = |p: &[f64]| {
+        (-1..=1).all(|i| {
+            let x = p[0] + 0.3 * f64::from(i) * p[2].cos();
+            let y = p[1] + 0.3 * f64::from(i) * p[2].sin();
+            x.abs() < 3.0 && y.abs() < 3.0 && !(y < 1.0 && x.abs() > 0.4)
+        })
+    };
+    let start = [0.0, 2.0, 0.0];
+    let goal = [0.0, 0.0, -PI / 2.0];
+    let reeds_shepp = ReedsShepp::new(0.5);
+    let path = dual_rrt_connect_steering(
+        &start,
+        &goal,
+        is_free,
+        &reeds_shepp,
+        UniformBoxSampler::new(&[-3.0, -3.0, -PI], &[3.0, 3.0, PI]).unwrap(),
+        &DualRrtConnectOptions::new(1.0, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        let (a, b) = (&segment[0], &segment[1]);
+        assert!((b[0] - a[0]).hypot(b[1] - a[1]) <= 0.05 + 1e-6);
+        assert!(normalize_angle(b[2] - a[2]).abs() <= 0.05 / 0.5 + 1e-6);
+    }
+    let car_path = CarPath::from_states(&path, 0.5);
+    assert_eq!(car_path.start(), start);
+    let driven = path
+        .windows(2)
+        .map(|s| (s[1][0] - s[0][0]).hypot(s[1][1] - s[0][1]))
+        .sum::<f64>();
+    // within the resolution at each boundary of the segments
+    let num_segments = car_path.segments().len() as f64;
+    assert!((car_path.length() - driven).abs() < 0.01 * driven + 0.05 * num_segments);
+    assert_eq!(
+        car_path.num_gear_changes(),
+        car_path
+            .segments()
+            .windows(2)
+            .filter(|s| (s[0].1 > 0.0) != (s[1].1 > 0.0))
+            .count()
+    );
+}
@@ -0,0 +1,278 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::check_bounds;
+use crate::{is_valid_point, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shape<N> {
+    Aabb { lower: Vec<N>, upper: Vec<N> },
+    Sphere { center: Vec<N>, radius: N },
+    Union(Vec<Region<N>>),
+    Intersection(Vec<Region<N>>),
+    Difference(Box<Region<N>>, Box<Region<N>>),
+}
+
+/// Region of the configuration space composed of axis-aligned boxes and spheres
+/// by union, intersection and difference.
+///
+/// [`RegionSampler`] draws uniform samples in it, e.g. for irregular joint limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region<N> {
+    shape: Shape<N>,
+    dim: usize,
+}
+
+impl<N> Region<N>
+where
+    N: Float + Debug,
+{
+    /// Returns the box between `lower` and `upper`, or an error if the bounds are
+    /// invalid as [`UniformBoxSampler::new`](crate::UniformBoxSampler::new).
+    pub fn aabb(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        Ok(Self {
+            shape: Shape::Aabb {
+                lower: lower.to_vec(),
+                upper: upper.to_vec(),
+            },
+            dim: lower.len(),
+        })
+    }
+
+    /// Returns the ball of `radius` around `center`, or an error if `center` is not
+    /// finite or `radius` is not positive.
+    pub fn sphere(center: &[N], radius: N) -> Result<Self, String> {
+        if !is_valid_point(center, center.len()) || !radius.is_finite() || radius <= N::zero() {
+            return Err(format!("invalid sphere {center:?}, {radius:?}"));
+        }
+        Ok(Self {
+            shape: Shape::Sphere {
+                center: center.to_vec(),
+                radius,
+            },
+            dim: center.len(),
+        })
+    }
+
+    /// Returns the union of `self` and `other`, or an error if their dimensions differ.
+    pub fn union(self, other: Self) -> Result<Self, String> {
+        self.combine(other, Shape::Union)
+    }
+
+    /// Returns the intersection of `self` and `other`, or an error if their dimensions
+    /// differ.
+    pub fn intersection(self, other: Self) -> Result<Self, String> {
+        self.combine(other, Shape::Intersection)
+    }
+
+    /// Returns `self` without `other`, or an error if their dimensions differ.
+    pub fn difference(self, other: Self) -> Result<Self, String> {
+        self.combine(other, |regions| {
+            let mut regions = regions.into_iter();
+            Shape::Difference(
+                Box::new(regions.next().unwrap()),
+                Box::new(regions.next().unwrap()),
+            )
+        })
+    }
+
+    fn combine<F>(self, other: Self, f: F) -> Result<Self, String>
+    where
+        F: FnOnce(Vec<Self>) -> Shape<N>,
+    {
+        if self.dim != other.dim {
+            return Err(format!(
+                "regions have different dimensions: {} and {}",
+                self.dim, other.dim
+            ));
+        }
+        let dim = self.dim;
+        Ok(Self {
+            shape: f(vec![self, other]),
+            dim,
+        })
+    }
+
+    /// dimension of the region
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Returns `true` if `q` is in the region.
+    pub fn contains(&self, q: &[N]) -> bool {
+        if q.len() != self.dim {
+            return false;
+        }
+        match &self.shape {
+            Shape::Aabb { lower, upper } => q
+                .iter()
+                .zip(lower.iter().zip(upper))
+                .all(|(v, (l, u))| l <= v && v <= u),
+            Shape::Sphere { center, radius } => squared_euclidean(q, center) <= *radius * *radius,
+            Shape::Union(regions) => regions.iter().any(|region| region.contains(q)),
+            Shape::Intersection(regions) => regions.iter().all(|region| region.contains(q)),
+            Shape::Difference(a, b) => a.contains(q) && !b.contains(q),
+        }
+    }
+
+    /// Returns the lower and upper bounds of the box which contains the region.
+    /// Some lower bounds are larger than the upper ones if the region is empty.
+    pub fn bounds(&self) -> (Vec<N>, Vec<N>) {
+        match &self.shape {
+            Shape::Aabb { lower, upper } => (lower.clone(), upper.clone()),
+            Shape::Sphere { center, radius } => (
+                center.iter().map(|c| *c - *radius).collect(),
+                center.iter().map(|c| *c + *radius).collect(),
+            ),
+            Shape::Union(regions) => regions
+                .iter()
+                .map(|region| region.bounds())
+                .reduce(|(l0, u0), (l1, u1)| {
+                    (
+                        l0.iter().zip(&l1).map(|(a, b)| a.min(*b)).collect(),
+                        u0.iter().zip(&u1).map(|(a, b)| a.max(*b)).collect(),
+                    )
+                })
+                .unwrap(),
+            Shape::Intersection(regions) => regions
+                .iter()
+                .map(|region| region.bounds())
+                .reduce(|(l0, u0), (l1, u1)| {
+                    (
+                        l0.iter().zip(&l1).map(|(a, b)| a.max(*b)).collect(),
+                        u0.iter().zip(&u1).map(|(a, b)| a.min(*b)).collect(),
+                    )
+                })
+                .unwrap(),
+            Shape::Difference(a, _) => a.bounds(),
+        }
+    }
+}
+
+/// Sampler of the uniform distribution in a [`Region`].
+///
+/// It draws uniform samples in the bounds of the region until one is in the region,
+/// up to `num_max_try` times, and returns the last one otherwise. The rate of the
+/// accepted samples is the ratio of the volume of the region to its bounds.
+#[derive(Debug, Clone)]
+pub struct RegionSampler<N> {
+    region: Region<N>,
+    lower: Vec<N>,
+    upper: Vec<N>,
+    num_max_try: usize,
+}
+
+impl<N> RegionSampler<N>
+where
+    N: Float + Debug,
+{
+    /// Returns an error if the bounds of the region are empty.
+    pub fn new(region: Region<N>, num_max_try: usize) -> Result<Self, String> {
+        assert!(num_max_try > 0);
+        let (lower, upper) = region.bounds();
+        if lower.iter().zip(&upper).any(|(l, u)| l > u) {
+            return Err(format!("region is empty: {region:?}"));
+        }
+        Ok(Self {
+            region,
+            lower,
+            upper,
+            num_max_try,
+        })
+    }
+
+    /// region of the samples
+    pub fn region(&self) -> &Region<N> {
+        &self.region
+    }
+}
+
+impl<N> Sampler<N> for RegionSampler<N>
+where
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let mut q = Vec::new();
+        for _ in 0..self.num_max_try {
+            q = self
+                .lower
+                .iter()
+                .zip(&self.upper)
+                .map(|(l, u)| *l + (*u - *l) * N::from(rng.gen::<f64>()).unwrap())
+                .collect();
+            if self.region.contains(&q) {
+                break;
+            }
+        }
+        q
+    }
+}
+
+impl<N> Sampler<N> for &mut RegionSampler<N>
+where
+    N: Float + Debug,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+}
+
+#[test]
+fn region_sampler_works() {
+    // a box with a hole, and a half ball on it
+    let region = Region::aabb(&[-2.0, -2.0], &[2.0, 0.0])
+        .unwrap()
+        .difference(Region::sphere(&[0.0, -1.0], 0.5).unwrap())
+        .unwrap()
+        .union(
+            Region::sphere(&[0.0, 0.0], 1.0)
+                .unwrap()
+                .intersection(Region::aabb(&[-1.0, 0.0], &[1.0, 1.0]).unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(region.bounds(), (vec![-2.0, -2.0], vec![2.0, 1.0]));
+    assert!(region.contains(&[1.5, -1.5]));
+    assert!(!region.contains(&[0.0, -1.0]));
+    assert!(region.contains(&[0.0, 0.9]));
+    assert!(!region.contains(&[0.9, 0.9]));
+    assert!(!region.contains(&[0.0]));
+    let mut sampler = RegionSampler::new(region.clone(), 100).unwrap();
+    let mut rng = rand::thread_rng();
+    let samples = (0..1000)
+        .map(|_| sampler.sample(&mut rng))
+        .collect::<Vec<_>>();
+    assert!(samples.iter().all(|q| region.contains(q)));
+    // uniform: the half ball has about pi / 2 / (8 - pi / 4 + pi / 2) of the samples
+    let num_ball = samples.iter().filter(|q| q[1] > 0.0).count();
+    assert!((120..250).contains(&num_ball), "{num_ball}");
+
+    let disjoint = Region::aabb(&[0.0], &[1.0])
+        .unwrap()
+        .intersection(Region::aabb(&[2.0], &[3.0]).unwrap())
+        .unwrap();
+    assert!(RegionSampler::new(disjoint, 10).is_err());
+    assert!(Region::aabb(&[0.0], &[1.0])
+        .unwrap()
+        .union(Region::sphere(&[0.0, 0.0], 1.0).unwrap())
+        .is_err());
+    assert!(Region::sphere(&[0.0], 0.0).is_err());
+}
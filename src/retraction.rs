@@ -0,0 +1,189 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{dual_rrt_connect_with_rng_fn, is_valid_point, DualRrtConnectOptions, Sampler};
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`retraction_rrt_connect`].
+#[derive(Debug, Clone)]
+pub struct RetractionOptions<N> {
+    /// parameters of the underlying dual RRT connect
+    pub rrt: DualRrtConnectOptions<N>,
+    /// max number of steps to retract a sample out of the obstacles
+    pub num_max_retraction: usize,
+    /// clearance which a retracted sample gets beyond the boundary of the obstacles
+    pub margin: N,
+}
+
+impl<N> RetractionOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 10 retraction steps and the margin of 1/10 of `extend_length`.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            rrt: DualRrtConnectOptions::new(extend_length, num_max_try),
+            num_max_retraction: 10,
+            margin: extend_length / N::from(10.0).unwrap(),
+        }
+    }
+}
+
+/// Moves `q` out of the obstacles along the gradient of `clearance`,
+/// or returns `None` if it is not free after `num_max_retraction` steps.
+fn retract<FC, N>(q: &[N], clearance: &FC, options: &RetractionOptions<N>) -> Option<Vec<N>>
+where
+    FC: Fn(&[N]) -> N,
+    N: Float + Debug,
+{
+    let h = options.rrt.extend_length / N::from(10.0).unwrap();
+    let mut q = q.to_vec();
+    for _ in 0..options.num_max_retraction {
+        let d = clearance(&q);
+        if d > N::zero() {
+            return Some(q);
+        }
+        // gradient of the clearance by central difference
+        let mut q_h = q.clone();
+        let gradient = (0..q.len())
+            .map(|i| {
+                q_h[i] = q[i] + h;
+                let d_plus = clearance(&q_h);
+                q_h[i] = q[i] - h;
+                let d_minus = clearance(&q_h);
+                q_h[i] = q[i];
+                (d_plus - d_minus) / (h + h)
+            })
+            .collect::<Vec<_>>();
+        let norm = gradient
+            .iter()
+            .fold(N::zero(), |sum, g| sum + *g * *g)
+            .sqrt();
+        if !norm.is_normal() {
+            return None;
+        }
+        // the penetration depth is -d, so move by it with the margin
+        let step = (options.margin - d) / norm;
+        for (q, g) in q.iter_mut().zip(&gradient) {
+            *q = *q + *g * step / norm;
+        }
+    }
+    (clearance(&q) > N::zero()).then_some(q)
+}
+
+/// search the path from start to goal which is free, using random_sample function
+/// (retraction-based RRT connect).
+///
+/// `clearance` is the signed distance from a configuration to the nearest obstacle,
+/// zero or negative in collision (the penetration depth). Samples in collision are
+/// retracted toward the boundary of the free space instead of being wasted,
+/// so the trees grow along the obstacles and into narrow passages.
+pub fn retraction_rrt_connect<FC, FR, N>(
+    start: &[N],
+    goal: &[N],
+    clearance: FC,
+    random_sample: FR,
+    options: &RetractionOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FC: Fn(&[N]) -> N,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    retraction_rrt_connect_with_rng(
+        start,
+        goal,
+        clearance,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`retraction_rrt_connect`] which takes all randomness from `rng`.
+pub fn retraction_rrt_connect_with_rng<FC, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    clearance: FC,
+    mut random_sample: FR,
+    options: &RetractionOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FC: Fn(&[N]) -> N,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    assert!(options.rrt.extend_length > N::zero());
+    assert!(options.margin > N::zero());
+    let dim = start.len();
+    dual_rrt_connect_with_rng_fn(
+        start,
+        goal,
+        |q: &[N]| clearance(q) > N::zero(),
+        |rng: &mut R| {
+            let q = random_sample.sample(rng);
+            if !is_valid_point(&q, dim) {
+                return q;
+            }
+            match retract(&q, &clearance, options) {
+                Some(retracted) => retracted,
+                None => {
+                    debug!("failed to retract {q:?}");
+                    q
+                }
+            }
+        },
+        &options.rrt,
+        rng,
+        None,
+    )
+}
+
+#[test]
+fn retraction_rrt_connect_works() {
+    let world = crate::scenario::World::square();
+    // signed distance to the box [-1, -1] - [1, 1]
+    let clearance = |p: &[f64]| {
+        let dx = p[0].abs() - 1.0;
+        let dy = p[1].abs() - 1.0;
+        if dx > 0.0 || dy > 0.0 {
+            (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt()
+        } else {
+            dx.max(dy)
+        }
+    };
+    let options = RetractionOptions::new(0.2, 1000);
+    // samples are retracted out of the box
+    let retracted = retract(&[0.5, 0.1], &clearance, &options).unwrap();
+    assert!(clearance(&retracted) > 0.0);
+    assert!((retracted[0] - 1.02).abs() < 1e-6);
+    let path = retraction_rrt_connect(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        clearance,
+        world.sampler(1),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path.iter().all(|q| clearance(q) > 0.0));
+}
@@ -0,0 +1,188 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{is_edge_free, is_valid_point, PrmOptions, Roadmap, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`rrg`].
+#[derive(Debug, Clone)]
+pub struct RrgOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// number of iterations
+    pub num_max_try: usize,
+    /// a new vertex is connected to all vertices within this radius by free edges
+    pub neighbor_radius: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+}
+
+impl<N> RrgOptions<N>
+where
+    N: Float,
+{
+    /// Creates options whose neighbor radius is twice the extend length.
+    pub fn new(extend_length: N, num_max_try: usize, resolution: N) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            neighbor_radius: extend_length + extend_length,
+            resolution,
+        }
+    }
+}
+
+/// explore the free space from start, using random_sample function
+/// (Rapidly-exploring Random Graph, RRG).
+///
+/// The graph is extended like RRT, but each new vertex is connected to all vertices
+/// within `neighbor_radius` by free edges instead of a single parent, so it has cycles
+/// around obstacles. It is returned as a [`Roadmap`], which answers later queries of
+/// the shortest paths with [`Roadmap::query`] and of the alternative ones with
+/// [`Roadmap::k_shortest_paths`]. Query endpoints are connected to the vertices within
+/// `neighbor_radius`.
+pub fn rrg<FF, FR, N>(
+    start: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &RrgOptions<N>,
+) -> Result<Roadmap<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    rrg_with_rng(
+        start,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`rrg`] which takes all randomness from `rng`.
+pub fn rrg_with_rng<FF, FR, N, R>(
+    start: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &RrgOptions<N>,
+    rng: &mut R,
+) -> Result<Roadmap<N>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    let dim = start.len();
+    if !is_valid_point(start, dim) {
+        return Err(format!("start is not finite: {start:?}"));
+    }
+    if !is_free(start) {
+        return Err(format!("start is not free: {start:?}"));
+    }
+    assert!(options.extend_length > N::zero());
+    assert!(options.neighbor_radius >= options.extend_length);
+    let mut roadmap = Roadmap::new(
+        dim,
+        PrmOptions {
+            num_neighbors: usize::MAX,
+            ..PrmOptions::new(options.neighbor_radius, options.resolution)
+        },
+    );
+    roadmap.add_isolated_vertex(start);
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, dim) {
+            continue;
+        }
+        let (nearest_index, dist) = roadmap.nearest(&q_rand);
+        let nearest_q = roadmap.vertex(nearest_index);
+        let q_new = if dist < options.extend_length {
+            q_rand
+        } else {
+            nearest_q
+                .iter()
+                .zip(&q_rand)
+                .map(|(near, target)| *near + (*target - *near) * options.extend_length / dist)
+                .collect()
+        };
+        if !is_free(&q_new)
+            || !is_edge_free(nearest_q, &q_new, &mut is_free, options.resolution)
+            || squared_euclidean(nearest_q, &q_new) == N::zero()
+        {
+            continue;
+        }
+        let near = roadmap.within(&q_new, options.neighbor_radius);
+        let new_index = roadmap.add_isolated_vertex(&q_new);
+        for (i, _) in near {
+            if i == nearest_index
+                || is_edge_free(roadmap.vertex(i), &q_new, &mut is_free, options.resolution)
+            {
+                roadmap.add_edge(i, new_index);
+            }
+        }
+    }
+    debug!("{} vertices", roadmap.len());
+    Ok(roadmap)
+}
+
+#[test]
+fn rrg_works() {
+    use crate::path_length;
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut roadmap = rrg(
+        &[-1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &RrgOptions::new(0.2, 1000, 0.01),
+    )
+    .unwrap();
+    let num_edges = (0..roadmap.len())
+        .map(|i| roadmap.neighbors(i).len())
+        .sum::<usize>()
+        / 2;
+    // a graph with cycles, not a tree
+    assert!(num_edges > roadmap.len());
+    let paths = roadmap
+        .k_shortest_paths(&[-1.2, 0.0], &[1.2, 0.0], 3, is_free)
+        .unwrap();
+    assert_eq!(paths.len(), 3);
+    for path in &paths {
+        assert_eq!(path[0], vec![-1.2, 0.0]);
+        assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+        for segment in path.windows(2) {
+            assert!(is_edge_free(
+                &segment[0],
+                &segment[1],
+                &mut { is_free },
+                0.01
+            ));
+        }
+    }
+    assert_ne!(paths[0], paths[1]);
+    assert!(paths
+        .windows(2)
+        .all(|p| path_length(&p[0]) <= path_length(&p[1])));
+    let shortest = roadmap.query(&[-1.2, 0.0], &[1.2, 0.0], is_free).unwrap();
+    assert!((path_length(&shortest) - path_length(&paths[0])).abs() < 1e-9);
+}
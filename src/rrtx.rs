@@ -0,0 +1,280 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::birrtstar::{add_and_rewire, best_parent};
+use crate::{is_edge_free, is_valid_point, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`RrtX`].
+#[derive(Debug, Clone)]
+pub struct RrtXOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num of each [`RrtX::plan`]
+    pub num_max_try: usize,
+    /// radius to choose the parent of a new vertex, to rewire, to connect the start,
+    /// and to repair the tree
+    pub neighbor_radius: N,
+    /// interval to check edges with `is_free`
+    pub resolution: N,
+    /// probability (0.0 - 1.0) to extend toward the start instead of a random sample
+    pub start_bias: f64,
+}
+
+impl<N> RrtXOptions<N>
+where
+    N: Float,
+{
+    /// Creates options whose neighbor radius is twice the extend length, with 0.05 start bias.
+    pub fn new(extend_length: N, num_max_try: usize, resolution: N) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            neighbor_radius: extend_length + extend_length,
+            resolution,
+            start_bias: 0.05,
+        }
+    }
+}
+
+/// Incremental replanning planner like RRT-X, for environments which change
+/// while the robot moves.
+///
+/// The RRT* tree is rooted at the goal and kept between queries, so a query from
+/// a new start only needs to connect the start to the tree. When obstacles change,
+/// [`report_change`](Self::report_change) repairs only the branches invalidated
+/// in the changed region.
+#[derive(Debug)]
+pub struct RrtX<N>
+where
+    N: Float + Debug,
+{
+    tree: Tree<N>,
+    options: RrtXOptions<N>,
+}
+
+impl<N> RrtX<N>
+where
+    N: Float + Debug,
+{
+    /// Creates a planner whose tree only has the goal.
+    pub fn new(goal: &[N], options: RrtXOptions<N>) -> Result<Self, String> {
+        if !is_valid_point(goal, goal.len()) {
+            return Err(format!("goal is not finite: {goal:?}"));
+        }
+        assert!(options.extend_length > N::zero());
+        assert!((0.0..=1.0).contains(&options.start_bias));
+        let mut tree = Tree::with_name("goal", goal.len());
+        tree.add_vertex(goal);
+        Ok(Self { tree, options })
+    }
+
+    /// Returns the tree rooted at the goal.
+    pub fn tree(&self) -> &Tree<N> {
+        &self.tree
+    }
+
+    /// search the path from start to the goal which is free, using random_sample function.
+    ///
+    /// The tree keeps growing from where the previous call stopped, until the start
+    /// can be connected to it.
+    pub fn plan<FF, FR>(
+        &mut self,
+        start: &[N],
+        is_free: FF,
+        random_sample: FR,
+    ) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+    {
+        self.plan_with_rng(start, is_free, random_sample, &mut rand::thread_rng())
+    }
+
+    /// [`Self::plan`] which takes all randomness from `rng`.
+    pub fn plan_with_rng<FF, FR, R>(
+        &mut self,
+        start: &[N],
+        mut is_free: FF,
+        mut random_sample: FR,
+        rng: &mut R,
+    ) -> Result<Vec<Vec<N>>, String>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+        R: Rng,
+    {
+        if !is_valid_point(start, self.tree.dim()) {
+            return Err(format!("start is not valid: {start:?}"));
+        }
+        if !is_free(start) {
+            return Err(format!("start is not free: {start:?}"));
+        }
+        for _ in 0..=self.options.num_max_try {
+            if let Some(path) = self.connect_start(start, &mut is_free) {
+                return Ok(path);
+            }
+            let q_target = if rng.gen_bool(self.options.start_bias) {
+                start.to_vec()
+            } else {
+                random_sample.sample(rng)
+            };
+            if !is_valid_point(&q_target, self.tree.dim()) {
+                continue;
+            }
+            self.extend(&q_target, &mut is_free);
+        }
+        Err("failed".to_string())
+    }
+
+    fn extend<FF>(&mut self, q_target: &[N], is_free: &mut FF)
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let extend_length = self.options.extend_length;
+        let nearest_index = self.tree.get_nearest_index(q_target);
+        let nearest_q = self.tree.vertex(nearest_index);
+        let dist = squared_euclidean(nearest_q, q_target).sqrt();
+        let q_new = if dist < extend_length {
+            q_target.to_vec()
+        } else {
+            nearest_q
+                .iter()
+                .zip(q_target)
+                .map(|(near, target)| *near + (*target - *near) * extend_length / dist)
+                .collect::<Vec<_>>()
+        };
+        if !is_free(&q_new) {
+            return;
+        }
+        let mut near = self
+            .tree
+            .within(&q_new, self.options.neighbor_radius)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if !near.contains(&nearest_index) {
+            near.push(nearest_index);
+        }
+        add_and_rewire(
+            &mut self.tree,
+            &q_new,
+            &near,
+            is_free,
+            self.options.resolution,
+            0,
+        );
+    }
+
+    /// Returns the path from start through the best vertex near it, if any.
+    fn connect_start<FF>(&self, start: &[N], is_free: &mut FF) -> Option<Vec<Vec<N>>>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let near = self
+            .tree
+            .within(start, self.options.neighbor_radius)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let (index, _) = best_parent(&self.tree, &near, start, is_free, self.options.resolution)?;
+        let mut path = vec![start.to_vec(), self.tree.vertex(index).to_vec()];
+        path.extend(self.tree.get_until_root(index));
+        if path[0] == path[1] {
+            path.remove(0);
+        }
+        Some(path)
+    }
+
+    /// Repairs the tree after obstacles changed in the region where `is_changed`
+    /// returns `true`, with the new `is_free`.
+    ///
+    /// Vertices which became not free are invalidated, and vertices whose
+    /// edges to their parents became not free are detached with their descendants.
+    /// Then the detached subtrees are reconnected within `neighbor_radius` if possible.
+    /// Returns the number of vertices detached.
+    pub fn report_change<FC, FF>(&mut self, mut is_changed: FC, mut is_free: FF) -> usize
+    where
+        FC: FnMut(&[N]) -> bool,
+        FF: FnMut(&[N]) -> bool,
+    {
+        let mut invalid = vec![false; self.tree.len()];
+        let mut cut = vec![false; self.tree.len()];
+        for index in 0..self.tree.len() {
+            let Some(parent) = self.tree.parent(index) else {
+                continue;
+            };
+            if !self.tree.is_attached(index) {
+                continue;
+            }
+            let q = self.tree.vertex(index);
+            if is_changed(q) && !is_free(q) {
+                invalid[index] = true;
+            } else if !is_edge_free(
+                q,
+                self.tree.vertex(parent),
+                &mut |p: &[N]| !is_changed(p) || is_free(p),
+                self.options.resolution,
+            ) {
+                cut[index] = true;
+            }
+        }
+        let num_detached = self.tree.detach(&invalid, &cut);
+        let num_attached = self.tree.reconnect_detached(
+            &mut is_free,
+            self.options.neighbor_radius,
+            self.options.resolution,
+        );
+        debug!("detached {num_detached}, reattached {num_attached}");
+        num_detached
+    }
+}
+
+#[test]
+fn rrtx_works() {
+    use std::cell::Cell;
+    let world = crate::scenario::World::square();
+    let wall = Cell::new(false);
+    let is_free = |p: &[f64]| {
+        let in_wall = wall.get() && (p[0] + 1.5).abs() < 0.1 && p[1] > -0.5;
+        !(p[0].abs() < 1.0 && p[1].abs() < 1.0 || in_wall)
+    };
+    let random_sample = world.sampler(1);
+    let mut planner = RrtX::new(&[1.2, 0.0], RrtXOptions::new(0.2, 10000, 0.01)).unwrap();
+    let path = planner.plan(&[-1.8, 0.0], is_free, &random_sample).unwrap();
+    assert_eq!(path[0], vec![-1.8, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    // a wall appears between the start and the box
+    wall.set(true);
+    planner.report_change(|p| (p[0] + 1.5).abs() < 0.1 && p[1] > -0.5, is_free);
+    let num_vertices = planner.tree().len();
+    let path = planner.plan(&[-1.8, 0.0], is_free, &random_sample).unwrap();
+    assert!(planner.tree().len() >= num_vertices);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    for segment in path.windows(2) {
+        assert!(is_free(&segment[0]));
+        assert!(is_edge_free(
+            &segment[0],
+            &segment[1],
+            &mut { is_free },
+            0.01
+        ));
+    }
+}
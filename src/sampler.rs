@@ -0,0 +1,607 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use num_traits::float::Float;
+use rand::distributions::uniform::SampleUniform;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::fmt::Debug;
+
+/// Source of the random configurations which the planners extend toward.
+///
+/// Closures `FnMut() -> Vec<N>` are samplers which ignore `rng`, so all planners still
+/// accept them. Implement this for stateful samplers, e.g. quasi-random sequences or
+/// samplers which adapt to the planning, and use `rng` for the randomness to make them
+/// reproducible with a seeded rng.
+pub trait Sampler<N> {
+    /// Returns a random configuration.
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N>;
+
+    /// Called with each vertex added to the trees by the planners, so that samplers can
+    /// adapt to the planning, e.g. [`DensityAdaptiveSampler`](crate::DensityAdaptiveSampler).
+    ///
+    /// [`rrt`](crate::rrt), [`dual_rrt_connect`](crate::dual_rrt_connect) and the planners
+    /// which grow its trees, e.g. [`DualRrtConnectPlanner`](crate::DualRrtConnectPlanner), call this.
+    /// Does nothing by default.
+    fn add_vertex(&mut self, _q: &[N]) {}
+
+    /// Called before each sample with the number of the samples since the trees last
+    /// grew, so that samplers can widen their distributions while the planning stalls
+    /// and narrow them while it progresses, e.g. [`AnnealingSampler`](crate::AnnealingSampler).
+    ///
+    /// The planners which call [`add_vertex`](Self::add_vertex) call this.
+    /// Does nothing by default.
+    fn report_stall(&mut self, _num_stalled: usize) {}
+
+    /// Returns `n` random configurations in one buffer, for the planners which use the
+    /// samples in batches, e.g. to check the collisions in parallel.
+    ///
+    /// By default, this calls [`sample`](Self::sample) `n` times. The dimension of the
+    /// batch is that of the first sample, and samples of other dimensions are dropped.
+    /// Override this to amortize the costs of the rng and the allocations.
+    fn sample_batch(&mut self, n: usize, rng: &mut dyn RngCore) -> SampleBatch<N> {
+        let mut batch = SampleBatch::new(0);
+        for i in 0..n {
+            let q = self.sample(rng);
+            if i == 0 {
+                batch = SampleBatch::with_capacity(q.len(), n);
+            }
+            batch.push(q);
+        }
+        batch
+    }
+}
+
+/// Samples stored in one row-major buffer, returned by [`Sampler::sample_batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleBatch<N> {
+    dim: usize,
+    data: Vec<N>,
+}
+
+impl<N> SampleBatch<N> {
+    /// Creates an empty batch of samples of `dim` dimensions.
+    pub fn new(dim: usize) -> Self {
+        Self::with_capacity(dim, 0)
+    }
+
+    /// Creates an empty batch with the capacity for `n` samples.
+    pub fn with_capacity(dim: usize, n: usize) -> Self {
+        Self {
+            dim,
+            data: Vec::with_capacity(dim * n),
+        }
+    }
+
+    /// Adds a sample, and returns `false` without adding it if its dimension is wrong.
+    pub fn push<I>(&mut self, q: I) -> bool
+    where
+        I: IntoIterator<Item = N>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let q = q.into_iter();
+        if q.len() != self.dim {
+            return false;
+        }
+        self.data.extend(q);
+        true
+    }
+
+    /// dimension of the samples
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// number of the samples
+    pub fn len(&self) -> usize {
+        self.data.len().checked_div(self.dim).unwrap_or(0)
+    }
+
+    /// Returns `true` if there is no sample.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `i`-th sample.
+    pub fn row(&self, i: usize) -> &[N] {
+        &self.data[i * self.dim..(i + 1) * self.dim]
+    }
+
+    /// Returns an iterator over the samples.
+    pub fn rows(&self) -> impl Iterator<Item = &[N]> {
+        // chunks_exact panics with 0
+        self.data.chunks_exact(self.dim.max(1))
+    }
+
+    /// the buffer of all samples, row by row
+    pub fn as_slice(&self) -> &[N] {
+        &self.data
+    }
+}
+
+/// Sampler which counts the samples since the last vertex, and reports it to `sampler`
+/// by [`Sampler::report_stall`].
+#[derive(Debug)]
+pub(crate) struct StallTracker<S> {
+    sampler: S,
+    num_stalled: usize,
+    is_grown: bool,
+}
+
+impl<S> StallTracker<S> {
+    pub(crate) fn new(sampler: S) -> Self {
+        Self {
+            sampler,
+            num_stalled: 0,
+            is_grown: true,
+        }
+    }
+}
+
+impl<S, N> Sampler<N> for StallTracker<S>
+where
+    S: Sampler<N>,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        if self.is_grown {
+            self.num_stalled = 0;
+        } else {
+            self.num_stalled += 1;
+        }
+        self.is_grown = false;
+        self.sampler.report_stall(self.num_stalled);
+        self.sampler.sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.is_grown = true;
+        self.sampler.add_vertex(q);
+    }
+}
+
+impl<N, F> Sampler<N> for F
+where
+    F: FnMut() -> Vec<N>,
+{
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> Vec<N> {
+        self()
+    }
+}
+
+/// Sampler which calls a closure with the rng, made by [`from_rng_fn`].
+#[derive(Debug, Clone)]
+pub struct RngFn<F>(F);
+
+impl<N, F> Sampler<N> for RngFn<F>
+where
+    F: FnMut(&mut dyn RngCore) -> Vec<N>,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (self.0)(rng)
+    }
+}
+
+/// Returns a sampler which calls `f` with the rng given by the planner.
+pub fn from_rng_fn<F, N>(f: F) -> RngFn<F>
+where
+    F: FnMut(&mut dyn RngCore) -> Vec<N>,
+{
+    RngFn(f)
+}
+
+/// Returns an error if the dimensions of the bounds differ, or `lower` is not less
+/// than `upper` in some axis.
+pub(crate) fn check_bounds<N: Float + Debug>(lower: &[N], upper: &[N]) -> Result<(), String> {
+    if lower.len() != upper.len() {
+        return Err(format!(
+            "lower and upper have different dimensions: {} and {}",
+            lower.len(),
+            upper.len()
+        ));
+    }
+    if lower
+        .iter()
+        .zip(upper)
+        .any(|(l, u)| !l.is_finite() || !u.is_finite() || l >= u)
+    {
+        return Err(format!("invalid bounds {lower:?} - {upper:?}"));
+    }
+    Ok(())
+}
+
+/// Distribution of one axis of [`UniformBoxSampler`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AxisDistribution<N> {
+    /// uniform between the bounds of the axis
+    Uniform,
+    /// normal distribution, clamped into the bounds of the axis
+    Gaussian {
+        /// mean of the distribution
+        mean: N,
+        /// standard deviation of the distribution
+        std_dev: N,
+    },
+    /// one of the values, chosen uniformly, e.g. open and closed states of a gripper
+    Discrete(Vec<N>),
+}
+
+/// Sampler of the uniform distribution in the box between `lower` and `upper`.
+///
+/// It uses the rng given by the planner, or its own rng if it is made by
+/// [`with_seed`](Self::with_seed). `&mut UniformBoxSampler` is also a sampler,
+/// so one sampler can be used for several plans. The distribution of each axis can
+/// be changed by [`with_distribution`](Self::with_distribution), e.g. for joint spaces
+/// which mix revolute joints, prismatic joints and grippers.
+#[derive(Debug, Clone)]
+pub struct UniformBoxSampler<N> {
+    lower: Vec<N>,
+    upper: Vec<N>,
+    distributions: Vec<AxisDistribution<N>>,
+    rng: Option<StdRng>,
+}
+
+impl<N> UniformBoxSampler<N>
+where
+    N: Float + Debug + SampleUniform,
+{
+    /// Returns an error if the dimensions of the bounds differ, or `lower` is not less
+    /// than `upper` in some axis.
+    pub fn new(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        Ok(Self {
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+            distributions: vec![AxisDistribution::Uniform; lower.len()],
+            rng: None,
+        })
+    }
+
+    /// Sets the distribution of `axis`.
+    ///
+    /// Returns an error if `axis` is out of the dimension, the standard deviation is
+    /// not positive, or the discrete values are empty or out of the bounds.
+    pub fn with_distribution(
+        mut self,
+        axis: usize,
+        distribution: AxisDistribution<N>,
+    ) -> Result<Self, String> {
+        if axis >= self.lower.len() {
+            return Err(format!(
+                "axis {axis} is out of the dimension {}",
+                self.lower.len()
+            ));
+        }
+        let (l, u) = (self.lower[axis], self.upper[axis]);
+        match &distribution {
+            AxisDistribution::Uniform => {}
+            AxisDistribution::Gaussian { mean, std_dev } => {
+                if !mean.is_finite() || !std_dev.is_finite() || *std_dev <= N::zero() {
+                    return Err(format!("invalid gaussian of axis {axis}: {distribution:?}"));
+                }
+            }
+            AxisDistribution::Discrete(values) => {
+                if values.is_empty() || values.iter().any(|v| !(l..=u).contains(v)) {
+                    return Err(format!(
+                        "discrete values of axis {axis} must be in {l:?} - {u:?}: {values:?}"
+                    ));
+                }
+            }
+        }
+        self.distributions[axis] = distribution;
+        Ok(self)
+    }
+
+    /// Makes the sampler use its own rng seeded by `seed`, so the samples are the same
+    /// for the same seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// lower bounds
+    pub fn lower(&self) -> &[N] {
+        &self.lower
+    }
+
+    /// upper bounds
+    pub fn upper(&self) -> &[N] {
+        &self.upper
+    }
+}
+
+impl<N> Sampler<N> for UniformBoxSampler<N>
+where
+    N: Float + SampleUniform,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        self.sample_batch(1, rng).data
+    }
+
+    fn sample_batch(&mut self, n: usize, rng: &mut dyn RngCore) -> SampleBatch<N> {
+        let Self {
+            lower,
+            upper,
+            distributions,
+            rng: own_rng,
+        } = self;
+        let rng: &mut dyn RngCore = match own_rng {
+            Some(own) => own,
+            None => rng,
+        };
+        let mut batch = SampleBatch::with_capacity(lower.len(), n);
+        for _ in 0..n {
+            for ((l, u), distribution) in lower.iter().zip(upper.iter()).zip(distributions.iter()) {
+                batch.data.push(match distribution {
+                    AxisDistribution::Uniform => rng.gen_range(*l..*u),
+                    AxisDistribution::Gaussian { mean, std_dev } => (*mean
+                        + *std_dev * N::from(standard_normal(rng)).unwrap())
+                    .max(*l)
+                    .min(*u),
+                    AxisDistribution::Discrete(values) => values[rng.gen_range(0..values.len())],
+                });
+            }
+        }
+        batch
+    }
+}
+
+impl<N> Sampler<N> for &mut UniformBoxSampler<N>
+where
+    N: Float + SampleUniform,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn sample_batch(&mut self, n: usize, rng: &mut dyn RngCore) -> SampleBatch<N> {
+        (**self).sample_batch(n, rng)
+    }
+}
+
+/// Returns a sample of the standard normal distribution (Box-Muller transform).
+pub(crate) fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    // 1 - [0, 1) avoids ln(0)
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Returns a point of the uniform distribution in the ball of `radius` around `center`.
+pub(crate) fn uniform_in_ball<N: Float>(center: &[N], radius: N, rng: &mut dyn RngCore) -> Vec<N> {
+    // normal direction, and the distance of the density r^(dim-1)
+    let direction = center
+        .iter()
+        .map(|_| N::from(standard_normal(rng)).unwrap())
+        .collect::<Vec<_>>();
+    let norm = direction
+        .iter()
+        .fold(N::zero(), |sum, d| sum + *d * *d)
+        .sqrt();
+    if !norm.is_normal() {
+        return center.to_vec();
+    }
+    let distance = radius * N::from(rng.gen::<f64>().powf(1.0 / center.len() as f64)).unwrap();
+    center
+        .iter()
+        .zip(&direction)
+        .map(|(c, d)| *c + *d * distance / norm)
+        .collect()
+}
+
+/// Sampler which takes the samples of `biased` with `probability`, made by [`with_bias`].
+#[derive(Debug, Clone)]
+struct Biased<S, B> {
+    sampler: S,
+    biased: B,
+    probability: f64,
+}
+
+impl<S, B, N> Sampler<N> for Biased<S, B>
+where
+    S: Sampler<N>,
+    B: Sampler<N>,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        if rng.gen_bool(self.probability) {
+            self.biased.sample(rng)
+        } else {
+            self.sampler.sample(rng)
+        }
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.sampler.add_vertex(q);
+        self.biased.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.sampler.report_stall(num_stalled);
+        self.biased.report_stall(num_stalled);
+    }
+}
+
+/// Returns a sampler which returns a sample of `biased_sample` with `probability`
+/// (0.0 - 1.0), and a sample of `random_sample` otherwise.
+///
+/// This adds a bias to the planners which take a sampler but have no bias option,
+/// e.g. toward a goal, a region or the previous path. The bias is drawn from the rng
+/// given by the planner, and the vertices and stalls are passed to both samplers.
+pub fn with_bias<S, B, N>(random_sample: S, biased_sample: B, probability: f64) -> impl Sampler<N>
+where
+    S: Sampler<N>,
+    B: Sampler<N>,
+{
+    assert!((0.0..=1.0).contains(&probability));
+    Biased {
+        sampler: random_sample,
+        biased: biased_sample,
+        probability,
+    }
+}
+
+/// Returns a sampler which returns `goal` with `probability` (0.0 - 1.0),
+/// and a sample of `random_sample` otherwise.
+pub fn with_goal_bias<S, N>(random_sample: S, goal: &[N], probability: f64) -> impl Sampler<N>
+where
+    S: Sampler<N>,
+    N: Clone,
+{
+    let goal = goal.to_vec();
+    with_bias(random_sample, move || goal.clone(), probability)
+}
+
+#[test]
+fn with_bias_works() {
+    use rand::distributions::{Distribution, Uniform};
+    use rand::SeedableRng;
+    let mut sampler = from_rng_fn(|rng: &mut dyn RngCore| vec![Uniform::new(0.0, 1.0).sample(rng)]);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let first = sampler.sample(&mut rng);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    assert_eq!(sampler.sample(&mut rng), first);
+    assert_eq!(Sampler::sample(&mut || vec![1.0], &mut rng), vec![1.0]);
+
+    let mut sample = with_goal_bias(|| vec![0.0, 0.0], &[1.0, 2.0], 0.3);
+    let goals = (0..10000)
+        .map(|_| sample.sample(&mut rng) == vec![1.0, 2.0])
+        .collect::<Vec<_>>();
+    let num_goals = goals.iter().filter(|is_goal| **is_goal).count();
+    assert!((2500..3500).contains(&num_goals), "{num_goals}");
+    assert!(with_bias(|| vec![0.0], || vec![1.0], 0.0).sample(&mut rng) == vec![0.0]);
+    assert!(with_bias(|| vec![0.0], || vec![1.0], 1.0).sample(&mut rng) == vec![1.0]);
+    // the bias is drawn from the given rng
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let mut sample = with_goal_bias(|| vec![0.0, 0.0], &[1.0, 2.0], 0.3);
+    let same_goals = (0..10000)
+        .map(|_| sample.sample(&mut rng) == vec![1.0, 2.0])
+        .collect::<Vec<_>>();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let mut sample = with_goal_bias(|| vec![0.0, 0.0], &[1.0, 2.0], 0.3);
+    assert!((0..10000).all(|i| (sample.sample(&mut rng) == vec![1.0, 2.0]) == same_goals[i]));
+    // the vertices and stalls are passed to the wrapped sampler
+    let mut annealing = with_bias(
+        crate::AnnealingSampler::new(|| vec![0.0, 0.0], 0.1, 1.0, 2.0),
+        || vec![1.0, 2.0],
+        0.0,
+    );
+    annealing.add_vertex(&[5.0, 5.0]);
+    annealing.report_stall(0);
+    let q = annealing.sample(&mut rng);
+    assert!((q[0] - 5.0).hypot(q[1] - 5.0) < 0.1, "{q:?}");
+}
+
+#[test]
+fn uniform_box_sampler_works() {
+    use crate::dual_rrt_connect;
+    let mut sampler = UniformBoxSampler::new(&[-2.0, -1.0], &[2.0, 1.0]).unwrap();
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        let q = sampler.sample(&mut rng);
+        assert!((-2.0..2.0).contains(&q[0]) && (-1.0..1.0).contains(&q[1]));
+    }
+    let mut a = UniformBoxSampler::new(&[0.0], &[1.0]).unwrap().with_seed(1);
+    let mut b = a.clone();
+    assert_eq!(a.sample(&mut rng), b.sample(&mut rng));
+    assert!(UniformBoxSampler::new(&[0.0], &[1.0, 1.0]).is_err());
+    assert!(UniformBoxSampler::new(&[1.0], &[1.0]).is_err());
+    // used by reference for several plans
+    let mut sampler = UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    for goal in [[1.2, 0.0], [0.0, 1.2]] {
+        let path = dual_rrt_connect(&[-1.2, 0.0], &goal, is_free, &mut sampler, 0.2, 1000).unwrap();
+        assert_eq!(path[path.len() - 1], goal.to_vec());
+    }
+}
+
+#[test]
+fn axis_distributions_work() {
+    let mut sampler = UniformBoxSampler::new(&[-3.0, 0.0, 0.0], &[3.0, 0.1, 1.0])
+        .unwrap()
+        .with_distribution(
+            1,
+            AxisDistribution::Gaussian {
+                mean: 0.05,
+                std_dev: 0.01,
+            },
+        )
+        .unwrap()
+        .with_distribution(2, AxisDistribution::Discrete(vec![0.0, 1.0]))
+        .unwrap()
+        .with_seed(0);
+    let mut rng = rand::thread_rng();
+    let samples = (0..1000)
+        .map(|_| sampler.sample(&mut rng))
+        .collect::<Vec<_>>();
+    assert!(samples.iter().all(|q| (-3.0..3.0).contains(&q[0])));
+    assert!(samples.iter().all(|q| (0.0..=0.1).contains(&q[1])));
+    let mean = samples.iter().map(|q| q[1]).sum::<f64>() / 1000.0;
+    assert!((mean - 0.05).abs() < 0.005, "{mean}");
+    let num_closed = samples.iter().filter(|q| q[2] == 1.0).count();
+    assert!(samples.iter().all(|q| q[2] == 0.0 || q[2] == 1.0));
+    assert!((400..600).contains(&num_closed), "{num_closed}");
+
+    let sampler = UniformBoxSampler::new(&[0.0], &[1.0]).unwrap();
+    assert!(sampler
+        .clone()
+        .with_distribution(1, AxisDistribution::Uniform)
+        .is_err());
+    assert!(sampler
+        .clone()
+        .with_distribution(0, AxisDistribution::Discrete(vec![2.0]))
+        .is_err());
+    assert!(sampler
+        .with_distribution(
+            0,
+            AxisDistribution::Gaussian {
+                mean: 0.5,
+                std_dev: 0.0
+            }
+        )
+        .is_err());
+}
+
+#[test]
+fn sample_batch_works() {
+    let mut rng = rand::thread_rng();
+    let mut sampler = UniformBoxSampler::new(&[-2.0, -1.0], &[2.0, 1.0])
+        .unwrap()
+        .with_seed(0);
+    let batch = sampler.clone().sample_batch(100, &mut rng);
+    assert_eq!((batch.len(), batch.dim()), (100, 2));
+    assert_eq!(batch.as_slice().len(), 200);
+    assert!(batch
+        .rows()
+        .all(|q| (-2.0..2.0).contains(&q[0]) && (-1.0..1.0).contains(&q[1])));
+    // same as the samples one by one
+    for i in 0..100 {
+        assert_eq!(batch.row(i), sampler.sample(&mut rng).as_slice());
+    }
+
+    // the default implementation drops the samples of other dimensions
+    let mut i = 0;
+    let mut closure = || {
+        i += 1;
+        vec![0.0; if i == 2 { 1 } else { 3 }]
+    };
+    let batch = closure.sample_batch(4, &mut rng);
+    assert_eq!((batch.len(), batch.dim()), (3, 3));
+    assert!(Sampler::<f64>::sample_batch(&mut || vec![], 0, &mut rng).is_empty());
+    let mut batch = SampleBatch::new(2);
+    assert!(batch.push(vec![1.0, 2.0]));
+    assert!(!batch.push(vec![1.0]));
+    assert_eq!(batch.rows().collect::<Vec<_>>(), vec![&[1.0, 2.0]]);
+}
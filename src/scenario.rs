@@ -0,0 +1,346 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Reproducible problem generators, for testing and comparing planners.
+//!
+//! ```
+//! let world = rrt::scenario::World::random_obstacles(&[0.0; 2], &[10.0; 2], 0.2, 0.5, 1);
+//! let path = rrt::dual_rrt_connect(
+//!     &world.start,
+//!     &world.goal,
+//!     |q: &[f64]| world.is_free(q),
+//!     world.sampler(1),
+//!     0.2,
+//!     10000,
+//! );
+//! ```
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+
+/// Obstacle in a [`World`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Obstacle {
+    /// Axis-aligned box
+    Box {
+        /// center of the box
+        center: Vec<f64>,
+        /// half of the size in each axis
+        half_extents: Vec<f64>,
+    },
+    /// Sphere
+    Sphere {
+        /// center of the sphere
+        center: Vec<f64>,
+        /// radius of the sphere
+        radius: f64,
+    },
+}
+
+impl Obstacle {
+    /// Returns `true` if `q` is inside the obstacle.
+    pub fn contains(&self, q: &[f64]) -> bool {
+        match self {
+            Obstacle::Box {
+                center,
+                half_extents,
+            } => q
+                .iter()
+                .zip(center)
+                .zip(half_extents)
+                .all(|((q, c), h)| (q - c).abs() <= *h),
+            Obstacle::Sphere { center, radius } => {
+                q.iter()
+                    .zip(center)
+                    .map(|(q, c)| (q - c) * (q - c))
+                    .sum::<f64>()
+                    <= radius * radius
+            }
+        }
+    }
+}
+
+/// Planning problem: bounds, obstacles, start and goal.
+#[derive(Debug, Clone)]
+pub struct World {
+    /// lower bounds of the configuration space
+    pub lower: Vec<f64>,
+    /// upper bounds of the configuration space
+    pub upper: Vec<f64>,
+    /// obstacles
+    pub obstacles: Vec<Obstacle>,
+    /// start configuration, which is free
+    pub start: Vec<f64>,
+    /// goal configuration, which is free
+    pub goal: Vec<f64>,
+}
+
+impl World {
+    /// Returns `true` if `q` is inside the bounds and not inside any obstacle.
+    pub fn is_free(&self, q: &[f64]) -> bool {
+        q.iter()
+            .zip(&self.lower)
+            .zip(&self.upper)
+            .all(|((q, l), u)| l <= q && q <= u)
+            && !self.obstacles.iter().any(|o| o.contains(q))
+    }
+
+    /// Returns a uniform sampler inside the bounds, which can be passed to
+    /// [`dual_rrt_connect`](crate::dual_rrt_connect).
+    pub fn sampler(&self, seed: u64) -> impl Fn() -> Vec<f64> + Sync {
+        let rng = Mutex::new(StdRng::seed_from_u64(seed));
+        let lower = self.lower.clone();
+        let upper = self.upper.clone();
+        move || {
+            let mut rng = rng.lock().unwrap();
+            lower
+                .iter()
+                .zip(&upper)
+                .map(|(l, u)| rng.gen_range(*l..=*u))
+                .collect()
+        }
+    }
+
+    /// Generates random boxes and spheres which cover about `density` (0.0 - 1.0)
+    /// of the volume inside the bounds.
+    ///
+    /// Each obstacle has a size up to `max_half_size` in each axis.
+    /// The start is near the lower corner and the goal is near the upper corner.
+    pub fn random_obstacles(
+        lower: &[f64],
+        upper: &[f64],
+        density: f64,
+        max_half_size: f64,
+        seed: u64,
+    ) -> Self {
+        assert_eq!(lower.len(), upper.len());
+        assert!((0.0..1.0).contains(&density));
+        assert!(max_half_size > 0.0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let margin = |l: &f64, u: &f64| (u - l) * 0.05;
+        let start = lower
+            .iter()
+            .zip(upper)
+            .map(|(l, u)| l + margin(l, u))
+            .collect::<Vec<_>>();
+        let goal = lower
+            .iter()
+            .zip(upper)
+            .map(|(l, u)| u - margin(l, u))
+            .collect::<Vec<_>>();
+        let volume = lower.iter().zip(upper).map(|(l, u)| u - l).product::<f64>();
+        let mut covered = 0.0;
+        let mut obstacles = Vec::new();
+        while covered < volume * density {
+            let center = lower
+                .iter()
+                .zip(upper)
+                .map(|(l, u)| rng.gen_range(*l..=*u))
+                .collect::<Vec<_>>();
+            let (obstacle, obstacle_volume) = if rng.gen_bool(0.5) {
+                let half_extents = (0..lower.len())
+                    .map(|_| rng.gen_range(max_half_size * 0.1..=max_half_size))
+                    .collect::<Vec<_>>();
+                let v = half_extents.iter().map(|h| h * 2.0).product::<f64>();
+                (
+                    Obstacle::Box {
+                        center,
+                        half_extents,
+                    },
+                    v,
+                )
+            } else {
+                let radius = rng.gen_range(max_half_size * 0.1..=max_half_size);
+                // volume of the bounding box, which is good enough for density
+                let v = (radius * 2.0).powi(lower.len() as i32);
+                (Obstacle::Sphere { center, radius }, v)
+            };
+            if obstacle.contains(&start) || obstacle.contains(&goal) {
+                continue;
+            }
+            covered += obstacle_volume;
+            obstacles.push(obstacle);
+        }
+        World {
+            lower: lower.to_vec(),
+            upper: upper.to_vec(),
+            obstacles,
+            start,
+            goal,
+        }
+    }
+
+    /// Generates a 2D maze of `size` x `size` unit cells in `[0, size]`.
+    ///
+    /// The start is the center of the cell at the origin and the goal is the
+    /// center of the opposite corner cell. A path between them always exists.
+    pub fn maze(size: usize, wall_thickness: f64, seed: u64) -> Self {
+        assert!(size > 0);
+        assert!(wall_thickness > 0.0 && wall_thickness < 0.5);
+        let mut rng = StdRng::seed_from_u64(seed);
+        // walls between (x, y) and (x + 1, y), and between (x, y) and (x, y + 1)
+        let mut vertical_walls = vec![vec![true; size]; size];
+        let mut horizontal_walls = vec![vec![true; size]; size];
+        // randomized depth first search
+        let mut visited = vec![vec![false; size]; size];
+        let mut stack = vec![(0, 0)];
+        visited[0][0] = true;
+        while let Some(&(x, y)) = stack.last() {
+            let mut neighbors = Vec::new();
+            if x > 0 && !visited[x - 1][y] {
+                neighbors.push((x - 1, y));
+            }
+            if x + 1 < size && !visited[x + 1][y] {
+                neighbors.push((x + 1, y));
+            }
+            if y > 0 && !visited[x][y - 1] {
+                neighbors.push((x, y - 1));
+            }
+            if y + 1 < size && !visited[x][y + 1] {
+                neighbors.push((x, y + 1));
+            }
+            match neighbors.choose(&mut rng) {
+                Some(&(nx, ny)) => {
+                    if nx != x {
+                        vertical_walls[x.min(nx)][y] = false;
+                    } else {
+                        horizontal_walls[x][y.min(ny)] = false;
+                    }
+                    visited[nx][ny] = true;
+                    stack.push((nx, ny));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        let half = wall_thickness / 2.0;
+        let mut obstacles = Vec::new();
+        for x in 0..size {
+            for y in 0..size {
+                if x + 1 < size && vertical_walls[x][y] {
+                    obstacles.push(Obstacle::Box {
+                        center: vec![x as f64 + 1.0, y as f64 + 0.5],
+                        half_extents: vec![half, 0.5 + half],
+                    });
+                }
+                if y + 1 < size && horizontal_walls[x][y] {
+                    obstacles.push(Obstacle::Box {
+                        center: vec![x as f64 + 0.5, y as f64 + 1.0],
+                        half_extents: vec![0.5 + half, half],
+                    });
+                }
+            }
+        }
+        World {
+            lower: vec![0.0; 2],
+            upper: vec![size as f64; 2],
+            obstacles,
+            start: vec![0.5, 0.5],
+            goal: vec![size as f64 - 0.5; 2],
+        }
+    }
+
+    /// Generates a `dim` dimensional world in `[-1, 1]` split by a wall at
+    /// the first axis, with a hole of `width` at a random position.
+    ///
+    /// The start and the goal are on opposite sides of the wall.
+    pub fn narrow_passage(dim: usize, width: f64, seed: u64) -> Self {
+        assert!(dim >= 2);
+        assert!(width > 0.0 && width < 1.0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let hole = (1..dim)
+            .map(|_| rng.gen_range(-0.5..=0.5))
+            .collect::<Vec<f64>>();
+        let thickness = 0.1;
+        let mut obstacles = Vec::new();
+        // The wall minus the hole is covered by two boxes for each axis except the first one.
+        for (i, h) in hole.iter().enumerate() {
+            for side in [-1.0, 1.0] {
+                let edge = h + side * width / 2.0;
+                let end = side;
+                let mut center = vec![0.0; dim];
+                let mut half_extents = vec![1.0; dim];
+                half_extents[0] = thickness / 2.0;
+                center[i + 1] = (edge + end) / 2.0;
+                half_extents[i + 1] = (end - edge).abs() / 2.0;
+                obstacles.push(Obstacle::Box {
+                    center,
+                    half_extents,
+                });
+            }
+        }
+        let mut start = vec![-0.5];
+        start.extend(&hole);
+        let mut goal = vec![0.5];
+        goal.extend(&hole);
+        World {
+            lower: vec![-1.0; dim],
+            upper: vec![1.0; dim],
+            obstacles,
+            start,
+            goal,
+        }
+    }
+}
+
+#[cfg(test)]
+impl World {
+    /// [-2, 2]^2 with a box obstacle of [-1, 1]^2, from [-1.2, 0] to [1.2, 0].
+    pub(crate) fn square() -> Self {
+        Self {
+            lower: vec![-2.0; 2],
+            upper: vec![2.0; 2],
+            obstacles: vec![Obstacle::Box {
+                center: vec![0.0; 2],
+                half_extents: vec![1.0; 2],
+            }],
+            start: vec![-1.2, 0.0],
+            goal: vec![1.2, 0.0],
+        }
+    }
+}
+
+#[test]
+fn scenario_works() {
+    let world = World::random_obstacles(&[0.0; 2], &[10.0; 2], 0.2, 0.5, 1);
+    assert_eq!(
+        world.obstacles,
+        World::random_obstacles(&[0.0; 2], &[10.0; 2], 0.2, 0.5, 1).obstacles
+    );
+    assert!(world.is_free(&world.start) && world.is_free(&world.goal));
+
+    let maze = World::maze(5, 0.1, 2);
+    let result = crate::grid_astar(
+        &maze.start,
+        &maze.goal,
+        |q: &[f64]| maze.is_free(q),
+        &maze.lower,
+        &maze.upper,
+        0.25,
+    );
+    assert!(result.is_ok());
+
+    let world = World::narrow_passage(3, 0.1, 3);
+    assert!(world.is_free(&world.start) && world.is_free(&world.goal));
+    let mut q = world.start.clone();
+    q[0] = 0.0;
+    assert!(world.is_free(&q));
+    q[1] += 0.1;
+    assert!(!world.is_free(&q));
+}
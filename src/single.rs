@@ -0,0 +1,142 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::StallTracker;
+use crate::{check_start_goal, is_valid_point, ExtendStatus, Metric, Sampler, Tree};
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`rrt`].
+#[derive(Debug, Clone)]
+pub struct RrtOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// probability (0.0 - 1.0) to extend toward the goal instead of a random sample
+    pub goal_bias: f64,
+    /// distance for the nearest vertices and the extension. [`Metric::Euclidean`] by
+    /// default.
+    pub metric: Metric<N>,
+}
+
+impl<N> RrtOptions<N> {
+    /// Creates options with 0.05 goal bias.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            goal_bias: 0.05,
+            metric: Metric::Euclidean,
+        }
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with a single tree grown from the start toward random samples or the goal.
+pub fn rrt<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    options: &RrtOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    rrt_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`rrt`] which takes all randomness from `rng`.
+pub fn rrt_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    random_sample: FR,
+    options: &RrtOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    let mut random_sample = StallTracker::new(random_sample);
+    let mut tree = Tree::with_name("start", start.len()).with_metric(options.metric.clone())?;
+    tree.add_vertex(start);
+    for _ in 0..options.num_max_try {
+        let q_target = if rng.gen_bool(options.goal_bias) {
+            goal.to_vec()
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_target, start.len()) {
+            continue;
+        }
+        let new_index = match tree.extend(&q_target, options.extend_length, &mut is_free) {
+            ExtendStatus::Trapped => continue,
+            ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) => index,
+        };
+        random_sample.add_vertex(tree.vertex(new_index));
+        if tree.distance(tree.vertex(new_index), goal) >= options.extend_length {
+            continue;
+        }
+        let goal_index =
+            match tree.extend_from(new_index, goal, options.extend_length, &mut is_free) {
+                ExtendStatus::Trapped => continue,
+                ExtendStatus::Advanced(index) | ExtendStatus::Reached(index) => index,
+            };
+        debug!("reached the goal with {} vertices", tree.len());
+        let mut path = tree.get_until_root(goal_index);
+        path.reverse();
+        path.push(tree.vertex(goal_index).to_vec());
+        return Ok(path);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn rrt_works() {
+    use kdtree::distance::squared_euclidean;
+    let world = crate::scenario::World::square();
+    let result = rrt(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        |p: &[f64]| world.is_free(p),
+        world.sampler(1),
+        &RrtOptions::new(0.2, 10000),
+    )
+    .unwrap();
+    assert_eq!(result[0], vec![-1.2, 0.0]);
+    assert_eq!(result[result.len() - 1], vec![1.2, 0.0]);
+    for segment in result.windows(2) {
+        assert!(squared_euclidean(&segment[0], &segment[1]).sqrt() <= 0.2 + 1e-9);
+    }
+}
@@ -0,0 +1,586 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::hybrid_astar::normalize_angle;
+use crate::sampler::check_bounds;
+use crate::Metric;
+use num_traits::float::Float;
+use num_traits::FloatConst;
+use std::fmt::{self, Debug};
+
+/// Space of the configurations, which defines the distance, the interpolation and the
+/// bounds of a [`Tree`](crate::Tree), e.g. used by
+/// [`dual_rrt_connect_in_space`](crate::dual_rrt_connect_in_space).
+///
+/// Implement this for spaces which are not euclidean, e.g. with angles or poses.
+/// The other planners plan in a [`RealVectorSpace`], optionally with a [`Metric`] from
+/// their options, and [`Steering`](crate::Steering) is for the spaces where the
+/// configurations can't be interpolated directly, e.g. of car-like robots.
+pub trait StateSpace<N> {
+    /// number of the values of a configuration
+    fn dimension(&self) -> usize;
+
+    /// Returns the distance between `a` and `b`.
+    fn distance(&self, a: &[N], b: &[N]) -> N;
+
+    /// Returns the configuration at `t` (0.0 - 1.0) of the way from `from` to `to`,
+    /// whose distance from `from` is `t` times the distance between them.
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N>;
+
+    /// Moves `q` into the bounds of the space, e.g. clamping or wrapping the values.
+    fn enforce_bounds(&self, q: &mut [N]);
+
+    /// Returns the metric if the distance is that of a [`Metric`], so that the trees can
+    /// search the nearest vertices by a kd-tree. `None` by default.
+    fn metric(&self) -> Option<&Metric<N>> {
+        None
+    }
+}
+
+impl<N, S> StateSpace<N> for &S
+where
+    S: StateSpace<N> + ?Sized,
+{
+    fn dimension(&self) -> usize {
+        (**self).dimension()
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        (**self).distance(a, b)
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        (**self).interpolate(from, to, t)
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        (**self).enforce_bounds(q)
+    }
+
+    fn metric(&self) -> Option<&Metric<N>> {
+        (**self).metric()
+    }
+}
+
+/// Euclidean space, optionally bounded by a box, which is the space of the planners
+/// without a [`StateSpace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealVectorSpace<N> {
+    dim: usize,
+    bounds: Option<(Vec<N>, Vec<N>)>,
+    metric: Metric<N>,
+}
+
+impl<N> RealVectorSpace<N>
+where
+    N: Float + Debug,
+{
+    /// Creates an unbounded space of `dim` dimensions.
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            bounds: None,
+            metric: Metric::Euclidean,
+        }
+    }
+
+    /// Creates a space bounded by the box between `lower` and `upper`.
+    ///
+    /// Returns an error if the bounds are invalid as
+    /// [`UniformBoxSampler::new`](crate::UniformBoxSampler::new).
+    pub fn with_bounds(lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        Ok(Self {
+            dim: lower.len(),
+            bounds: Some((lower.to_vec(), upper.to_vec())),
+            metric: Metric::Euclidean,
+        })
+    }
+
+    /// Uses `metric` as the distance, along whose straight lines the configurations are
+    /// interpolated.
+    ///
+    /// Returns an error if `metric` is not for the dimension of the space.
+    pub fn with_metric(mut self, metric: Metric<N>) -> Result<Self, String> {
+        metric.check(self.dim)?;
+        self.metric = metric;
+        Ok(self)
+    }
+}
+
+impl<N> StateSpace<N> for RealVectorSpace<N>
+where
+    N: Float + Debug,
+{
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        self.metric.distance(a, b)
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        from.iter()
+            .zip(to)
+            .map(|(a, b)| *a + (*b - *a) * t)
+            .collect()
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        if let Some((lower, upper)) = &self.bounds {
+            for (v, (l, u)) in q.iter_mut().zip(lower.iter().zip(upper)) {
+                *v = v.max(*l).min(*u);
+            }
+        }
+    }
+
+    fn metric(&self) -> Option<&Metric<N>> {
+        Some(&self.metric)
+    }
+}
+
+/// Space of the poses `[x, y, heading]` of a mobile base (SE(2)).
+///
+/// The distance is `sqrt(dx^2 + dy^2 + (rotation_weight * dheading)^2)`, where the
+/// heading difference takes the short way across +-pi. The position is interpolated
+/// linearly and the heading along the shorter rotation. The headings are normalized to
+/// [-pi, pi), and the position is clamped if the space is bounded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Se2Space<N> {
+    rotation_weight: N,
+    bounds: Option<([N; 2], [N; 2])>,
+}
+
+impl<N> Se2Space<N>
+where
+    N: Float + FloatConst + Debug,
+{
+    /// Creates an unbounded space, where turning by 1 radian is as far as moving by
+    /// `rotation_weight` (> 0.0), e.g. the radius of the base.
+    pub fn new(rotation_weight: N) -> Self {
+        assert!(rotation_weight > N::zero());
+        Self {
+            rotation_weight,
+            bounds: None,
+        }
+    }
+
+    /// Bounds the position `[x, y]` by the box between `lower` and `upper`.
+    ///
+    /// Returns an error if the bounds are not of 2 dimensions or invalid.
+    pub fn with_bounds(mut self, lower: &[N], upper: &[N]) -> Result<Self, String> {
+        check_bounds(lower, upper)?;
+        if lower.len() != 2 {
+            return Err(format!("bounds must be [x, y]: {lower:?}"));
+        }
+        self.bounds = Some(([lower[0], lower[1]], [upper[0], upper[1]]));
+        Ok(self)
+    }
+
+    /// Returns the weight of the rotation in the distance.
+    pub fn rotation_weight(&self) -> N {
+        self.rotation_weight
+    }
+}
+
+impl<N> StateSpace<N> for Se2Space<N>
+where
+    N: Float + FloatConst,
+{
+    fn dimension(&self) -> usize {
+        3
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        let rotation = normalize_angle(b[2] - a[2]) * self.rotation_weight;
+        (b[0] - a[0]).hypot(b[1] - a[1]).hypot(rotation)
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        vec![
+            from[0] + (to[0] - from[0]) * t,
+            from[1] + (to[1] - from[1]) * t,
+            normalize_angle(from[2] + normalize_angle(to[2] - from[2]) * t),
+        ]
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        if let Some((lower, upper)) = &self.bounds {
+            for i in 0..2 {
+                q[i] = q[i].max(lower[i]).min(upper[i]);
+            }
+        }
+        q[2] = normalize_angle(q[2]);
+    }
+}
+
+/// Space of the rotations in 3D (SO(3)) as unit quaternions `[x, y, z, w]`.
+///
+/// The distance is the angle of the rotation between them, and the interpolation is
+/// the spherical linear interpolation (slerp) along the shorter rotation. Quaternions
+/// are normalized by [`StateSpace::enforce_bounds`], so samples in `[-1, 1]^4` are valid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct So3Space;
+
+impl<N> StateSpace<N> for So3Space
+where
+    N: Float,
+{
+    fn dimension(&self) -> usize {
+        4
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        let dot = a.iter().zip(b).fold(N::zero(), |sum, (a, b)| sum + *a * *b);
+        (dot.abs().min(N::one())).acos() * (N::one() + N::one())
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        let dot = from
+            .iter()
+            .zip(to)
+            .fold(N::zero(), |sum, (a, b)| sum + *a * *b);
+        // q and -q are the same rotation
+        let sign = if dot < N::zero() { -N::one() } else { N::one() };
+        let theta = (dot.abs().min(N::one())).acos();
+        let (wa, wb) = if theta.sin() <= N::epsilon() {
+            (N::one() - t, t)
+        } else {
+            (
+                ((N::one() - t) * theta).sin() / theta.sin(),
+                (t * theta).sin() / theta.sin(),
+            )
+        };
+        let mut q = from
+            .iter()
+            .zip(to)
+            .map(|(a, b)| *a * wa + *b * wb * sign)
+            .collect::<Vec<_>>();
+        self.enforce_bounds(&mut q);
+        q
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        let norm = q.iter().fold(N::zero(), |sum, v| sum + *v * *v).sqrt();
+        if norm.is_normal() {
+            for v in q.iter_mut() {
+                *v = *v / norm;
+            }
+        } else {
+            q.copy_from_slice(&[N::zero(), N::zero(), N::zero(), N::one()]);
+        }
+    }
+}
+
+/// Space of one integer value between `lower` and `upper`, e.g. the modes of a gripper.
+///
+/// The distance is the difference, and the interpolated values are rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscreteSpace {
+    lower: i64,
+    upper: i64,
+}
+
+impl DiscreteSpace {
+    /// Creates a space of the values from `lower` to `upper` (inclusive).
+    pub fn new(lower: i64, upper: i64) -> Self {
+        assert!(lower <= upper);
+        Self { lower, upper }
+    }
+}
+
+impl<N> StateSpace<N> for DiscreteSpace
+where
+    N: Float,
+{
+    fn dimension(&self) -> usize {
+        1
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        (a[0] - b[0]).abs()
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        let mut q = vec![from[0] + (to[0] - from[0]) * t];
+        self.enforce_bounds(&mut q);
+        q
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        q[0] = q[0]
+            .round()
+            .max(N::from(self.lower).unwrap())
+            .min(N::from(self.upper).unwrap());
+    }
+}
+
+/// Space made of components, whose configurations are the concatenation of those of the
+/// components (like `CompoundStateSpace` of OMPL).
+///
+/// The distance is the weighted sum of the distances of the components, and all
+/// components are interpolated by the same fraction, e.g. the base ([`Se2Space`]) and
+/// the arm ([`WrappedSpace`](crate::WrappedSpace) or [`RealVectorSpace`]) of a mobile
+/// manipulator, where the weights balance meters with radians.
+/// [`CompositeSpace`](crate::CompositeSpace) is the one of several robots in euclidean
+/// spaces.
+pub struct CompoundSpace<N> {
+    components: Vec<(Box<dyn StateSpace<N>>, N)>,
+    dim: usize,
+}
+
+impl<N> Debug for CompoundSpace<N>
+where
+    N: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompoundSpace")
+            .field(
+                "components",
+                &self
+                    .components
+                    .iter()
+                    .map(|(space, weight)| (space.dimension(), weight))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<N> Default for CompoundSpace<N> {
+    fn default() -> Self {
+        Self {
+            components: Vec::new(),
+            dim: 0,
+        }
+    }
+}
+
+impl<N> CompoundSpace<N>
+where
+    N: Float,
+{
+    /// Creates a space without components.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `space` as the next values of the configurations, whose distance is
+    /// multiplied by `weight` (> 0.0).
+    pub fn with_component<S>(mut self, space: S, weight: N) -> Self
+    where
+        S: StateSpace<N> + 'static,
+    {
+        assert!(weight > N::zero());
+        self.dim += space.dimension();
+        self.components.push((Box::new(space), weight));
+        self
+    }
+
+    /// Returns the number of the components.
+    pub fn num_components(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Calls `f` with each component, its weight and its range in the configurations.
+    fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&dyn StateSpace<N>, N, std::ops::Range<usize>),
+    {
+        let mut start = 0;
+        for (space, weight) in &self.components {
+            let end = start + space.dimension();
+            f(space.as_ref(), *weight, start..end);
+            start = end;
+        }
+    }
+}
+
+impl<N> StateSpace<N> for CompoundSpace<N>
+where
+    N: Float,
+{
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        let mut sum = N::zero();
+        self.for_each(|space, weight, range| {
+            sum = sum + weight * space.distance(&a[range.clone()], &b[range]);
+        });
+        sum
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        let mut q = Vec::with_capacity(self.dim);
+        self.for_each(|space, _, range| {
+            q.extend(space.interpolate(&from[range.clone()], &to[range], t));
+        });
+        q
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        self.for_each(|space, _, range| space.enforce_bounds(&mut q[range]));
+    }
+}
+
+#[test]
+fn real_vector_space_works() {
+    use crate::{dual_rrt_connect_in_space, DualRrtConnectOptions, UniformBoxSampler};
+    let space = RealVectorSpace::with_bounds(&[-2.0, -2.0], &[2.0, 2.0]).unwrap();
+    assert_eq!(space.dimension(), 2);
+    assert_eq!(space.distance(&[0.0, 0.0], &[3.0, 4.0]), 5.0);
+    assert_eq!(
+        space.interpolate(&[0.0, 0.0], &[3.0, 4.0], 0.5),
+        vec![1.5, 2.0]
+    );
+    let mut q = [3.0, -1.0];
+    space.enforce_bounds(&mut q);
+    assert_eq!(q, [2.0, -1.0]);
+    let mut q = [3.0, -1.0];
+    RealVectorSpace::new(2).enforce_bounds(&mut q);
+    assert_eq!(q, [3.0, -1.0]);
+    assert!(RealVectorSpace::with_bounds(&[0.0], &[0.0]).is_err());
+
+    // samples out of the bounds are clamped
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let path = dual_rrt_connect_in_space(
+        &space,
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        UniformBoxSampler::new(&[-3.0, -3.0], &[3.0, 3.0]).unwrap(),
+        &DualRrtConnectOptions::new(0.2, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    for segment in path.windows(2) {
+        assert!(space.distance(&segment[0], &segment[1]) <= 0.2 + 1e-9);
+    }
+    assert!(path
+        .iter()
+        .all(|q| is_free(q) && q.iter().all(|v| v.abs() <= 2.0)));
+    assert!(dual_rrt_connect_in_space(
+        &space,
+        &[-1.2, 0.0, 0.0],
+        &[1.2, 0.0, 0.0],
+        is_free,
+        || vec![0.0, 0.0, 0.0],
+        &DualRrtConnectOptions::new(0.2, 1000),
+    )
+    .is_err());
+}
+
+#[test]
+fn se2_space_works() {
+    use crate::{dual_rrt_connect_in_space, DualRrtConnectOptions, UniformBoxSampler};
+    use std::f64::consts::PI;
+    let space = Se2Space::new(0.5)
+        .with_bounds(&[-2.0, -2.0], &[2.0, 2.0])
+        .unwrap();
+    assert!(Se2Space::new(0.5).with_bounds(&[0.0], &[1.0]).is_err());
+    assert!(
+        (space.distance(&[0.0, 0.0, 3.0], &[0.0, 0.0, -3.0]) - (2.0 * PI - 6.0) * 0.5).abs() < 1e-9
+    );
+    assert!((space.distance(&[0.0, 0.0, 0.0], &[3.0, 4.0, 0.0]) - 5.0).abs() < 1e-9);
+    let mid = space.interpolate(&[0.0, 0.0, 3.0], &[2.0, 0.0, -3.0], 0.5);
+    assert!((mid[0] - 1.0).abs() < 1e-9 && (mid[2] + PI).abs() < 1e-9);
+    let mut q = [3.0, 0.0, 4.0];
+    space.enforce_bounds(&mut q);
+    assert!(q[0] == 2.0 && (q[2] - (4.0 - 2.0 * PI)).abs() < 1e-9);
+
+    // a long base which has to turn to pass the gap in the wall
+    let is_free = |p: &[f64]| {
+        (-1..=1).all(|i| {
+            let x = p[0] + 0.2 * f64::from(i) * p[2].cos();
+            let y = p[1] + 0.2 * f64::from(i) * p[2].sin();
+            !(x.abs() < 0.1 && y.abs() > 0.1)
+        })
+    };
+    let start = [-1.0, 0.0, PI / 2.0];
+    let goal = [1.0, 0.0, -PI / 2.0];
+    let path = dual_rrt_connect_in_space(
+        &space,
+        &start,
+        &goal,
+        is_free,
+        UniformBoxSampler::new(&[-2.0, -2.0, -PI], &[2.0, 2.0, PI]).unwrap(),
+        &DualRrtConnectOptions::new(0.1, 20000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        assert!(space.distance(&segment[0], &segment[1]) <= 0.1 + 1e-9);
+    }
+}
+
+#[test]
+fn compound_space_works() {
+    use crate::{
+        dual_rrt_connect_in_space, DualRrtConnectOptions, UniformBoxSampler, WrappedSpace,
+    };
+    use std::f64::consts::PI;
+    let so3 = So3Space;
+    let identity = [0.0, 0.0, 0.0, 1.0];
+    let half = [0.0, 0.0, (PI / 4.0).sin(), (PI / 4.0).cos()];
+    assert!((so3.distance(&identity, &half) - PI / 2.0).abs() < 1e-9);
+    // the same rotation
+    assert!(so3.distance(&half, &half.map(|v| -v)).abs() < 1e-6);
+    let mid = so3.interpolate(&identity, &half, 0.5);
+    assert!((so3.distance(&identity, &mid) - PI / 4.0).abs() < 1e-9);
+    let mut q = [0.0, 0.0, 0.0, 2.0];
+    so3.enforce_bounds(&mut q);
+    assert_eq!(q, identity);
+    let discrete = DiscreteSpace::new(0, 2);
+    assert_eq!(discrete.interpolate(&[0.0], &[2.0], 0.4), vec![1.0]);
+    let mut q = [3.4];
+    discrete.enforce_bounds(&mut q);
+    assert_eq!(q, [2.0]);
+
+    // a mobile base with a revolute joint and a gripper
+    let space = CompoundSpace::new()
+        .with_component(Se2Space::new(0.5), 1.0)
+        .with_component(WrappedSpace::new(&[true]), 0.5)
+        .with_component(discrete, 0.1);
+    assert_eq!(space.dimension(), 5);
+    assert_eq!(space.num_components(), 3);
+    let (a, b) = ([0.0, 0.0, 0.0, 3.0, 0.0], [3.0, 4.0, 0.0, -3.0, 2.0]);
+    let expected = 5.0 + 0.5 * (2.0 * PI - 6.0) + 0.1 * 2.0;
+    assert!((space.distance(&a, &b) - expected).abs() < 1e-9);
+    let mid = space.interpolate(&a, &b, 0.5);
+    assert!((mid[0] - 1.5).abs() < 1e-9 && (mid[3] + PI).abs() < 1e-9 && mid[4] == 1.0);
+
+    // the joint has to be folded while the base is near the line of y = 0
+    let is_free = |p: &[f64]| p[1].abs() > 0.5 || p[3].abs() < 0.5;
+    let path = dual_rrt_connect_in_space(
+        &space,
+        &[-1.0, 0.0, 0.0, 0.0, 0.0],
+        &[1.0, 0.0, 0.0, 0.0, 2.0],
+        is_free,
+        UniformBoxSampler::new(&[-2.0, -2.0, -PI, -PI, 0.0], &[2.0, 2.0, PI, PI, 2.0]).unwrap(),
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.0, 0.0, 0.0, 0.0, 2.0]);
+    assert!(path.iter().all(|q| is_free(q) && q[4].fract() == 0.0));
+}
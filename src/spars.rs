@@ -0,0 +1,225 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{is_edge_free, is_valid_point, Roadmap, Sampler};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`Roadmap::grow_sparse`].
+#[derive(Debug, Clone)]
+pub struct SparsOptions<N> {
+    /// visibility range of the vertices. Samples within this distance of a vertex
+    /// by a free edge are covered by it.
+    pub sparse_delta: N,
+    /// max ratio (> 1) of the length of the path on the roadmap to the length of
+    /// the straight edge between two visible vertices
+    pub stretch_factor: N,
+    /// growing stops after this number of consecutive samples which add nothing
+    pub max_failures: usize,
+}
+
+impl<N> SparsOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with the stretch factor of 3 and 1000 max failures.
+    pub fn new(sparse_delta: N) -> Self {
+        Self {
+            sparse_delta,
+            stretch_factor: N::from(3.0).unwrap(),
+            max_failures: 1000,
+        }
+    }
+}
+
+/// Length of the shortest path between `a` and `b` on the roadmap.
+fn graph_distance<N>(roadmap: &Roadmap<N>, a: usize, b: usize) -> Option<N>
+where
+    N: Float + Debug,
+{
+    let path = roadmap.shortest_path(a, b)?;
+    Some(path.windows(2).fold(N::zero(), |sum, edge| {
+        sum + squared_euclidean(roadmap.vertex(edge[0]), roadmap.vertex(edge[1])).sqrt()
+    }))
+}
+
+impl<N> Roadmap<N>
+where
+    N: Float + Debug,
+{
+    /// Adds samples out of `num_samples` samples which are needed to keep the roadmap
+    /// sparse but connected and near-optimal, like SPARS2 (sparse roadmap spanner), and
+    /// returns the number of vertices added.
+    ///
+    /// A free sample is added only if no vertex is visible within `sparse_delta`
+    /// (coverage), if it connects vertices which are not connected yet (connectivity),
+    /// or if the roadmap lacks an edge between its two nearest visible vertices
+    /// (interface), or if the path on the roadmap between visible vertices is longer
+    /// than `stretch_factor` times the one through it (quality). Otherwise only the
+    /// straight edge between the visible vertices may be added. So the number of
+    /// vertices is bounded by the visibility of the free space, unlike [`grow`](Self::grow)
+    /// with PRM*, and growing stops after `max_failures` consecutive samples add nothing.
+    /// The connection rule of the options is only used to connect start and goal in
+    /// queries. Do not mix this with [`grow`](Self::grow) in one roadmap.
+    pub fn grow_sparse<FF, FR>(
+        &mut self,
+        is_free: FF,
+        random_sample: FR,
+        num_samples: usize,
+        options: &SparsOptions<N>,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+    {
+        self.grow_sparse_with_rng(
+            is_free,
+            random_sample,
+            num_samples,
+            options,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// [`Self::grow_sparse`] which takes all randomness from `rng`.
+    pub fn grow_sparse_with_rng<FF, FR, R>(
+        &mut self,
+        mut is_free: FF,
+        mut random_sample: FR,
+        num_samples: usize,
+        options: &SparsOptions<N>,
+        rng: &mut R,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Sampler<N>,
+        R: Rng,
+    {
+        assert!(options.sparse_delta > N::zero());
+        assert!(options.stretch_factor > N::one());
+        let resolution = self.options.resolution;
+        let mut num_added = 0;
+        let mut num_failures = 0;
+        for _ in 0..num_samples {
+            if num_failures >= options.max_failures {
+                debug!("stopped after {num_failures} failures");
+                break;
+            }
+            num_failures += 1;
+            let q = random_sample.sample(rng);
+            if !is_valid_point(&q, self.dim) || !is_free(&q) {
+                continue;
+            }
+            let visible = self
+                .within(&q, options.sparse_delta)
+                .into_iter()
+                .filter(|(i, _)| is_edge_free(&q, self.vertex(*i), &mut is_free, resolution))
+                .collect::<Vec<_>>();
+            // coverage
+            let Some((nearest, nearest_dist)) = visible.first().copied() else {
+                self.add_isolated_vertex(&q);
+                num_added += 1;
+                num_failures = 0;
+                continue;
+            };
+            // connectivity
+            let mut disconnected = Vec::new();
+            for (i, _) in &visible[1..] {
+                if self.shortest_path(nearest, *i).is_none()
+                    && disconnected
+                        .iter()
+                        .all(|j| self.shortest_path(*i, *j).is_none())
+                {
+                    disconnected.push(*i);
+                }
+            }
+            if !disconnected.is_empty() {
+                let index = self.add_isolated_vertex(&q);
+                self.add_edge(index, nearest);
+                for i in disconnected {
+                    self.add_edge(index, i);
+                }
+                num_added += 1;
+                num_failures = 0;
+                continue;
+            }
+            // interface and quality
+            for (i, dist) in &visible[1..] {
+                let direct = squared_euclidean(self.vertex(nearest), self.vertex(*i)).sqrt();
+                let interface =
+                    visible[1].0 == *i && !self.neighbors(nearest).iter().any(|(j, _)| j == i);
+                let detour = graph_distance(self, nearest, *i).unwrap();
+                if !interface && detour <= options.stretch_factor * (nearest_dist + *dist) {
+                    continue;
+                }
+                if is_edge_free(
+                    self.vertex(nearest),
+                    self.vertex(*i),
+                    &mut is_free,
+                    resolution,
+                ) {
+                    if interface || detour > options.stretch_factor * direct {
+                        self.add_edge(nearest, *i);
+                        num_failures = 0;
+                    }
+                } else {
+                    let index = self.add_isolated_vertex(&q);
+                    self.add_edge(index, nearest);
+                    self.add_edge(index, *i);
+                    num_added += 1;
+                    num_failures = 0;
+                }
+                break;
+            }
+        }
+        num_added
+    }
+}
+
+#[test]
+fn grow_sparse_works() {
+    use crate::{path_length, PrmOptions};
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let random_sample = world.sampler(1);
+    let mut roadmap = Roadmap::new(2, PrmOptions::new(1.0, 0.01));
+    let options = SparsOptions::new(0.5);
+    let num_added = roadmap.grow_sparse(is_free, &random_sample, 5000, &options);
+    assert_eq!(num_added, roadmap.len());
+    // far fewer vertices than samples
+    assert!(roadmap.len() < 200, "{}", roadmap.len());
+    // every vertex and edge is free
+    for i in 0..roadmap.len() {
+        assert!(is_free(roadmap.vertex(i)));
+        for (j, _) in roadmap.neighbors(i) {
+            assert!(is_edge_free(
+                roadmap.vertex(i),
+                roadmap.vertex(*j),
+                &mut { is_free },
+                0.01
+            ));
+        }
+    }
+    let path = roadmap.query(&[-1.2, 0.0], &[1.2, 0.0], is_free).unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path_length(&path) < 3.0 * 4.1, "{}", path_length(&path));
+    // saturated roadmap hardly grows
+    assert!(roadmap.grow_sparse(is_free, &random_sample, 1000, &options) < 10);
+}
@@ -0,0 +1,221 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{dual_rrt_connect_with_rng_fn, DualRrtConnectOptions};
+use num_traits::float::Float;
+use rand::rngs::ThreadRng;
+use rand::Rng;
+use std::fmt::Debug;
+
+/// Configuration type which [`dual_rrt_connect_state`] takes instead of `Vec<N>`, so that
+/// the callbacks get the type of the user, e.g. arrays or the vectors of linear algebra
+/// crates, without converting them in each callback.
+///
+/// This is implemented for `Vec<N>`, `[N; D]`, and `SVector` and `DVector` of nalgebra
+/// with the `nalgebra` feature.
+pub trait State<N>: Sized {
+    /// Returns the values of the configuration.
+    fn as_slice(&self) -> &[N];
+
+    /// Returns the configuration of `values`, or `None` if their dimension is wrong.
+    fn from_slice(values: &[N]) -> Option<Self>;
+}
+
+impl<N: Clone> State<N> for Vec<N> {
+    fn as_slice(&self) -> &[N] {
+        self
+    }
+
+    fn from_slice(values: &[N]) -> Option<Self> {
+        Some(values.to_vec())
+    }
+}
+
+impl<N: Copy, const D: usize> State<N> for [N; D] {
+    fn as_slice(&self) -> &[N] {
+        self
+    }
+
+    fn from_slice(values: &[N]) -> Option<Self> {
+        values.try_into().ok()
+    }
+}
+
+/// Requires the `nalgebra` feature.
+#[cfg(feature = "nalgebra")]
+impl<N: nalgebra::Scalar, const D: usize> State<N> for nalgebra::SVector<N, D> {
+    fn as_slice(&self) -> &[N] {
+        nalgebra::Matrix::as_slice(self)
+    }
+
+    fn from_slice(values: &[N]) -> Option<Self> {
+        (values.len() == D).then(|| Self::from_column_slice(values))
+    }
+}
+
+/// Requires the `nalgebra` feature.
+#[cfg(feature = "nalgebra")]
+impl<N: nalgebra::Scalar> State<N> for nalgebra::DVector<N> {
+    fn as_slice(&self) -> &[N] {
+        nalgebra::Matrix::as_slice(self)
+    }
+
+    fn from_slice(values: &[N]) -> Option<Self> {
+        Some(Self::from_column_slice(values))
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// with the configurations of the type `S`.
+///
+/// This is [`dual_rrt_connect_with_options`](crate::dual_rrt_connect_with_options) whose
+/// callbacks and path are of `S`.
+/// Configurations whose dimension is wrong for `S` are not free.
+pub fn dual_rrt_connect_state<S, FF, FR, N>(
+    start: &S,
+    goal: &S,
+    is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<S>, String>
+where
+    S: State<N>,
+    FF: FnMut(&S) -> bool,
+    FR: FnMut() -> S,
+    N: Float + Debug,
+{
+    dual_rrt_connect_state_with_rng(
+        start,
+        goal,
+        is_free,
+        |_: &mut ThreadRng| random_sample(),
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_state`] which takes all randomness from `rng`, and gives it to
+/// `random_sample`.
+pub fn dual_rrt_connect_state_with_rng<S, FF, FR, N, R>(
+    start: &S,
+    goal: &S,
+    mut is_free: FF,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<S>, String>
+where
+    S: State<N>,
+    FF: FnMut(&S) -> bool,
+    FR: FnMut(&mut R) -> S,
+    N: Float + Debug,
+    R: Rng,
+{
+    let path = dual_rrt_connect_with_rng_fn(
+        start.as_slice(),
+        goal.as_slice(),
+        |q: &[N]| S::from_slice(q).is_some_and(|q| is_free(&q)),
+        |rng: &mut R| random_sample(rng).as_slice().to_vec(),
+        options,
+        rng,
+        None,
+    )?;
+    path.iter()
+        .map(|q| S::from_slice(q).ok_or_else(|| format!("wrong dimension: {q:?}")))
+        .collect()
+}
+
+#[test]
+fn dual_rrt_connect_state_works() {
+    /// type of another crate
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Point {
+        values: [f64; 2],
+    }
+    impl State<f64> for Point {
+        fn as_slice(&self) -> &[f64] {
+            &self.values
+        }
+        fn from_slice(values: &[f64]) -> Option<Self> {
+            Some(Self {
+                values: State::from_slice(values)?,
+            })
+        }
+    }
+    let world = crate::scenario::World::square();
+    let random_sample = world.sampler(1);
+    let is_free = |p: &Point| world.is_free(&p.values);
+    let start = Point {
+        values: [-1.2, 0.0],
+    };
+    let goal = Point { values: [1.2, 0.0] };
+    let path = dual_rrt_connect_state(
+        &start,
+        &goal,
+        is_free,
+        || {
+            let q = random_sample();
+            Point {
+                values: [q[0], q[1]],
+            }
+        },
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(is_free));
+    assert_eq!(<[f64; 2]>::from_slice(&[1.0]), None);
+    assert_eq!(Vec::from_slice(&[1.0]), Some(vec![1.0]));
+}
+
+#[cfg(feature = "nalgebra")]
+#[test]
+fn nalgebra_state_works() {
+    use nalgebra::{DVector, Vector2};
+    let world = crate::scenario::World::square();
+    let random_sample = world.sampler(1);
+    let is_free = |p: &Vector2<f64>| world.is_free(p.as_slice());
+    let start = Vector2::new(-1.2, 0.0);
+    let goal = Vector2::new(1.2, 0.0);
+    let path = dual_rrt_connect_state(
+        &start,
+        &goal,
+        is_free,
+        || Vector2::from_column_slice(&random_sample()),
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(path[path.len() - 1], goal);
+    assert!(path.iter().all(is_free));
+    assert_eq!(Vector2::<f64>::from_slice(&[1.0]), None);
+    assert_eq!(State::as_slice(&Vector2::new(1.0, 2.0)), &[1.0, 2.0]);
+
+    let path = dual_rrt_connect_state(
+        &DVector::from_vec(vec![-1.2, 0.0]),
+        &DVector::from_vec(vec![1.2, 0.0]),
+        |p: &DVector<f64>| world.is_free(p.as_slice()),
+        || DVector::from_vec(random_sample()),
+        &DualRrtConnectOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    assert_eq!(path[path.len() - 1], DVector::from_vec(vec![1.2, 0.0]));
+    assert_eq!(
+        DVector::<f64>::from_slice(&[1.0, 2.0, 3.0]).map(|v| v.len()),
+        Some(3)
+    );
+}
@@ -0,0 +1,449 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, DualRrtConnectOptions, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// tree whose edges are the trajectories of the steering function
+#[derive(Debug)]
+struct SteerTree<N>
+where
+    N: Float + Debug,
+{
+    tree: Tree<N>,
+    /// states along the edge to each vertex in the direction of time, from the
+    /// parent (exclusive) to the vertex (inclusive) in the start tree, and from the
+    /// vertex (exclusive) to the parent (inclusive) in the goal tree
+    edges: Vec<Vec<Vec<N>>>,
+}
+
+impl<N> SteerTree<N>
+where
+    N: Float + Debug,
+{
+    fn new(name: &'static str, root: &[N]) -> Self {
+        let mut tree = Tree::with_name(name, root.len());
+        tree.add_vertex(root);
+        Self {
+            tree,
+            edges: vec![Vec::new()],
+        }
+    }
+
+    fn is_start(&self) -> bool {
+        self.tree.name == "start"
+    }
+
+    /// Returns the indices of `num_candidates` nearest vertices to `q_target`, by the
+    /// euclidean distance or by `metric` in the direction of time.
+    fn nearest(
+        &self,
+        q_target: &[N],
+        num_candidates: usize,
+        metric: Option<&dyn Steering<N>>,
+    ) -> Vec<usize> {
+        let Some(metric) = metric else {
+            return self
+                .tree
+                .nearest_k(q_target, num_candidates)
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect();
+        };
+        let mut distances = (0..self.tree.len())
+            .map(|index| {
+                let q = self.tree.vertex(index);
+                if self.is_start() {
+                    (metric.distance(q, q_target), index)
+                } else {
+                    (metric.distance(q_target, q), index)
+                }
+            })
+            .collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        distances
+            .into_iter()
+            .take(num_candidates)
+            .map(|(_, index)| index)
+            .collect()
+    }
+
+    /// Steers between one of `num_candidates` nearest vertices and `q_target` in the
+    /// direction of time, and adds the vertex at most `extend_length` (along the
+    /// trajectory) away from it, or at `q_target` if `extend_length` is `None`.
+    /// Returns the index of the vertex, and whether it is `q_target`.
+    fn extend<FF, FS>(
+        &mut self,
+        q_target: &[N],
+        extend_length: Option<N>,
+        num_candidates: usize,
+        metric: Option<&dyn Steering<N>>,
+        is_free: &mut FF,
+        steer: &mut FS,
+    ) -> Option<(usize, bool)>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FS: FnMut(&[N], &[N]) -> Option<Vec<Vec<N>>>,
+    {
+        self.nearest(q_target, num_candidates, metric)
+            .into_iter()
+            .find_map(|index| self.extend_from(index, q_target, extend_length, is_free, steer))
+    }
+
+    fn extend_from<FF, FS>(
+        &mut self,
+        nearest_index: usize,
+        q_target: &[N],
+        extend_length: Option<N>,
+        is_free: &mut FF,
+        steer: &mut FS,
+    ) -> Option<(usize, bool)>
+    where
+        FF: FnMut(&[N]) -> bool,
+        FS: FnMut(&[N], &[N]) -> Option<Vec<Vec<N>>>,
+    {
+        let nearest_q = self.tree.vertex(nearest_index).to_vec();
+        let mut states = if self.is_start() {
+            steer(&nearest_q, q_target)?
+        } else {
+            // from q_target (exclusive) to the nearest vertex (inclusive)
+            let mut states = steer(q_target, &nearest_q)?;
+            states.pop()?;
+            states.reverse();
+            states.push(q_target.to_vec());
+            states
+        };
+        // now states are from the nearest vertex (exclusive) to q_target (inclusive)
+        if states.is_empty() {
+            return None;
+        }
+        let num_states = states.len();
+        let mut len = num_states;
+        if let Some(extend_length) = extend_length {
+            let mut length = N::zero();
+            let mut previous = &nearest_q;
+            for (i, q) in states.iter().enumerate() {
+                length = length + squared_euclidean(previous, q).sqrt();
+                previous = q;
+                if length >= extend_length {
+                    len = i + 1;
+                    break;
+                }
+            }
+        }
+        states.truncate(len);
+        if !states.iter().all(|q| is_free(q)) {
+            return None;
+        }
+        let reached = len == num_states;
+        let new_index = self.tree.add_vertex(&states[len - 1]);
+        self.tree.set_parent(nearest_index, new_index);
+        if !self.is_start() {
+            // in the direction of time, from the new vertex to the nearest one
+            states.pop();
+            states.reverse();
+            states.push(nearest_q);
+        }
+        self.edges.push(states);
+        Some((new_index, reached))
+    }
+
+    /// states from the root to the vertex at `index` (inclusive) in the start tree,
+    /// or from the vertex (exclusive) to the root (inclusive) in the goal tree
+    fn states(&self, index: usize) -> Vec<Vec<N>> {
+        let mut edges = Vec::new();
+        let mut current = index;
+        while let Some(parent) = self.tree.parent(current) {
+            edges.push(&self.edges[current]);
+            current = parent;
+        }
+        if self.is_start() {
+            let mut states = vec![self.tree.vertex(0).to_vec()];
+            for edge in edges.into_iter().rev() {
+                states.extend_from_slice(edge);
+            }
+            states
+        } else {
+            edges.into_iter().flatten().cloned().collect()
+        }
+    }
+}
+
+/// search the trajectory from start to goal which is free, using random_sample function
+/// and `steer`, an exact two-point boundary value solver, for non-holonomic or
+/// kinodynamic systems.
+///
+/// `steer(from, to)` returns the states along the trajectory from `from` to `to` at
+/// small intervals, excluding `from` and ending exactly at `to`, or `None` if there is
+/// no trajectory, e.g. Dubins or Reeds-Shepp curves, or the solutions of LQR.
+/// Both trees use it in the direction of time: the start tree steers from its vertices
+/// and the goal tree steers to its vertices. The trees are extended by the states up to
+/// `extend_length` along the trajectories, and connected when the trajectory between
+/// them is free. All states are checked with `is_free`, and all of them are returned.
+/// The nearest vertices are chosen by the euclidean distance, and
+/// `num_nearest_candidates` of them are tried in order until `steer` succeeds and its
+/// trajectory is free. `tree_balance`, `tree_bias` and `heuristic_weight` of the options
+/// are not used.
+pub fn dual_rrt_connect_steer<FF, FS, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    steer: FS,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FS: FnMut(&[N], &[N]) -> Option<Vec<Vec<N>>>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_steer_with_rng(
+        start,
+        goal,
+        is_free,
+        steer,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_steer`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_steer_with_rng<FF, FS, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    steer: FS,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FS: FnMut(&[N], &[N]) -> Option<Vec<Vec<N>>>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    connect_steer(
+        start,
+        goal,
+        is_free,
+        steer,
+        None,
+        random_sample,
+        options,
+        rng,
+    )
+}
+
+/// Steering function and its distance for [`dual_rrt_connect_steering`], e.g.
+/// [`Dubins`](crate::Dubins).
+///
+/// Unlike [`StateSpace`](crate::StateSpace), the distance is the length of a feasible
+/// trajectory, which may not be symmetric, and steering gives the whole trajectory.
+pub trait Steering<N> {
+    /// Returns the length of the trajectory from `from` to `to`, or infinity if there is
+    /// no trajectory.
+    fn distance(&self, from: &[N], to: &[N]) -> N;
+
+    /// Returns the states along the trajectory from `from` to `to` at small intervals,
+    /// excluding `from` and ending exactly at `to`, or `None` if there is no trajectory.
+    fn steer(&self, from: &[N], to: &[N]) -> Option<Vec<Vec<N>>>;
+}
+
+/// search the trajectory from start to goal which is free, using random_sample function
+/// and `steering`, both as the steering function and as the distance of the nearest
+/// vertices.
+///
+/// This is [`dual_rrt_connect_steer`] whose nearest vertices are chosen by the length
+/// of the trajectories to them in the direction of time, by a linear scan over the
+/// vertices, so the trees grow along the feasible trajectories.
+pub fn dual_rrt_connect_steering<FF, S, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    steering: &S,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    S: Steering<N>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    dual_rrt_connect_steering_with_rng(
+        start,
+        goal,
+        is_free,
+        steering,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`dual_rrt_connect_steering`] which takes all randomness from `rng`.
+pub fn dual_rrt_connect_steering_with_rng<FF, S, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    steering: &S,
+    random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    S: Steering<N>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    connect_steer(
+        start,
+        goal,
+        is_free,
+        |from: &[N], to: &[N]| steering.steer(from, to),
+        Some(steering),
+        random_sample,
+        options,
+        rng,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn connect_steer<FF, FS, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut steer: FS,
+    metric: Option<&dyn Steering<N>>,
+    mut random_sample: FR,
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FS: FnMut(&[N], &[N]) -> Option<Vec<Vec<N>>>,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    let num_candidates = options.num_nearest_candidates.max(1);
+    let mut start_tree = SteerTree::new("start", start);
+    let mut goal_tree = SteerTree::new("goal", goal);
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        if let Some((new_index, _)) = tree_a.extend(
+            &q_rand,
+            Some(options.extend_length),
+            num_candidates,
+            metric,
+            &mut is_free,
+            &mut steer,
+        ) {
+            let q_new = tree_a.tree.vertex(new_index).to_vec();
+            if let Some((reach_index, true)) = tree_b.extend(
+                &q_new,
+                None,
+                num_candidates,
+                metric,
+                &mut is_free,
+                &mut steer,
+            ) {
+                debug!(
+                    "connected with {} vertices",
+                    tree_a.tree.len() + tree_b.tree.len()
+                );
+                let (start_tree, start_index, goal_tree, goal_index) = if tree_a.is_start() {
+                    (&*tree_a, new_index, &*tree_b, reach_index)
+                } else {
+                    (&*tree_b, reach_index, &*tree_a, new_index)
+                };
+                // the vertices at both ends of the connection are the same state
+                let mut path = start_tree.states(start_index);
+                path.extend(goal_tree.states(goal_index));
+                return Ok(path);
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn dual_rrt_connect_steer_works() {
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    // moves only toward +x, first along x and then along y
+    let steer = |from: &[f64], to: &[f64]| {
+        if to[0] < from[0] {
+            return None;
+        }
+        let mut states = Vec::new();
+        let mut q = from.to_vec();
+        for axis in 0..2 {
+            let num_steps = ((to[axis] - from[axis]).abs() / 0.02).ceil() as usize;
+            for step in 1..num_steps {
+                q[axis] = from[axis] + (to[axis] - from[axis]) * step as f64 / num_steps as f64;
+                states.push(q.clone());
+            }
+            q[axis] = to[axis];
+            if num_steps > 0 {
+                states.push(q.clone());
+            }
+        }
+        Some(states)
+    };
+    let path = dual_rrt_connect_steer(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        steer,
+        world.sampler(1),
+        &DualRrtConnectOptions {
+            num_nearest_candidates: 5,
+            ..DualRrtConnectOptions::new(0.2, 5000)
+        },
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.2, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.2, 0.0]);
+    assert!(path.iter().all(|q| is_free(q)));
+    for segment in path.windows(2) {
+        // every state is on a trajectory of steer
+        assert!(segment[1][0] >= segment[0][0]);
+        assert!(segment[1][0] == segment[0][0] || segment[1][1] == segment[0][1]);
+        assert!(squared_euclidean(&segment[0], &segment[1]).sqrt() <= 0.02 + 1e-9);
+    }
+}
@@ -0,0 +1,165 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Invariant checks for planner outputs, to be used in tests and fuzzing.
+//!
+//! Each check returns `Err` with a description of the first violation.
+//! This module requires the `test-util` feature.
+
+use crate::{is_edge_free, path_length, BiRrtStarSolution, Tree};
+use num_traits::float::Float;
+use std::fmt::Debug;
+
+/// Checks that `path` starts at `start` and ends at `goal`.
+pub fn check_path_endpoints<N>(path: &[Vec<N>], start: &[N], goal: &[N]) -> Result<(), String>
+where
+    N: Float + Debug,
+{
+    match (path.first(), path.last()) {
+        (Some(first), Some(last)) => {
+            if first.as_slice() != start {
+                return Err(format!("path starts at {first:?}, not {start:?}"));
+            }
+            if last.as_slice() != goal {
+                return Err(format!("path ends at {last:?}, not {goal:?}"));
+            }
+            Ok(())
+        }
+        _ => Err("path is empty".to_string()),
+    }
+}
+
+/// Checks that every waypoint of `path`, and every point between them
+/// at `resolution` intervals, is free.
+pub fn check_path_is_free<FF, N>(
+    path: &[Vec<N>],
+    mut is_free: FF,
+    resolution: N,
+) -> Result<(), String>
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + Debug,
+{
+    assert!(resolution > N::zero());
+    for (i, q) in path.iter().enumerate() {
+        if !is_free(q) {
+            return Err(format!("waypoint {i} {q:?} is not free"));
+        }
+    }
+    for (i, segment) in path.windows(2).enumerate() {
+        let mut collided = None;
+        let mut check = |q: &[N]| {
+            let free = is_free(q);
+            if !free {
+                collided = Some(q.to_vec());
+            }
+            free
+        };
+        if !is_edge_free(&segment[0], &segment[1], &mut check, resolution) {
+            return Err(format!(
+                "{:?} between waypoint {i} and {} is not free",
+                collided.unwrap_or_default(),
+                i + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every parent link of `tree` points to an existing vertex
+/// and that following them from any vertex reaches a root.
+pub fn check_tree_is_acyclic<N>(tree: &Tree<N>) -> Result<(), String>
+where
+    N: Float + Debug,
+{
+    for index in 0..tree.len() {
+        let mut current = index;
+        let mut depth = 0;
+        while let Some(parent) = tree.parent(current) {
+            if parent >= tree.len() {
+                return Err(format!("vertex {current} has invalid parent {parent}"));
+            }
+            depth += 1;
+            if depth > tree.len() {
+                return Err(format!("vertex {index} is in a cycle"));
+            }
+            current = parent;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the cost of `solution` is the length of its path, and that the cost to
+/// the end of the path in its merged tree is the same.
+pub fn check_solution_cost<N>(solution: &BiRrtStarSolution<N>) -> Result<(), String>
+where
+    N: Float + Debug,
+{
+    check_tree_is_acyclic(&solution.tree)?;
+    let length = path_length(&solution.path);
+    let tolerance = N::from(1e-9).unwrap() * length.max(N::one());
+    if (solution.cost - length).abs() > tolerance {
+        return Err(format!(
+            "cost {:?} is not the length of the path {length:?}",
+            solution.cost
+        ));
+    }
+    let last = solution.path.last().ok_or("path is empty")?;
+    let goal = (0..solution.tree.len())
+        .find(|i| solution.tree.vertex(*i) == last.as_slice())
+        .ok_or_else(|| format!("the end of the path {last:?} is not in the tree"))?;
+    let tree_cost = solution.tree.cost_to(goal);
+    if (tree_cost - solution.cost).abs() > tolerance {
+        return Err(format!(
+            "cost to the end of the path in the tree {tree_cost:?} is not the cost {:?}",
+            solution.cost
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_util_works() {
+    let world = crate::scenario::World::square();
+    let path = vec![vec![-1.2, 0.0], vec![0.0, 1.2], vec![1.2, 0.0]];
+    let is_free = |p: &[f64]| world.is_free(p);
+    assert!(check_path_endpoints(&path, &[-1.2, 0.0], &[1.2, 0.0]).is_ok());
+    assert!(check_path_endpoints(&path, &[-1.2, 0.0], &[1.0, 0.0]).is_err());
+    assert!(check_path_is_free(&path, is_free, 0.01).is_err());
+
+    let mut tree = Tree::new(1);
+    tree.add_vertex(&[0.0]);
+    tree.add_vertex(&[1.0]);
+    tree.add_edge(0, 1).unwrap();
+    assert!(check_tree_is_acyclic(&tree).is_ok());
+    // add_edge rejects cycles
+    tree.set_parent(1, 0);
+    assert!(check_tree_is_acyclic(&tree).is_err());
+
+    let solution = crate::birrtstar_merged(
+        &[-1.2, 0.0],
+        &[1.2, 0.0],
+        is_free,
+        world.sampler(1),
+        &crate::BiRrtStarOptions::new(0.2, 1000, 0.01),
+    )
+    .unwrap();
+    assert!(check_solution_cost(&solution).is_ok());
+    assert!(check_path_is_free(&solution.path, is_free, 0.01).is_ok());
+    let mut wrong = solution;
+    wrong.cost *= 0.9;
+    assert!(check_solution_cost(&wrong).is_err());
+}
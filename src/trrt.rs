@@ -0,0 +1,245 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{check_start_goal, is_valid_point, Sampler, Tree};
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::Rng;
+use std::fmt::Debug;
+use tracing::debug;
+
+/// Parameters of [`trrt`].
+#[derive(Debug, Clone)]
+pub struct TrrtOptions<N> {
+    /// unit length of extend
+    pub extend_length: N,
+    /// max repeat num
+    pub num_max_try: usize,
+    /// probability (0.0 - 1.0) to extend toward the goal instead of a random sample
+    pub goal_bias: f64,
+    /// configurations whose cost is above this are never reached
+    pub max_cost: N,
+    /// temperature at the beginning
+    pub initial_temperature: N,
+    /// factor (> 1.0) to raise or lower the temperature
+    pub temperature_factor: N,
+    /// number of rejected transitions in a row to raise the temperature
+    pub max_failures: usize,
+}
+
+impl<N> TrrtOptions<N>
+where
+    N: Float,
+{
+    /// Creates options with 0.05 goal bias, no max cost, initial temperature 1.0,
+    /// temperature factor 2.0 and 10 max failures.
+    pub fn new(extend_length: N, num_max_try: usize) -> Self {
+        Self {
+            extend_length,
+            num_max_try,
+            goal_bias: 0.05,
+            max_cost: N::infinity(),
+            initial_temperature: N::one(),
+            temperature_factor: N::from(2.0).unwrap(),
+            max_failures: 10,
+        }
+    }
+}
+
+/// Metropolis-like transition test of T-RRT with adaptive temperature
+#[derive(Debug)]
+struct TransitionTest<'a, N> {
+    options: &'a TrrtOptions<N>,
+    temperature: N,
+    num_failures: usize,
+    /// range of the costs of the vertices
+    min_cost: N,
+    max_cost: N,
+}
+
+impl<N> TransitionTest<'_, N>
+where
+    N: Float + Debug,
+{
+    fn add_cost(&mut self, cost: N) {
+        self.min_cost = self.min_cost.min(cost);
+        self.max_cost = self.max_cost.max(cost);
+    }
+    fn accept<R: Rng>(&mut self, cost_from: N, cost_to: N, rng: &mut R) -> bool {
+        if cost_to > self.options.max_cost {
+            return false;
+        }
+        if cost_to <= cost_from {
+            return true;
+        }
+        let climb = cost_to - cost_from;
+        let probability = (-climb / self.temperature).exp();
+        if N::from(rng.gen::<f64>()).unwrap() < probability {
+            // lower the temperature more for a larger climb, relative to the
+            // cost range of the vertices
+            let range = self.max_cost - self.min_cost;
+            let exponent = if range > N::zero() {
+                climb / (N::from(0.1).unwrap() * range)
+            } else {
+                N::one()
+            };
+            self.temperature = self.temperature / self.options.temperature_factor.powf(exponent);
+            self.num_failures = 0;
+            true
+        } else {
+            self.num_failures += 1;
+            if self.num_failures > self.options.max_failures {
+                self.temperature = self.temperature * self.options.temperature_factor;
+                self.num_failures = 0;
+                debug!("temperature = {:?}", self.temperature);
+            }
+            false
+        }
+    }
+}
+
+/// search the path from start to goal which is free, preferring low cost regions
+/// of `cost`, using random_sample function (Transition-based RRT).
+///
+/// An extension which increases `cost` by `d` is accepted with the probability
+/// `exp(-d / temperature)`. The temperature is raised when too many extensions
+/// are rejected in a row, and lowered when a climbing extension is accepted.
+/// The goal is connected if it is free and its cost is `max_cost` or less.
+pub fn trrt<FF, FC, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    cost: FC,
+    random_sample: FR,
+    options: &TrrtOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FC: FnMut(&[N]) -> N,
+    FR: Sampler<N>,
+    N: Float + Debug,
+{
+    trrt_with_rng(
+        start,
+        goal,
+        is_free,
+        cost,
+        random_sample,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`trrt`] which takes all randomness from `rng`.
+pub fn trrt_with_rng<FF, FC, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut cost: FC,
+    mut random_sample: FR,
+    options: &TrrtOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FC: FnMut(&[N]) -> N,
+    FR: Sampler<N>,
+    N: Float + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    assert!(options.extend_length > N::zero());
+    assert!((0.0..=1.0).contains(&options.goal_bias));
+    assert!(options.temperature_factor > N::one());
+    let mut tree = Tree::with_name("start", start.len());
+    tree.add_vertex(start);
+    let mut costs = vec![cost(start)];
+    let mut test = TransitionTest {
+        options,
+        temperature: options.initial_temperature,
+        num_failures: 0,
+        min_cost: costs[0],
+        max_cost: costs[0],
+    };
+    for _ in 0..options.num_max_try {
+        let q_target = if rng.gen_bool(options.goal_bias) {
+            goal.to_vec()
+        } else {
+            random_sample.sample(rng)
+        };
+        if !is_valid_point(&q_target, start.len()) {
+            continue;
+        }
+        let nearest_index = tree.get_nearest_index(&q_target);
+        let nearest_q = tree.vertex(nearest_index);
+        let dist = squared_euclidean(nearest_q, &q_target).sqrt();
+        let q_new = if dist < options.extend_length {
+            q_target
+        } else {
+            nearest_q
+                .iter()
+                .zip(&q_target)
+                .map(|(near, target)| *near + (*target - *near) * options.extend_length / dist)
+                .collect()
+        };
+        if !is_free(&q_new) {
+            continue;
+        }
+        let new_cost = cost(&q_new);
+        if !test.accept(costs[nearest_index], new_cost, rng) {
+            continue;
+        }
+        let new_index = tree.add_vertex(&q_new);
+        tree.set_parent(nearest_index, new_index);
+        costs.push(new_cost);
+        test.add_cost(new_cost);
+        let dist_to_goal = squared_euclidean(&q_new, goal).sqrt();
+        if dist_to_goal < options.extend_length && is_free(goal) && cost(goal) <= options.max_cost {
+            debug!("reached the goal with {} vertices", tree.len());
+            let mut path = tree.get_until_root(new_index);
+            path.reverse();
+            path.push(q_new);
+            if dist_to_goal > N::zero() {
+                path.push(goal.to_vec());
+            }
+            return Ok(path);
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn trrt_works() {
+    let world = crate::scenario::World::square();
+    // a hill at the origin
+    let cost = |p: &[f64]| 10.0 * (-(p[0] * p[0] + p[1] * p[1])).exp();
+    let options = TrrtOptions {
+        max_cost: 5.0,
+        ..TrrtOptions::new(0.1, 30000)
+    };
+    let path = trrt(
+        &[-1.5, 0.0],
+        &[1.5, 0.0],
+        |_: &[f64]| true,
+        cost,
+        world.sampler(1),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-1.5, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![1.5, 0.0]);
+    assert!(path.iter().all(|q| cost(q) <= 5.0));
+}
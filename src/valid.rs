@@ -0,0 +1,170 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::Sampler;
+use rand::RngCore;
+use tracing::debug;
+
+/// Statistics of [`ValidStateSampler`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SamplingStatistics {
+    /// number of the samples returned to the planner
+    pub num_samples: usize,
+    /// number of the samples drawn from the base sampler
+    pub num_tries: usize,
+    /// number of the samples drawn from the base sampler which are free
+    pub num_accepted: usize,
+    /// number of the samples returned without finding a free one
+    pub num_failures: usize,
+}
+
+impl SamplingStatistics {
+    /// Returns the ratio of the free samples to the drawn samples, or 1.0 if nothing
+    /// is drawn yet.
+    ///
+    /// A low rate means that the space is overly constrained for the base sampler.
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.num_tries == 0 {
+            1.0
+        } else {
+            self.num_accepted as f64 / self.num_tries as f64
+        }
+    }
+}
+
+/// Sampler which draws samples of `base` until `is_free` returns `true`.
+///
+/// Up to `num_max_try` samples are drawn for each sample, and the last one is returned
+/// if none of them is free, so the planner does not stall in an overly constrained
+/// space. [`statistics`](Self::statistics) reports the acceptance rate to diagnose it.
+#[derive(Debug, Clone)]
+pub struct ValidStateSampler<S, FF> {
+    base: S,
+    is_free: FF,
+    num_max_try: usize,
+    statistics: SamplingStatistics,
+}
+
+impl<S, FF> ValidStateSampler<S, FF> {
+    /// `num_max_try` must be at least 1.
+    pub fn new(base: S, is_free: FF, num_max_try: usize) -> Self {
+        assert!(num_max_try > 0);
+        Self {
+            base,
+            is_free,
+            num_max_try,
+            statistics: SamplingStatistics::default(),
+        }
+    }
+
+    /// statistics of the samples so far
+    pub fn statistics(&self) -> &SamplingStatistics {
+        &self.statistics
+    }
+}
+
+impl<S, FF, N> Sampler<N> for ValidStateSampler<S, FF>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        self.statistics.num_samples += 1;
+        let mut q = self.base.sample(rng);
+        for i in 0..self.num_max_try {
+            if i > 0 {
+                q = self.base.sample(rng);
+            }
+            self.statistics.num_tries += 1;
+            if (self.is_free)(&q) {
+                self.statistics.num_accepted += 1;
+                return q;
+            }
+        }
+        self.statistics.num_failures += 1;
+        debug!(
+            "no free sample in {} tries, acceptance rate {}",
+            self.num_max_try,
+            self.statistics.acceptance_rate()
+        );
+        q
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+impl<S, FF, N> Sampler<N> for &mut ValidStateSampler<S, FF>
+where
+    S: Sampler<N>,
+    FF: FnMut(&[N]) -> bool,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        (**self).sample(rng)
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        (**self).add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        (**self).report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn valid_state_sampler_works() {
+    use crate::{dual_rrt_connect, UniformBoxSampler};
+    let world = crate::scenario::World::square();
+    let is_free = |p: &[f64]| world.is_free(p);
+    let mut sampler = ValidStateSampler::new(
+        UniformBoxSampler::new(&[-2.0, -2.0], &[2.0, 2.0]).unwrap(),
+        is_free,
+        100,
+    );
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        assert!(is_free(&sampler.sample(&mut rng)));
+    }
+    let statistics = *sampler.statistics();
+    assert_eq!(statistics.num_samples, 1000);
+    assert_eq!(statistics.num_accepted, 1000);
+    assert_eq!(statistics.num_failures, 0);
+    // a quarter of the box is in collision
+    let rate = statistics.acceptance_rate();
+    assert!((0.7..0.8).contains(&rate), "{rate}");
+    let path =
+        dual_rrt_connect(&[-1.2, 0.0], &[1.2, 0.0], is_free, &mut sampler, 0.2, 1000).unwrap();
+    assert!(path.iter().all(|q| is_free(q)));
+
+    let mut sampler = ValidStateSampler::new(|| vec![0.0, 0.0], is_free, 10);
+    assert_eq!(sampler.sample(&mut rng), vec![0.0, 0.0]);
+    assert_eq!(
+        *sampler.statistics(),
+        SamplingStatistics {
+            num_samples: 1,
+            num_tries: 10,
+            num_accepted: 0,
+            num_failures: 1,
+        }
+    );
+    assert_eq!(sampler.statistics().acceptance_rate(), 0.0);
+}
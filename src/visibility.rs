@@ -0,0 +1,188 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::check_start_goal;
+use num_traits::float::Float;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use tracing::debug;
+
+fn cross<N: Float>(o: [N; 2], a: [N; 2], b: [N; 2]) -> N {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn distance<N: Float>(a: [N; 2], b: [N; 2]) -> N {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+fn edges<N: Copy>(polygon: &[[N; 2]]) -> impl Iterator<Item = ([N; 2], [N; 2])> + '_ {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+
+/// `true` if `p` is on the segment `a`-`b` (including both ends)
+fn is_on_segment<N: Float>(p: [N; 2], a: [N; 2], b: [N; 2]) -> bool {
+    cross(a, b, p) == N::zero()
+        && p[0] >= a[0].min(b[0])
+        && p[0] <= a[0].max(b[0])
+        && p[1] >= a[1].min(b[1])
+        && p[1] <= a[1].max(b[1])
+}
+
+/// `true` if the two segments cross at a single point which is not an end of either segment
+fn is_crossing<N: Float>(a: [N; 2], b: [N; 2], c: [N; 2], d: [N; 2]) -> bool {
+    let d1 = cross(a, b, c);
+    let d2 = cross(a, b, d);
+    let d3 = cross(c, d, a);
+    let d4 = cross(c, d, b);
+    d1 * d2 < N::zero() && d3 * d4 < N::zero()
+}
+
+/// `true` if `p` is inside the polygon and not on its boundary
+fn is_inside<N: Float>(p: [N; 2], polygon: &[[N; 2]]) -> bool {
+    if edges(polygon).any(|(a, b)| is_on_segment(p, a, b)) {
+        return false;
+    }
+    let mut inside = false;
+    for (a, b) in edges(polygon) {
+        if (a[1] > p[1]) != (b[1] > p[1]) {
+            let x = a[0] + (p[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1]);
+            if p[0] < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn is_visible<N: Float>(a: [N; 2], b: [N; 2], obstacles: &[Vec<[N; 2]>]) -> bool {
+    let two = N::one() + N::one();
+    if obstacles
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .any(|(c, d)| is_crossing(a, b, c, d))
+    {
+        return false;
+    }
+    // The segment can still enter an obstacle through its vertices,
+    // so check a point between each pair of vertices on the segment.
+    let length = distance(a, b);
+    let mut ts = vec![N::zero(), N::one()];
+    for &v in obstacles.iter().flatten() {
+        if is_on_segment(v, a, b) && length > N::zero() {
+            ts.push(distance(a, v) / length);
+        }
+    }
+    ts.sort_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal));
+    ts.windows(2).all(|t| {
+        let t = (t[0] + t[1]) / two;
+        let p = [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t];
+        !obstacles.iter().any(|polygon| is_inside(p, polygon))
+    })
+}
+
+/// search the shortest path from start to goal in 2D, avoiding polygonal obstacles,
+/// using a visibility graph.
+///
+/// Each obstacle is a polygon given as a list of its vertices.
+/// Paths may touch the boundaries of obstacles, but never enter them.
+/// Returns an error if start or goal is not a finite 2D point, or a vertex of the
+/// obstacles is not finite.
+pub fn visibility_graph<N>(
+    start: &[N],
+    goal: &[N],
+    obstacles: &[Vec<[N; 2]>],
+) -> Result<Vec<Vec<N>>, String>
+where
+    N: Float + Debug,
+{
+    check_start_goal(start, goal)?;
+    if start.len() != 2 {
+        return Err(format!("start and goal must be 2D: {start:?}, {goal:?}"));
+    }
+    if let Some(v) = obstacles
+        .iter()
+        .flatten()
+        .find(|v| !v[0].is_finite() || !v[1].is_finite())
+    {
+        return Err(format!("vertex of an obstacle is not finite: {v:?}"));
+    }
+    let mut points = vec![[start[0], start[1]], [goal[0], goal[1]]];
+    if points
+        .iter()
+        .any(|p| obstacles.iter().any(|polygon| is_inside(*p, polygon)))
+    {
+        return Err("start or goal is inside an obstacle".to_string());
+    }
+    points.extend(obstacles.iter().flatten().copied());
+
+    // Dijkstra on the (dense) visibility graph
+    let num_points = points.len();
+    let mut costs = vec![N::infinity(); num_points];
+    let mut parents: Vec<Option<usize>> = vec![None; num_points];
+    let mut done = vec![false; num_points];
+    costs[0] = N::zero();
+    while let Some(current) = (0..num_points)
+        .filter(|i| !done[*i] && costs[*i] < N::infinity())
+        .min_by(|a, b| costs[*a].partial_cmp(&costs[*b]).unwrap_or(Ordering::Equal))
+    {
+        if current == 1 {
+            let mut path = vec![goal.to_vec()];
+            let mut index = current;
+            while let Some(parent) = parents[index] {
+                path.push(points[parent].to_vec());
+                index = parent;
+            }
+            path.reverse();
+            return Ok(path);
+        }
+        done[current] = true;
+        for next in 0..num_points {
+            if done[next] {
+                continue;
+            }
+            let cost = costs[current] + distance(points[current], points[next]);
+            if cost < costs[next] && is_visible(points[current], points[next], obstacles) {
+                debug!("{:?} -> {:?}", points[current], points[next]);
+                costs[next] = cost;
+                parents[next] = Some(current);
+            }
+        }
+    }
+    Err("failed".to_string())
+}
+
+#[test]
+fn visibility_graph_works() {
+    let obstacles = vec![vec![[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]];
+    let result = visibility_graph(&[-2.0, 0.0], &[2.0, 0.0], &obstacles).unwrap();
+    assert_eq!(result.len(), 4);
+    let length: f64 = result
+        .windows(2)
+        .map(|w| (w[0][0] - w[1][0]).hypot(w[0][1] - w[1][1]))
+        .sum();
+    assert!((length - (2.0 * 2.0f64.sqrt() + 2.0)).abs() < 1e-9);
+    assert!(visibility_graph(&[0.0, 0.0], &[2.0, 0.0], &obstacles).is_err());
+    // invalid inputs
+    assert!(visibility_graph(&[-2.0], &[2.0], &obstacles).is_err());
+    assert!(visibility_graph(&[-2.0, 0.0, 0.0], &[2.0, 0.0, 0.0], &obstacles).is_err());
+    assert!(visibility_graph(&[-2.0, 0.0], &[2.0, 0.0, 0.0], &obstacles).is_err());
+    assert!(visibility_graph(&[f64::NAN, 0.0], &[2.0, 0.0], &obstacles).is_err());
+    let nan_obstacles = vec![vec![[-1.0, -1.0], [f64::NAN, -1.0], [1.0, 1.0]]];
+    assert!(visibility_graph(&[-2.0, 0.0], &[2.0, 0.0], &nan_obstacles).is_err());
+}
@@ -0,0 +1,158 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::sampler::uniform_in_ball;
+use crate::Sampler;
+use kdtree::distance::squared_euclidean;
+use num_traits::float::Float;
+use rand::{Rng, RngCore};
+
+/// Sampler which biases the samples of `base` toward the configurations whose workspace
+/// projection is in the ball of `tolerance` around `goal`.
+///
+/// `projection` maps a configuration to the workspace, e.g. the forward kinematics of the
+/// end effector. `inverse` is a hint to find a configuration near the seed configuration
+/// whose projection is the target in the workspace, e.g. a numerical IK with a few
+/// iterations, and returns `None` if it fails. With the probability `bias` (0.0 - 1.0),
+/// a target is drawn uniformly in the goal region, and the configuration found by
+/// `inverse` from a sample of `base` is returned if its projection is in the goal region.
+/// Otherwise the sample of `base` is returned.
+///
+/// This guides high-DOF arms to the workspace goal, where a goal bias in the
+/// configuration space is not possible or not enough.
+#[derive(Debug, Clone)]
+pub struct WorkspaceBiasedSampler<S, FP, FI, N> {
+    base: S,
+    projection: FP,
+    inverse: FI,
+    goal: Vec<N>,
+    tolerance: N,
+    bias: f64,
+}
+
+impl<S, FP, FI, N> WorkspaceBiasedSampler<S, FP, FI, N>
+where
+    S: Sampler<N>,
+    FP: FnMut(&[N]) -> Vec<N>,
+    FI: FnMut(&[N], &[N]) -> Option<Vec<N>>,
+    N: Float,
+{
+    /// `inverse` takes the target in the workspace and the seed configuration.
+    pub fn new(base: S, projection: FP, inverse: FI, goal: &[N], tolerance: N, bias: f64) -> Self {
+        assert!(tolerance >= N::zero());
+        assert!((0.0..=1.0).contains(&bias));
+        Self {
+            base,
+            projection,
+            inverse,
+            goal: goal.to_vec(),
+            tolerance,
+            bias,
+        }
+    }
+
+    /// Returns `true` if the projection of `q` is in the goal region.
+    pub fn is_goal(&mut self, q: &[N]) -> bool {
+        let p = (self.projection)(q);
+        p.len() == self.goal.len() && squared_euclidean(&p, &self.goal).sqrt() <= self.tolerance
+    }
+}
+
+impl<S, FP, FI, N> Sampler<N> for WorkspaceBiasedSampler<S, FP, FI, N>
+where
+    S: Sampler<N>,
+    FP: FnMut(&[N]) -> Vec<N>,
+    FI: FnMut(&[N], &[N]) -> Option<Vec<N>>,
+    N: Float,
+{
+    fn sample(&mut self, rng: &mut dyn RngCore) -> Vec<N> {
+        let seed = self.base.sample(rng);
+        if !rng.gen_bool(self.bias) {
+            return seed;
+        }
+        let target = uniform_in_ball(&self.goal, self.tolerance, rng);
+        match (self.inverse)(&target, &seed) {
+            Some(q) if q.len() == seed.len() && self.is_goal(&q) => q,
+            _ => seed,
+        }
+    }
+
+    fn add_vertex(&mut self, q: &[N]) {
+        self.base.add_vertex(q);
+    }
+
+    fn report_stall(&mut self, num_stalled: usize) {
+        self.base.report_stall(num_stalled);
+    }
+}
+
+#[test]
+fn workspace_biased_sampler_works() {
+    use crate::{rrt, RrtOptions, UniformBoxSampler};
+    use std::f64::consts::PI;
+    // a planar arm of two links of length 1
+    let forward = |q: &[f64]| {
+        vec![
+            q[0].cos() + (q[0] + q[1]).cos(),
+            q[0].sin() + (q[0] + q[1]).sin(),
+        ]
+    };
+    // a few steps of the Jacobian transpose method
+    let inverse = |target: &[f64], seed: &[f64]| {
+        let mut q = seed.to_vec();
+        for _ in 0..100 {
+            let p = forward(&q);
+            let e = [target[0] - p[0], target[1] - p[1]];
+            let (s0, c0) = q[0].sin_cos();
+            let (s01, c01) = (q[0] + q[1]).sin_cos();
+            let j = [[-s0 - s01, -s01], [c0 + c01, c01]];
+            q[0] += 0.2 * (j[0][0] * e[0] + j[1][0] * e[1]);
+            q[1] += 0.2 * (j[0][1] * e[0] + j[1][1] * e[1]);
+        }
+        Some(q)
+    };
+    let goal = [0.0, 1.5];
+    let base = UniformBoxSampler::new(&[-PI, -PI], &[PI, PI]).unwrap();
+    let mut sampler = WorkspaceBiasedSampler::new(base, forward, inverse, &goal, 0.1, 0.5);
+    let mut rng = rand::thread_rng();
+    let num_goals = (0..1000)
+        .filter(|_| {
+            let q = sampler.sample(&mut rng);
+            sampler.is_goal(&q)
+        })
+        .count();
+    // the goal region is a small part of the configuration space
+    assert!(num_goals > 400, "{num_goals}");
+
+    // plan to a configuration reaching the goal
+    let mut q_goal = None;
+    while q_goal.is_none() {
+        let q = sampler.sample(&mut rng);
+        if sampler.is_goal(&q) {
+            q_goal = Some(q);
+        }
+    }
+    let path = rrt(
+        &[0.0, 0.0],
+        &q_goal.unwrap(),
+        |_: &[f64]| true,
+        sampler,
+        &RrtOptions::new(0.2, 5000),
+    )
+    .unwrap();
+    let last = &path[path.len() - 1];
+    assert!((forward(last)[0] - goal[0]).hypot(forward(last)[1] - goal[1]) <= 0.1 + 1e-9);
+}
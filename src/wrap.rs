@@ -0,0 +1,371 @@
+/*
+  Copyright 2017 Takashi Ogura
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::hybrid_astar::normalize_angle;
+use crate::{
+    check_start_goal, is_valid_point, DualRrtConnectOptions, ExtendStatus, Sampler, StateSpace,
+    Tree,
+};
+use num_traits::float::Float;
+use num_traits::FloatConst;
+use rand::Rng;
+use std::fmt::Debug;
+use std::mem;
+use tracing::debug;
+
+/// Returns `q` whose angles are normalized to [-pi, pi).
+fn normalize<N: Float + FloatConst>(q: &[N], is_angle: &[bool]) -> Vec<N> {
+    q.iter()
+        .zip(is_angle)
+        .map(|(v, angle)| if *angle { normalize_angle(*v) } else { *v })
+        .collect()
+}
+
+/// Returns `b - a`, whose angles are the shortest differences.
+fn difference<N: Float + FloatConst>(a: &[N], b: &[N], is_angle: &[bool]) -> Vec<N> {
+    a.iter()
+        .zip(b)
+        .zip(is_angle)
+        .map(|((a, b), angle)| {
+            if *angle {
+                normalize_angle(*b - *a)
+            } else {
+                *b - *a
+            }
+        })
+        .collect()
+}
+
+fn norm<N: Float>(v: &[N]) -> N {
+    v.iter().fold(N::zero(), |sum, v| sum + *v * *v).sqrt()
+}
+
+/// Returns the index of the nearest vertex to `q` with the distance wrapping at +-pi.
+///
+/// The nearest vertex by the euclidean distance bounds the wrapped distance, and
+/// only the images of `q` shifted by 2 pi in the angles within the bound from +-pi
+/// can be nearer.
+fn nearest_wrapped<N>(tree: &Tree<N>, q: &[N], is_angle: &[bool]) -> usize
+where
+    N: Float + FloatConst + Debug,
+{
+    let (mut nearest, bound) = tree.nearest_k(q, 1)[0];
+    let mut best = norm(&difference(tree.vertex(nearest), q, is_angle));
+    let near_dims = (0..q.len())
+        .filter(|i| is_angle[*i] && (q[*i] + N::PI() < bound || N::PI() - q[*i] < bound))
+        .collect::<Vec<_>>();
+    let two_pi = N::PI() + N::PI();
+    for mask in 1..(1usize << near_dims.len()) {
+        let mut image = q.to_vec();
+        for (bit, i) in near_dims.iter().enumerate() {
+            if mask & (1 << bit) != 0 {
+                image[*i] = if q[*i] < N::zero() {
+                    q[*i] + two_pi
+                } else {
+                    q[*i] - two_pi
+                };
+            }
+        }
+        let (index, _) = tree.nearest_k(&image, 1)[0];
+        let dist = norm(&difference(tree.vertex(index), q, is_angle));
+        if dist < best {
+            best = dist;
+            nearest = index;
+        }
+    }
+    nearest
+}
+
+fn extend_wrapped<FF, N>(
+    tree: &mut Tree<N>,
+    q_target: &[N],
+    extend_length: N,
+    is_free: &mut FF,
+    is_angle: &[bool],
+) -> ExtendStatus
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float + FloatConst + Debug,
+{
+    let nearest_index = nearest_wrapped(tree, q_target, is_angle);
+    let nearest_q = tree.vertex(nearest_index);
+    let diff = difference(nearest_q, q_target, is_angle);
+    let dist = norm(&diff);
+    let q_new = if dist < extend_length {
+        q_target.to_vec()
+    } else {
+        let q_new = nearest_q
+            .iter()
+            .zip(&diff)
+            .map(|(near, d)| *near + *d * extend_length / dist)
+            .collect::<Vec<_>>();
+        normalize(&q_new, is_angle)
+    };
+    if !is_free(&q_new) {
+        return ExtendStatus::Trapped;
+    }
+    let new_index = tree.add_vertex(&q_new);
+    tree.set_parent(nearest_index, new_index);
+    if dist < extend_length {
+        ExtendStatus::Reached(new_index)
+    } else {
+        ExtendStatus::Advanced(new_index)
+    }
+}
+
+/// search the path from start to goal which is free, using random_sample function,
+/// treating the dimensions marked in `is_angle` as angles of revolute joints which
+/// can rotate without limits.
+///
+/// The distance, the nearest neighbor search and the extension of the trees wrap
+/// at +-pi in the angles, so the path can go the short way across +-pi.
+/// The angles of the path are normalized to [-pi, pi), so two consecutive
+/// configurations may differ by about 2 pi in an angle, which means the short way.
+/// [`unwrap_angles`] makes them continuous. `tree_balance`, `tree_bias` and
+/// `num_nearest_candidates` of the options are not used.
+pub fn wrapped_rrt_connect<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    is_free: FF,
+    random_sample: FR,
+    is_angle: &[bool],
+    options: &DualRrtConnectOptions<N>,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + FloatConst + Debug,
+{
+    wrapped_rrt_connect_with_rng(
+        start,
+        goal,
+        is_free,
+        random_sample,
+        is_angle,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// [`wrapped_rrt_connect`] which takes all randomness from `rng`.
+pub fn wrapped_rrt_connect_with_rng<FF, FR, N, R>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut random_sample: FR,
+    is_angle: &[bool],
+    options: &DualRrtConnectOptions<N>,
+    rng: &mut R,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Sampler<N>,
+    N: Float + FloatConst + Debug,
+    R: Rng,
+{
+    check_start_goal(start, goal)?;
+    if is_angle.len() != start.len() {
+        return Err(format!(
+            "is_angle has {} dimensions, but start has {}",
+            is_angle.len(),
+            start.len()
+        ));
+    }
+    assert!(options.extend_length > N::zero());
+    let mut start_tree = Tree::with_name("start", start.len());
+    start_tree.add_vertex(&normalize(start, is_angle));
+    let mut goal_tree = Tree::with_name("goal", goal.len());
+    goal_tree.add_vertex(&normalize(goal, is_angle));
+    let mut tree_a = &mut start_tree;
+    let mut tree_b = &mut goal_tree;
+    for _ in 0..options.num_max_try {
+        let q_rand = random_sample.sample(rng);
+        if !is_valid_point(&q_rand, start.len()) {
+            mem::swap(&mut tree_a, &mut tree_b);
+            continue;
+        }
+        let q_rand = normalize(&q_rand, is_angle);
+        if let ExtendStatus::Advanced(new_index) | ExtendStatus::Reached(new_index) = extend_wrapped(
+            tree_a,
+            &q_rand,
+            options.extend_length,
+            &mut is_free,
+            is_angle,
+        ) {
+            let q_new = tree_a.vertex(new_index).to_vec();
+            let reach_index = loop {
+                match extend_wrapped(
+                    tree_b,
+                    &q_new,
+                    options.extend_length,
+                    &mut is_free,
+                    is_angle,
+                ) {
+                    ExtendStatus::Trapped => break None,
+                    ExtendStatus::Reached(index) => break Some(index),
+                    ExtendStatus::Advanced(_) => {}
+                }
+            };
+            if let Some(reach_index) = reach_index {
+                debug!("connected with {} vertices", tree_a.len() + tree_b.len());
+                let mut path = tree_a.get_until_root(new_index);
+                path.reverse();
+                path.push(q_new);
+                path.extend(tree_b.get_until_root(reach_index));
+                if tree_b.name == "start" {
+                    path.reverse();
+                }
+                return Ok(path);
+            }
+        }
+        mem::swap(&mut tree_a, &mut tree_b);
+    }
+    Err("failed".to_string())
+}
+
+/// Returns the path whose angles marked in `is_angle` are shifted by multiples of 2 pi
+/// to be continuous, taking the short way between consecutive configurations.
+///
+/// The first configuration is not changed.
+pub fn unwrap_angles<N>(path: &[Vec<N>], is_angle: &[bool]) -> Vec<Vec<N>>
+where
+    N: Float + FloatConst,
+{
+    let mut unwrapped = Vec::with_capacity(path.len());
+    let Some(first) = path.first() else {
+        return unwrapped;
+    };
+    unwrapped.push(first.clone());
+    for q in &path[1..] {
+        let previous = &unwrapped[unwrapped.len() - 1];
+        let diff = difference(previous, q, is_angle);
+        let next = previous.iter().zip(&diff).map(|(p, d)| *p + *d).collect();
+        unwrapped.push(next);
+    }
+    unwrapped
+}
+
+/// [`StateSpace`] whose dimensions marked in `is_angle` are SO(2), i.e. angles wrapping
+/// at +-pi, and the others are real values.
+///
+/// The distance and the interpolation take the short way across +-pi, so
+/// [`dual_rrt_connect_in_space`](crate::dual_rrt_connect_in_space) plans with unlimited
+/// revolute joints as [`wrapped_rrt_connect`]. The angles are normalized to [-pi, pi).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedSpace {
+    is_angle: Vec<bool>,
+}
+
+impl WrappedSpace {
+    /// Creates a space of `is_angle.len()` dimensions.
+    pub fn new(is_angle: &[bool]) -> Self {
+        Self {
+            is_angle: is_angle.to_vec(),
+        }
+    }
+
+    /// Returns whether each dimension is an angle.
+    pub fn is_angle(&self) -> &[bool] {
+        &self.is_angle
+    }
+}
+
+impl<N> StateSpace<N> for WrappedSpace
+where
+    N: Float + FloatConst,
+{
+    fn dimension(&self) -> usize {
+        self.is_angle.len()
+    }
+
+    fn distance(&self, a: &[N], b: &[N]) -> N {
+        norm(&difference(a, b, &self.is_angle))
+    }
+
+    fn interpolate(&self, from: &[N], to: &[N], t: N) -> Vec<N> {
+        let diff = difference(from, to, &self.is_angle);
+        let q = from
+            .iter()
+            .zip(&diff)
+            .map(|(a, d)| *a + *d * t)
+            .collect::<Vec<_>>();
+        normalize(&q, &self.is_angle)
+    }
+
+    fn enforce_bounds(&self, q: &mut [N]) {
+        for (v, angle) in q.iter_mut().zip(&self.is_angle) {
+            if *angle {
+                *v = normalize_angle(*v);
+            }
+        }
+    }
+}
+
+#[test]
+fn wrapped_rrt_connect_works() {
+    use rand::distributions::{Distribution, Uniform};
+    use std::f64::consts::PI;
+    // a wall at the angle 0 blocks the long way
+    let is_free = |p: &[f64]| normalize_angle(p[0]).abs() > 0.5;
+    let is_angle = [true, false];
+    let path = wrapped_rrt_connect(
+        &[-2.8, 0.0],
+        &[2.8, 0.0],
+        is_free,
+        || {
+            let mut rng = rand::thread_rng();
+            vec![
+                Uniform::new(-PI, PI).sample(&mut rng),
+                Uniform::new(-1.0, 1.0).sample(&mut rng),
+            ]
+        },
+        &is_angle,
+        &DualRrtConnectOptions::new(0.1, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-2.8, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![2.8, 0.0]);
+    assert!(path.iter().all(|q| is_free(q) && q[0] >= -PI && q[0] < PI));
+    for segment in path.windows(2) {
+        assert!(norm(&difference(&segment[0], &segment[1], &is_angle)) < 0.1 + 1e-9);
+    }
+    let unwrapped = unwrap_angles(&path, &is_angle);
+    assert_eq!(unwrapped[0], vec![-2.8, 0.0]);
+    // goes across -pi
+    assert!((unwrapped[unwrapped.len() - 1][0] - (2.8 - 2.0 * PI)).abs() < 1e-9);
+
+    // the same with the space
+    let space = WrappedSpace::new(&is_angle);
+    assert!((space.distance(&[-3.0, 0.0], &[3.0, 0.0]) - (2.0 * PI - 6.0)).abs() < 1e-9);
+    let mid = space.interpolate(&[3.0, 0.0], &[-3.0, 1.0], 0.5);
+    assert!((mid[0] + PI).abs() < 1e-9 && (mid[1] - 0.5).abs() < 1e-9);
+    let path = crate::dual_rrt_connect_in_space(
+        &space,
+        &[-2.8, 0.0],
+        &[2.8, 0.0],
+        is_free,
+        // out of [-pi, pi) to be normalized
+        crate::UniformBoxSampler::new(&[-2.0 * PI, -1.0], &[2.0 * PI, 1.0]).unwrap(),
+        &DualRrtConnectOptions::new(0.1, 1000),
+    )
+    .unwrap();
+    assert_eq!(path[0], vec![-2.8, 0.0]);
+    assert_eq!(path[path.len() - 1], vec![2.8, 0.0]);
+    assert!(path.iter().all(|q| is_free(q) && q[0] >= -PI && q[0] < PI));
+    for segment in path.windows(2) {
+        assert!(space.distance(&segment[0], &segment[1]) < 0.1 + 1e-9);
+    }
+}